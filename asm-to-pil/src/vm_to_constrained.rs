@@ -734,6 +734,7 @@ impl<T: FieldElement> VMConverter<T> {
         match value {
             Expression::PublicReference(_) => panic!(),
             Expression::IndexAccess(_) => panic!(),
+            Expression::ArraySlice(_) => panic!(),
             Expression::FunctionCall(_) => panic!(),
             Expression::Reference(reference) => {
                 // TODO check it actually is a register
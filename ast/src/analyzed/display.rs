@@ -262,6 +262,9 @@ impl Display for RepeatedArray {
 
 impl Display for Identity<Expression> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(label) = &self.label {
+            write!(f, "#[{label}] ")?;
+        }
         match self.kind {
             IdentityKind::Polynomial => {
                 let expression = self.expression_for_poly_id();
@@ -280,6 +283,9 @@ impl Display for Identity<Expression> {
 
 impl<T: Display> Display for Identity<AlgebraicExpression<T>> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(label) = &self.label {
+            write!(f, "#[{label}] ")?;
+        }
         match self.kind {
             IdentityKind::Polynomial => {
                 let expression = self.expression_for_poly_id();
@@ -15,7 +15,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::parsed::asm::SymbolPath;
 use crate::parsed::types::{ArrayType, Type, TypeScheme};
-use crate::parsed::visitor::{Children, ExpressionVisitable};
+use crate::parsed::visitor::{AllChildren, Children, ExpressionVisitable};
 pub use crate::parsed::BinaryOperator;
 pub use crate::parsed::UnaryOperator;
 use crate::parsed::{self, EnumDeclaration, EnumVariant, SelectedExpressions};
@@ -192,6 +192,19 @@ impl<T> Analyzed<T> {
         })
     }
 
+    /// Removes the given public declarations by name.
+    fn remove_public_declarations(&mut self, to_remove: &BTreeSet<String>) {
+        self.public_declarations
+            .retain(|name, _| !to_remove.contains(name));
+        self.source_order.retain(|s| {
+            if let StatementIdentifier::PublicDeclaration(name) = s {
+                !to_remove.contains(name)
+            } else {
+                true
+            }
+        });
+    }
+
     /// Removes the given definitions and itermediate columns by name. Those must not be referenced
     /// by any remaining definitions, identities or public declarations.
     pub fn remove_definitions(&mut self, to_remove: &BTreeSet<String>) {
@@ -291,6 +304,90 @@ impl<T> Analyzed<T> {
     }
 }
 
+impl<T: Clone> Analyzed<T> {
+    /// Removes all columns of the given namespace together with the identities and
+    /// public declarations referencing them, and returns those as a new standalone `Analyzed`.
+    /// IDs are re-assigned to be contiguous in both halves.
+    /// Identities that reference columns both inside and outside of the namespace
+    /// (e.g. links between machines) are removed from both halves. Intermediate columns
+    /// must not reference columns on the other side of the namespace boundary.
+    /// All definitions that are not columns (functions, constants, types) are kept in both halves.
+    pub fn remove_namespace(&mut self, namespace: &str) -> Analyzed<T> {
+        let prefix = format!("{namespace}.");
+        let (inside, outside): (Vec<&Symbol>, Vec<&Symbol>) = self
+            .definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .chain(self.intermediate_columns.values().map(|(symbol, _)| symbol))
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Poly(_)))
+            .partition(|symbol| symbol.absolute_name.starts_with(&prefix));
+        let names = |symbols: &[&Symbol]| -> BTreeSet<String> {
+            symbols.iter().map(|s| s.absolute_name.clone()).collect()
+        };
+        let poly_ids = |symbols: &[&Symbol]| -> BTreeSet<PolyID> {
+            symbols
+                .iter()
+                .flat_map(|s| s.array_elements().map(|(_, poly_id)| poly_id))
+                .collect()
+        };
+        let (inside_names, inside_ids) = (names(&inside), poly_ids(&inside));
+        let (outside_names, outside_ids) = (names(&outside), poly_ids(&outside));
+
+        let mut moved_identities = BTreeSet::new();
+        let mut crossing_identities = BTreeSet::new();
+        for (index, identity) in self.identities.iter().enumerate() {
+            let referenced = identity
+                .all_children()
+                .filter_map(|e| match e {
+                    AlgebraicExpression::Reference(r) => Some(r.poly_id),
+                    _ => None,
+                })
+                .collect::<BTreeSet<_>>();
+            match (
+                !referenced.is_disjoint(&inside_ids),
+                !referenced.is_disjoint(&outside_ids),
+            ) {
+                (true, false) => moved_identities.insert(index),
+                (true, true) => crossing_identities.insert(index),
+                _ => false,
+            };
+        }
+
+        let moved_publics = self
+            .public_declarations
+            .iter()
+            .filter(|(_, decl)| inside_names.contains(&decl.polynomial.name))
+            .map(|(name, _)| name.clone())
+            .collect::<BTreeSet<_>>();
+        let remaining_publics = self
+            .public_declarations
+            .keys()
+            .filter(|name| !moved_publics.contains(*name))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let mut extracted = self.clone();
+        extracted.remove_identities(
+            &(0..self.identities.len())
+                .filter(|index| !moved_identities.contains(index))
+                .collect(),
+        );
+        extracted.remove_public_declarations(&remaining_publics);
+        extracted.remove_definitions(&outside_names);
+
+        self.remove_identities(
+            &moved_identities
+                .union(&crossing_identities)
+                .cloned()
+                .collect(),
+        );
+        self.remove_public_declarations(&moved_publics);
+        self.remove_definitions(&inside_names);
+
+        extracted
+    }
+}
+
 impl<T: FieldElement> Analyzed<T> {
     /// @returns all identities with intermediate polynomials inlined.
     pub fn identities_with_inlined_intermediate_polynomials(
@@ -310,6 +407,48 @@ impl<T: FieldElement> Analyzed<T> {
         substitute_intermediate(self.identities.clone(), intermediates)
     }
 
+    /// @returns a summary of the size of the constraint system, which can be used
+    /// to pick a suitable backend, or an error if the degree of an identity
+    /// cannot be determined.
+    pub fn cost_estimate(&self) -> Result<CostEstimate, String> {
+        let mut estimate = CostEstimate {
+            degree: self.degree,
+            committed_columns: self.commitment_count(),
+            constant_columns: self.constant_count(),
+            intermediate_columns: self.intermediate_count(),
+            ..Default::default()
+        };
+        for identity in self.identities_with_inlined_intermediate_polynomials() {
+            match identity.kind {
+                IdentityKind::Polynomial => estimate.polynomial_identities += 1,
+                IdentityKind::Plookup => estimate.plookup_identities += 1,
+                IdentityKind::Permutation => estimate.permutation_identities += 1,
+                IdentityKind::Connect => estimate.connect_identities += 1,
+            }
+            let degree = identity
+                .degree()
+                .ok_or_else(|| format!("Could not determine the degree of identity: {identity}"))?;
+            estimate.total_constraint_degree += degree;
+            estimate.max_constraint_degree = max(estimate.max_constraint_degree, degree);
+        }
+        Ok(estimate)
+    }
+
+    /// @returns the furthest row offset (relative to the current row) referenced by any
+    /// identity, after inlining intermediate polynomials: 1 if a next row is referenced
+    /// and 0 otherwise.
+    pub fn max_reference_offset(&self) -> i64 {
+        if self
+            .identities_with_inlined_intermediate_polynomials()
+            .iter()
+            .any(|identity| identity.contains_next_ref())
+        {
+            1
+        } else {
+            0
+        }
+    }
+
     pub fn get_struct_schema() -> schemars::schema::RootSchema {
         schemars::schema_for!(Self)
     }
@@ -323,6 +462,27 @@ impl<T: FieldElement> Analyzed<T> {
     }
 }
 
+/// Size metrics of an `Analyzed`, see `Analyzed::cost_estimate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// The degree (number of rows), if any.
+    pub degree: Option<DegreeType>,
+    /// The number of committed columns (with multiplicities for arrays).
+    pub committed_columns: usize,
+    /// The number of constant columns (with multiplicities for arrays).
+    pub constant_columns: usize,
+    /// The number of intermediate columns (with multiplicities for arrays).
+    pub intermediate_columns: usize,
+    pub polynomial_identities: usize,
+    pub plookup_identities: usize,
+    pub permutation_identities: usize,
+    pub connect_identities: usize,
+    /// The sum of the degrees of all identities, with intermediate columns inlined.
+    pub total_constraint_degree: usize,
+    /// The maximum degree of any identity, with intermediate columns inlined.
+    pub max_constraint_degree: usize,
+}
+
 /// Takes identities as values and inlines intermediate polynomials everywhere, returning a vector of the updated identities
 /// TODO: this could return an iterator
 fn substitute_intermediate<T: Copy + Display>(
@@ -626,6 +786,8 @@ pub struct Identity<Expr> {
     pub id: u64,
     pub kind: IdentityKind,
     pub source: SourceRef,
+    /// The label given to the identity in the source, e.g. `#[fibonacci_step]`.
+    pub label: Option<String>,
     /// For a simple polynomial identity, the selector contains
     /// the actual expression (see expression_for_poly_id).
     pub left: SelectedExpressions<Expr>,
@@ -639,6 +801,7 @@ impl<Expr> Identity<Expr> {
             id,
             kind: IdentityKind::Polynomial,
             source,
+            label: None,
             left: SelectedExpressions {
                 selector: Some(identity),
                 expressions: vec![],
@@ -665,6 +828,18 @@ impl<T> Identity<AlgebraicExpression<T>> {
     }
 }
 
+impl<T: FieldElement> Identity<AlgebraicExpression<T>> {
+    /// @returns the degree of the identity. For lookups and permutations, this is
+    /// the maximum degree of a selector multiplied by one of its expressions.
+    /// Returns None if the degree of one of the expressions cannot be determined.
+    pub fn degree(&self) -> Option<usize> {
+        match self.kind {
+            IdentityKind::Polynomial => self.expression_for_poly_id().degree(),
+            _ => Some(max(self.left.degree()?, self.right.degree()?)),
+        }
+    }
+}
+
 impl<Expr> Children<Expr> for Identity<Expr> {
     fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expr> + '_> {
         Box::new(self.left.children_mut().chain(self.right.children_mut()))
@@ -696,6 +871,22 @@ impl<T> SelectedExpressions<AlgebraicExpression<T>> {
     }
 }
 
+impl<T: FieldElement> SelectedExpressions<AlgebraicExpression<T>> {
+    /// @returns the maximum degree of the selector multiplied by any of the expressions,
+    /// or None if the degree of one of them cannot be determined.
+    pub fn degree(&self) -> Option<usize> {
+        let selector_degree = match &self.selector {
+            Some(s) => s.degree()?,
+            None => 0,
+        };
+        self.expressions
+            .iter()
+            .map(|e| Some(selector_degree + e.degree()?))
+            .collect::<Option<Vec<_>>>()
+            .map(|degrees| degrees.into_iter().max().unwrap_or(selector_degree))
+    }
+}
+
 pub type Expression = parsed::Expression<Reference>;
 pub type TypedExpression = crate::parsed::TypedExpression<Reference, u64>;
 
@@ -753,7 +944,9 @@ impl Hash for AlgebraicReference {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub enum AlgebraicExpression<T> {
     Reference(AlgebraicReference),
     PublicReference(String),
@@ -803,7 +996,9 @@ impl<T> AlgebraicExpression<T> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Challenge {
     /// Challenge ID
     pub id: u64,
@@ -811,7 +1006,7 @@ pub struct Challenge {
 }
 
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize, JsonSchema,
 )]
 pub enum AlgebraicBinaryOperator {
     Add,
@@ -849,7 +1044,7 @@ impl TryFrom<BinaryOperator> for AlgebraicBinaryOperator {
 }
 
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize, JsonSchema,
 )]
 pub enum AlgebraicUnaryOperator {
     Minus,
@@ -907,6 +1102,100 @@ impl<T> AlgebraicExpression<T> {
     }
 }
 
+impl<T: Clone> AlgebraicExpression<T> {
+    /// Returns a copy of the expression where all column references use the local IDs
+    /// of the columns in `namespace`, as given by `layout`.
+    /// Panics if the expression references a column outside of the namespace.
+    pub fn localize(&self, namespace: &str, layout: &ColumnLayout) -> AlgebraicExpression<T> {
+        let mut localized = self.clone();
+        localized.pre_visit_expressions_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                reference.poly_id = layout
+                    .local_id(namespace, &reference.poly_id)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Column {} is not part of namespace {namespace}.",
+                            reference.name
+                        )
+                    });
+            }
+        });
+        localized
+    }
+}
+
+impl<T: FieldElement> AlgebraicExpression<T> {
+    /// @returns the degree of the expression as a polynomial in the column values.
+    /// Intermediate columns are counted as degree one, they need to be inlined first
+    /// to get the actual degree.
+    /// Returns None if the expression contains an exponent that is not a number.
+    pub fn degree(&self) -> Option<usize> {
+        match self {
+            AlgebraicExpression::Reference(_) => Some(1),
+            AlgebraicExpression::PublicReference(_)
+            | AlgebraicExpression::Challenge(_)
+            | AlgebraicExpression::Number(_) => Some(0),
+            AlgebraicExpression::BinaryOperation(left, op, right) => match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    Some(max(left.degree()?, right.degree()?))
+                }
+                AlgebraicBinaryOperator::Mul => Some(left.degree()? + right.degree()?),
+                AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                    AlgebraicExpression::Number(exponent) => {
+                        Some(left.degree()? * exponent.to_degree() as usize)
+                    }
+                    _ => None,
+                },
+            },
+            AlgebraicExpression::UnaryOperation(_, e) => e.degree(),
+        }
+    }
+
+    /// Substitutes the values of fixed columns in a specific row and folds the
+    /// resulting constant sub-expressions.
+    /// References to fixed columns in the next row and to fixed columns not
+    /// contained in `fixed_row` are left as they are, as are all other references.
+    pub fn partial_eval_fixed(&self, fixed_row: &BTreeMap<PolyID, T>) -> AlgebraicExpression<T> {
+        match self {
+            AlgebraicExpression::Reference(poly)
+                if poly.poly_id.ptype == PolynomialType::Constant && !poly.next =>
+            {
+                match fixed_row.get(&poly.poly_id) {
+                    Some(value) => AlgebraicExpression::Number(*value),
+                    None => self.clone(),
+                }
+            }
+            AlgebraicExpression::Reference(_)
+            | AlgebraicExpression::PublicReference(_)
+            | AlgebraicExpression::Challenge(_)
+            | AlgebraicExpression::Number(_) => self.clone(),
+            AlgebraicExpression::BinaryOperation(left, op, right) => {
+                let left = left.partial_eval_fixed(fixed_row);
+                let right = right.partial_eval_fixed(fixed_row);
+                match (&left, &right) {
+                    (AlgebraicExpression::Number(l), AlgebraicExpression::Number(r)) => {
+                        AlgebraicExpression::Number(match op {
+                            AlgebraicBinaryOperator::Add => *l + *r,
+                            AlgebraicBinaryOperator::Sub => *l - *r,
+                            AlgebraicBinaryOperator::Mul => *l * *r,
+                            AlgebraicBinaryOperator::Pow => l.pow(r.to_integer()),
+                        })
+                    }
+                    _ => AlgebraicExpression::new_binary(left, *op, right),
+                }
+            }
+            AlgebraicExpression::UnaryOperation(op, e) => {
+                match (op, e.partial_eval_fixed(fixed_row)) {
+                    (AlgebraicUnaryOperator::Minus, AlgebraicExpression::Number(n)) => {
+                        AlgebraicExpression::Number(-n)
+                    }
+                    (op, e) => AlgebraicExpression::UnaryOperation(*op, Box::new(e)),
+                }
+            }
+        }
+    }
+}
+
 impl<T> ops::Add for AlgebraicExpression<T> {
     type Output = Self;
 
@@ -978,6 +1267,54 @@ impl Hash for PolyID {
     }
 }
 
+/// Assigns local IDs to the columns of each namespace (machine), so that the columns
+/// of a machine can be indexed independently of the rest of the PIL.
+/// Local IDs start at zero for each namespace and polynomial type and follow
+/// the order of the global IDs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnLayout {
+    local_ids: BTreeMap<String, BTreeMap<PolyID, PolyID>>,
+}
+
+impl ColumnLayout {
+    pub fn new<T>(pil: &Analyzed<T>) -> Self {
+        let mut columns = pil
+            .definitions
+            .values()
+            .filter(|(symbol, _)| matches!(symbol.kind, SymbolKind::Poly(_)))
+            .flat_map(|(symbol, _)| symbol.array_elements())
+            .collect::<Vec<_>>();
+        columns.sort_by_key(|(_, poly_id)| *poly_id);
+
+        let mut local_ids: BTreeMap<String, BTreeMap<PolyID, PolyID>> = BTreeMap::new();
+        let mut counters: BTreeMap<(String, PolynomialType), u64> = BTreeMap::new();
+        for (name, poly_id) in columns {
+            let namespace = name
+                .rsplit_once('.')
+                .map(|(namespace, _)| namespace)
+                .unwrap_or_default();
+            let counter = counters
+                .entry((namespace.to_string(), poly_id.ptype))
+                .or_default();
+            let local_id = PolyID {
+                id: *counter,
+                ptype: poly_id.ptype,
+            };
+            *counter += 1;
+            local_ids
+                .entry(namespace.to_string())
+                .or_default()
+                .insert(poly_id, local_id);
+        }
+        Self { local_ids }
+    }
+
+    /// Returns the local ID of a column, or `None` if the column is not part of the namespace.
+    pub fn local_id(&self, namespace: &str, poly_id: &PolyID) -> Option<PolyID> {
+        self.local_ids.get(namespace)?.get(poly_id).cloned()
+    }
+}
+
 #[derive(
     Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
 )]
@@ -1003,9 +1340,16 @@ impl Display for PolynomialType {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
+    use powdr_number::GoldilocksField;
+
     use crate::SourceRef;
 
-    use super::{AlgebraicExpression, Analyzed};
+    use super::{
+        AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, Analyzed, PolyID,
+        PolynomialType, SelectedExpressions,
+    };
 
     #[test]
     fn insert_remove_identities() {
@@ -1044,4 +1388,87 @@ mod tests {
         assert_eq!(pil.identities, pil_result.identities);
         assert_eq!(pil.source_order, pil_result.source_order);
     }
+
+    #[test]
+    fn partial_eval_fixed() {
+        let reference = |name: &str, id, ptype| {
+            AlgebraicExpression::<GoldilocksField>::Reference(AlgebraicReference {
+                name: name.to_string(),
+                poly_id: PolyID { id, ptype },
+                next: false,
+            })
+        };
+        let a = reference("a", 0, PolynomialType::Committed);
+        let z = reference("z", 0, PolynomialType::Constant);
+
+        // a = z + 1
+        let identity = a.clone() - (z + AlgebraicExpression::Number(1.into()));
+        let fixed_row = BTreeMap::from([(
+            PolyID {
+                id: 0,
+                ptype: PolynomialType::Constant,
+            },
+            GoldilocksField::from(5),
+        )]);
+        assert_eq!(
+            identity.partial_eval_fixed(&fixed_row),
+            a - AlgebraicExpression::Number(6.into())
+        );
+    }
+
+    #[test]
+    fn degree_of_non_constant_exponent() {
+        let reference = |name: &str, id| {
+            AlgebraicExpression::<GoldilocksField>::Reference(AlgebraicReference {
+                name: name.to_string(),
+                poly_id: PolyID {
+                    id,
+                    ptype: PolynomialType::Committed,
+                },
+                next: false,
+            })
+        };
+        let pow = |base, exponent| {
+            AlgebraicExpression::BinaryOperation(
+                Box::new(base),
+                AlgebraicBinaryOperator::Pow,
+                Box::new(exponent),
+            )
+        };
+        let x = reference("x", 0);
+        assert_eq!(
+            pow(x.clone(), AlgebraicExpression::Number(3.into())).degree(),
+            Some(3)
+        );
+        let non_constant = pow(x, reference("y", 1));
+        assert_eq!(non_constant.degree(), None);
+
+        let mut pil = Analyzed::default();
+        pil.append_polynomial_identity(non_constant, SourceRef::unknown());
+        assert_eq!(
+            pil.cost_estimate(),
+            Err("Could not determine the degree of identity: (x ** y) = 0;".to_string())
+        );
+    }
+
+    #[test]
+    fn display_selected_expressions() {
+        let reference = |name: &str, id| {
+            AlgebraicExpression::<GoldilocksField>::Reference(AlgebraicReference {
+                name: name.to_string(),
+                poly_id: PolyID {
+                    id,
+                    ptype: PolynomialType::Committed,
+                },
+                next: false,
+            })
+        };
+        let mut selected = SelectedExpressions {
+            selector: None,
+            expressions: vec![reference("a", 0), reference("b", 1) + reference("c", 2)],
+        };
+        assert_eq!(selected.to_string(), "{ a, (b + c) }");
+        selected.selector = Some(reference("sel", 3));
+        assert_eq!(selected.to_string(), "sel { a, (b + c) }");
+    }
 }
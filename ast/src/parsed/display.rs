@@ -311,6 +311,20 @@ impl<Ref: Display> Display for IndexAccess<Ref> {
     }
 }
 
+impl<Ref: Display> Display for ArraySlice<Ref> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}[", self.array)?;
+        if let Some(from) = &self.from {
+            write!(f, "{from}")?;
+        }
+        write!(f, "..")?;
+        if let Some(to) = &self.to {
+            write!(f, "{to}")?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl<Ref: Display> Display for FunctionCall<Ref> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
@@ -338,6 +352,8 @@ impl Display for Pattern {
             Pattern::Tuple(t) => write!(f, "({})", t.iter().format(", ")),
             Pattern::Array(a) => write!(f, "[{}]", a.iter().format(", ")),
             Pattern::Variable(v) => write!(f, "{v}"),
+            Pattern::Enum(name, fields) if fields.is_empty() => write!(f, "{name}"),
+            Pattern::Enum(name, fields) => write!(f, "{name}({})", fields.iter().format(", ")),
         }
     }
 }
@@ -472,6 +488,11 @@ impl Display for PilStatement {
             }
             PilStatement::Expression(_, e) => write_indented_by(f, format!("{e};"), 1),
             PilStatement::EnumDeclaration(_, enum_decl) => write_indented_by(f, enum_decl, 1),
+            PilStatement::LabeledIdentity(_, label, statement) => write_indented_by(
+                f,
+                format!("#[{label}] {}", statement.to_string().trim_start()),
+                1,
+            ),
         }
     }
 }
@@ -563,6 +584,7 @@ impl<Ref: Display> Display for Expression<Ref> {
                 }
             }
             Expression::IndexAccess(index_access) => write!(f, "{index_access}"),
+            Expression::ArraySlice(slice) => write!(f, "{slice}"),
             Expression::FunctionCall(fun_call) => write!(f, "{fun_call}"),
             Expression::FreeInput(input) => write!(f, "${{ {input} }}"),
             Expression::MatchExpression(scrutinee, arms) => {
@@ -11,6 +11,7 @@ use std::{
     ops,
 };
 
+use num_traits::Zero;
 use powdr_number::{BigInt, BigUint, DegreeType};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -22,10 +23,10 @@ use self::{
 };
 use crate::SourceRef;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PILFile(pub Vec<PilStatement>);
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum PilStatement {
     /// File name
     Include(SourceRef, String),
@@ -74,6 +75,8 @@ pub enum PilStatement {
     ConstantDefinition(SourceRef, String, Expression),
     EnumDeclaration(SourceRef, EnumDeclaration<Expression>),
     Expression(SourceRef, Expression),
+    /// An identity statement with a user-provided label, e.g. `#[fibonacci_step] x' = y;`.
+    LabeledIdentity(SourceRef, String, Box<PilStatement>),
 }
 
 impl PilStatement {
@@ -100,7 +103,8 @@ impl PilStatement {
             | PilStatement::PlookupIdentity(_, _, _)
             | PilStatement::PermutationIdentity(_, _, _)
             | PilStatement::ConnectIdentity(_, _, _)
-            | PilStatement::Expression(_, _) => Box::new(empty()),
+            | PilStatement::Expression(_, _)
+            | PilStatement::LabeledIdentity(_, _, _) => Box::new(empty()),
         }
     }
 
@@ -137,6 +141,8 @@ impl Children<Expression> for PilStatement {
 
             PilStatement::EnumDeclaration(_, enum_decl) => enum_decl.children(),
 
+            PilStatement::LabeledIdentity(_, _, statement) => statement.children(),
+
             PilStatement::LetStatement(_, _, type_scheme, value) => Box::new(
                 type_scheme
                     .iter()
@@ -172,6 +178,8 @@ impl Children<Expression> for PilStatement {
 
             PilStatement::EnumDeclaration(_, enum_decl) => enum_decl.children_mut(),
 
+            PilStatement::LabeledIdentity(_, _, statement) => statement.children_mut(),
+
             PilStatement::LetStatement(_, _, ty, value) => {
                 Box::new(ty.iter_mut().flat_map(|t| t.ty.children_mut()).chain(value))
             }
@@ -300,6 +308,7 @@ pub enum Expression<Ref = NamespacedPolynomialReference> {
     BinaryOperation(Box<Expression<Ref>>, BinaryOperator, Box<Expression<Ref>>),
     UnaryOperation(UnaryOperator, Box<Expression<Ref>>),
     IndexAccess(IndexAccess<Ref>),
+    ArraySlice(ArraySlice<Ref>),
     FunctionCall(FunctionCall<Ref>),
     FreeInput(Box<Expression<Ref>>),
     MatchExpression(Box<Expression<Ref>>, Vec<MatchArm<Ref>>),
@@ -376,6 +385,161 @@ impl From<NamespacedPolynomialReference> for Expression {
     }
 }
 
+impl Expression {
+    /// Replaces all references to `from` in this expression by `to`.
+    /// Does not descend into lambda expressions whose parameters shadow `from`.
+    pub fn substitute(&mut self, from: &NamespacedPolynomialReference, to: &Expression) {
+        match self {
+            Expression::Reference(r) if r == from => *self = to.clone(),
+            Expression::LambdaExpression(LambdaExpression { params, .. })
+                if from.try_to_identifier().is_some_and(|name| {
+                    params.iter().flat_map(|p| p.variables()).any(|v| v == name)
+                }) => {}
+            _ => self.children_mut().for_each(|e| e.substitute(from, to)),
+        }
+    }
+
+    /// Returns the paths of all symbols referenced in this expression, excluding
+    /// names bound inside the expression by lambda parameters, match arms or
+    /// let statements in blocks.
+    pub fn referenced_symbols(&self) -> BTreeSet<&SymbolPath> {
+        let mut symbols = BTreeSet::new();
+        self.collect_referenced_symbols(&mut vec![], &mut symbols);
+        symbols
+    }
+
+    /// Adds all symbols referenced in this expression to `symbols`, skipping the
+    /// local variables in `bound`.
+    fn collect_referenced_symbols<'a>(
+        &'a self,
+        bound: &mut Vec<&'a String>,
+        symbols: &mut BTreeSet<&'a SymbolPath>,
+    ) {
+        let scope_start = bound.len();
+        match self {
+            Expression::Reference(r) => {
+                if !r
+                    .path
+                    .try_to_identifier()
+                    .is_some_and(|name| bound.contains(&name))
+                {
+                    symbols.insert(&r.path);
+                }
+            }
+            Expression::LambdaExpression(LambdaExpression { params, body, .. }) => {
+                bound.extend(params.iter().flat_map(|p| p.variables()));
+                body.collect_referenced_symbols(bound, symbols);
+            }
+            Expression::MatchExpression(scrutinee, arms) => {
+                scrutinee.collect_referenced_symbols(bound, symbols);
+                for MatchArm { pattern, value } in arms {
+                    bound.extend(pattern.variables());
+                    value.collect_referenced_symbols(bound, symbols);
+                    bound.truncate(scope_start);
+                }
+            }
+            Expression::BlockExpression(statements, expr) => {
+                for statement in statements {
+                    match statement {
+                        StatementInsideBlock::LetStatement(LetStatementInsideBlock {
+                            pattern,
+                            value,
+                        }) => {
+                            // The value of a let statement cannot refer to the variables it defines.
+                            if let Some(value) = value {
+                                value.collect_referenced_symbols(bound, symbols);
+                            }
+                            bound.extend(pattern.variables());
+                        }
+                        StatementInsideBlock::Expression(e) => {
+                            e.collect_referenced_symbols(bound, symbols)
+                        }
+                    }
+                }
+                expr.collect_referenced_symbols(bound, symbols);
+            }
+            _ => self
+                .children()
+                .for_each(|e| e.collect_referenced_symbols(bound, symbols)),
+        }
+        bound.truncate(scope_start);
+    }
+}
+
+/// Results of constant folding with more bits than this are left unfolded.
+const MAX_FOLDED_BITS: usize = 256;
+
+/// Replaces all binary operations on number literals in `e` by their result, bottom-up,
+/// so that e.g. `a + (2 + 3) * 4` becomes `a + 20`. References are left untouched.
+/// Operations whose result cannot be represented as a number literal (like `2 - 3`),
+/// divisions by zero and shifts or powers with overly large results are not folded.
+/// Slices of array literals with constant bounds are folded into array literals,
+/// unless they are out of bounds, which is left for the evaluator to report.
+pub fn fold_constants(e: &mut Expression) {
+    e.children_mut().for_each(fold_constants);
+    if let Expression::ArraySlice(slice) = e {
+        if let Some(items) = fold_array_slice(slice) {
+            *e = Expression::ArrayLiteral(ArrayLiteral { items });
+        }
+        return;
+    }
+    let Expression::BinaryOperation(left, op, right) = e else {
+        return;
+    };
+    let (Expression::Number(l, l_ty), Expression::Number(r, r_ty)) =
+        (left.as_ref(), right.as_ref())
+    else {
+        return;
+    };
+    // The right operand of powers and shifts is always an integer.
+    let ty = match op {
+        BinaryOperator::Pow | BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => l_ty,
+        _ if l_ty == r_ty => l_ty,
+        _ => return,
+    };
+    if let Some(value) = fold_binary_operation(l, *op, r) {
+        *e = Expression::Number(value, ty.clone());
+    }
+}
+
+fn fold_array_slice(slice: &ArraySlice) -> Option<Vec<Expression>> {
+    let Expression::ArrayLiteral(ArrayLiteral { items }) = slice.array.as_ref() else {
+        return None;
+    };
+    let bound = |bound: &Option<Box<Expression>>, default: usize| match bound.as_deref() {
+        None => Some(default),
+        Some(Expression::Number(n, _)) => usize::try_from(n).ok(),
+        Some(_) => None,
+    };
+    let from = bound(&slice.from, 0)?;
+    let to = bound(&slice.to, items.len())?;
+    (from <= to && to <= items.len()).then(|| items[from..to].to_vec())
+}
+
+fn fold_binary_operation(l: &BigUint, op: BinaryOperator, r: &BigUint) -> Option<BigUint> {
+    match op {
+        BinaryOperator::Add => Some(l + r),
+        BinaryOperator::Sub => (l >= r).then(|| l - r),
+        BinaryOperator::Mul => Some(l * r),
+        // Only exact divisions have the same result for integers and field elements.
+        BinaryOperator::Div => (!r.is_zero() && (l % r).is_zero()).then(|| l / r),
+        BinaryOperator::Mod => (!r.is_zero()).then(|| l % r),
+        BinaryOperator::Pow => {
+            let exponent = usize::try_from(r).ok()?;
+            (l.bit_len().checked_mul(exponent)? <= MAX_FOLDED_BITS).then(|| l.pow(exponent))
+        }
+        BinaryOperator::BinaryAnd => Some(l & r),
+        BinaryOperator::BinaryXor => Some(l ^ r),
+        BinaryOperator::BinaryOr => Some(l | r),
+        BinaryOperator::ShiftLeft => {
+            let shift = usize::try_from(r).ok()?;
+            (l.bit_len().checked_add(shift)? <= MAX_FOLDED_BITS).then(|| l << shift)
+        }
+        BinaryOperator::ShiftRight => Some(l >> usize::try_from(r).ok()?),
+        _ => None,
+    }
+}
+
 impl<R> Expression<R> {
     /// Returns an iterator over all (top-level) expressions in this expression.
     /// This specifically does not implement Children because otherwise it would
@@ -399,6 +563,7 @@ impl<R> Expression<R> {
             Expression::IndexAccess(IndexAccess { array, index }) => {
                 Box::new([array.as_ref(), index.as_ref()].into_iter())
             }
+            Expression::ArraySlice(slice) => slice.children(),
             Expression::FunctionCall(FunctionCall {
                 function,
                 arguments,
@@ -443,6 +608,7 @@ impl<R> Expression<R> {
             Expression::IndexAccess(IndexAccess { array, index }) => {
                 Box::new([array.as_mut(), index.as_mut()].into_iter())
             }
+            Expression::ArraySlice(slice) => slice.children_mut(),
             Expression::FunctionCall(FunctionCall {
                 function,
                 arguments,
@@ -466,13 +632,17 @@ impl<R> Expression<R> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone, Serialize, Deserialize, JsonSchema,
+)]
 pub struct PolynomialName {
     pub name: String,
     pub array_size: Option<Expression>,
 }
 
-#[derive(Debug, PartialEq, Eq, Default, Clone, PartialOrd, Ord)]
+#[derive(
+    Debug, PartialEq, Eq, Default, Clone, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 /// A polynomial with an optional namespace
 /// This is different from SymbolPath mainly due to different formatting.
 pub struct NamespacedPolynomialReference {
@@ -605,6 +775,29 @@ impl<R> Children<Expression<R>> for IndexAccess<R> {
     }
 }
 
+/// A sub-array `array[from..to]`, where both bounds are optional.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArraySlice<Ref = NamespacedPolynomialReference> {
+    pub array: Box<Expression<Ref>>,
+    pub from: Option<Box<Expression<Ref>>>,
+    pub to: Option<Box<Expression<Ref>>>,
+}
+
+impl<Ref> Children<Expression<Ref>> for ArraySlice<Ref> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<Ref>> + '_> {
+        Box::new(
+            once(self.array.as_ref()).chain(self.from.iter().chain(&self.to).map(|e| e.as_ref())),
+        )
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<Ref>> + '_> {
+        Box::new(
+            once(self.array.as_mut())
+                .chain(self.from.iter_mut().chain(&mut self.to).map(|e| e.as_mut())),
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FunctionCall<Ref = NamespacedPolynomialReference> {
     pub function: Box<Expression<Ref>>,
@@ -703,7 +896,7 @@ impl<R> Children<Expression<R>> for LetStatementInsideBlock<R> {
 }
 
 /// The definition of a function (excluding its name):
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum FunctionDefinition {
     /// Array expression.
     Array(ArrayExpression),
@@ -718,7 +911,7 @@ impl Children<Expression> for FunctionDefinition {
         match self {
             FunctionDefinition::Array(ae) => ae.children(),
             FunctionDefinition::Expression(e) => Box::new(once(e)),
-            FunctionDefinition::TypeDeclaration(_enum_declaration) => todo!(),
+            FunctionDefinition::TypeDeclaration(enum_declaration) => enum_declaration.children(),
         }
     }
 
@@ -726,12 +919,14 @@ impl Children<Expression> for FunctionDefinition {
         match self {
             FunctionDefinition::Array(ae) => ae.children_mut(),
             FunctionDefinition::Expression(e) => Box::new(once(e)),
-            FunctionDefinition::TypeDeclaration(_enum_declaration) => todo!(),
+            FunctionDefinition::TypeDeclaration(enum_declaration) => {
+                enum_declaration.children_mut()
+            }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ArrayExpression {
     Value(Vec<Expression>),
     RepeatedValue(Vec<Expression>),
@@ -842,6 +1037,9 @@ pub enum Pattern {
     Tuple(Vec<Pattern>),
     Array(Vec<Pattern>),
     Variable(String),
+    /// An enum variant, e.g. `Option::Some(x)`, matching the fields of
+    /// the variant against the sub-patterns.
+    Enum(SymbolPath, Vec<Pattern>),
 }
 
 impl Pattern {
@@ -864,6 +1062,8 @@ impl Pattern {
                 items == &vec![Pattern::Ellipsis]
             }
             Pattern::Tuple(p) => p.iter().all(|p| p.is_irrefutable()),
+            // The enum could have other variants.
+            Pattern::Enum(_, _) => false,
         }
     }
 }
@@ -876,7 +1076,7 @@ impl Children<Pattern> for Pattern {
             | Pattern::Number(_)
             | Pattern::String(_)
             | Pattern::Variable(_) => Box::new(empty()),
-            Pattern::Tuple(p) | Pattern::Array(p) => Box::new(p.iter()),
+            Pattern::Tuple(p) | Pattern::Array(p) | Pattern::Enum(_, p) => Box::new(p.iter()),
         }
     }
 
@@ -887,7 +1087,7 @@ impl Children<Pattern> for Pattern {
             | Pattern::Number(_)
             | Pattern::String(_)
             | Pattern::Variable(_) => Box::new(empty()),
-            Pattern::Tuple(p) | Pattern::Array(p) => Box::new(p.iter_mut()),
+            Pattern::Tuple(p) | Pattern::Array(p) | Pattern::Enum(_, p) => Box::new(p.iter_mut()),
         }
     }
 }
@@ -897,3 +1097,280 @@ pub struct TypedExpression<Ref = NamespacedPolynomialReference, E = Expression<R
     pub e: Expression<Ref>,
     pub type_scheme: Option<TypeScheme<E>>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use types::ArrayType;
+
+    #[test]
+    fn type_declaration_children() {
+        let array_of = |length: u64| {
+            Type::Array(ArrayType {
+                base: Box::new(Type::Int),
+                length: Some(Expression::Number(length.into(), None)),
+            })
+        };
+        let mut definition = FunctionDefinition::TypeDeclaration(EnumDeclaration {
+            name: "E".to_string(),
+            variants: vec![
+                EnumVariant {
+                    name: "A".to_string(),
+                    fields: None,
+                },
+                EnumVariant {
+                    name: "B".to_string(),
+                    fields: Some(vec![Type::Fe, array_of(2), array_of(3)]),
+                },
+            ],
+        });
+        assert_eq!(
+            definition.children().cloned().collect::<Vec<_>>(),
+            vec![
+                Expression::Number(2u64.into(), None),
+                Expression::Number(3u64.into(), None)
+            ]
+        );
+        assert_eq!(definition.children_mut().count(), 2);
+    }
+
+    #[test]
+    fn substitute() {
+        let x = NamespacedPolynomialReference::from_identifier("x".to_string());
+        let reference = |name: &str| {
+            Expression::Reference(NamespacedPolynomialReference::from_identifier(
+                name.to_string(),
+            ))
+        };
+        let lambda = |param: &str, body: Expression| {
+            Expression::LambdaExpression(LambdaExpression {
+                kind: FunctionKind::Pure,
+                params: vec![Pattern::Variable(param.to_string())],
+                body: Box::new(body),
+            })
+        };
+        let index_access = |array: Expression, index: Expression| {
+            Expression::IndexAccess(IndexAccess {
+                array: Box::new(array),
+                index: Box::new(index),
+            })
+        };
+        let to = reference("y") + Expression::from(1u32);
+
+        let mut e = reference("x") * reference("z") + index_access(reference("a"), reference("x"));
+        e.substitute(&x, &to);
+        assert_eq!(
+            e,
+            to.clone() * reference("z") + index_access(reference("a"), to.clone())
+        );
+
+        let mut e = lambda("x", reference("x") + reference("z")) + reference("x");
+        e.substitute(&x, &to);
+        assert_eq!(e, lambda("x", reference("x") + reference("z")) + to.clone());
+
+        let mut e = lambda("i", reference("x") + reference("i"));
+        e.substitute(&x, &to);
+        assert_eq!(e, lambda("i", to + reference("i")));
+    }
+
+    #[test]
+    fn referenced_symbols() {
+        let reference = |name: &str| {
+            Expression::Reference(NamespacedPolynomialReference::from_identifier(
+                name.to_string(),
+            ))
+        };
+        let lambda = |param: &str, body: Expression| {
+            Expression::LambdaExpression(LambdaExpression {
+                kind: FunctionKind::Pure,
+                params: vec![Pattern::Variable(param.to_string())],
+                body: Box::new(body),
+            })
+        };
+        let let_statement = |name: &str, value: Expression| {
+            StatementInsideBlock::LetStatement(LetStatementInsideBlock {
+                pattern: Pattern::Variable(name.to_string()),
+                value: Some(value),
+            })
+        };
+        let names = |e: &Expression| {
+            e.referenced_symbols()
+                .into_iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // |x| |y| x + y + z
+        let e = lambda(
+            "x",
+            lambda("y", reference("x") + reference("y") + reference("z")),
+        );
+        assert_eq!(names(&e), vec!["z"]);
+
+        // x + (|x| x * w): the outer x is free, the inner one is bound.
+        let e = reference("x") + lambda("x", reference("x") * reference("w"));
+        assert_eq!(names(&e), vec!["w", "x"]);
+
+        // { let a = b; let b = a + c; b + x }
+        let e = Expression::BlockExpression(
+            vec![
+                let_statement("a", reference("b")),
+                let_statement("b", reference("a") + reference("c")),
+            ],
+            Box::new(reference("b") + reference("x")),
+        );
+        assert_eq!(names(&e), vec!["b", "c", "x"]);
+
+        // Names bound in a block are not visible outside of it.
+        let block = Expression::BlockExpression(
+            vec![let_statement("a", 1u32.into())],
+            Box::new(reference("a")),
+        );
+        let e = lambda("y", block + reference("a") + reference("y"));
+        assert_eq!(names(&e), vec!["a"]);
+
+        // Qualified paths are never shadowed.
+        let qualified = Expression::Reference(
+            SymbolPath::from_parts([Part::Named("N".into()), Part::Named("x".into())]).into(),
+        );
+        let e = lambda("x", qualified);
+        assert_eq!(names(&e), vec!["N::x"]);
+    }
+
+    #[test]
+    fn fold_constants() {
+        let number = |n: u32| Expression::from(n);
+        let a = Expression::Reference(NamespacedPolynomialReference::from_identifier(
+            "a".to_string(),
+        ));
+        let binary = Expression::new_binary;
+
+        let mut e = (number(2) + number(3)) * number(4);
+        super::fold_constants(&mut e);
+        assert_eq!(e, number(20));
+
+        let mut e = a.clone() + (number(2) + number(3));
+        super::fold_constants(&mut e);
+        assert_eq!(e, a.clone() + number(5));
+
+        let mut e = binary(number(1), BinaryOperator::ShiftLeft, number(8))
+            - binary(number(7), BinaryOperator::Mod, number(4))
+            + binary(number(2), BinaryOperator::Pow, number(10));
+        super::fold_constants(&mut e);
+        assert_eq!(e, number(253 + 1024));
+
+        // Negative results, divisions by zero, inexact divisions and overly large
+        // shifts are left unfolded.
+        for unfolded in [
+            number(2) - number(3),
+            binary(number(5), BinaryOperator::Div, number(0)),
+            binary(number(5), BinaryOperator::Mod, number(0)),
+            binary(number(5), BinaryOperator::Div, number(2)),
+            binary(number(1), BinaryOperator::ShiftLeft, number(1000)),
+            binary(number(2), BinaryOperator::Pow, number(1000)),
+        ] {
+            let mut e = unfolded.clone() * (number(1) + number(1));
+            super::fold_constants(&mut e);
+            assert_eq!(e, unfolded * number(2));
+        }
+    }
+
+    #[test]
+    fn fold_array_slices() {
+        let number = |n: u32| Expression::from(n);
+        let array = |items: Vec<Expression>| Expression::ArrayLiteral(ArrayLiteral { items });
+        let slice = |from: Option<Expression>, to: Option<Expression>| {
+            Expression::ArraySlice(ArraySlice {
+                array: Box::new(array((1..=4).map(number).collect())),
+                from: from.map(Box::new),
+                to: to.map(Box::new),
+            })
+        };
+
+        let mut e = slice(Some(number(0) + number(1)), Some(number(3)));
+        super::fold_constants(&mut e);
+        assert_eq!(e, array(vec![number(2), number(3)]));
+
+        let mut e = slice(None, Some(number(2)));
+        super::fold_constants(&mut e);
+        assert_eq!(e, array(vec![number(1), number(2)]));
+
+        let mut e = slice(Some(number(4)), None);
+        super::fold_constants(&mut e);
+        assert_eq!(e, array(vec![]));
+
+        // Out-of-bounds and reversed slices are left for the evaluator to report.
+        for unfolded in [
+            slice(Some(number(1)), Some(number(5))),
+            slice(Some(number(3)), Some(number(2))),
+        ] {
+            let mut e = unfolded.clone();
+            super::fold_constants(&mut e);
+            assert_eq!(e, unfolded);
+        }
+    }
+
+    #[test]
+    fn array_slice_children() {
+        let reference = |name: &str| {
+            Box::new(Expression::Reference(
+                NamespacedPolynomialReference::from_identifier(name.to_string()),
+            ))
+        };
+        let mut slice = Expression::ArraySlice(ArraySlice {
+            array: reference("arr"),
+            from: Some(reference("a")),
+            to: Some(reference("b")),
+        });
+        assert_eq!(
+            slice.children().cloned().collect::<Vec<_>>(),
+            vec![*reference("arr"), *reference("a"), *reference("b")]
+        );
+        assert_eq!(slice.children_mut().count(), 3);
+
+        let open_ended = Expression::ArraySlice(ArraySlice {
+            array: reference("arr"),
+            from: None,
+            to: Some(reference("b")),
+        });
+        assert_eq!(
+            open_ended.children().cloned().collect::<Vec<_>>(),
+            vec![*reference("arr"), *reference("b")]
+        );
+        assert_eq!(
+            open_ended
+                .referenced_symbols()
+                .into_iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>(),
+            vec!["arr", "b"]
+        );
+    }
+
+    #[test]
+    fn enum_pattern_children() {
+        let path = |p: &str| SymbolPath::from_parts(p.split("::").map(|n| Part::Named(n.into())));
+        let inner = Pattern::Enum(
+            path("Pair::P"),
+            vec![Pattern::Variable("a".to_string()), Pattern::CatchAll],
+        );
+        let mut pattern = Pattern::Enum(
+            path("Opt::Some"),
+            vec![inner.clone(), Pattern::Number(7.into())],
+        );
+        assert_eq!(
+            pattern.children().collect::<Vec<_>>(),
+            vec![&inner, &Pattern::Number(7.into())]
+        );
+        assert_eq!(
+            pattern
+                .children()
+                .flat_map(|p| p.children())
+                .collect::<Vec<_>>(),
+            vec![&Pattern::Variable("a".to_string()), &Pattern::CatchAll]
+        );
+        assert_eq!(pattern.children_mut().count(), 2);
+        assert_eq!(pattern.variables().collect::<Vec<_>>(), vec!["a"]);
+        assert!(!pattern.is_irrefutable());
+    }
+}
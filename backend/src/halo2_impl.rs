@@ -1,10 +1,10 @@
 use std::{io, path::Path};
 
-use crate::{Backend, BackendFactory, Error, Proof};
-use powdr_ast::analyzed::Analyzed;
+use crate::{Backend, BackendCapabilities, BackendFactory, Error, Proof};
+use powdr_ast::analyzed::{Analyzed, IdentityKind};
 use powdr_executor::witgen::WitgenCallback;
 use powdr_halo2::{generate_setup, Halo2Prover, Params};
-use powdr_number::{DegreeType, FieldElement};
+use powdr_number::{DegreeType, FieldElement, KnownField};
 
 pub(crate) struct Halo2ProverFactory;
 
@@ -32,6 +32,20 @@ impl<F: FieldElement> BackendFactory<F> for Halo2ProverFactory {
         let setup = generate_setup(size);
         Ok(setup.write(&mut output)?)
     }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            fields: Some(vec![KnownField::Bn254Field]),
+            identity_kinds: vec![
+                IdentityKind::Polynomial,
+                IdentityKind::Plookup,
+                IdentityKind::Permutation,
+            ],
+            aggregation: true,
+            publics: true,
+            power_of_two_degree: false,
+        }
+    }
 }
 
 impl<'a, T: FieldElement> Backend<'a, T> for Halo2Prover<'a, T> {
@@ -84,6 +98,20 @@ impl<F: FieldElement> BackendFactory<F> for Halo2MockFactory {
         }
         Ok(Box::new(Halo2Mock { pil, fixed }))
     }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            fields: Some(vec![KnownField::Bn254Field]),
+            identity_kinds: vec![
+                IdentityKind::Polynomial,
+                IdentityKind::Plookup,
+                IdentityKind::Permutation,
+            ],
+            aggregation: false,
+            publics: true,
+            power_of_two_degree: false,
+        }
+    }
 }
 
 pub struct Halo2Mock<'a, F: FieldElement> {
@@ -4,10 +4,11 @@
 mod halo2_impl;
 mod pilstark;
 
-use powdr_ast::analyzed::Analyzed;
+pub use pilstark::estark::{EStarkFactory, StarkStructConfig};
+use powdr_ast::analyzed::{Analyzed, IdentityKind};
 use powdr_executor::witgen::WitgenCallback;
-use powdr_number::{DegreeType, FieldElement};
-use std::{io, path::Path};
+use powdr_number::{DegreeType, FieldElement, KnownField};
+use std::{collections::BTreeMap, io, path::Path, time::Duration};
 use strum::{Display, EnumString, EnumVariantNames};
 
 #[derive(Clone, EnumString, EnumVariantNames, Display, Copy)]
@@ -30,7 +31,8 @@ impl BackendType {
         const HALO2_FACTORY: halo2_impl::Halo2ProverFactory = halo2_impl::Halo2ProverFactory;
         #[cfg(feature = "halo2")]
         const HALO2_MOCK_FACTORY: halo2_impl::Halo2MockFactory = halo2_impl::Halo2MockFactory;
-        const ESTARK_FACTORY: pilstark::estark::EStarkFactory = pilstark::estark::EStarkFactory;
+        static ESTARK_FACTORY: pilstark::estark::EStarkFactory =
+            pilstark::estark::EStarkFactory::new(pilstark::estark::StarkStructConfig::DEFAULT);
         const PIL_STARK_CLI_FACTORY: pilstark::PilStarkCliFactory = pilstark::PilStarkCliFactory;
 
         match self {
@@ -56,6 +58,29 @@ pub enum Error {
     NoVerificationAvailable,
     #[error("the backend does not support proof aggregation")]
     NoAggregationAvailable,
+    #[error("the backend does not support {0}")]
+    Unsupported(String),
+    #[error("the degree {degree} of namespace {namespace} is not a power of two")]
+    NonPowerOfTwoDegree {
+        namespace: String,
+        degree: DegreeType,
+    },
+    #[error("expected {expected} columns, but got {actual}")]
+    ColumnCountMismatch { expected: usize, actual: usize },
+    #[error("expected {expected} rows in column {column}, but got {actual}")]
+    ColumnLengthMismatch {
+        column: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("the value {value} in row {row} of column {column} does not fit into 64 bits")]
+    ValueOutOfRange {
+        column: String,
+        row: usize,
+        value: String,
+    },
+    #[error("the public inputs do not match the proof")]
+    PublicMismatch(#[from] PublicMismatch),
     #[error("internal backend error")]
     BackendError(String),
 }
@@ -68,6 +93,121 @@ impl From<String> for Error {
 
 pub type Proof = Vec<u8>;
 
+/// Information about how a proof was generated, for benchmarks and reproducibility records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofMetadata {
+    /// The name of the backend, as accepted by [BackendType]'s `FromStr`.
+    pub backend: String,
+    /// The field the proof is over, if it is a known field.
+    pub field: Option<KnownField>,
+    pub degree: DegreeType,
+    /// The time spent in [Backend::prove].
+    pub prover_time: Duration,
+    /// The size of the serialized proof in bytes.
+    pub proof_size: usize,
+    /// Backend-specific parameters, see [Backend::parameters].
+    pub parameters: BTreeMap<String, String>,
+    /// See `Pipeline::program_hash`.
+    pub program_hash: [u8; 32],
+}
+
+/// Describes how the publics committed in a proof differ from the expected ones.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("{mismatch_count} public(s) differ, the first one at index {first_index} (claimed {claimed_len}, expected {expected_len})")]
+pub struct PublicMismatch {
+    pub mismatch_count: usize,
+    pub first_index: usize,
+    pub claimed_len: usize,
+    pub expected_len: usize,
+}
+
+/// Checks that the publics claimed by a proof match the expected ones.
+///
+/// All elements are compared using `FieldElement::ct_eq` and the mismatches
+/// are only inspected after the full comparison, so the running time does not
+/// depend on the position of the first difference.
+pub fn verify_publics<F: FieldElement>(
+    claimed: &[F],
+    expected: &[F],
+) -> Result<(), PublicMismatch> {
+    let len = claimed.len().max(expected.len());
+    let matches = (0..len)
+        .map(|i| match (claimed.get(i), expected.get(i)) {
+            (Some(c), Some(e)) => c.ct_eq(e),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let mismatch_count = matches.iter().filter(|m| !**m).count();
+    if mismatch_count == 0 {
+        Ok(())
+    } else {
+        Err(PublicMismatch {
+            mismatch_count,
+            first_index: matches.iter().position(|m| !m).unwrap(),
+            claimed_len: claimed.len(),
+            expected_len: expected.len(),
+        })
+    }
+}
+
+/// Describes what a backend supports, so that unsupported PIL can be
+/// rejected before proving.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BackendCapabilities {
+    /// The fields the backend can prove over, or None if it supports any field.
+    pub fields: Option<Vec<KnownField>>,
+    /// The kinds of identities the backend can prove.
+    pub identity_kinds: Vec<IdentityKind>,
+    /// Whether the backend supports proof aggregation.
+    pub aggregation: bool,
+    /// Whether the backend supports public declarations.
+    pub publics: bool,
+    /// Whether the backend requires the degree to be a power of two.
+    pub power_of_two_degree: bool,
+}
+
+impl BackendCapabilities {
+    /// Checks that the field and the given PIL only use features supported by the backend.
+    pub fn check<F: FieldElement>(&self, pil: &Analyzed<F>) -> Result<(), Error> {
+        if let Some(fields) = &self.fields {
+            if !F::known_field().is_some_and(|field| fields.contains(&field)) {
+                return Err(Error::Unsupported(format!(
+                    "the field {:?}",
+                    F::known_field()
+                )));
+            }
+        }
+        if let Some(identity) = pil
+            .identities
+            .iter()
+            .find(|identity| !self.identity_kinds.contains(&identity.kind))
+        {
+            return Err(Error::Unsupported(format!(
+                "identities of kind {:?}, found: {identity}",
+                identity.kind
+            )));
+        }
+        if !self.publics && !pil.public_declarations.is_empty() {
+            return Err(Error::Unsupported("public declarations".to_string()));
+        }
+        if let Some(degree) = pil.degree.filter(|degree| !degree.is_power_of_two()) {
+            if self.power_of_two_degree {
+                // All namespaces share the degree, so report the one of the first column.
+                let namespace = pil
+                    .committed_polys_in_source_order()
+                    .into_iter()
+                    .chain(pil.constant_polys_in_source_order())
+                    .next()
+                    .and_then(|(symbol, _)| symbol.absolute_name.rsplit_once('.'))
+                    .map(|(namespace, _)| namespace.to_string())
+                    .unwrap_or_default();
+                return Err(Error::NonPowerOfTwoDegree { namespace, degree });
+            }
+        }
+        Ok(())
+    }
+}
+
 /*
     Bellow are the public interface traits. They are implemented in this
     module, wrapping the traits implemented by each backend.
@@ -85,10 +225,18 @@ pub trait BackendFactory<F: FieldElement> {
         verification_key: Option<&mut dyn io::Read>,
     ) -> Result<Box<dyn Backend<'a, F> + 'a>, Error>;
 
+    /// Returns what the backend supports.
+    fn capabilities(&self) -> BackendCapabilities;
+
     /// Generate a new setup.
     fn generate_setup(&self, _size: DegreeType, _output: &mut dyn io::Write) -> Result<(), Error> {
         Err(Error::NoSetupAvailable)
     }
+
+    /// Estimates the size in bytes of a proof for the given PIL, without proving.
+    fn estimated_proof_size(&self, _pil: &Analyzed<F>) -> Result<usize, Error> {
+        Err(Error::Unsupported("proof size estimates".to_string()))
+    }
 }
 
 /// Dynamic interface for a backend.
@@ -105,6 +253,30 @@ pub trait Backend<'a, F: FieldElement> {
         witgen_callback: WitgenCallback<F>,
     ) -> Result<Proof, Error>;
 
+    /// Like [Backend::prove], but requests the witness columns with the given names one
+    /// at a time from `witness`, so that backends that convert the witness into their own
+    /// representation do not need all of it in its original form at once.
+    /// By default, collects all columns and calls [Backend::prove].
+    fn prove_with_witness_provider(
+        &self,
+        column_names: &[String],
+        witness: &mut dyn FnMut(&str) -> Vec<F>,
+        prev_proof: Option<Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<Proof, Error> {
+        let witness = column_names
+            .iter()
+            .map(|name| (name.clone(), witness(name)))
+            .collect::<Vec<_>>();
+        self.prove(&witness, prev_proof, witgen_callback)
+    }
+
+    /// Returns the backend-specific parameters proofs are generated with,
+    /// like the FRI configuration, by name.
+    fn parameters(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
     /// Verifies a proof.
     fn verify(&self, _proof: &[u8], _instances: &[Vec<F>]) -> Result<(), Error> {
         Err(Error::NoVerificationAvailable)
@@ -122,3 +294,185 @@ pub trait Backend<'a, F: FieldElement> {
         Err(Error::NoVerificationAvailable)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use powdr_number::{Bn254Field, GoldilocksField};
+    use test_log::test;
+
+    fn publics(values: &[u64]) -> Vec<GoldilocksField> {
+        values.iter().map(|v| GoldilocksField::from(*v)).collect()
+    }
+
+    #[test]
+    fn estark_capabilities() {
+        let capabilities = BackendType::EStark
+            .factory::<GoldilocksField>()
+            .capabilities();
+        assert_eq!(capabilities.fields, Some(vec![KnownField::GoldilocksField]));
+        assert!(!capabilities.aggregation);
+        assert!(capabilities.publics);
+        assert!(capabilities.power_of_two_degree);
+    }
+
+    #[test]
+    fn estark_proof_size_scales_with_queries() {
+        let params = |n_queries| starky::types::StarkStruct {
+            nBits: 10,
+            nBitsExt: 11,
+            nQueries: n_queries,
+            verificationHashType: "GL".to_owned(),
+            steps: [11, 7, 3]
+                .map(|b| starky::types::Step { nBits: b })
+                .to_vec(),
+        };
+        let size = |n_queries| pilstark::estark::estimated_proof_size(&params(n_queries), 10, 3);
+        assert!(size(2) < size(4));
+        assert!(size(4) < size(8));
+        // Everything but the queries is independent of their number.
+        assert_eq!(size(8) - size(4), 2 * (size(4) - size(2)));
+    }
+
+    #[test]
+    fn estark_hash_type() {
+        let config = |hash_type: &str| StarkStructConfig {
+            verification_hash_type: hash_type.to_string().into(),
+            ..StarkStructConfig::DEFAULT
+        };
+        let params = config("BN128").stark_struct::<GoldilocksField>(8).unwrap();
+        assert_eq!(params.verificationHashType, "BN128");
+
+        let err = config("SHA256")
+            .stark_struct::<GoldilocksField>(8)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the backend does not support the verification hash type \"SHA256\", expected one of GL, BN128"
+        );
+    }
+
+    #[test]
+    fn estark_final_poly_bits() {
+        let step_bits = |final_poly_bits| {
+            StarkStructConfig {
+                final_poly_bits,
+                ..StarkStructConfig::DEFAULT
+            }
+            .stark_struct::<GoldilocksField>(1024)
+            .map(|params| params.steps.iter().map(|s| s.nBits).collect::<Vec<_>>())
+        };
+        assert_eq!(step_bits(None).unwrap(), vec![11, 7, 3]);
+        assert_eq!(step_bits(Some(3)).unwrap(), vec![11, 7, 3]);
+        assert_eq!(step_bits(Some(5)).unwrap(), vec![11, 7, 5]);
+        assert_eq!(step_bits(Some(0)).unwrap(), vec![11, 7, 3, 0]);
+        assert_eq!(step_bits(Some(11)).unwrap(), vec![11]);
+        assert_eq!(
+            step_bits(Some(12)).unwrap_err().to_string(),
+            "the backend does not support a final FRI polynomial of 2^12 elements for degree 1024, it can have at most 2^11 elements"
+        );
+    }
+
+    #[test]
+    fn estark_n_queries() {
+        let config = |n_queries| StarkStructConfig {
+            n_queries,
+            ..StarkStructConfig::DEFAULT
+        };
+        // One bit of security per query with a blowup factor of 2.
+        let params = config(None).stark_struct::<GoldilocksField>(8).unwrap();
+        assert_eq!(params.nQueries, pilstark::estark::SECURITY_BITS);
+
+        let params = config(Some(16)).stark_struct::<GoldilocksField>(8).unwrap();
+        assert_eq!(params.nQueries, 16);
+
+        let err = config(Some(0))
+            .stark_struct::<GoldilocksField>(8)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the backend does not support zero FRI queries, at least one is needed"
+        );
+    }
+
+    #[test]
+    fn estark_with_params() {
+        let params = StarkStructConfig::DEFAULT
+            .stark_struct::<GoldilocksField>(8)
+            .unwrap();
+        let err = |degree, params| EStarkFactory::with_params(degree, params).err().unwrap();
+        assert!(EStarkFactory::with_params(8, params.clone()).is_ok());
+        assert_eq!(
+            err(16, params.clone()).to_string(),
+            "the backend does not support 3 bits for degree 16, expected 4"
+        );
+        assert_eq!(
+            err(
+                8,
+                starky::types::StarkStruct {
+                    nQueries: 0,
+                    ..params.clone()
+                }
+            )
+            .to_string(),
+            "the backend does not support zero FRI queries, at least one is needed"
+        );
+        assert_eq!(
+            err(
+                8,
+                starky::types::StarkStruct {
+                    nBitsExt: 3,
+                    ..params
+                }
+            )
+            .to_string(),
+            "the backend does not support 3 extended bits for 3 bits, the blowup factor has to be at least 2"
+        );
+    }
+
+    #[test]
+    fn estark_value_out_of_range() {
+        let value = |v: u64| Bn254Field::from(v);
+        assert_eq!(pilstark::estark::to_u64(&value(7), "main.x", 3).unwrap(), 7);
+        let err = pilstark::estark::to_u64(&(value(u64::MAX) + value(1)), "main.x", 3).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the value 18446744073709551616 in row 3 of column main.x does not fit into 64 bits"
+        );
+    }
+
+    #[test]
+    fn matching_publics() {
+        assert_eq!(
+            verify_publics(&publics(&[1, 2, 3]), &publics(&[1, 2, 3])),
+            Ok(())
+        );
+        assert_eq!(verify_publics::<GoldilocksField>(&[], &[]), Ok(()));
+    }
+
+    #[test]
+    fn mismatching_publics() {
+        assert_eq!(
+            verify_publics(&publics(&[1, 7, 3, 8]), &publics(&[1, 2, 3, 4])),
+            Err(PublicMismatch {
+                mismatch_count: 2,
+                first_index: 1,
+                claimed_len: 4,
+                expected_len: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn publics_length_mismatch() {
+        assert_eq!(
+            verify_publics(&publics(&[1, 2]), &publics(&[1, 2, 3])),
+            Err(PublicMismatch {
+                mismatch_count: 1,
+                first_index: 2,
+                claimed_len: 2,
+                expected_len: 3,
+            })
+        );
+    }
+}
@@ -1,24 +1,201 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io;
 use std::iter::{once, repeat};
+use std::marker::PhantomData;
 use std::time::Instant;
 
-use crate::{pilstark, Backend, BackendFactory, Error};
-use powdr_ast::analyzed::Analyzed;
+use crate::{pilstark, Backend, BackendCapabilities, BackendFactory, Error};
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, Analyzed, IdentityKind,
+};
+use powdr_ast::SourceRef;
 use powdr_executor::witgen::WitgenCallback;
-use powdr_number::{DegreeType, FieldElement, GoldilocksField, LargeInt};
+use powdr_number::{DegreeType, FieldElement, KnownField, LargeInt};
 
+use serde::{de::DeserializeOwned, Serialize};
 use starky::{
     merklehash::MerkleTreeGL,
+    merklehash_bn128::MerkleTreeBN128,
     polsarray::{PolKind, PolsArray},
     stark_gen::StarkProof,
     stark_setup::StarkSetup,
     stark_verify::stark_verify,
-    traits::FieldExtension,
-    transcript::TranscriptGL,
+    traits::{FieldExtension, MerkleTree, Transcript},
+    transcript::{TranscriptBN128, TranscriptGL},
     types::{StarkStruct, Step, PIL},
 };
 
-pub struct EStarkFactory;
+/// The verification hash types supported by the eSTARK backend: Poseidon over
+/// Goldilocks and Poseidon over BN254, which is cheaper to verify inside a SNARK.
+pub const SUPPORTED_HASH_TYPES: [&str; 2] = ["GL", "BN128"];
+
+/// The conjectured security in bits that the default number of FRI queries achieves.
+pub const SECURITY_BITS: usize = 100;
+
+/// The parameters of an eSTARK proof that do not depend on the PIL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarkStructConfig {
+    /// The hash used for the Merkle trees and the transcript, one of [SUPPORTED_HASH_TYPES].
+    pub verification_hash_type: Cow<'static, str>,
+    /// The log2 of the size of the final FRI polynomial, which is sent in the clear.
+    /// A larger final polynomial needs fewer FRI steps, but makes the proof larger.
+    /// If not set, the FRI steps reduce the size by 2^4 until at most 2^5 elements are left.
+    pub final_poly_bits: Option<usize>,
+    /// The number of FRI queries. Each query adds log2 of the blowup factor bits of
+    /// conjectured security. If not set, enough queries for [SECURITY_BITS] are used.
+    pub n_queries: Option<usize>,
+}
+
+impl StarkStructConfig {
+    pub const DEFAULT: Self = StarkStructConfig {
+        verification_hash_type: Cow::Borrowed("GL"),
+        final_poly_bits: None,
+        n_queries: None,
+    };
+
+    /// Returns the parameters for a PIL of the given degree over the field `F`.
+    pub fn stark_struct<F: FieldElement>(&self, degree: DegreeType) -> Result<StarkStruct, Error> {
+        check_field::<F>()?;
+        check_hash_type(&self.verification_hash_type)?;
+
+        if self.n_queries == Some(0) {
+            return Err(Error::Unsupported(
+                "zero FRI queries, at least one is needed".to_string(),
+            ));
+        }
+
+        let n_bits = degree_bits(degree);
+        let n_bits_ext = n_bits + 1;
+        let blowup_bits = n_bits_ext - n_bits;
+        let n_queries = self
+            .n_queries
+            .unwrap_or((SECURITY_BITS + blowup_bits - 1) / blowup_bits);
+
+        let final_poly_bits = match self.final_poly_bits {
+            Some(bits) if bits > n_bits_ext => {
+                return Err(Error::Unsupported(format!(
+                    "a final FRI polynomial of 2^{bits} elements for degree {degree}, it can have at most 2^{n_bits_ext} elements"
+                )));
+            }
+            Some(bits) => bits,
+            None => (2..=n_bits_ext).rev().step_by(4).last().unwrap(),
+        };
+        let mut step_bits = (final_poly_bits..=n_bits_ext)
+            .rev()
+            .step_by(4)
+            .collect::<Vec<_>>();
+        if step_bits.last() != Some(&final_poly_bits) {
+            step_bits.push(final_poly_bits);
+        }
+        let steps = step_bits.into_iter().map(|b| Step { nBits: b }).collect();
+
+        Ok(StarkStruct {
+            nBits: n_bits,
+            nBitsExt: n_bits_ext,
+            nQueries: n_queries,
+            verificationHashType: self.verification_hash_type.to_string(),
+            steps,
+        })
+    }
+}
+
+/// Returns the log2 of the degree, rounded up.
+fn degree_bits(degree: DegreeType) -> usize {
+    assert!(degree > 1);
+    (DegreeType::BITS - (degree - 1).leading_zeros()) as usize
+}
+
+fn check_field<F: FieldElement>() -> Result<(), Error> {
+    if F::known_field() != Some(KnownField::GoldilocksField) {
+        return Err(Error::Unsupported(format!(
+            "the field {:?}, eSTARK is only implemented for Goldilocks",
+            F::known_field()
+        )));
+    }
+    Ok(())
+}
+
+fn check_hash_type(hash_type: &str) -> Result<(), Error> {
+    if !SUPPORTED_HASH_TYPES.contains(&hash_type) {
+        return Err(Error::Unsupported(format!(
+            "the verification hash type \"{hash_type}\", expected one of {}",
+            SUPPORTED_HASH_TYPES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+impl Default for StarkStructConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+pub struct EStarkFactory {
+    config: StarkStructConfig,
+    /// Parameters for PILs of a single degree, see [EStarkFactory::with_params].
+    params: Option<(DegreeType, StarkStruct)>,
+}
+
+impl EStarkFactory {
+    pub const fn new(config: StarkStructConfig) -> Self {
+        EStarkFactory {
+            config,
+            params: None,
+        }
+    }
+
+    /// Creates a factory that uses `params` as they are instead of deriving them from
+    /// a [StarkStructConfig]. It can only create backends for PILs of the given degree.
+    pub fn with_params(degree: DegreeType, params: StarkStruct) -> Result<Self, Error> {
+        let n_bits = degree_bits(degree);
+        if params.nBits != n_bits {
+            return Err(Error::Unsupported(format!(
+                "{} bits for degree {degree}, expected {n_bits}",
+                params.nBits
+            )));
+        }
+        if params.nBitsExt <= params.nBits {
+            return Err(Error::Unsupported(format!(
+                "{} extended bits for {} bits, the blowup factor has to be at least 2",
+                params.nBitsExt, params.nBits
+            )));
+        }
+        if params.nQueries == 0 {
+            return Err(Error::Unsupported(
+                "zero FRI queries, at least one is needed".to_string(),
+            ));
+        }
+        check_hash_type(&params.verificationHashType)?;
+        let step_bits = params.steps.iter().map(|s| s.nBits).collect::<Vec<_>>();
+        if step_bits.first() != Some(&params.nBitsExt) || !step_bits.windows(2).all(|w| w[0] > w[1])
+        {
+            return Err(Error::Unsupported(format!(
+                "the FRI steps {step_bits:?}, they have to start at {} bits and decrease",
+                params.nBitsExt
+            )));
+        }
+        Ok(EStarkFactory {
+            config: StarkStructConfig::DEFAULT,
+            params: Some((degree, params)),
+        })
+    }
+
+    /// Returns the parameters for a PIL of the given degree over the field `F`.
+    fn stark_struct<F: FieldElement>(&self, degree: DegreeType) -> Result<StarkStruct, Error> {
+        match &self.params {
+            Some((params_degree, params)) if *params_degree == degree => {
+                check_field::<F>()?;
+                Ok(params.clone())
+            }
+            Some((params_degree, _)) => Err(Error::Unsupported(format!(
+                "degree {degree}, the eSTARK parameters are for degree {params_degree}"
+            ))),
+            None => self.config.stark_struct::<F>(degree),
+        }
+    }
+}
 
 impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
     fn create<'a>(
@@ -29,58 +206,108 @@ impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
         setup: Option<&mut dyn std::io::Read>,
         verification_key: Option<&mut dyn std::io::Read>,
     ) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error> {
-        if F::modulus().to_arbitrary_integer() != GoldilocksField::modulus().to_arbitrary_integer()
-        {
-            unimplemented!("eSTARK is only implemented for Goldilocks field");
-        }
-
         if setup.is_some() {
             return Err(Error::NoSetupAvailable);
         }
 
-        let degree = pil.degree();
-        assert!(degree > 1);
-        let n_bits = (DegreeType::BITS - (degree - 1).leading_zeros()) as usize;
-        let n_bits_ext = n_bits + 1;
-
-        let steps = (2..=n_bits_ext)
-            .rev()
-            .step_by(4)
-            .map(|b| Step { nBits: b })
-            .collect();
-
-        let params = StarkStruct {
-            nBits: n_bits,
-            nBitsExt: n_bits_ext,
-            nQueries: 2,
-            verificationHashType: "GL".to_owned(),
-            steps,
-        };
-
-        let (pil_json, fixed) = pil_json(pil, fixed);
-        let const_pols = to_starky_pols_array(&fixed, &pil_json, PolKind::Constant);
+        let params = self.stark_struct::<F>(pil.degree())?;
+        match params.verificationHashType.as_str() {
+            "GL" => {
+                create_estark::<F, MerkleTreeGL, TranscriptGL>(pil, fixed, params, verification_key)
+            }
+            "BN128" => create_estark::<F, MerkleTreeBN128, TranscriptBN128>(
+                pil,
+                fixed,
+                params,
+                verification_key,
+            ),
+            _ => unreachable!(),
+        }
+    }
 
-        let setup = if let Some(vkey) = verification_key {
-            serde_json::from_reader(vkey).unwrap()
-        } else {
-            create_stark_setup(pil_json.clone(), &const_pols, &params)
-        };
+    fn estimated_proof_size(&self, pil: &Analyzed<F>) -> Result<usize, Error> {
+        Ok(estimated_proof_size(
+            &self.stark_struct::<F>(pil.degree())?,
+            pil.commitment_count(),
+            pil.constant_count(),
+        ))
+    }
 
-        Ok(Box::new(EStark {
-            fixed,
-            pil_json,
-            params,
-            setup,
-        }))
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            fields: Some(vec![KnownField::GoldilocksField]),
+            identity_kinds: vec![
+                IdentityKind::Polynomial,
+                IdentityKind::Plookup,
+                IdentityKind::Permutation,
+                IdentityKind::Connect,
+            ],
+            aggregation: false,
+            publics: true,
+            power_of_two_degree: true,
+        }
     }
 }
 
+/// Size of a Goldilocks element in bytes.
+const ELEMENT_SIZE: usize = 8;
+/// Size of an element of the cubic extension of Goldilocks in bytes.
+const EXTENSION_ELEMENT_SIZE: usize = 3 * ELEMENT_SIZE;
+/// Size of a Merkle tree node (four Goldilocks elements) in bytes.
+const HASH_SIZE: usize = 4 * ELEMENT_SIZE;
+/// The number of Merkle trees the polynomials are committed to: the constants
+/// and the four stages of the prover.
+const TREE_COUNT: usize = 5;
+
+/// Estimates the size in bytes of an eSTARK proof for a PIL with the given number
+/// of witness and fixed columns.
+/// Only accounts for the columns of the first stage and the quotient, so the
+/// actual proof is somewhat larger if there are lookups or permutations.
+pub fn estimated_proof_size(
+    params: &StarkStruct,
+    witness_count: usize,
+    fixed_count: usize,
+) -> usize {
+    let merkle_path_size = |bits: usize| bits * HASH_SIZE;
+
+    // The roots of the stage trees and the evaluations at the challenge point and its shift.
+    let roots = (TREE_COUNT - 1) * HASH_SIZE;
+    let evaluations = 2 * (witness_count + fixed_count + 2) * EXTENSION_ELEMENT_SIZE;
+
+    // For each query, the opened row in each tree and the authentication paths...
+    let tree_openings = (witness_count + fixed_count) * ELEMENT_SIZE
+        + 2 * EXTENSION_ELEMENT_SIZE
+        + TREE_COUNT * merkle_path_size(params.nBitsExt);
+    // ...and the folded values and paths of each FRI step.
+    let fri_openings = params
+        .steps
+        .windows(2)
+        .map(|steps| {
+            (1 << (steps[0].nBits - steps[1].nBits)) * EXTENSION_ELEMENT_SIZE
+                + merkle_path_size(steps[1].nBits)
+        })
+        .sum::<usize>();
+
+    let last_step_bits = params.steps.last().map_or(params.nBitsExt, |s| s.nBits);
+    let final_polynomial = (1 << last_step_bits) * EXTENSION_ELEMENT_SIZE;
+
+    roots + evaluations + params.nQueries * (tree_openings + fri_openings) + final_polynomial
+}
+
 fn pil_json<'a, F: FieldElement>(
     pil: &'a Analyzed<F>,
     fixed: &'a [(String, Vec<F>)],
 ) -> (PIL, Vec<(String, Vec<F>)>) {
     let degree = pil.degree();
 
+    let pil_with_identity;
+    let pil = if pil.identities.is_empty() {
+        pil_with_identity = with_vacuous_identity(pil);
+        &pil_with_identity
+    } else {
+        pil
+    };
+
     let mut pil: PIL = pilstark::json_exporter::export(pil);
 
     // TODO starky requires a fixed column with the equivalent
@@ -118,12 +345,76 @@ fn pil_json<'a, F: FieldElement>(
     (pil, fixed)
 }
 
-fn create_stark_setup(
+/// starky cannot handle a PIL without any constraints, so we add the identity
+/// `c * c - c * c = 0` on one of its columns. It holds for any assignment,
+/// which means that a proof for such a PIL only attests to the column values.
+fn with_vacuous_identity<F: FieldElement>(pil: &Analyzed<F>) -> Analyzed<F> {
+    let (symbol, _) = pil
+        .committed_polys_in_source_order()
+        .into_iter()
+        .chain(pil.constant_polys_in_source_order())
+        .next()
+        .expect("PIL has no columns");
+    let (name, poly_id) = symbol.array_elements().next().unwrap();
+    let column = AlgebraicExpression::Reference(AlgebraicReference {
+        name,
+        poly_id,
+        next: false,
+    });
+    let square = AlgebraicExpression::BinaryOperation(
+        Box::new(column.clone()),
+        AlgebraicBinaryOperator::Mul,
+        Box::new(column),
+    );
+    let identity = AlgebraicExpression::BinaryOperation(
+        Box::new(square.clone()),
+        AlgebraicBinaryOperator::Sub,
+        Box::new(square),
+    );
+
+    let mut pil = pil.clone();
+    pil.append_polynomial_identity(identity, SourceRef::unknown());
+    pil
+}
+
+fn create_estark<'a, F: FieldElement, M, T>(
+    pil: &'a Analyzed<F>,
+    fixed: &'a [(String, Vec<F>)],
+    params: StarkStruct,
+    verification_key: Option<&mut dyn std::io::Read>,
+) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error>
+where
+    M: MerkleTree + 'static,
+    T: Transcript + 'static,
+    StarkSetup<M>: Serialize + DeserializeOwned,
+    StarkProof<M>: Serialize + DeserializeOwned,
+{
+    let (pil_json, fixed) = pil_json(pil, fixed);
+    let const_pols = to_starky_pols_array(&fixed, &pil_json, PolKind::Constant)?;
+
+    let setup = if let Some(vkey) = verification_key {
+        serde_json::from_reader(vkey).map_err(|e| {
+            Error::BackendError(format!("Could not deserialize verification key: {e}"))
+        })?
+    } else {
+        create_stark_setup::<M>(pil_json.clone(), &const_pols, &params)
+    };
+
+    Ok(Box::new(EStark::<F, M, T> {
+        fixed,
+        pil_json,
+        params,
+        setup,
+        _transcript: PhantomData,
+    }))
+}
+
+fn create_stark_setup<M: MerkleTree>(
     mut pil: PIL,
     const_pols: &PolsArray,
     params: &StarkStruct,
-) -> StarkSetup<MerkleTreeGL> {
-    StarkSetup::<MerkleTreeGL>::new(
+) -> StarkSetup<M> {
+    StarkSetup::<M>::new(
         const_pols,
         &mut pil,
         params,
@@ -132,19 +423,22 @@ fn create_stark_setup(
     .unwrap()
 }
 
-pub struct EStark<F: FieldElement> {
+/// The eSTARK backend, using the Merkle tree `M` and the transcript `T`
+/// of the configured verification hash type.
+pub struct EStark<F: FieldElement, M: MerkleTree, T: Transcript> {
     fixed: Vec<(String, Vec<F>)>,
     pil_json: PIL,
     params: StarkStruct,
     // eSTARK calls it setup, but it works similarly to a verification key and depends only on the
     // constants and circuit.
-    setup: StarkSetup<MerkleTreeGL>,
+    setup: StarkSetup<M>,
+    _transcript: PhantomData<T>,
 }
 
-impl<F: FieldElement> EStark<F> {
+impl<F: FieldElement, M: MerkleTree, T: Transcript> EStark<F, M, T> {
     fn verify_stark_with_publics(
         &self,
-        proof: &StarkProof<MerkleTreeGL>,
+        proof: &StarkProof<M>,
         instances: &[Vec<F>],
     ) -> Result<(), Error> {
         assert_eq!(instances.len(), 1);
@@ -153,13 +447,49 @@ impl<F: FieldElement> EStark<F> {
             .iter()
             .map(|x| F::from(x.as_int()))
             .collect::<Vec<_>>();
-        assert_eq!(instances[0], proof_publics);
+        crate::verify_publics(&proof_publics, &instances[0])?;
 
         self.verify_stark(proof)
     }
 
-    fn verify_stark(&self, proof: &StarkProof<MerkleTreeGL>) -> Result<(), Error> {
-        match stark_verify::<MerkleTreeGL, TranscriptGL>(
+    /// Creates a proof for the given witness, converted to starky polynomials.
+    fn prove_pols(&self, cm_pols: PolsArray) -> Result<crate::Proof, Error>
+    where
+        StarkProof<M>: Serialize,
+    {
+        let start = Instant::now();
+
+        // TODO it would be good not to recompute this here
+        let const_pols = to_starky_pols_array(&self.fixed, &self.pil_json, PolKind::Constant)?;
+
+        let starkproof = StarkProof::<M>::stark_gen::<T>(
+            cm_pols,
+            const_pols,
+            &self.setup.const_tree,
+            &self.setup.starkinfo,
+            &self.setup.program,
+            &self.pil_json,
+            &self.params,
+            "",
+        );
+
+        let starkproof = match starkproof {
+            Ok(p) => p,
+            Err(e) => return Err(Error::BackendError(e.to_string())),
+        };
+
+        let duration = start.elapsed();
+
+        log::info!("Proof done in: {:?}", duration);
+
+        match self.verify_stark(&starkproof) {
+            Ok(_) => Ok(serde_json::to_string(&starkproof).unwrap().into_bytes()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn verify_stark(&self, proof: &StarkProof<M>) -> Result<(), Error> {
+        match stark_verify::<M, T>(
             proof,
             &self.setup.const_root,
             &self.setup.starkinfo,
@@ -173,10 +503,28 @@ impl<F: FieldElement> EStark<F> {
     }
 }
 
-impl<'a, F: FieldElement> Backend<'a, F> for EStark<F> {
+impl<'a, F: FieldElement, M: MerkleTree, T: Transcript> Backend<'a, F> for EStark<F, M, T>
+where
+    StarkSetup<M>: Serialize,
+    StarkProof<M>: Serialize + DeserializeOwned,
+{
+    fn parameters(&self) -> BTreeMap<String, String> {
+        let steps = self.params.steps.iter().map(|s| s.nBits.to_string());
+        [
+            ("hash", self.params.verificationHashType.clone()),
+            ("n_bits", self.params.nBits.to_string()),
+            ("n_bits_ext", self.params.nBitsExt.to_string()),
+            ("n_queries", self.params.nQueries.to_string()),
+            ("fri_steps", steps.collect::<Vec<_>>().join(",")),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+    }
+
     fn verify(&self, proof: &[u8], instances: &[Vec<F>]) -> Result<(), Error> {
-        let proof: StarkProof<MerkleTreeGL> =
-            serde_json::from_str(&String::from_utf8(proof.to_vec()).unwrap()).unwrap();
+        let proof: StarkProof<M> = serde_json::from_slice(proof)
+            .map_err(|e| Error::BackendError(format!("Could not deserialize proof: {e}")))?;
         self.verify_stark_with_publics(&proof, instances)
     }
 
@@ -196,36 +544,33 @@ impl<'a, F: FieldElement> Backend<'a, F> for EStark<F> {
 
         log::info!("Creating eSTARK proof.");
 
-        let cm_pols = to_starky_pols_array(witness, &self.pil_json, PolKind::Commit);
-        let start = Instant::now();
-
-        // TODO it would be good not to recompute this here
-        let const_pols = to_starky_pols_array(&self.fixed, &self.pil_json, PolKind::Constant);
-
-        let starkproof = StarkProof::<MerkleTreeGL>::stark_gen::<TranscriptGL>(
-            cm_pols,
-            const_pols,
-            &self.setup.const_tree,
-            &self.setup.starkinfo,
-            &self.setup.program,
-            &self.pil_json,
-            &self.params,
-            "",
-        );
-
-        let starkproof = match starkproof {
-            Ok(p) => p,
-            Err(e) => return Err(Error::BackendError(e.to_string())),
-        };
+        let cm_pols = to_starky_pols_array(witness, &self.pil_json, PolKind::Commit)?;
+        self.prove_pols(cm_pols)
+    }
 
-        let duration = start.elapsed();
+    fn prove_with_witness_provider(
+        &self,
+        column_names: &[String],
+        witness: &mut dyn FnMut(&str) -> Vec<F>,
+        prev_proof: Option<crate::Proof>,
+        _witgen_callback: WitgenCallback<F>,
+    ) -> Result<crate::Proof, Error> {
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+        if column_names.is_empty() {
+            return Err(Error::EmptyWitness);
+        }
 
-        log::info!("Proof done in: {:?}", duration);
+        log::info!("Creating eSTARK proof from a witness provider.");
 
-        match self.verify_stark(&starkproof) {
-            Ok(_) => Ok(serde_json::to_string(&starkproof).unwrap().into_bytes()),
-            Err(e) => Err(e),
+        // Only one column is held in its original form at a time.
+        let mut cm_pols = PolsArray::new(&self.pil_json, PolKind::Commit);
+        check_column_count(cm_pols.array.len(), column_names.len())?;
+        for (name, to) in column_names.iter().zip(cm_pols.array.iter_mut()) {
+            to_starky_pol(name, &witness(name), to)?;
         }
+        self.prove_pols(cm_pols)
     }
 
     fn export_verification_key(&self, output: &mut dyn io::Write) -> Result<(), Error> {
@@ -242,18 +587,53 @@ fn to_starky_pols_array<F: FieldElement>(
     array: &[(String, Vec<F>)],
     pil: &PIL,
     kind: PolKind,
-) -> PolsArray {
+) -> Result<PolsArray, Error> {
     let mut output = PolsArray::new(pil, kind);
-    assert_eq!(output.array.len(), array.len());
-    for ((_, from), to) in array.iter().zip(output.array.iter_mut()) {
-        assert_eq!(from.len(), to.len());
-
-        for (f, t) in from.iter().zip(to.iter_mut()) {
-            *t = TryInto::<u64>::try_into(f.to_integer().to_arbitrary_integer())
-                .unwrap()
-                .into();
-        }
+    check_column_count(output.array.len(), array.len())?;
+    for ((name, from), to) in array.iter().zip(output.array.iter_mut()) {
+        to_starky_pol(name, from, to)?;
+    }
+
+    Ok(output)
+}
+
+fn check_column_count(expected: usize, actual: usize) -> Result<(), Error> {
+    if expected != actual {
+        return Err(Error::ColumnCountMismatch { expected, actual });
     }
+    Ok(())
+}
+
+/// Converts the values of the column `name` into the starky polynomial `to`.
+fn to_starky_pol<F: FieldElement, E: From<u64>>(
+    name: &str,
+    from: &[F],
+    to: &mut [E],
+) -> Result<(), Error> {
+    if from.len() != to.len() {
+        return Err(Error::ColumnLengthMismatch {
+            column: name.to_string(),
+            expected: to.len(),
+            actual: from.len(),
+        });
+    }
+
+    for (row, (f, t)) in from.iter().zip(to.iter_mut()).enumerate() {
+        *t = to_u64(f, name, row)?.into();
+    }
+    Ok(())
+}
 
-    output
+/// Converts the value in the given row of the given column to a `u64`, which fails
+/// if the value was computed in a field larger than Goldilocks.
+pub(crate) fn to_u64<F: FieldElement>(value: &F, column: &str, row: usize) -> Result<u64, Error> {
+    value
+        .to_integer()
+        .to_arbitrary_integer()
+        .try_into()
+        .map_err(|_| Error::ValueOutOfRange {
+            column: column.to_string(),
+            row,
+            value: value.to_string(),
+        })
 }
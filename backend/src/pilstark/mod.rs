@@ -7,10 +7,10 @@ use std::{
     path::Path,
 };
 
-use crate::{Backend, BackendFactory, Error, Proof};
-use powdr_ast::analyzed::Analyzed;
+use crate::{Backend, BackendCapabilities, BackendFactory, Error, Proof};
+use powdr_ast::analyzed::{Analyzed, IdentityKind};
 use powdr_executor::witgen::WitgenCallback;
-use powdr_number::FieldElement;
+use powdr_number::{FieldElement, KnownField};
 
 pub struct PilStarkCliFactory;
 
@@ -34,6 +34,21 @@ impl<F: FieldElement> BackendFactory<F> for PilStarkCliFactory {
             output_dir,
         }))
     }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            fields: Some(vec![KnownField::GoldilocksField]),
+            identity_kinds: vec![
+                IdentityKind::Polynomial,
+                IdentityKind::Plookup,
+                IdentityKind::Permutation,
+                IdentityKind::Connect,
+            ],
+            aggregation: false,
+            publics: true,
+            power_of_two_degree: true,
+        }
+    }
 }
 
 pub struct PilStarkCli<'a, F: FieldElement> {
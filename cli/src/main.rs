@@ -11,7 +11,9 @@ use powdr_number::{read_polys_csv_file, CsvRenderMode};
 use powdr_number::{Bn254Field, FieldElement, GoldilocksField};
 use powdr_pipeline::util::write_or_panic;
 use powdr_pipeline::Pipeline;
-use powdr_riscv::continuations::{rust_continuations, rust_continuations_dry_run};
+use powdr_riscv::continuations::{
+    rust_continuations, rust_continuations_dry_run, ContinuationsOptions,
+};
 use powdr_riscv::{compile_riscv_asm, compile_rust};
 use std::io::{self, BufWriter};
 use std::path::PathBuf;
@@ -807,9 +809,14 @@ fn run<F: FieldElement>(
 ) -> Result<(), Vec<String>> {
     let bootloader_inputs = if continuations {
         pipeline = pipeline.with_prover_inputs(inputs.clone());
-        rust_continuations_dry_run(&mut pipeline)
+        rust_continuations_dry_run(
+            &mut pipeline,
+            &Default::default(),
+            ContinuationsOptions::default(),
+        )
+        .map_err(|e| vec![e])?
     } else {
-        vec![]
+        Default::default()
     };
 
     let generate_witness_and_prove_maybe = |mut pipeline: Pipeline<F>| -> Result<(), Vec<String>> {
@@ -833,6 +840,7 @@ fn run<F: FieldElement>(
                 pipeline.data_callback().unwrap(),
                 &[],
                 powdr_riscv_executor::ExecMode::Fast,
+                Default::default(),
             );
         }
         (false, true) => {
@@ -840,6 +848,7 @@ fn run<F: FieldElement>(
                 pipeline,
                 generate_witness_and_prove_maybe,
                 bootloader_inputs,
+                ContinuationsOptions::default(),
             )?;
         }
         (false, false) => {
@@ -1,11 +1,12 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::{Arc, RwLock},
 };
 
 use itertools::Itertools;
 use powdr_ast::{
-    analyzed::{Analyzed, Expression, FunctionValueDefinition, Symbol, TypedExpression},
+    analyzed::{Analyzed, Expression, FunctionValueDefinition, PolyID, Symbol, TypedExpression},
     parsed::{
         types::{ArrayType, Type},
         IndexAccess,
@@ -20,20 +21,140 @@ use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 /// @returns the names (in source order) and the values for the columns.
 /// Arrays of columns are flattened, the name of the `i`th array element
 /// is `name[i]`.
+/// The columns are generated in parallel, but the result does not depend on the
+/// order in which they complete.
 pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(String, Vec<T>)> {
-    let mut fixed_cols = HashMap::new();
+    let fixed_cols = defined_columns(analyzed)
+        .into_par_iter()
+        .map(|(name, id, value, index)| {
+            let values = generate_values(analyzed, analyzed.degree(), &name, value, index);
+            (name, (id, values))
+        })
+        .collect::<Vec<_>>();
+    sorted_by_poly_id(fixed_cols)
+}
+
+/// The name of a fixed column and the values of its definition at some rows.
+pub type ColumnSamples<T> = (String, Vec<(DegreeType, T)>);
+
+/// Re-evaluates the definitions of all defined fixed columns at a few rows,
+/// independently of [generate], so that materialized columns can be checked.
+/// The sampled rows are the first, middle and last row and, for array definitions,
+/// the first and last row of each part and the row where a repeated pattern starts over.
+/// @returns the names (in source order) and the sampled rows with their values.
+/// Fails if a definition cannot be evaluated at one of the sampled rows.
+pub fn sample<T: FieldElement>(analyzed: &Analyzed<T>) -> Result<Vec<ColumnSamples<T>>, String> {
+    let degree = analyzed.degree();
+    defined_columns(analyzed)
+        .into_iter()
+        .map(|(name, _, value, index)| {
+            let samples = sampled_rows(value, degree)
+                .into_iter()
+                .map(|row| Ok((row, evaluate_at_row(analyzed, &name, value, index, row)?)))
+                .collect::<Result<_, String>>()?;
+            Ok((name, samples))
+        })
+        .collect()
+}
+
+fn sampled_rows(body: &FunctionValueDefinition, degree: DegreeType) -> BTreeSet<DegreeType> {
+    let mut rows = BTreeSet::from([0, degree / 2, degree.saturating_sub(1)]);
+    if let FunctionValueDefinition::Array(parts) = body {
+        let mut start = 0;
+        for part in parts {
+            let end = start + part.size();
+            let seam = start + part.pattern().len() as DegreeType;
+            rows.extend(
+                [start, seam, end.saturating_sub(1)]
+                    .into_iter()
+                    .filter(|row| (start..end).contains(row)),
+            );
+            start = end;
+        }
+    }
+    rows.retain(|row| *row < degree);
+    rows
+}
+
+/// Evaluates the definition of a single fixed column in a single row.
+fn evaluate_at_row<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    name: &str,
+    body: &FunctionValueDefinition,
+    index: Option<u64>,
+    row: DegreeType,
+) -> Result<T, String> {
+    let mut symbols = CachedSymbols {
+        symbols: &analyzed.definitions,
+        cache: Arc::new(RwLock::new(Default::default())),
+    };
+    let result = match body {
+        FunctionValueDefinition::Expression(TypedExpression { e, .. }) => {
+            let e = column_expression(e, index);
+            evaluator::evaluate(&e, &mut symbols)
+                .and_then(|fun| {
+                    evaluator::evaluate_function_call(
+                        fun,
+                        vec![Arc::new(Value::Integer(BigInt::from(row)))],
+                        &mut symbols,
+                    )
+                })
+                .and_then(|v| v.try_to_field_element())
+        }
+        FunctionValueDefinition::Array(parts) => {
+            let mut start = 0;
+            let (part, offset) = parts
+                .iter()
+                .find_map(|part| {
+                    let part_start = start;
+                    start += part.size();
+                    (row < start).then_some((part, row - part_start))
+                })
+                .unwrap();
+            let pattern = part.pattern();
+            evaluator::evaluate(
+                &pattern[(offset % pattern.len() as DegreeType) as usize],
+                &mut symbols,
+            )
+            .and_then(|v| v.try_to_field_element())
+        }
+        FunctionValueDefinition::TypeDeclaration(_)
+        | FunctionValueDefinition::TypeConstructor(_, _) => panic!(),
+    };
+    result.map_err(|err| format!("Error evaluating fixed polynomial {name} in row {row}: {err}"))
+}
+
+/// Returns the expression defining a column, which is `e[index]` for elements of arrays of columns.
+fn column_expression(e: &Expression, index: Option<u64>) -> Cow<'_, Expression> {
+    match index {
+        Some(index) => Cow::Owned(Expression::IndexAccess(IndexAccess {
+            array: e.clone().into(),
+            index: Box::new(Expression::Number(index.into(), None)),
+        })),
+        None => Cow::Borrowed(e),
+    }
+}
+
+/// @returns the name, ID, definition and array index of each defined fixed column.
+fn defined_columns<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+) -> Vec<(String, PolyID, &FunctionValueDefinition, Option<u64>)> {
+    let mut columns = vec![];
     for (poly, value) in analyzed.constant_polys_in_source_order() {
         if let Some(value) = value {
             // For arrays, generate values for each index,
             // for non-arrays, set index to None.
             for (index, (name, id)) in poly.array_elements().enumerate() {
                 let index = poly.is_array().then_some(index as u64);
-                let values = generate_values(analyzed, analyzed.degree(), &name, value, index);
-                assert!(fixed_cols.insert(name, (id, values)).is_none());
+                columns.push((name, id, value, index));
             }
         }
     }
+    columns
+}
 
+fn sorted_by_poly_id<T>(fixed_cols: Vec<(String, (PolyID, Vec<T>))>) -> Vec<(String, Vec<T>)> {
+    assert!(fixed_cols.iter().map(|(name, _)| name).all_unique());
     fixed_cols
         .into_iter()
         .sorted_by_key(|(_, (id, _))| *id)
@@ -66,21 +187,12 @@ fn generate_values<T: FieldElement>(
                     panic!("Invalid fixed column type: {}", ty);
                 }
             };
-            let index_expr;
-            let e = if let Some(index) = index {
-                index_expr = Expression::IndexAccess(IndexAccess {
-                    array: e.clone().into(),
-                    index: Box::new(Expression::Number(index.into(), None)),
-                });
-                &index_expr
-            } else {
-                e
-            };
+            let e = column_expression(e, index);
             (0..degree)
                 .into_par_iter()
                 .map(|i| {
                     let mut symbols = symbols.clone();
-                    let fun = evaluator::evaluate(e, &mut symbols).unwrap();
+                    let fun = evaluator::evaluate(&e, &mut symbols).unwrap();
                     evaluator::evaluate_function_call(
                         fun,
                         vec![Arc::new(Value::Integer(BigInt::from(i)))],
@@ -600,4 +712,77 @@ mod test {
             ("F.a".to_string(), convert([14, 15, 16, 17].to_vec()))
         );
     }
+
+    #[test]
+    pub fn parallel_matches_serial() {
+        let src = r#"
+            let N = 16;
+            namespace F(N);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST(i) { if i == N - 1 { 1 } else { 0 } };
+                col fixed SQUARE(i) { i * i };
+                col fixed TRIPLE(i) { 3 * i };
+                col fixed MOD5(i) { i % 5 };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let serial = sorted_by_poly_id(
+            defined_columns(&analyzed)
+                .into_iter()
+                .map(|(name, id, value, index)| {
+                    let values = generate_values(&analyzed, analyzed.degree(), &name, value, index);
+                    (name, (id, values))
+                })
+                .collect(),
+        );
+        assert_eq!(serial.len(), 5);
+        for _ in 0..4 {
+            assert_eq!(generate(&analyzed), serial);
+        }
+    }
+
+    #[test]
+    pub fn sample_matches_generate() {
+        let src = r#"
+            let N: int = 16;
+            namespace F(N);
+                col fixed A = [1, 2] + [3, 4, 5]* + [6];
+                col fixed SQUARE(i) { i * i };
+                let ARR: col[2] = [|i| 7, |i| 9];
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let generated = generate(&analyzed).into_iter().collect::<HashMap<_, _>>();
+        let samples = sample(&analyzed).unwrap();
+        assert_eq!(
+            samples
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["F.A", "F.SQUARE", "F.ARR[0]", "F.ARR[1]"]
+        );
+        for (name, samples) in &samples {
+            for (row, value) in samples {
+                assert_eq!(generated[name][*row as usize], *value);
+            }
+        }
+        // First and last row of each part, the start of the second repetition
+        // and the middle row.
+        let rows = samples[0].1.iter().map(|(row, _)| *row).collect::<Vec<_>>();
+        assert_eq!(rows, [0, 1, 2, 5, 8, 14, 15]);
+    }
+
+    #[test]
+    pub fn sample_reports_errors() {
+        let src = r#"
+            let N: int = 16;
+            namespace F(N);
+                let values: fe[] = [1, 2];
+                col fixed A(i) { values[i] };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let err = sample(&analyzed).unwrap_err();
+        assert!(
+            err.starts_with("Error evaluating fixed polynomial F.A in row 8:"),
+            "{err}"
+        );
+    }
 }
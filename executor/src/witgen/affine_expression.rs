@@ -204,6 +204,12 @@ where
             return Ok(result);
         }
 
+        // If all variables are boolean, there might be a single assignment that satisfies
+        // the equation even though the bit masks overlap, e.g. `x + y = 2`.
+        if let Some(result) = self.try_solve_booleans(known_constraints) {
+            return result;
+        }
+
         // Now at least try to propagate constraints to a variable from the other parts of the equation.
         let constraints = (match (
             self.try_transfer_constraints(known_constraints),
@@ -338,6 +344,57 @@ where
         Some((*solve_for, constraint))
     }
 
+    /// Tries to find the unique assignment of all variables, if they are all boolean
+    /// and there are at most `MAX_BOOLEAN_VARIABLES` of them.
+    /// Returns None if this is not the case or if there are multiple solutions.
+    fn try_solve_booleans(
+        &self,
+        known_constraints: &impl RangeConstraintSet<K, T>,
+    ) -> Option<EvalResult<T, K>> {
+        const MAX_BOOLEAN_VARIABLES: usize = 10;
+
+        let variables = self
+            .nonzero_coefficients()
+            .map(|(i, coeff)| {
+                known_constraints
+                    .range_constraint(*i)
+                    .filter(|constraint| constraint.is_boolean())
+                    .map(|_| (*i, *coeff))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if variables.len() > MAX_BOOLEAN_VARIABLES {
+            return None;
+        }
+
+        let offset = self.offset();
+        let mut solutions = (0..1u32 << variables.len()).filter(|assignment| {
+            variables
+                .iter()
+                .enumerate()
+                .filter(|(bit, _)| assignment & (1 << bit) != 0)
+                .fold(offset, |acc, (_, (_, coeff))| acc + *coeff)
+                .is_zero()
+        });
+        Some(match (solutions.next(), solutions.next()) {
+            (None, _) => Err(ConflictingRangeConstraints),
+            (Some(assignment), None) => Ok(EvalValue::complete(
+                variables
+                    .iter()
+                    .enumerate()
+                    .map(|(bit, (i, _))| {
+                        let value = if assignment & (1 << bit) != 0 {
+                            T::one()
+                        } else {
+                            T::zero()
+                        };
+                        (*i, Constraint::Assignment(value))
+                    })
+                    .collect(),
+            )),
+            (Some(_), Some(_)) => return None,
+        })
+    }
+
     /// Tries to assign values to all variables through their bit constraints.
     /// This can also determine if the equation is not satisfiable,
     /// if the bit-constraints do not cover all the bits of the offset.
@@ -601,7 +658,7 @@ mod test {
 
     use super::*;
     use crate::witgen::{range_constraints::RangeConstraint, EvalError};
-    use powdr_number::{FieldElement, GoldilocksField};
+    use powdr_number::{FieldElement, GoldilocksField, MockField};
     use pretty_assertions::assert_eq;
     use test_log::test;
 
@@ -643,6 +700,18 @@ mod test {
         assert_eq!(a.constant_value().unwrap(), 0.into());
     }
 
+    #[test]
+    pub fn test_affine_solve_mock_field() {
+        // 3 * x + 95 = 0 over the integers modulo 97, i.e. 3 * x = 2, so x = 2 / 3 = 2 * 65 = 33.
+        let a = AffineExpression::<_, MockField>::OneVar((0, 3.into()), 95.into());
+        let updates = a.solve().unwrap();
+        assert_eq!(
+            updates.constraints,
+            [(0, Constraint::Assignment(33.into()))]
+        );
+        assert_eq!(MockField::from(3) * MockField::from(33), MockField::from(2));
+    }
+
     #[test]
     pub fn test_affine_neg() {
         let a = AffineExpression::ManyVars(convert(vec![1, 2]), 9.into());
@@ -843,6 +912,41 @@ mod test {
         };
     }
 
+    #[test]
+    pub fn solve_booleans() {
+        let known_constraints: TestRangeConstraints<GoldilocksField> =
+            TestRangeConstraints((1..=3).map(|i| (i, RangeConstraint::boolean())).collect());
+        let sum = AffineExpression::from_variable_id(1)
+            + AffineExpression::from_variable_id(2)
+            + AffineExpression::from_variable_id(3);
+
+        // The bit masks overlap, but only one assignment satisfies the equation.
+        let expr = sum.clone() - AffineExpression::from(GoldilocksField::from(3));
+        assert_eq!(
+            expr.solve_with_range_constraints(&known_constraints)
+                .unwrap(),
+            EvalValue::complete(
+                (1..=3)
+                    .map(|i| (i, Constraint::Assignment(1.into())))
+                    .collect()
+            )
+        );
+
+        // Several assignments satisfy the equation.
+        let expr = sum.clone() - AffineExpression::from(GoldilocksField::from(1));
+        assert!(!expr
+            .solve_with_range_constraints(&known_constraints)
+            .unwrap()
+            .is_complete());
+
+        // No assignment satisfies the equation.
+        let expr = sum - AffineExpression::from(GoldilocksField::from(4));
+        match expr.solve_with_range_constraints(&known_constraints) {
+            Err(EvalError::ConflictingRangeConstraints) => {}
+            _ => panic!(),
+        };
+    }
+
     #[test]
     pub fn transfer_range_constraints() {
         // x2 * 0x100 + x3 - x1 - 200 = 0,
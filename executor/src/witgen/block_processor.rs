@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use powdr_ast::analyzed::{
-    AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID,
+    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
 };
 use powdr_number::FieldElement;
 
@@ -10,9 +10,28 @@ use super::{
     processor::{OuterQuery, Processor},
     rows::{RowIndex, UnknownStrategy},
     sequence_iterator::{Action, ProcessingSequenceIterator, SequenceStep},
-    EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
+    Constraints, EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
 };
 
+/// The default maximum number of passes over the rows of a block in [BlockProcessor::solve].
+/// It is far above what any reasonable PIL needs and only guards against runaway solving.
+pub const DEFAULT_MAX_PASSES: usize = 1_000_000;
+
+/// The default number of times [BlockProcessor::solve] starts over with a different
+/// identity order if an attempt made progress but did not determine all values.
+pub const DEFAULT_MAX_RETRIES: usize = 2;
+
+/// Whether the identities of a block also constrain the last row together
+/// with the first row, see [BlockProcessor::check_constraints].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrappingPolicy {
+    /// The row after the last row is the first row.
+    Cyclic,
+    /// The last row has no next row, like in the Fibonacci example.
+    #[default]
+    Acyclic,
+}
+
 /// A basic processor that knows how to determine a unique satisfying witness
 /// for a given list of identities.
 /// The lifetimes mean the following:
@@ -23,6 +42,14 @@ pub struct BlockProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     processor: Processor<'a, 'b, 'c, T, Q>,
     /// The list of identities
     identities: &'c [&'a Identity<Expression<T>>],
+    /// The steps of the last call to `solve` that made progress.
+    recorded_sequence: Vec<SequenceStep>,
+    /// The maximum number of passes (see [ProcessingSequenceIterator::round_count]) in `solve`.
+    max_passes: usize,
+    /// The maximum number of additional attempts in `solve`, see [DEFAULT_MAX_RETRIES].
+    max_retries: usize,
+    /// Whether `check_constraints` also checks the pair of the last and the first row.
+    wrapping_policy: WrappingPolicy,
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c, T, Q> {
@@ -38,6 +65,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
         Self {
             processor,
             identities,
+            recorded_sequence: vec![],
+            max_passes: DEFAULT_MAX_PASSES,
+            max_retries: DEFAULT_MAX_RETRIES,
+            wrapping_policy: WrappingPolicy::default(),
         }
     }
 
@@ -48,6 +79,38 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
         Self {
             processor,
             identities,
+            recorded_sequence: vec![],
+            max_passes: DEFAULT_MAX_PASSES,
+            max_retries: DEFAULT_MAX_RETRIES,
+            wrapping_policy: WrappingPolicy::default(),
+        }
+    }
+
+    /// Sets the maximum number of passes over the rows in `solve`, after which
+    /// it fails with [EvalError::IterationLimit].
+    #[cfg(test)]
+    pub fn with_max_passes(self, max_passes: usize) -> BlockProcessor<'a, 'b, 'c, T, Q> {
+        Self { max_passes, ..self }
+    }
+
+    /// Sets how often `solve` starts over with a different identity order if
+    /// an attempt made progress but did not determine all values.
+    #[cfg(test)]
+    pub fn with_max_retries(self, max_retries: usize) -> BlockProcessor<'a, 'b, 'c, T, Q> {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    /// Sets whether `check_constraints` also checks the pair of the last and the first row.
+    pub fn with_wrapping_policy(
+        self,
+        wrapping_policy: WrappingPolicy,
+    ) -> BlockProcessor<'a, 'b, 'c, T, Q> {
+        Self {
+            wrapping_policy,
+            ..self
         }
     }
 
@@ -60,14 +123,53 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
     }
 
     /// Figures out unknown values.
+    /// If the sequence ends with values still unknown, but the attempt made progress,
+    /// starts over (deterministically) with the identities that were blocked moved
+    /// to the end, up to the maximum number of retries.
     /// Returns the assignments to outer query columns.
     pub fn solve(
         &mut self,
         sequence_iterator: &mut ProcessingSequenceIterator,
     ) -> Result<EvalValue<&'a AlgebraicReference, T>, EvalError<T>> {
         let mut outer_assignments = vec![];
+        self.recorded_sequence.clear();
+
+        let mut retries = 0;
+        loop {
+            let progress_in_attempt =
+                self.solve_attempt(sequence_iterator, &mut outer_assignments)?;
+            if !progress_in_attempt
+                || retries == self.max_retries
+                || self.processor.unknown_count() == 0
+                || !sequence_iterator.restart()
+            {
+                break;
+            }
+            retries += 1;
+            log::trace!(
+                "Block not solved yet, retrying with blocked identities last (retry {retries})."
+            );
+        }
+
+        match self.processor.finished_outer_query() {
+            true => Ok(EvalValue::complete(outer_assignments)),
+            false => Ok(EvalValue::incomplete_with_constraints(
+                outer_assignments,
+                IncompleteCause::BlockMachineLookupIncomplete,
+            )),
+        }
+    }
 
-        while let Some(SequenceStep { row_delta, action }) = sequence_iterator.next() {
+    /// Processes the steps of the sequence iterator until it ends.
+    /// Returns whether any step made progress.
+    fn solve_attempt(
+        &mut self,
+        sequence_iterator: &mut ProcessingSequenceIterator,
+        outer_assignments: &mut Constraints<&'a AlgebraicReference, T>,
+    ) -> Result<bool, EvalError<T>> {
+        let mut progress_in_attempt = false;
+        while let Some(step) = sequence_iterator.next() {
+            let SequenceStep { row_delta, action } = step;
             let row_index = (1 + row_delta) as usize;
             let progress = match action {
                 Action::InternalIdentity(identity_index) => {
@@ -87,16 +189,99 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
                 }
                 Action::ProverQueries => self.processor.process_queries(row_index)?,
             };
+            if progress {
+                self.recorded_sequence.push(step);
+                progress_in_attempt = true;
+            }
             sequence_iterator.report_progress(progress);
+
+            if let Some(passes) = sequence_iterator.round_count() {
+                if passes > self.max_passes {
+                    return Err(EvalError::IterationLimit {
+                        passes,
+                        remaining_unknowns: self.processor.unknown_count(),
+                    });
+                }
+            }
         }
+        Ok(progress_in_attempt)
+    }
 
-        match self.processor.finished_outer_query() {
-            true => Ok(EvalValue::complete(outer_assignments)),
-            false => Ok(EvalValue::incomplete_with_constraints(
-                outer_assignments,
-                IncompleteCause::BlockMachineLookupIncomplete,
-            )),
+    /// Returns the steps of the last call to `solve` that made progress, in order.
+    /// Replaying them via `ProcessingSequenceIterator::Cached` solves the same block.
+    pub fn recorded_sequence(&self) -> Vec<SequenceStep> {
+        self.recorded_sequence.clone()
+    }
+
+    /// Checks whether values are unknown because the identities depend on each other
+    /// cyclically, see [Processor::cyclic_dependency]. This is a diagnostic for a block
+    /// that could not be completed: It builds the dependency graph of all rows, so it should
+    /// only be called once `solve` has finally failed.
+    pub fn check_cyclic_dependencies(&self) -> Result<(), EvalError<T>> {
+        match self.processor.cyclic_dependency(self.identities) {
+            None => Ok(()),
+            Some((row, identities)) => Err(EvalError::CyclicDependency {
+                row,
+                identities: identities.iter().map(|i| i.to_string()).collect(),
+            }),
+        }
+    }
+
+    /// Checks that all identities are satisfied on all pairs of consecutive rows,
+    /// treating unknown values as zero.
+    /// The pair of the last and the first row is only checked with [WrappingPolicy::Cyclic].
+    /// Can be used to validate a block before its rows are committed. On failure, the error
+    /// names the first failing identity, its row and the known values of the rows involved.
+    pub fn check_constraints(&mut self) -> Result<(), EvalError<T>> {
+        let row_pairs = match self.wrapping_policy {
+            WrappingPolicy::Cyclic => self.processor.len(),
+            WrappingPolicy::Acyclic => self.processor.len().saturating_sub(1),
+        };
+        for row_index in 0..row_pairs {
+            for &identity in self.identities {
+                self.processor.check_identity(row_index, identity)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [BlockProcessor::check_constraints], but evaluates the polynomial identities
+    /// on all rows in parallel, see [Processor::find_polynomial_identity_violation].
+    /// All other identities need the mutable state and are still checked sequentially.
+    /// Returns the same error as [BlockProcessor::check_constraints], i.e. the one of
+    /// the lowest failing row.
+    pub fn check_constraints_parallel(&mut self) -> Result<(), EvalError<T>> {
+        let row_pairs = match self.wrapping_policy {
+            WrappingPolicy::Cyclic => self.processor.len(),
+            WrappingPolicy::Acyclic => self.processor.len().saturating_sub(1),
+        };
+        let (polynomial_identities, other_identities): (Vec<_>, Vec<_>) = self
+            .identities
+            .iter()
+            .copied()
+            .partition(|identity| identity.kind == IdentityKind::Polynomial);
+        let mut start = 0;
+        while start < row_pairs {
+            let violation = self
+                .processor
+                .find_polynomial_identity_violation(start..row_pairs, &polynomial_identities);
+            let end = violation.unwrap_or(row_pairs);
+            for row_index in start..end {
+                for &identity in &other_identities {
+                    self.processor.check_identity(row_index, identity)?;
+                }
+            }
+            let Some(row_index) = violation else {
+                break;
+            };
+            // Check all identities in their original order to report the same error
+            // as `check_constraints`.
+            for &identity in self.identities {
+                self.processor.check_identity(row_index, identity)?;
+            }
+            start = row_index + 1;
         }
+        Ok(())
     }
 
     pub fn finish(self) -> FinalizableData<'a, T> {
@@ -106,10 +291,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashMap};
 
-    use powdr_ast::analyzed::{PolyID, PolynomialType};
-    use powdr_number::{FieldElement, GoldilocksField};
+    use powdr_ast::analyzed::{IdentityKind, PolyID, PolynomialType};
+    use powdr_number::{FieldElement, GoldilocksField, MockField};
     use powdr_pil_analyzer::analyze_string;
 
     use crate::{
@@ -118,14 +303,19 @@ mod tests {
             data_structures::{column_map::FixedColumnMap, finalizable_data::FinalizableData},
             global_constraints::GlobalConstraints,
             identity_processor::Machines,
-            machines::FixedLookup,
+            machines::{FixedLookup, KnownMachine, StubMachine},
             rows::{RowFactory, RowIndex},
-            sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator},
-            unused_query_callback, FixedData, MutableState, QueryCallback,
+            sequence_iterator::{
+                Action, DefaultSequenceIterator, DependencyOrderedIterator,
+                ProcessingSequenceIterator, SequenceStep,
+            },
+            unused_query_callback,
+            util::try_to_simple_poly,
+            EvalError, FixedData, HintProvider, MutableState, QueryCallback, RowPair,
         },
     };
 
-    use super::BlockProcessor;
+    use super::{BlockProcessor, WrappingPolicy};
 
     fn name_to_poly_id<T: FieldElement>(fixed_data: &FixedData<T>) -> BTreeMap<String, PolyID> {
         let mut name_to_poly_id = BTreeMap::new();
@@ -140,8 +330,30 @@ mod tests {
 
     /// Constructs a processor for a given PIL, then calls a function on it.
     fn do_with_processor<T: FieldElement, Q: QueryCallback<T>, R>(
+        src: &str,
+        query_callback: Q,
+        f: impl Fn(BlockProcessor<T, Q>, BTreeMap<String, PolyID>, u64, usize) -> R,
+    ) -> R {
+        do_with_configured_processor(src, query_callback, |fixed_data| fixed_data, f)
+    }
+
+    /// Like [do_with_processor], but lets `configure` modify the fixed data first.
+    fn do_with_configured_processor<T: FieldElement, Q: QueryCallback<T>, R>(
+        src: &str,
+        query_callback: Q,
+        configure: impl for<'x> FnOnce(FixedData<'x, T>) -> FixedData<'x, T>,
+        f: impl Fn(BlockProcessor<T, Q>, BTreeMap<String, PolyID>, u64, usize) -> R,
+    ) -> R {
+        do_with_stubbed_processor(src, query_callback, configure, &HashMap::new(), f)
+    }
+
+    /// Like [do_with_configured_processor], but answers all lookups into columns
+    /// of `sub_witness` from their values, see [StubMachine].
+    fn do_with_stubbed_processor<T: FieldElement, Q: QueryCallback<T>, R>(
         src: &str,
         mut query_callback: Q,
+        configure: impl for<'x> FnOnce(FixedData<'x, T>) -> FixedData<'x, T>,
+        sub_witness: &HashMap<String, Vec<T>>,
         f: impl Fn(BlockProcessor<T, Q>, BTreeMap<String, PolyID>, u64, usize) -> R,
     ) -> R {
         let analyzed = analyze_string(src);
@@ -149,7 +361,12 @@ mod tests {
             .into_iter()
             .map(|(n, c)| (n.to_string(), c))
             .collect::<Vec<_>>();
-        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default());
+        let fixed_data = configure(FixedData::new(
+            &analyzed,
+            &constants,
+            &[],
+            Default::default(),
+        ));
 
         // No global range constraints
         let global_range_constraints = GlobalConstraints {
@@ -157,9 +374,25 @@ mod tests {
             fixed_constraints: FixedColumnMap::new(None, fixed_data.fixed_cols.len()),
         };
 
-        // No submachines
+        // No submachines, except for stubs
         let mut fixed_lookup = FixedLookup::new(global_range_constraints.clone());
-        let mut machines = [];
+        let mut machines = analyzed
+            .identities
+            .iter()
+            .filter(|identity| {
+                identity.kind == IdentityKind::Plookup
+                    && identity.right.expressions.iter().all(|e| {
+                        try_to_simple_poly(e).is_some_and(|p| sub_witness.contains_key(&p.name))
+                    })
+            })
+            .map(|identity| {
+                KnownMachine::Stub(StubMachine::new(
+                    format!("stub_{}", identity.id),
+                    &[identity],
+                    sub_witness,
+                ))
+            })
+            .collect::<Vec<_>>();
 
         let row_factory = RowFactory::new(&fixed_data, global_range_constraints);
         let columns = (0..fixed_data.witness_cols.len())
@@ -244,4 +477,838 @@ mod tests {
 
         solve_and_assert::<GoldilocksField>(src, &[(7, "Fibonacci.y", 34)]);
     }
+
+    #[test]
+    fn test_fibonacci_mock_field() {
+        let src = r#"
+            constant %N = 16;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                // Start with 1, 1
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                (1-ISLAST) * (x' - y) = 0;
+                (1-ISLAST) * (y' - (x + y)) = 0;
+        "#;
+
+        // The values wrap around the modulus 97: 1597 = 16 * 97 + 45
+        solve_and_assert::<MockField>(src, &[(7, "Fibonacci.y", 34), (15, "Fibonacci.y", 45)]);
+    }
+
+    /// Fills column `a` (the first witness column) with the square of the row index.
+    struct SquareHints;
+
+    impl<T: FieldElement> HintProvider<T> for SquareHints {
+        fn hint(&self, poly_id: &PolyID, row: u64, _row_pair: &RowPair<T>) -> Option<T>
+        where
+            T: FieldElement,
+        {
+            (poly_id.id == 0).then(|| T::from(row * row))
+        }
+    }
+
+    #[test]
+    fn test_hint_provider() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Hints(%N);
+                col witness a, b;
+
+                // `a` is not determined by any constraint.
+                b = a + 1;
+        "#;
+
+        do_with_configured_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |fixed_data| fixed_data.with_hint_provider(&SquareHints),
+            |mut processor, poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor
+                    .solve(&mut sequence_iterator)
+                    .unwrap()
+                    .is_complete());
+
+                let data = processor.finish();
+                for i in 1..(degree as usize - 1) {
+                    let a = data[i][&poly_ids["Hints.a"]].value.unwrap_or_default();
+                    let b = data[i][&poly_ids["Hints.b"]].value.unwrap_or_default();
+                    assert_eq!(a, GoldilocksField::from((i * i) as u64));
+                    assert_eq!(b, a + GoldilocksField::from(1));
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_iteration_limit() {
+        // Nothing determines the values of `x` and `y`.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Unconstrained(%N);
+                col witness x, y;
+                x = y;
+        "#;
+
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |processor, _poly_ids, degree, num_identities| {
+                let mut processor = processor.with_max_passes(5);
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                match processor.solve(&mut sequence_iterator) {
+                    Err(EvalError::IterationLimit {
+                        passes,
+                        remaining_unknowns,
+                    }) => {
+                        assert_eq!(passes, 6);
+                        assert_eq!(remaining_unknowns, 2 * degree as usize);
+                    }
+                    r => panic!("Expected iteration limit error, got {r:?}"),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_query_budget() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace std::prover(%N);
+                enum Query {
+                    Hint(int)
+                }
+
+            namespace Queries(%N);
+                col witness a(i) query std::prover::Query::Hint(i);
+                col witness b(i) query std::prover::Query::Hint(i);
+                col witness c(i) query std::prover::Query::Hint(i);
+                a = b;
+        "#;
+
+        do_with_configured_processor::<GoldilocksField, _, _>(
+            src,
+            |_: &str| -> Result<Option<GoldilocksField>, String> { Ok(None) },
+            |fixed_data| fixed_data.with_query_budget(2),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                match processor.solve(&mut sequence_iterator) {
+                    Err(EvalError::QueryBudgetExceeded {
+                        queries, budget, ..
+                    }) => {
+                        assert_eq!(queries, 3);
+                        assert_eq!(budget, 2);
+                    }
+                    r => panic!("Expected query budget error, got {r:?}"),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_retry_when_stalled() {
+        // Each column is copied from the previous one in the first or the (second to)
+        // last row and then propagated through the block in the opposite direction,
+        // so solving `e` needs one more pass over the rows than the default sequence has.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Chain(%N);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0, 0, 0, 0, 0, 0, 1, 0];
+                col witness a, b, c, d, e;
+
+                FIRST * (a - 1) = 0;
+                a' = a;
+                LAST * (b - a) = 0;
+                b' = b;
+                FIRST * (c - b) = 0;
+                c' = c;
+                LAST * (d - c) = 0;
+                d' = d;
+                FIRST * (e - d) = 0;
+                e' = e;
+        "#;
+
+        let solve = |max_retries: usize| {
+            do_with_processor::<GoldilocksField, _, _>(
+                src,
+                unused_query_callback(),
+                |processor, poly_ids, degree, num_identities| {
+                    let mut processor = processor.with_max_retries(max_retries);
+                    let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                        DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                    );
+                    processor.solve(&mut sequence_iterator).unwrap();
+                    let data = processor.finish();
+                    data[7][&poly_ids["Chain.e"]].value.is_known()
+                },
+            )
+        };
+
+        assert!(!solve(0));
+        assert!(solve(1));
+    }
+
+    #[test]
+    fn test_retry_processes_blocked_identities_last() {
+        // The same chain as in `test_retry_when_stalled`, but `f` copies `e` from the next
+        // row. As `e` is only known in the first two rows after the first attempt, the
+        // identity for `f` is blocked until the retry, in which it comes before the identity
+        // propagating `e` in the PIL. Processing it last saves a round in every row.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Chain(%N);
+                col fixed FIRST = [1] + [0]*;
+                col fixed NOT_FIRST = [0] + [1]*;
+                col fixed LAST = [0, 0, 0, 0, 0, 0, 1, 0];
+                col witness a, b, c, d, e, f;
+
+                FIRST * (a - 1) = 0;
+                a' = a;
+                LAST * (b - a) = 0;
+                b' = b;
+                FIRST * (c - b) = 0;
+                c' = c;
+                LAST * (d - c) = 0;
+                d' = d;
+                FIRST * (e - d) = 0;
+                NOT_FIRST * (f' - e') = 0;
+                e' = e;
+        "#;
+
+        let solve = |max_passes: usize| {
+            do_with_processor::<GoldilocksField, _, _>(
+                src,
+                unused_query_callback(),
+                |processor, poly_ids, degree, num_identities| {
+                    let mut processor = processor.with_max_passes(max_passes);
+                    let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                        DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                    );
+                    processor.solve(&mut sequence_iterator)?;
+                    let data = processor.finish();
+                    Ok::<_, EvalError<GoldilocksField>>(
+                        data[7][&poly_ids["Chain.f"]].value.is_known(),
+                    )
+                },
+            )
+        };
+
+        // Keeping the order of the PIL in the retry needs 111 passes.
+        assert!(solve(105).unwrap());
+        assert!(matches!(solve(104), Err(EvalError::IterationLimit { .. })));
+    }
+
+    #[test]
+    fn test_restart_with_blocked_identities_last() {
+        // A block of a single row with three identities, of which only the second one
+        // makes progress in the first attempt.
+        let mut iterator = DefaultSequenceIterator::new(1, 3, None);
+        let mut first_attempt = vec![];
+        while let Some(step) = iterator.next() {
+            let progress = matches!(step.action, Action::InternalIdentity(1))
+                && !first_attempt.contains(&(step.row_delta, 1));
+            if let Action::InternalIdentity(identity) = step.action {
+                first_attempt.push((step.row_delta, identity));
+            }
+            iterator.report_progress(progress);
+        }
+        assert_eq!(&first_attempt[..3], &[(-1, 0), (-1, 1), (-1, 2)]);
+
+        // The next attempt processes the blocked identities after the one that made progress.
+        iterator.restart_with_blocked_identities_last();
+        let second_attempt = (0..3)
+            .map(|_| {
+                let step = iterator.next().unwrap();
+                iterator.report_progress(false);
+                match step.action {
+                    Action::InternalIdentity(identity) => (step.row_delta, identity),
+                    action => panic!("Unexpected action: {action:?}"),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(second_attempt, [(-1, 1), (-1, 0), (-1, 2)]);
+    }
+
+    #[test]
+    fn test_evaluation_cache() {
+        // The sub-expression `(1 - ISLAST)` is shared between the identities.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                (1-ISLAST) * (x' - y) = 0;
+                (1-ISLAST) * (y' - (x + y)) = 0;
+        "#;
+
+        do_with_configured_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |fixed_data| fixed_data.with_evaluation_cache(),
+            |mut processor, poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor
+                    .solve(&mut sequence_iterator)
+                    .unwrap()
+                    .is_complete());
+
+                let data = processor.finish();
+                let y = data[7][&poly_ids["Fibonacci.y"]].value.unwrap_or_default();
+                assert_eq!(y, GoldilocksField::from(34));
+            },
+        );
+    }
+
+    #[test]
+    fn test_recorded_sequence_replays() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                // Start with 1, 1
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                (1-ISLAST) * (x' - y) = 0;
+                (1-ISLAST) * (y' - (x + y)) = 0;
+        "#;
+
+        let solve = |cached: Option<Vec<SequenceStep>>| {
+            do_with_processor::<GoldilocksField, _, _>(
+                src,
+                unused_query_callback(),
+                |mut processor, poly_ids, degree, num_identities| {
+                    let mut sequence_iterator = match &cached {
+                        Some(steps) => {
+                            ProcessingSequenceIterator::Cached(steps.clone().into_iter())
+                        }
+                        None => ProcessingSequenceIterator::Default(DefaultSequenceIterator::new(
+                            degree as usize - 2,
+                            num_identities,
+                            None,
+                        )),
+                    };
+                    assert!(processor
+                        .solve(&mut sequence_iterator)
+                        .unwrap()
+                        .is_complete());
+                    let sequence = processor.recorded_sequence();
+
+                    let data = processor.finish();
+                    let values = (0..degree as usize)
+                        .flat_map(|i| {
+                            ["Fibonacci.x", "Fibonacci.y"]
+                                .map(|name| data[i][&poly_ids[name]].value.unwrap_or_default())
+                        })
+                        .collect::<Vec<_>>();
+                    (sequence, values)
+                },
+            )
+        };
+
+        let (sequence, values) = solve(None);
+        assert!(!sequence.is_empty());
+        let (replayed_sequence, replayed_values) = solve(Some(sequence.clone()));
+        assert_eq!(replayed_sequence.len(), sequence.len());
+        assert_eq!(replayed_values, values);
+    }
+
+    #[test]
+    fn test_dependency_ordered_rounds() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                (1-ISLAST) * (y' - (x + y)) = 0;
+                (1-ISLAST) * (x' - y) = 0;
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+        "#;
+
+        let solve = |dependency_ordered: bool| {
+            do_with_processor::<GoldilocksField, _, _>(
+                src,
+                unused_query_callback(),
+                |mut processor, poly_ids, degree, num_identities| {
+                    let mut sequence_iterator = if dependency_ordered {
+                        let analyzed = analyze_string::<GoldilocksField>(src);
+                        let identities = analyzed.identities.iter().collect::<Vec<_>>();
+                        ProcessingSequenceIterator::DependencyOrdered(
+                            DependencyOrderedIterator::new(degree as usize - 2, &identities, None)
+                                .unwrap(),
+                        )
+                    } else {
+                        ProcessingSequenceIterator::Default(DefaultSequenceIterator::new(
+                            degree as usize - 2,
+                            num_identities,
+                            None,
+                        ))
+                    };
+                    assert!(processor
+                        .solve(&mut sequence_iterator)
+                        .unwrap()
+                        .is_complete());
+
+                    let data = processor.finish();
+                    let y = data[7][&poly_ids["Fibonacci.y"]].value.unwrap_or_default();
+                    assert_eq!(y, GoldilocksField::from(34));
+                    sequence_iterator.round_count().unwrap()
+                },
+            )
+        };
+
+        let default_rounds = solve(false);
+        let dependency_ordered_rounds = solve(true);
+        // A forward pass over the 7 rows solves them, a backward pass over the
+        // first 6 rows confirms that nothing changes.
+        assert_eq!(dependency_ordered_rounds, 7 + 6);
+        assert!(dependency_ordered_rounds < default_rounds);
+    }
+
+    #[test]
+    fn test_dependency_order_cycle() {
+        let analyzed = analyze_string::<GoldilocksField>(
+            r#"
+            namespace Main(8);
+                col witness x, y;
+                x = y + 1;
+                y = x - 1;
+        "#,
+        );
+        let identities = analyzed.identities.iter().collect::<Vec<_>>();
+        assert!(DependencyOrderedIterator::new(6, &identities, None).is_none());
+        assert!(matches!(
+            ProcessingSequenceIterator::dependency_ordered(6, &identities, None),
+            ProcessingSequenceIterator::Default(_)
+        ));
+    }
+
+    /// Solves the given PIL and checks the constraints with the given wrapping policy.
+    fn check_constraints_with_policy(
+        src: &str,
+        wrapping_policy: WrappingPolicy,
+    ) -> Result<(), EvalError<GoldilocksField>> {
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |processor, _poly_ids, degree, num_identities| {
+                let mut processor = processor.with_wrapping_policy(wrapping_policy);
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor
+                    .solve(&mut sequence_iterator)
+                    .unwrap()
+                    .is_complete());
+                let result = processor.check_constraints();
+                assert_eq!(
+                    processor
+                        .check_constraints_parallel()
+                        .map_err(|e| e.to_string()),
+                    result.as_ref().map_err(|e| e.to_string()).copied()
+                );
+                result
+            },
+        )
+    }
+
+    /// Checks the constraints of the given PIL without solving it first, both
+    /// sequentially and in parallel, and returns both results.
+    fn check_unsolved_constraints(src: &str) -> (Result<(), String>, Result<(), String>) {
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, _degree, _num_identities| {
+                let start = std::time::Instant::now();
+                let sequential = processor.check_constraints().map_err(|e| e.to_string());
+                let sequential_time = start.elapsed();
+                let start = std::time::Instant::now();
+                let parallel = processor
+                    .check_constraints_parallel()
+                    .map_err(|e| e.to_string());
+                log::info!(
+                    "Checked constraints in {:?} sequentially and in {:?} in parallel",
+                    sequential_time,
+                    start.elapsed()
+                );
+                (sequential, parallel)
+            },
+        )
+    }
+
+    #[test]
+    fn test_check_constraints_parallel_lowest_row() {
+        // All unknown values are treated as zero, so the second identity
+        // fails on all rows except for the first and the last one.
+        let src = r#"
+            constant %N = 1024;
+
+            namespace Main(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed X(i) { i };
+                col witness x;
+
+                ISFIRST * x = 0;
+                (1 - ISFIRST) * (x' - X) = 0;
+        "#;
+        let (sequential, parallel) = check_unsolved_constraints(src);
+        let error = parallel.unwrap_err();
+        assert!(error.contains("Known values in current row (local: 1, global 1)"));
+        assert_eq!(sequential.unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_check_constraints_parallel_large_trace() {
+        let src = r#"
+            constant %N = 65536;
+
+            namespace Main(%N);
+                col fixed X(i) { i };
+                col witness x, y;
+
+                X * x = 0;
+                x' = y;
+        "#;
+        let (sequential, parallel) = check_unsolved_constraints(src);
+        assert_eq!(sequential, Ok(()));
+        assert_eq!(parallel, Ok(()));
+    }
+
+    #[test]
+    fn test_labeled_identity_in_error() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col fixed ONE = [1]*;
+                col witness x;
+
+                x = 1;
+                #[never_zero] ONE = 0;
+        "#;
+
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                let Err(error) = processor.solve(&mut sequence_iterator) else {
+                    panic!("Expected an unsatisfiable constraint.");
+                };
+                assert!(error
+                    .to_string()
+                    .contains("Error in identity: #[never_zero] Main.ONE = 0;"));
+            },
+        );
+    }
+
+    #[test]
+    fn test_constant_identities() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x;
+
+                x = 1;
+                0 = 0;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor
+                    .solve(&mut sequence_iterator)
+                    .unwrap()
+                    .is_complete());
+            },
+        );
+
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x;
+
+                x = 1;
+                1 = 0;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                let Err(error) = processor.solve(&mut sequence_iterator) else {
+                    panic!("Expected an unsatisfiable constraint.");
+                };
+                let error = error.to_string();
+                assert!(error.contains("Identity at input:8:"));
+                assert!(error.contains("evaluates to the nonzero constant 1."));
+            },
+        );
+
+        // Identities with witness columns are reported together with the identity.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x;
+
+                x = 1;
+                x = 2;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                let Err(error) = processor.solve(&mut sequence_iterator) else {
+                    panic!("Expected an unsatisfiable constraint.");
+                };
+                let error = error.to_string();
+                assert!(error.contains("Error in identity: Main.x = 2;"), "{error}");
+                assert!(
+                    error.contains("Linear constraint is not satisfiable"),
+                    "{error}"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_wrapping_policy_acyclic() {
+        // The last row does not continue the sequence in the first row.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col witness x, y;
+
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                x' = y;
+                y' = x + y;
+        "#;
+
+        assert!(check_constraints_with_policy(src, WrappingPolicy::Acyclic).is_ok());
+        assert!(check_constraints_with_policy(src, WrappingPolicy::Cyclic).is_err());
+    }
+
+    #[test]
+    fn test_check_constraints_error() {
+        // The same sequence as in test_wrapping_policy_acyclic, which is inconsistent
+        // when the last row is followed by the first row.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col witness x, y;
+
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                x' = y;
+                y' = x + y;
+        "#;
+
+        let error = check_constraints_with_policy(src, WrappingPolicy::Cyclic)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Error in identity: Fibonacci.x' = Fibonacci.y;"));
+        assert!(error.contains("Known values in current row (local: 7, global 7)"));
+        assert!(error.contains("Known values in next row (local: 0, global 0)"));
+    }
+
+    #[test]
+    fn test_cyclic_dependency() {
+        // Each identity defines the column the other one uses.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x, y;
+
+                x = y * y + 1;
+                y = x * x;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor.solve(&mut sequence_iterator).is_ok());
+                let error = processor.check_cyclic_dependencies().unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "Identities depend on each other's unknown values in row 0, so none of them can make progress:\n    Main.x = ((Main.y * Main.y) + 1);\n    Main.y = (Main.x * Main.x);"
+                );
+            },
+        );
+
+        // `y` is not defined by any identity, so this is not a cycle, even though
+        // both identities reference `x`.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x, y, z;
+
+                x = y * y;
+                z = x + 1;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor.solve(&mut sequence_iterator).is_ok());
+                assert!(processor.processor.unknown_count() > 0);
+                assert!(processor.check_cyclic_dependencies().is_ok());
+            },
+        );
+
+        // Without the cycle, the same identities are solved.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col witness x, y;
+
+                x = 2;
+                y = x * x;
+        "#;
+        do_with_processor::<GoldilocksField, _, _>(
+            src,
+            unused_query_callback(),
+            |mut processor, _poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor.solve(&mut sequence_iterator).is_ok());
+            },
+        );
+    }
+
+    #[test]
+    fn test_wrapping_policy_cyclic() {
+        // A counter modulo %N, whose last row is followed by the first row.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Counter(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x;
+
+                ISFIRST * x = 0;
+                x' = x + 1 - 8 * ISLAST;
+        "#;
+
+        assert!(check_constraints_with_policy(src, WrappingPolicy::Acyclic).is_ok());
+        assert!(check_constraints_with_policy(src, WrappingPolicy::Cyclic).is_ok());
+    }
+
+    #[test]
+    fn test_stub_machine() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Main(%N);
+                col fixed X(i) { i };
+                col witness x, y;
+
+                x = X;
+                { x, y } in { Square.a, Square.b };
+
+            namespace Square(%N);
+                col witness a, b;
+        "#;
+
+        // The sub-witness of `Square`, which is never solved itself.
+        let sub_witness = HashMap::from([
+            (
+                "Square.a".to_string(),
+                (0..8u64).map(GoldilocksField::from).collect::<Vec<_>>(),
+            ),
+            (
+                "Square.b".to_string(),
+                (0..8u64).map(|i| GoldilocksField::from(i * i)).collect(),
+            ),
+        ]);
+
+        do_with_stubbed_processor(
+            src,
+            unused_query_callback(),
+            |fixed_data| fixed_data,
+            &sub_witness,
+            |mut processor, poly_ids, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                assert!(processor
+                    .solve(&mut sequence_iterator)
+                    .unwrap()
+                    .is_complete());
+
+                let data = processor.finish();
+                // The block covers all rows but the last one.
+                for i in 0..degree as usize - 1 {
+                    let y = data[i][&poly_ids["Main.y"]].value.unwrap_or_default();
+                    assert_eq!(y, GoldilocksField::from((i * i) as u64));
+                }
+                assert!(!data[degree as usize - 1][&poly_ids["Main.y"]]
+                    .value
+                    .is_known());
+            },
+        );
+    }
 }
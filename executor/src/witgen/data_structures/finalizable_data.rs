@@ -52,6 +52,14 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         self.data.len()
     }
 
+    /// Returns the rows that have not been finalized yet.
+    pub fn rows_in_progress(&self) -> impl Iterator<Item = &Row<'a, T>> {
+        self.data.iter().filter_map(|entry| match entry {
+            Entry::InProgress(row) => Some(row),
+            Entry::Finalized(_, _) => None,
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
@@ -1,7 +1,7 @@
 use std::fmt::{self, Debug};
 
-use powdr_ast::analyzed::AlgebraicReference;
-use powdr_number::FieldElement;
+use powdr_ast::{analyzed::AlgebraicReference, SourceRef};
+use powdr_number::{DegreeType, FieldElement};
 
 use super::range_constraints::RangeConstraint;
 
@@ -162,6 +162,12 @@ pub enum EvalError<T: FieldElement> {
     RowsExhausted,
     /// A constraint that cannot be satisfied (i.e. 2 = 1).
     ConstraintUnsatisfiable(String),
+    /// A polynomial identity that does not reference any witness column and evaluates
+    /// to a nonzero constant.
+    Unsatisfiable {
+        value: T,
+        source: SourceRef,
+    },
     /// Conflicting bit- or range constraints in an equation, i.e. for X = 0x100, where X is known to be at most 0xff.
     ConflictingRangeConstraints,
     /// A division pattern was recognized but the solution does not satisfy the range constraints.
@@ -172,6 +178,25 @@ pub enum EvalError<T: FieldElement> {
     ProverQueryError(String),
     Generic(String),
     Multiple(Vec<EvalError<T>>),
+    /// More prover queries than allowed would be run on a single row.
+    QueryBudgetExceeded {
+        row: DegreeType,
+        queries: usize,
+        budget: usize,
+    },
+    /// Solving a block did not reach a fixpoint within the maximum number of passes.
+    IterationLimit {
+        passes: usize,
+        remaining_unknowns: usize,
+    },
+    /// Solving a block stalled because the identities reference each other's unknown cells,
+    /// so none of them can make progress, no matter how often they are processed.
+    CyclicDependency {
+        row: DegreeType,
+        identities: Vec<String>,
+    },
+    /// An expression is nested more deeply than the evaluator allows. Argument: the maximum depth.
+    ExpressionTooDeep(usize),
 }
 
 impl<T: FieldElement> Debug for EvalError<T> {
@@ -206,6 +231,13 @@ impl<T: FieldElement> fmt::Display for EvalError<T> {
             EvalError::ConstraintUnsatisfiable(e) => {
                 write!(f, "Linear constraint is not satisfiable: {e} != 0",)
             }
+            EvalError::Unsatisfiable { value, source } => write!(
+                f,
+                "Identity at {}:{}:{} evaluates to the nonzero constant {value}.",
+                source.file.as_deref().unwrap_or("<input>"),
+                source.line,
+                source.col
+            ),
             EvalError::Multiple(errors) => {
                 for e in errors {
                     write!(f, "{e}")?;
@@ -234,6 +266,34 @@ impl<T: FieldElement> fmt::Display for EvalError<T> {
                 write!(f, "Error getting external information from the prover: {s}")
             }
             EvalError::Generic(s) => write!(f, "{s}"),
+            EvalError::QueryBudgetExceeded {
+                row,
+                queries,
+                budget,
+            } => write!(
+                f,
+                "Row {row} would run {queries} prover queries, but at most {budget} are allowed per row."
+            ),
+            EvalError::IterationLimit {
+                passes,
+                remaining_unknowns,
+            } => write!(
+                f,
+                "Solving did not finish within {passes} passes, {remaining_unknowns} cells are still unknown."
+            ),
+            EvalError::CyclicDependency { row, identities } => write!(
+                f,
+                "Identities depend on each other's unknown values in row {row}, so none of them can make progress:\n{}",
+                identities
+                    .iter()
+                    .map(|i| format!("    {i}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            EvalError::ExpressionTooDeep(max_depth) => write!(
+                f,
+                "Expression is nested more deeply than the maximum depth of {max_depth}."
+            ),
         }
     }
 }
@@ -1,4 +1,8 @@
-use std::marker::PhantomData;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    marker::PhantomData,
+};
 
 use powdr_ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference,
@@ -7,7 +11,7 @@ use powdr_ast::analyzed::{
 
 use powdr_number::FieldElement;
 
-use super::{affine_expression::AffineResult, IncompleteCause};
+use super::{affine_expression::AffineResult, EvalError, IncompleteCause};
 
 pub trait SymbolicVariables<T> {
     /// Value of a polynomial (fixed or witness).
@@ -20,8 +24,26 @@ pub trait SymbolicVariables<T> {
     }
 }
 
+/// Results of evaluating (non-trivial) sub-expressions, keyed by the expression itself,
+/// so that structurally equal sub-expressions are only evaluated once.
+/// Only valid as long as the values of the symbolic variables do not change.
+pub type EvaluationCache<'a, T> =
+    HashMap<&'a Expression<T>, AffineResult<&'a AlgebraicReference, T>>;
+
+/// The default maximum nesting depth of expressions the [ExpressionEvaluator] evaluates.
+/// Deeper expressions are rejected instead of risking a stack overflow.
+pub const DEFAULT_MAX_DEPTH: usize = 2048;
+
+/// The result of evaluating an expression: The affine expression (or the reason why
+/// it could not be determined yet), or an error if the expression cannot be evaluated
+/// at all.
+pub type EvaluationResult<'a, T> = Result<AffineResult<&'a AlgebraicReference, T>, EvalError<T>>;
+
 pub struct ExpressionEvaluator<T, SV> {
     variables: SV,
+    max_depth: usize,
+    /// Set when the current evaluation exceeded `max_depth`.
+    too_deep: Cell<bool>,
     marker: PhantomData<T>,
 }
 
@@ -33,22 +55,83 @@ where
     pub fn new(variables: SV) -> Self {
         Self {
             variables,
+            max_depth: DEFAULT_MAX_DEPTH,
+            too_deep: Cell::new(false),
             marker: PhantomData,
         }
     }
+
+    /// Sets the maximum nesting depth of expressions. Evaluating a deeper expression
+    /// fails with [EvalError::ExpressionTooDeep].
+    #[cfg(test)]
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
     /// Tries to evaluate the expression to an expression affine in the witness polynomials,
     /// taking current values of polynomials into account.
     /// @returns an expression affine in the witness polynomials
-    pub fn evaluate<'a>(&self, expr: &'a Expression<T>) -> AffineResult<&'a AlgebraicReference, T> {
-        // @TODO if we iterate on processing the constraints in the same row,
-        // we could store the simplified values.
+    pub fn evaluate<'a>(&self, expr: &'a Expression<T>) -> EvaluationResult<'a, T> {
+        self.evaluate_checked(expr, None)
+    }
+
+    /// Like [ExpressionEvaluator::evaluate], but re-uses the results of sub-expressions
+    /// found in the cache and stores the results of newly evaluated ones.
+    pub fn evaluate_with_cache<'a>(
+        &self,
+        expr: &'a Expression<T>,
+        cache: &RefCell<EvaluationCache<'a, T>>,
+    ) -> EvaluationResult<'a, T> {
+        self.evaluate_checked(expr, Some(cache))
+    }
+
+    fn evaluate_checked<'a>(
+        &self,
+        expr: &'a Expression<T>,
+        cache: Option<&RefCell<EvaluationCache<'a, T>>>,
+    ) -> EvaluationResult<'a, T> {
+        self.too_deep.set(false);
+        let result = self.evaluate_internal(expr, cache, 0);
+        if self.too_deep.get() {
+            Err(EvalError::ExpressionTooDeep(self.max_depth))
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn evaluate_internal<'a>(
+        &self,
+        expr: &'a Expression<T>,
+        cache: Option<&RefCell<EvaluationCache<'a, T>>>,
+        depth: usize,
+    ) -> AffineResult<&'a AlgebraicReference, T> {
+        if depth > self.max_depth {
+            // The actual error is returned by `evaluate_checked`, the cause
+            // only stops the evaluation.
+            self.too_deep.set(true);
+            return Err(IncompleteCause::SolvingFailed);
+        }
         match expr {
             Expression::Reference(poly) => self.variables.value(poly),
             Expression::Number(n) => Ok((*n).into()),
-            Expression::BinaryOperation(left, op, right) => {
-                self.evaluate_binary_operation(left, op, right)
+            Expression::BinaryOperation(..) | Expression::UnaryOperation(..) => {
+                if let Some(result) = cache.and_then(|cache| cache.borrow().get(expr).cloned()) {
+                    return result;
+                }
+                let result = match expr {
+                    Expression::BinaryOperation(left, op, right) => {
+                        self.evaluate_binary_operation(left, op, right, cache, depth + 1)
+                    }
+                    Expression::UnaryOperation(op, expr) => {
+                        self.evaluate_unary_operation(op, expr, cache, depth + 1)
+                    }
+                    _ => unreachable!(),
+                };
+                if let Some(cache) = cache.filter(|_| !self.too_deep.get()) {
+                    cache.borrow_mut().insert(expr, result.clone());
+                }
+                result
             }
-            Expression::UnaryOperation(op, expr) => self.evaluate_unary_operation(op, expr),
             Expression::Challenge(challenge) => self.variables.challenge(challenge),
             e => unimplemented!("Unexpected expression: {}", e),
         }
@@ -59,28 +142,33 @@ where
         left: &'a Expression<T>,
         op: &AlgebraicBinaryOperator,
         right: &'a Expression<T>,
+        cache: Option<&RefCell<EvaluationCache<'a, T>>>,
+        depth: usize,
     ) -> AffineResult<&'a AlgebraicReference, T> {
         match op {
             AlgebraicBinaryOperator::Add => {
-                let left_expr = self.evaluate(left)?;
+                let left_expr = self.evaluate_internal(left, cache, depth)?;
                 if left_expr.is_zero() {
-                    return self.evaluate(right);
+                    return self.evaluate_internal(right, cache, depth);
                 }
-                let right_expr = self.evaluate(right)?;
+                let right_expr = self.evaluate_internal(right, cache, depth)?;
                 if right_expr.is_zero() {
                     return Ok(left_expr);
                 }
                 Ok(left_expr + right_expr)
             }
-            AlgebraicBinaryOperator::Sub => Ok(self.evaluate(left)? - self.evaluate(right)?),
+            AlgebraicBinaryOperator::Sub => Ok(self.evaluate_internal(left, cache, depth)?
+                - self.evaluate_internal(right, cache, depth)?),
             AlgebraicBinaryOperator::Mul => {
                 // don't short circuit on err as rhs might still be 0
-                let left_res = self.evaluate(left);
+                let left_res = self.evaluate_internal(left, cache, depth);
                 match left_res {
                     Ok(left_expr) if left_expr.is_zero() => Ok(left_expr),
-                    Ok(left_expr) if left_expr.is_one() => self.evaluate(right),
+                    Ok(left_expr) if left_expr.is_one() => {
+                        self.evaluate_internal(right, cache, depth)
+                    }
                     Ok(left_expr) => {
-                        let right_expr = self.evaluate(right)?;
+                        let right_expr = self.evaluate_internal(right, cache, depth)?;
                         if let Some(n) = left_expr.constant_value() {
                             return Ok(right_expr * n);
                         }
@@ -93,7 +181,7 @@ where
                         }
                     }
                     // Err on lhs is ok if rhs is zero
-                    Err(left_err) => match self.evaluate(right) {
+                    Err(left_err) => match self.evaluate_internal(right, cache, depth) {
                         Ok(right_expr) => {
                             if let Some(n) = right_expr.constant_value() {
                                 if n.is_zero() {
@@ -108,8 +196,8 @@ where
             }
             AlgebraicBinaryOperator::Pow => {
                 if let (Some(l), r) = (
-                    self.evaluate(left)?.constant_value(),
-                    self.evaluate(right)?
+                    self.evaluate_internal(left, cache, depth)?.constant_value(),
+                    self.evaluate_internal(right, cache, depth)?
                         .constant_value()
                         .expect("non-constant exponent should be caught earlier"),
                 ) {
@@ -125,9 +213,127 @@ where
         &self,
         op: &AlgebraicUnaryOperator,
         expr: &'a Expression<T>,
+        cache: Option<&RefCell<EvaluationCache<'a, T>>>,
+        depth: usize,
     ) -> AffineResult<&'a AlgebraicReference, T> {
-        self.evaluate(expr).map(|v| match op {
-            AlgebraicUnaryOperator::Minus => -v,
+        self.evaluate_internal(expr, cache, depth)
+            .map(|v| match op {
+                AlgebraicUnaryOperator::Minus => -v,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use powdr_ast::analyzed::{PolyID, PolynomialType};
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+
+    /// Assigns the value `id + 1` to every column and counts the lookups.
+    #[derive(Default)]
+    struct CountingVariables {
+        lookups: Cell<usize>,
+    }
+
+    impl SymbolicVariables<GoldilocksField> for &CountingVariables {
+        fn value<'a>(
+            &self,
+            poly: &'a AlgebraicReference,
+        ) -> AffineResult<&'a AlgebraicReference, GoldilocksField> {
+            self.lookups.set(self.lookups.get() + 1);
+            Ok(GoldilocksField::from(poly.poly_id.id + 1).into())
+        }
+    }
+
+    fn column(name: &str, id: u64) -> Expression<GoldilocksField> {
+        Expression::Reference(AlgebraicReference {
+            name: name.to_string(),
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Committed,
+            },
+            next: false,
         })
     }
+
+    #[test]
+    fn cache_shared_subexpressions() {
+        let shared = (column("x", 0) + column("y", 1)) * Expression::Number(2.into());
+        let expr = shared.clone() + shared.clone() + shared.clone() + shared;
+
+        let variables = CountingVariables::default();
+        let evaluator = ExpressionEvaluator::new(&variables);
+        let uncached = evaluator.evaluate(&expr).unwrap().unwrap();
+        assert_eq!(variables.lookups.get(), 8);
+
+        variables.lookups.set(0);
+        let cache = RefCell::new(EvaluationCache::default());
+        let cached = evaluator
+            .evaluate_with_cache(&expr, &cache)
+            .unwrap()
+            .unwrap();
+        assert_eq!(variables.lookups.get(), 2);
+        assert_eq!(cached.constant_value(), uncached.constant_value());
+        assert_eq!(cached.constant_value(), Some(24.into()));
+
+        // A second evaluation is answered from the cache entirely.
+        evaluator
+            .evaluate_with_cache(&expr, &cache)
+            .unwrap()
+            .unwrap();
+        assert_eq!(variables.lookups.get(), 2);
+    }
+
+    /// x + (x + (x + ...)) with `additions` additions.
+    fn nested(additions: usize) -> Expression<GoldilocksField> {
+        (0..additions).fold(column("x", 0), |acc, _| column("x", 0) + acc)
+    }
+
+    #[test]
+    fn max_depth() {
+        let variables = CountingVariables::default();
+        let expr = nested(100);
+        assert_eq!(
+            ExpressionEvaluator::new(&variables)
+                .evaluate(&expr)
+                .unwrap()
+                .unwrap()
+                .constant_value(),
+            Some(101.into())
+        );
+        let evaluator = ExpressionEvaluator::new(&variables).with_max_depth(50);
+        assert_eq!(
+            evaluator.evaluate(&expr).err(),
+            Some(EvalError::ExpressionTooDeep(50))
+        );
+    }
+
+    #[test]
+    fn default_max_depth() {
+        // Evaluating up to the default depth needs more stack than the test threads have
+        // in debug builds, so use as much as the main thread has.
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| {
+                let variables = CountingVariables::default();
+                let evaluator = ExpressionEvaluator::new(&variables);
+                let expr = nested(DEFAULT_MAX_DEPTH);
+                assert_eq!(
+                    evaluator.evaluate(&expr).unwrap().unwrap().constant_value(),
+                    Some((DEFAULT_MAX_DEPTH as u64 + 1).into())
+                );
+
+                let expr = nested(DEFAULT_MAX_DEPTH + 1);
+                assert_eq!(
+                    evaluator.evaluate(&expr).err(),
+                    Some(EvalError::ExpressionTooDeep(DEFAULT_MAX_DEPTH))
+                );
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
 }
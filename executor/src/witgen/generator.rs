@@ -101,9 +101,20 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
         self.fill_remaining_rows(&mut mutable_state_no_machines);
         self.fix_first_row();
 
+        let sentinel = self.fixed_data.unknown_cell_sentinel;
         self.data
             .take_transposed()
-            .map(|(id, (values, _))| (self.fixed_data.column_name(&id).to_string(), values))
+            .map(|(id, (values, known_cells))| {
+                let values = match sentinel {
+                    Some(sentinel) => values
+                        .into_iter()
+                        .zip(known_cells)
+                        .map(|(value, known)| if known { value } else { sentinel })
+                        .collect(),
+                    None => values,
+                };
+                (self.fixed_data.column_name(&id).to_string(), values)
+            })
             .collect()
     }
 }
@@ -163,9 +163,13 @@ fn propagate_constraints<T: FieldElement>(
     match identity.kind {
         IdentityKind::Polynomial => {
             if let Some(p) = is_binary_constraint(identity.expression_for_poly_id()) {
-                assert!(known_constraints
-                    .insert(p, RangeConstraint::from_max_bit(0))
-                    .is_none());
+                // The column might already be constrained, e.g. by the same
+                // constraint on the next row.
+                let boolean = RangeConstraint::boolean();
+                known_constraints
+                    .entry(p)
+                    .and_modify(|existing| *existing = existing.conjunction(&boolean))
+                    .or_insert(boolean);
                 remove = true;
             } else {
                 for (p, c) in
@@ -230,10 +234,12 @@ fn is_binary_constraint<T: FieldElement>(expr: &Expression<T>) -> Option<PolyID>
         let left_root = ExpressionEvaluator::new(symbolic_ev.clone())
             .evaluate(left)
             .ok()
+            .and_then(Result::ok)
             .and_then(|l| l.solve().ok())?;
         let right_root = ExpressionEvaluator::new(symbolic_ev)
             .evaluate(right)
             .ok()
+            .and_then(Result::ok)
             .and_then(|r| r.solve().ok())?;
         if let ([(id1, Constraint::Assignment(value1))], [(id2, Constraint::Assignment(value2))]) =
             (&left_root.constraints[..], &right_root.constraints[..])
@@ -259,7 +265,11 @@ fn try_transfer_constraints<T: FieldElement>(
     }
 
     let symbolic_ev = SymbolicEvaluator;
-    let Some(aff_expr) = ExpressionEvaluator::new(symbolic_ev).evaluate(expr).ok() else {
+    let Some(aff_expr) = ExpressionEvaluator::new(symbolic_ev)
+        .evaluate(expr)
+        .ok()
+        .and_then(Result::ok)
+    else {
         return vec![];
     };
 
@@ -447,4 +457,39 @@ namespace Global(1024);
         );
         assert!(!removed);
     }
+
+    #[test]
+    fn boolean_constraints() {
+        let pil_source = r"
+namespace Global(1024);
+    col witness A, B;
+    A * (1 - A) = 0;
+    A' * (1 - A') = 0;
+    (B - 1) * B = 0;
+    { B } in { A };
+";
+        let analyzed = powdr_pil_analyzer::analyze_string::<GoldilocksField>(pil_source);
+        let mut known_constraints = BTreeMap::new();
+        let mut removed = vec![];
+        for identity in &analyzed.identities {
+            let remove;
+            (known_constraints, remove) =
+                propagate_constraints(known_constraints, identity, &Default::default());
+            removed.push(remove);
+        }
+        assert_eq!(removed, vec![true, true, true, false]);
+        assert_eq!(
+            known_constraints,
+            vec![
+                (witness_poly_id(0), RangeConstraint::boolean()),
+                (witness_poly_id(1), RangeConstraint::boolean()),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+        );
+        assert!(known_constraints
+            .values()
+            .all(|constraint| constraint.is_boolean()));
+        assert!(!RangeConstraint::<GoldilocksField>::from_max_bit(1).is_boolean());
+    }
 }
@@ -7,7 +7,7 @@ use itertools::{Either, Itertools};
 use lazy_static::lazy_static;
 use powdr_ast::{
     analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind},
-    parsed::SelectedExpressions,
+    parsed::{visitor::AllChildren, SelectedExpressions},
 };
 use powdr_number::FieldElement;
 
@@ -148,11 +148,28 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
     fn process_polynomial_identity(
         &self,
         identity: &'a Identity<Expression<T>>,
-        rows: &RowPair<T>,
+        rows: &RowPair<'_, 'a, T>,
     ) -> EvalResult<'a, T> {
-        match rows.evaluate(identity.expression_for_poly_id()) {
+        match rows.evaluate_cached(identity.expression_for_poly_id())? {
             Err(incomplete_cause) => Ok(EvalValue::incomplete(incomplete_cause)),
-            Ok(evaluated) => evaluated.solve_with_range_constraints(rows),
+            // An identity without witness columns can never be satisfied if it is violated
+            // in one row. Violated identities with witness columns are reported together
+            // with the evaluated expression by the solver.
+            Ok(evaluated) => match evaluated.constant_value() {
+                Some(value)
+                    if !value.is_zero()
+                        && !identity
+                            .expression_for_poly_id()
+                            .all_children()
+                            .any(|e| matches!(e, Expression::Reference(r) if r.is_witness())) =>
+                {
+                    Err(EvalError::Unsatisfiable {
+                        value,
+                        source: identity.source.clone(),
+                    })
+                }
+                _ => evaluated.solve_with_range_constraints(rows),
+            },
         }
     }
 
@@ -162,7 +179,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         rows: &RowPair<'_, 'a, T>,
     ) -> EvalResult<'a, T> {
         if let Some(left_selector) = &identity.left.selector {
-            if let Some(status) = self.handle_left_selector(left_selector, rows) {
+            if let Some(status) = self.handle_left_selector(left_selector, rows)? {
                 return Ok(status);
             }
         }
@@ -172,7 +189,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
             .expressions
             .iter()
             .map(|e| rows.evaluate(e))
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Fail if the LHS has an error.
         let (left, errors): (Vec<_>, Vec<_>) = left.into_iter().partition_map(|x| match x {
@@ -229,7 +246,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
             .as_ref()
             .map(|s| {
                 current_rows
-                    .evaluate(s)
+                    .evaluate(s)?
                     .ok()
                     .and_then(|affine_expression| affine_expression.constant_value())
                     .ok_or(EvalError::Generic("Selector is not 1!".to_string()))
@@ -240,7 +257,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         let mut updates = EvalValue::complete(vec![]);
 
         for (l, r) in left.iter().zip(right.expressions.iter()) {
-            match current_rows.evaluate(r) {
+            match current_rows.evaluate(r)? {
                 Ok(r) => {
                     let result = (l.clone() - r).solve_with_range_constraints(current_rows)?;
                     updates.combine(result);
@@ -258,18 +275,18 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         &self,
         left_selector: &'a Expression<T>,
         rows: &RowPair<T>,
-    ) -> Option<EvalValue<&'a AlgebraicReference, T>> {
-        let value = match rows.evaluate(left_selector) {
-            Err(incomplete_cause) => return Some(EvalValue::incomplete(incomplete_cause)),
+    ) -> Result<Option<EvalValue<&'a AlgebraicReference, T>>, EvalError<T>> {
+        let value = match rows.evaluate(left_selector)? {
+            Err(incomplete_cause) => return Ok(Some(EvalValue::incomplete(incomplete_cause))),
             Ok(value) => value,
         };
-        match value.constant_value() {
+        Ok(match value.constant_value() {
             Some(v) if v.is_zero() => Some(EvalValue::complete(vec![])),
             Some(v) if v.is_one() => None,
             _ => Some(EvalValue::incomplete(
                 IncompleteCause::NonConstantLeftSelector,
             )),
-        }
+        })
     }
 }
 
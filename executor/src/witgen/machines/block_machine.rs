@@ -4,14 +4,14 @@ use std::iter;
 use super::{EvalResult, FixedData, FixedLookup};
 use crate::witgen::affine_expression::AffineExpression;
 
-use crate::witgen::block_processor::BlockProcessor;
+use crate::witgen::block_processor::{BlockProcessor, WrappingPolicy};
 use crate::witgen::data_structures::finalizable_data::FinalizableData;
 use crate::witgen::global_constraints::GlobalConstraints;
 use crate::witgen::identity_processor::IdentityProcessor;
 use crate::witgen::processor::{OuterQuery, Processor};
 use crate::witgen::rows::{CellValue, Row, RowFactory, RowIndex, RowPair, UnknownStrategy};
 use crate::witgen::sequence_iterator::{
-    DefaultSequenceIterator, ProcessingSequenceCache, ProcessingSequenceIterator,
+    DefaultSequenceIterator, ProcessingSequenceCache, ProcessingSequenceIterator, SequenceStep,
 };
 use crate::witgen::util::try_to_simple_poly;
 use crate::witgen::{machines::Machine, EvalError, EvalValue, IncompleteCause};
@@ -25,22 +25,34 @@ use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::SelectedExpressions;
 use powdr_number::{DegreeType, FieldElement};
 
+/// The block size from which [BlockMachine::validate_block] checks the rows in parallel.
+const PARALLEL_VALIDATION_MIN_ROWS: usize = 1024;
+
 enum ProcessResult<'a, T: FieldElement> {
-    Success(FinalizableData<'a, T>, EvalValue<&'a AlgebraicReference, T>),
+    /// The new block, the updates and the steps that solved the block.
+    Success(
+        FinalizableData<'a, T>,
+        EvalValue<&'a AlgebraicReference, T>,
+        Vec<SequenceStep>,
+    ),
     Incomplete(EvalValue<&'a AlgebraicReference, T>),
 }
 
 impl<'a, T: FieldElement> ProcessResult<'a, T> {
-    fn new(data: FinalizableData<'a, T>, updates: EvalValue<&'a AlgebraicReference, T>) -> Self {
+    fn new(
+        data: FinalizableData<'a, T>,
+        updates: EvalValue<&'a AlgebraicReference, T>,
+        sequence: Vec<SequenceStep>,
+    ) -> Self {
         match updates.is_complete() {
-            true => ProcessResult::Success(data, updates),
+            true => ProcessResult::Success(data, updates, sequence),
             false => ProcessResult::Incomplete(updates),
         }
     }
 
     fn is_success(&self) -> bool {
         match self {
-            ProcessResult::Success(_, _) => true,
+            ProcessResult::Success(..) => true,
             ProcessResult::Incomplete(_) => false,
         }
     }
@@ -168,9 +180,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             row_factory,
             witness_cols: witness_cols.clone(),
             processing_sequence_cache: ProcessingSequenceCache::new(
-                block_size,
-                latch_row,
-                identities.len(),
+                block_size, latch_row, identities,
             ),
             fixed_data,
         })
@@ -556,16 +566,18 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         };
 
         match process_result {
-            ProcessResult::Success(new_block, updates) => {
+            ProcessResult::Success(new_block, updates, sequence) => {
                 log::trace!(
                     "End processing block machine '{}' (successfully)",
                     self.name()
                 );
                 self.append_block(new_block)?;
 
-                // We solved the query, so report it to the cache.
-                self.processing_sequence_cache
-                    .report_processing_sequence(left, sequence_iterator);
+                // We solved the query, so report it to the cache, unless it is cached already.
+                if !sequence_iterator.is_cached() {
+                    self.processing_sequence_cache
+                        .report_processing_sequence(left, sequence);
+                }
                 Ok(updates)
             }
             ProcessResult::Incomplete(updates) => {
@@ -605,9 +617,61 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         .with_outer_query(OuterQuery::new(left.to_vec(), right));
 
         let outer_assignments = processor.solve(sequence_iterator)?;
+        // The default sequence is the last attempt for this call, so this is the place
+        // to explain why it failed.
+        if !outer_assignments.is_complete()
+            && !sequence_iterator.is_cached()
+            && log::log_enabled!(log::Level::Debug)
+        {
+            if let Err(e) = processor.check_cyclic_dependencies() {
+                log::debug!("Block machine '{}' is incomplete: {e}", self.name());
+            }
+        }
+        let sequence = processor.recorded_sequence();
         let new_block = processor.finish();
 
-        Ok(ProcessResult::new(new_block, outer_assignments))
+        if let Some(wrapping_policy) = self.fixed_data.block_validation {
+            if outer_assignments.is_complete() {
+                self.validate_block(mutable_state, &new_block, wrapping_policy)?;
+            }
+        }
+
+        Ok(ProcessResult::new(new_block, outer_assignments, sequence))
+    }
+
+    /// Checks the rows of a new block, without the rows of its neighbours, against the
+    /// polynomial identities, see [BlockProcessor::check_constraints]. Other identities are
+    /// skipped, because checking a lookup would call into the other machine again.
+    fn validate_block<'b, Q: QueryCallback<T>>(
+        &self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+        new_block: &FinalizableData<'a, T>,
+        wrapping_policy: WrappingPolicy,
+    ) -> Result<(), EvalError<T>> {
+        let rows = FinalizableData::with_initial_rows_in_progress(
+            &self.witness_cols,
+            (1..=self.block_size).map(|i| new_block[i].clone()),
+        );
+        let identities = self
+            .identities
+            .iter()
+            .copied()
+            .filter(|identity| identity.kind == IdentityKind::Polynomial)
+            .collect::<Vec<_>>();
+        let mut processor = BlockProcessor::new(
+            self.last_row_index() + 1,
+            rows,
+            mutable_state,
+            &identities,
+            self.fixed_data,
+            &self.witness_cols,
+        )
+        .with_wrapping_policy(wrapping_policy);
+        if self.block_size >= PARALLEL_VALIDATION_MIN_ROWS {
+            processor.check_constraints_parallel()
+        } else {
+            processor.check_constraints()
+        }
     }
 
     /// Takes a block of rows, which contains the last row of its previous block
@@ -654,3 +718,106 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::{
+        constant_evaluator::generate,
+        witgen::{unused_query_callback, WitnessGenerator, WrappingPolicy},
+    };
+
+    /// Generates the witness of `src` with block validation and returns column `name`.
+    fn generate_validated(src: &str, name: &str) -> Vec<GoldilocksField> {
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed)
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect::<Vec<_>>();
+        let query_callback = unused_query_callback();
+        WitnessGenerator::new(&analyzed, &constants, &query_callback)
+            .with_block_validation(WrappingPolicy::Acyclic)
+            .generate()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .unwrap()
+            .1
+    }
+
+    const SQUARE_MACHINE: &str = r#"
+            constant %N = 16;
+
+            namespace Main(%N);
+                col fixed A(i) { i };
+                col fixed SEL = [1, 1, 1, 1] + [0]*;
+                col witness a, b;
+
+                a = A;
+                SEL { a, b } in Square.LATCH { Square.x, Square.y };
+
+            namespace Square(%N);
+                col fixed LATCH = [0, 1]*;
+                col witness x, y, u;
+
+                (1 - LATCH) * (x' - x) = 0;
+                y = x * x;
+    "#;
+
+    #[test]
+    fn block_validation() {
+        let b = generate_validated(SQUARE_MACHINE, "Main.b");
+        for (i, b) in b.iter().enumerate().take(4) {
+            assert_eq!(*b, GoldilocksField::from((i * i) as u64));
+        }
+    }
+
+    #[test]
+    #[should_panic = "Witness generation failed."]
+    fn block_validation_fails() {
+        // Solving the block does not determine `u`, so it is zero when validating.
+        let src = format!("{SQUARE_MACHINE}\n                u * u = x * x + 1;");
+        generate_validated(&src, "Main.b");
+    }
+
+    #[test]
+    fn incomplete_call_succeeds_later() {
+        // The lookup is processed before `b` is known, so the first call into `Sub`
+        // is incomplete. Without `y`, its identities depend on each other cyclically,
+        // which must not be an error, because the next call provides `y`.
+        let src = r#"
+            constant %N = 16;
+
+            namespace Main(%N);
+                col fixed A(i) { i };
+                col fixed B(i) { i % 2 };
+                col fixed SEL = [1, 1, 1, 1, 1, 1, 1, 1] + [0]*;
+                col witness a, b, c;
+
+                a = A;
+                SEL { a, b, c } in Sub.LATCH { Sub.x, Sub.y, Sub.z };
+                b = B;
+
+            namespace Sub(%N);
+                col fixed LATCH = [1]*;
+                col witness x, y, z, v;
+
+                v = y * y;
+                y = v * v;
+                z = x + v;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed)
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect::<Vec<_>>();
+        let query_callback = unused_query_callback();
+        let witness = WitnessGenerator::new(&analyzed, &constants, &query_callback).generate();
+
+        let c = &witness.iter().find(|(name, _)| name == "Main.c").unwrap().1;
+        for (i, c) in c.iter().enumerate().take(8) {
+            assert_eq!(*c, GoldilocksField::from((i + i % 2) as u64));
+        }
+    }
+}
@@ -335,13 +335,13 @@ impl<T: FieldElement> FixedLookup<T> {
 /// (used for fixed columns).
 /// This is useful in order to transfer range constraints from fixed columns to
 /// witness columns (see [FixedLookup::process_range_check]).
-pub struct UnifiedRangeConstraints<'a, T: FieldElement> {
-    witness_constraints: &'a RowPair<'a, 'a, T>,
+pub struct UnifiedRangeConstraints<'a, 'b, T: FieldElement> {
+    witness_constraints: &'a RowPair<'a, 'b, T>,
     global_constraints: &'a GlobalConstraints<T>,
 }
 
 impl<T: FieldElement> RangeConstraintSet<&AlgebraicReference, T>
-    for UnifiedRangeConstraints<'_, T>
+    for UnifiedRangeConstraints<'_, '_, T>
 {
     fn range_constraint(&self, poly: &AlgebraicReference) -> Option<RangeConstraint<T>> {
         match poly.poly_id.ptype {
@@ -9,6 +9,8 @@ pub use self::fixed_lookup_machine::FixedLookup;
 use self::profiling::record_end;
 use self::profiling::record_start;
 use self::sorted_witness_machine::SortedWitnesses;
+#[cfg(test)]
+pub use self::stub_machine::StubMachine;
 use self::write_once_memory::WriteOnceMemory;
 
 use super::affine_expression::AffineExpression;
@@ -24,6 +26,8 @@ mod fixed_lookup_machine;
 pub mod machine_extractor;
 pub mod profiling;
 mod sorted_witness_machine;
+#[cfg(test)]
+mod stub_machine;
 mod write_once_memory;
 
 /// A machine is a set of witness columns and identities where the columns
@@ -77,6 +81,9 @@ pub enum KnownMachine<'a, T: FieldElement> {
     WriteOnceMemory(WriteOnceMemory<'a, T>),
     BlockMachine(BlockMachine<'a, T>),
     Vm(Generator<'a, T>),
+    /// Only used in tests to solve a machine in isolation, see [StubMachine].
+    #[cfg(test)]
+    Stub(StubMachine<T>),
 }
 
 impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
@@ -94,6 +101,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             KnownMachine::WriteOnceMemory(m) => m.process_plookup(mutable_state, identity_id, args),
             KnownMachine::BlockMachine(m) => m.process_plookup(mutable_state, identity_id, args),
             KnownMachine::Vm(m) => m.process_plookup(mutable_state, identity_id, args),
+            #[cfg(test)]
+            KnownMachine::Stub(m) => m.process_plookup(mutable_state, identity_id, args),
         }
     }
 
@@ -104,6 +113,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             KnownMachine::WriteOnceMemory(m) => m.name(),
             KnownMachine::BlockMachine(m) => m.name(),
             KnownMachine::Vm(m) => m.name(),
+            #[cfg(test)]
+            KnownMachine::Stub(m) => m.name(),
         }
     }
 
@@ -126,6 +137,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
                 m.take_witness_col_values(fixed_lookup, query_callback)
             }
             KnownMachine::Vm(m) => m.take_witness_col_values(fixed_lookup, query_callback),
+            #[cfg(test)]
+            KnownMachine::Stub(m) => m.take_witness_col_values(fixed_lookup, query_callback),
         }
     }
 
@@ -136,6 +149,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             KnownMachine::WriteOnceMemory(m) => m.identity_ids(),
             KnownMachine::BlockMachine(m) => m.identity_ids(),
             KnownMachine::Vm(m) => m.identity_ids(),
+            #[cfg(test)]
+            KnownMachine::Stub(m) => m.identity_ids(),
         }
     }
 }
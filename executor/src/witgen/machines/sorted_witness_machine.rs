@@ -110,11 +110,11 @@ fn check_identity<T: FieldElement>(
     let degree = fixed_data.degree as usize;
     for row in 0..(degree) {
         let ev = ExpressionEvaluator::new(FixedEvaluator::new(fixed_data, row));
-        let nl = ev.evaluate(not_last).ok()?.constant_value()?;
+        let nl = ev.evaluate(not_last).ok()?.ok()?.constant_value()?;
         if (row == degree - 1 && !nl.is_zero()) || (row < degree - 1 && !nl.is_one()) {
             return None;
         }
-        let pos = ev.evaluate(positive).ok()?.constant_value()?;
+        let pos = ev.evaluate(positive).ok()?.ok()?.constant_value()?;
         if pos != (row as u64 + 1).into() {
             return None;
         }
@@ -127,8 +127,8 @@ fn check_identity<T: FieldElement>(
 fn check_constraint<T: FieldElement>(constraint: &Expression<T>) -> Option<PolyID> {
     let symbolic_ev = SymbolicEvaluator;
     let sort_constraint = match ExpressionEvaluator::new(symbolic_ev).evaluate(constraint) {
-        Ok(c) => c,
-        Err(_) => return None,
+        Ok(Ok(c)) => c,
+        _ => return None,
     };
     let mut coeff = sort_constraint.nonzero_coefficients();
     let first = coeff.next()?;
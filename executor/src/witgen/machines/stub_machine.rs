@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity};
+use powdr_number::FieldElement;
+
+use crate::witgen::{
+    affine_expression::AffineExpression, util::try_to_simple_poly, EvalError, EvalResult,
+    EvalValue, IncompleteCause, MutableState, QueryCallback,
+};
+
+use super::{FixedLookup, Machine};
+
+/// A machine that answers lookups from a precomputed sub-witness instead of
+/// generating the witness of the machine the lookups actually target.
+/// This allows solving a machine in isolation from its submachines.
+pub struct StubMachine<T: FieldElement> {
+    name: String,
+    connecting_identities: Vec<u64>,
+    /// The rows of the sub-witness, restricted to the columns on the
+    /// right-hand side of the connecting identities (in that order).
+    rows: Vec<Vec<T>>,
+}
+
+impl<T: FieldElement> StubMachine<T> {
+    /// Creates a stub for the connecting identities, whose right-hand sides must all
+    /// consist of the same plain column references without a selector. The values of
+    /// these columns are taken from `sub_witness`.
+    pub fn new(
+        name: String,
+        connecting_identities: &[&Identity<Expression<T>>],
+        sub_witness: &HashMap<String, Vec<T>>,
+    ) -> Self {
+        let rhs_expressions = &connecting_identities[0].right.expressions;
+        assert!(
+            connecting_identities
+                .iter()
+                .all(|i| i.right.expressions == *rhs_expressions),
+            "All connecting identities of a stub machine must have the same right-hand side."
+        );
+        assert!(
+            connecting_identities
+                .iter()
+                .all(|i| i.right.selector.is_none()),
+            "The connecting identities of a stub machine cannot have a selector on the right-hand side."
+        );
+        let columns = rhs_expressions
+            .iter()
+            .map(|e| {
+                let poly = try_to_simple_poly(e)
+                    .unwrap_or_else(|| panic!("Expected a column reference, got {e}."));
+                sub_witness
+                    .get(&poly.name)
+                    .unwrap_or_else(|| panic!("No values provided for column {}.", poly.name))
+            })
+            .collect::<Vec<_>>();
+        let row_count = columns.iter().map(|c| c.len()).min().unwrap_or(0);
+        let rows = (0..row_count)
+            .map(|row| columns.iter().map(|c| c[row]).collect())
+            .collect();
+
+        Self {
+            name,
+            connecting_identities: connecting_identities.iter().map(|i| i.id).collect(),
+            rows,
+        }
+    }
+}
+
+impl<'a, T: FieldElement> Machine<'a, T> for StubMachine<T> {
+    fn identity_ids(&self) -> Vec<u64> {
+        self.connecting_identities.clone()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process_plookup<'b, Q: QueryCallback<T>>(
+        &mut self,
+        _mutable_state: &'b mut MutableState<'a, 'b, T, Q>,
+        _identity_id: u64,
+        args: &[AffineExpression<&'a AlgebraicReference, T>],
+    ) -> EvalResult<'a, T> {
+        let known_args = args.iter().map(|a| a.constant_value()).collect::<Vec<_>>();
+        let matches = self
+            .rows
+            .iter()
+            .filter(|row| {
+                row.iter()
+                    .zip(&known_args)
+                    .all(|(value, arg)| arg.map_or(true, |arg| arg == *value))
+            })
+            .collect::<Vec<_>>();
+
+        match matches.as_slice() {
+            [] => Err(EvalError::from(format!(
+                "No row of stub machine {} matches the arguments ({}).",
+                self.name,
+                known_args
+                    .iter()
+                    .map(|arg| arg.map_or("?".to_string(), |arg| arg.to_string()))
+                    .join(", ")
+            ))),
+            [row] => {
+                let mut updates = vec![];
+                for (arg, value) in args.iter().zip(row.iter()) {
+                    updates.extend((arg.clone() - (*value).into()).solve()?.constraints);
+                }
+                Ok(EvalValue::complete(updates))
+            }
+            _ => Ok(EvalValue::incomplete(
+                IncompleteCause::MultipleLookupMatches,
+            )),
+        }
+    }
+
+    fn take_witness_col_values<'b, Q: QueryCallback<T>>(
+        &mut self,
+        _fixed_lookup: &'b mut FixedLookup<T>,
+        _query_callback: &'b mut Q,
+    ) -> HashMap<String, Vec<T>> {
+        // The stubbed columns belong to the machine that is not run.
+        HashMap::new()
+    }
+}
@@ -10,11 +10,13 @@ use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::{FunctionKind, LambdaExpression};
 use powdr_number::{DegreeType, FieldElement};
 
+pub use self::block_processor::WrappingPolicy;
 use self::data_structures::column_map::{FixedColumnMap, WitnessColumnMap};
 pub use self::eval_result::{
     Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IncompleteCause,
 };
 use self::generator::Generator;
+pub use self::rows::RowPair;
 
 use self::identity_processor::Machines;
 use self::machines::machine_extractor::ExtractionOutput;
@@ -92,6 +94,21 @@ pub fn chain_callbacks<T: FieldElement>(
     move |query| c1(query).or_else(|_| c2(query))
 }
 
+/// Provides values for cells of witness columns that cannot be derived from
+/// the constraints alone. In contrast to a [QueryCallback], which answers the
+/// string queries attached to columns in the PIL, a hint provider is asked
+/// directly for a cell by column and row.
+pub trait HintProvider<T>: Send + Sync {
+    /// Returns the value of the cell of column `poly_id` in row `row`, or `None`
+    /// if there is no hint for it. `row_pair` gives access to the values
+    /// already known in the current and next row.
+    /// Only called for columns without a prover query and only if the value is
+    /// not yet known after processing the identities of the row.
+    fn hint(&self, poly_id: &PolyID, row: DegreeType, row_pair: &RowPair<T>) -> Option<T>
+    where
+        T: FieldElement;
+}
+
 /// @returns a query callback that is never expected to be used.
 pub fn unused_query_callback<T>() -> impl QueryCallback<T> {
     |_| -> _ { unreachable!() }
@@ -108,6 +125,11 @@ pub struct WitnessGenerator<'a, 'b, T: FieldElement> {
     analyzed: &'a Analyzed<T>,
     fixed_col_values: &'b [(String, Vec<T>)],
     query_callback: &'b dyn QueryCallback<T>,
+    hint_provider: Option<&'b dyn HintProvider<T>>,
+    query_budget: Option<usize>,
+    unknown_cell_sentinel: Option<T>,
+    block_validation: Option<WrappingPolicy>,
+    evaluation_cache: bool,
     external_witness_values: &'b [(String, Vec<T>)],
     stage: u8,
     challenges: BTreeMap<u64, T>,
@@ -123,6 +145,11 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             analyzed,
             fixed_col_values,
             query_callback,
+            hint_provider: None,
+            query_budget: None,
+            unknown_cell_sentinel: None,
+            block_validation: None,
+            evaluation_cache: false,
             external_witness_values: &[],
             stage: 0,
             challenges: BTreeMap::new(),
@@ -139,6 +166,52 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
         }
     }
 
+    /// Sets a provider for values of cells that cannot be derived from the constraints.
+    pub fn with_hint_provider(self, hint_provider: &'b dyn HintProvider<T>) -> Self {
+        WitnessGenerator {
+            hint_provider: Some(hint_provider),
+            ..self
+        }
+    }
+
+    /// Limits the number of prover queries that may be run on a single row.
+    pub fn with_query_budget(self, query_budget: usize) -> Self {
+        WitnessGenerator {
+            query_budget: Some(query_budget),
+            ..self
+        }
+    }
+
+    /// Debugging aid: Fills the cells of the main machine that witness generation could
+    /// not determine with `sentinel` instead of zero, so that they stand out in the witness.
+    pub fn with_unknown_cell_sentinel(self, sentinel: T) -> Self {
+        WitnessGenerator {
+            unknown_cell_sentinel: Some(sentinel),
+            ..self
+        }
+    }
+
+    /// Debugging aid: Checks every block a block machine solves against the polynomial
+    /// identities of the machine before the block is committed, treating unknown values as
+    /// zero, and fails with the first violated identity. The last row of a block is only
+    /// checked together with the first row of the same block with [WrappingPolicy::Cyclic].
+    pub fn with_block_validation(self, wrapping_policy: WrappingPolicy) -> Self {
+        WitnessGenerator {
+            block_validation: Some(wrapping_policy),
+            ..self
+        }
+    }
+
+    /// Caches the evaluation of structurally equal sub-expressions (e.g. inlined
+    /// intermediate columns) across the identities of the same row, so that they
+    /// are only evaluated once as long as the row does not change.
+    pub fn with_evaluation_cache(self) -> Self {
+        WitnessGenerator {
+            evaluation_cache: true,
+            ..self
+        }
+    }
+
     pub fn with_challenges(self, stage: u8, challenges: BTreeMap<u64, T>) -> Self {
         WitnessGenerator {
             stage,
@@ -151,12 +224,27 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
     /// @returns the values (in source order) and the degree of the polynomials.
     pub fn generate(self) -> Vec<(String, Vec<T>)> {
         record_start(OUTER_CODE_NAME);
-        let fixed = FixedData::new(
+        let mut fixed = FixedData::new(
             self.analyzed,
             self.fixed_col_values,
             self.external_witness_values,
             self.challenges,
         );
+        if let Some(hint_provider) = self.hint_provider {
+            fixed = fixed.with_hint_provider(hint_provider);
+        }
+        if let Some(query_budget) = self.query_budget {
+            fixed = fixed.with_query_budget(query_budget);
+        }
+        if let Some(sentinel) = self.unknown_cell_sentinel {
+            fixed = fixed.with_unknown_cell_sentinel(sentinel);
+        }
+        if let Some(wrapping_policy) = self.block_validation {
+            fixed = fixed.with_block_validation(wrapping_policy);
+        }
+        if self.evaluation_cache {
+            fixed = fixed.with_evaluation_cache();
+        }
         let identities = self
             .analyzed
             .identities_with_inlined_intermediate_polynomials()
@@ -281,6 +369,11 @@ pub struct FixedData<'a, T> {
     witness_cols: WitnessColumnMap<WitnessColumn<'a, T>>,
     column_by_name: HashMap<String, PolyID>,
     challenges: BTreeMap<u64, T>,
+    hint_provider: Option<&'a dyn HintProvider<T>>,
+    query_budget: Option<usize>,
+    unknown_cell_sentinel: Option<T>,
+    block_validation: Option<WrappingPolicy>,
+    evaluation_cache: bool,
 }
 
 impl<'a, T: FieldElement> FixedData<'a, T> {
@@ -344,6 +437,46 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                 .map(|(name, (symbol, _))| (name.clone(), symbol.into()))
                 .collect(),
             challenges,
+            hint_provider: None,
+            query_budget: None,
+            unknown_cell_sentinel: None,
+            block_validation: None,
+            evaluation_cache: false,
+        }
+    }
+
+    pub fn with_hint_provider(self, hint_provider: &'a dyn HintProvider<T>) -> Self {
+        FixedData {
+            hint_provider: Some(hint_provider),
+            ..self
+        }
+    }
+
+    pub fn with_query_budget(self, query_budget: usize) -> Self {
+        FixedData {
+            query_budget: Some(query_budget),
+            ..self
+        }
+    }
+
+    pub fn with_unknown_cell_sentinel(self, sentinel: T) -> Self {
+        FixedData {
+            unknown_cell_sentinel: Some(sentinel),
+            ..self
+        }
+    }
+
+    pub fn with_block_validation(self, wrapping_policy: WrappingPolicy) -> Self {
+        FixedData {
+            block_validation: Some(wrapping_policy),
+            ..self
+        }
+    }
+
+    pub fn with_evaluation_cache(self) -> Self {
+        FixedData {
+            evaluation_cache: true,
+            ..self
         }
     }
 
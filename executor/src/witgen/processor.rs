@@ -1,18 +1,28 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ops::Range,
+};
 
+use itertools::Itertools;
 use powdr_ast::{
-    analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID},
-    parsed::SelectedExpressions,
+    analyzed::{
+        AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+    },
+    parsed::{visitor::AllChildren, SelectedExpressions},
 };
 use powdr_number::{DegreeType, FieldElement};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
 use crate::witgen::{query_processor::QueryProcessor, util::try_to_simple_poly, Constraint};
 
 use super::{
     affine_expression::AffineExpression,
     data_structures::{column_map::WitnessColumnMap, finalizable_data::FinalizableData},
+    expression_evaluator::EvaluationCache,
     identity_processor::IdentityProcessor,
     rows::{CellValue, Row, RowIndex, RowPair, RowUpdater, UnknownStrategy},
+    sequence_iterator::defined_column,
     Constraints, EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
 };
 
@@ -67,6 +77,11 @@ pub struct Processor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     outer_query: Option<OuterQuery<'a, T>>,
     inputs: Vec<(PolyID, T)>,
     previously_set_inputs: BTreeMap<PolyID, usize>,
+    /// If set, caches the evaluation of sub-expressions of polynomial identities
+    /// in the row [Processor::evaluation_cache_row], until any row is updated.
+    evaluation_cache: Option<RefCell<EvaluationCache<'a, T>>>,
+    /// The row the evaluation cache is valid for, if any.
+    evaluation_cache_row: Option<usize>,
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T, Q> {
@@ -93,6 +108,8 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
             outer_query: None,
             inputs: Vec::new(),
             previously_set_inputs: BTreeMap::new(),
+            evaluation_cache: fixed_data.evaluation_cache.then(Default::default),
+            evaluation_cache_row: None,
         }
     }
 
@@ -135,7 +152,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
         self.outer_query
             .as_ref()
             .and_then(|outer_query| outer_query.right.selector.as_ref())
-            .and_then(|latch| row_pair.evaluate(latch).ok())
+            .and_then(|latch| row_pair.evaluate(latch).ok()?.ok())
             .and_then(|l| l.constant_value())
             .map(|l| l.is_one())
     }
@@ -151,6 +168,25 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
             self.fixed_data,
             UnknownStrategy::Unknown,
         );
+        if let Some(budget) = self.fixed_data.query_budget {
+            let queries = self
+                .fixed_data
+                .witness_cols
+                .iter()
+                .filter(|(poly_id, column)| {
+                    self.is_relevant_witness[poly_id]
+                        && column.query.is_some()
+                        && row_pair.get_value(&column.poly).is_none()
+                })
+                .count();
+            if queries > budget {
+                return Err(EvalError::QueryBudgetExceeded {
+                    row: global_row_index.into(),
+                    queries,
+                    budget,
+                });
+            }
+        }
         let mut updates = EvalValue::complete(vec![]);
         for poly_id in self.fixed_data.witness_cols.keys() {
             if self.is_relevant_witness[&poly_id] {
@@ -170,37 +206,29 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
     ) -> Result<IdentityResult, EvalError<T>> {
         // Create row pair
         let global_row_index = self.row_offset + row_index as u64;
-        let row_pair = RowPair::new(
+        let mut row_pair = RowPair::new(
             &self.data[row_index],
             &self.data[row_index + 1],
             global_row_index,
             self.fixed_data,
             unknown_strategy,
         );
+        // Cached results are only valid for the unknown strategy they were computed with.
+        if unknown_strategy == UnknownStrategy::Unknown {
+            if let Some(cache) = &self.evaluation_cache {
+                if self.evaluation_cache_row != Some(row_index) {
+                    cache.borrow_mut().clear();
+                    self.evaluation_cache_row = Some(row_index);
+                }
+                row_pair = row_pair.with_evaluation_cache(cache);
+            }
+        }
 
         // Compute updates
         let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
         let updates = identity_processor
             .process_identity(identity, &row_pair)
-            .map_err(|e| -> EvalError<T> {
-                let mut error = format!(
-                    r"Error in identity: {identity}
-Known values in current row (local: {row_index}, global {global_row_index}):
-{}
-",
-                    self.data[row_index].render_values(false, Some(self.witness_cols))
-                );
-                if identity.contains_next_ref() {
-                    error += &format!(
-                        "Known values in next row (local: {}, global {}):\n{}\n",
-                        row_index + 1,
-                        global_row_index + 1,
-                        self.data[row_index + 1].render_values(false, Some(self.witness_cols))
-                    );
-                }
-                error += &format!("   => Error: {e}");
-                error.into()
-            })?;
+            .map_err(|e| self.identity_error(row_index, identity, e))?;
 
         if unknown_strategy == UnknownStrategy::Zero {
             assert!(updates.constraints.is_empty());
@@ -247,9 +275,13 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             .process_link(left, right, &row_pair)
             .map_err(|e| {
                 log::warn!("Error in outer query: {e}");
+                log::warn!(
+                    "The outer query is {{ {} }} in {right}.",
+                    left.iter().format(", ")
+                );
                 log::warn!("Some of the following entries could not be matched:");
                 for (l, r) in left.iter().zip(right.expressions.iter()) {
-                    if let Ok(r) = row_pair.evaluate(r) {
+                    if let Ok(Ok(r)) = row_pair.evaluate(r) {
                         log::warn!("  => {} = {}", l, r);
                     }
                 }
@@ -323,7 +355,7 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             self.fixed_data,
             UnknownStrategy::Unknown,
         );
-        let affine_expression = row_pair.evaluate(expression)?;
+        let affine_expression = row_pair.evaluate(expression).unwrap()?;
         let updates = (affine_expression - value.into())
             .solve_with_range_constraints(&row_pair)
             .unwrap();
@@ -363,6 +395,9 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             };
         }
 
+        if progress {
+            self.evaluation_cache_row = None;
+        }
         progress
     }
 
@@ -370,7 +405,17 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         self.data.len()
     }
 
+    /// Returns the number of cells of relevant witness columns whose value is not yet known.
+    pub fn unknown_count(&self) -> usize {
+        self.data
+            .rows_in_progress()
+            .flat_map(|row| row.iter())
+            .filter(|(poly_id, cell)| self.is_relevant_witness[poly_id] && !cell.value.is_known())
+            .count()
+    }
+
     pub fn finalize_range(&mut self, range: impl Iterator<Item = usize>) {
+        self.evaluation_cache_row = None;
         self.data.finalize_range(range)
     }
 
@@ -384,6 +429,7 @@ Known values in current row (local: {row_index}, global {global_row_index}):
 
     /// Sets the ith row, extending the data if necessary.
     pub fn set_row(&mut self, i: usize, row: Row<'a, T>) {
+        self.evaluation_cache_row = None;
         if i < self.data.len() {
             self.data[i] = row;
         } else {
@@ -392,6 +438,186 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         }
     }
 
+    /// Checks whether a given identity is satisfied on the given row and the row after it,
+    /// where the row after the last row is the first row.
+    /// Unknown values are treated as zero.
+    pub fn check_identity(
+        &mut self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+    ) -> Result<(), EvalError<T>> {
+        let next_row_index = (row_index + 1) % self.data.len();
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[next_row_index],
+            self.row_offset + row_index as DegreeType,
+            self.fixed_data,
+            UnknownStrategy::Zero,
+        );
+        let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
+        identity_processor
+            .process_identity(identity, &row_pair)
+            .map(|_| ())
+            .map_err(|e| self.identity_error(row_index, identity, e))
+    }
+
+    /// Returns the first row in `row_indices` on which one of the polynomial identities among
+    /// `identities` might not be satisfied, treating unknown values as zero. Other identities
+    /// are ignored. The rows are checked in parallel.
+    ///
+    /// This only reads the rows and the fixed data, which can be shared between threads.
+    /// The mutable state cannot: evaluating a polynomial identity never touches it, but
+    /// lookups call into other machines and queries call the query callback, both of which
+    /// mutate it. A returned row has to be checked with [Processor::check_identity] to get
+    /// the actual error, since identities that do not evaluate to a constant might still
+    /// be satisfiable.
+    pub fn find_polynomial_identity_violation(
+        &self,
+        row_indices: Range<usize>,
+        identities: &[&'a Identity<Expression<T>>],
+    ) -> Option<usize> {
+        let data = &self.data;
+        let fixed_data = self.fixed_data;
+        let row_offset = self.row_offset;
+        row_indices.into_par_iter().find_first(|&row_index| {
+            let row_pair = RowPair::new(
+                &data[row_index],
+                &data[(row_index + 1) % data.len()],
+                row_offset + row_index as DegreeType,
+                fixed_data,
+                UnknownStrategy::Zero,
+            );
+            identities
+                .iter()
+                .filter(|identity| identity.kind == IdentityKind::Polynomial)
+                .any(|identity| {
+                    // Incomplete evaluations are not errors, see `check_identity`,
+                    // other errors are reported by it.
+                    row_pair
+                        .evaluate(identity.expression_for_poly_id())
+                        .map_or(true, |evaluated| {
+                            evaluated.is_ok_and(|evaluated| {
+                                evaluated.constant_value().map_or(true, |v| !v.is_zero())
+                            })
+                        })
+                })
+        })
+    }
+
+    /// Returns the (global) index of the first row of a cyclic dependency between the given
+    /// identities, together with the identities in the cycle, in their original order.
+    /// On a given row, an identity defines the cell of its defined column (see
+    /// [defined_column]) if it is unknown, and uses all its other unknown cells.
+    /// A cycle is a strongly connected component of at least two identities in the graph
+    /// of these dependencies, where each cell used in the component is only defined inside it.
+    /// None of these identities can make progress before another one does, so processing
+    /// them more often cannot resolve it. Other reasons for unknown values, like cells that
+    /// no identity defines, are not reported.
+    pub fn cyclic_dependency(
+        &self,
+        identities: &[&'a Identity<Expression<T>>],
+    ) -> Option<(DegreeType, Vec<&'a Identity<Expression<T>>>)> {
+        let cell =
+            |row_index: usize, r: &AlgebraicReference| (row_index + usize::from(r.next), r.poly_id);
+        let is_unknown = |(row_index, poly_id): &(usize, PolyID)| {
+            self.is_relevant_witness[poly_id] && !self.data[*row_index][poly_id].value.is_known()
+        };
+
+        // The nodes of the graph are pairs of a row index and an identity index.
+        let mut nodes = vec![];
+        let mut used_cells = vec![];
+        let mut definitions: BTreeMap<(usize, PolyID), Vec<usize>> = BTreeMap::new();
+        for row_index in 0..self.data.len().saturating_sub(1) {
+            for (identity_index, identity) in identities.iter().enumerate() {
+                let defined = (identity.kind == IdentityKind::Polynomial)
+                    .then(|| defined_column(identity.expression_for_poly_id()))
+                    .flatten()
+                    .map(|r| cell(row_index, r))
+                    .filter(is_unknown);
+                let used = identity
+                    .all_children()
+                    .filter_map(|e| match e {
+                        Expression::Reference(r) if r.is_witness() => Some(cell(row_index, r)),
+                        _ => None,
+                    })
+                    .filter(|c| is_unknown(c) && Some(*c) != defined)
+                    .collect::<BTreeSet<_>>();
+                if defined.is_none() && used.is_empty() {
+                    continue;
+                }
+                if let Some(defined) = defined {
+                    definitions.entry(defined).or_default().push(nodes.len());
+                }
+                nodes.push((row_index, identity_index));
+                used_cells.push(used);
+            }
+        }
+
+        // Each node depends on the nodes defining the cells it uses.
+        let dependencies = used_cells
+            .iter()
+            .map(|used| {
+                used.iter()
+                    .flat_map(|c| definitions.get(c).into_iter().flatten().copied())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let is_closed = |component: &[usize]| {
+            component.iter().all(|node| {
+                used_cells[*node].iter().all(|c| {
+                    definitions.get(c).is_some_and(|definitions| {
+                        definitions
+                            .iter()
+                            .all(|d| component.binary_search(d).is_ok())
+                    })
+                })
+            })
+        };
+        strongly_connected_components(&dependencies)
+            .into_iter()
+            .filter(|component| component.len() > 1 && is_closed(component))
+            .min_by_key(|component| component[0])
+            .map(|component| {
+                let row_index = nodes[component[0]].0;
+                let cycle = component
+                    .iter()
+                    .map(|node| nodes[*node].1)
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(|identity_index| identities[identity_index])
+                    .collect();
+                ((self.row_offset + row_index as DegreeType).into(), cycle)
+            })
+    }
+
+    /// Adds the identity and the known values of the rows it was evaluated on to an error
+    /// that occurred while processing the identity on the given row.
+    fn identity_error(
+        &self,
+        row_index: usize,
+        identity: &Identity<Expression<T>>,
+        error: EvalError<T>,
+    ) -> EvalError<T> {
+        let global_row_index = self.row_offset + row_index as DegreeType;
+        let mut message = format!(
+            r"Error in identity: {identity}
+Known values in current row (local: {row_index}, global {global_row_index}):
+{}
+",
+            self.data[row_index].render_values(false, Some(self.witness_cols))
+        );
+        if identity.contains_next_ref() {
+            let next_row_index = (row_index + 1) % self.data.len();
+            message += &format!(
+                "Known values in next row (local: {next_row_index}, global {}):\n{}\n",
+                self.row_offset + next_row_index as DegreeType,
+                self.data[next_row_index].render_values(false, Some(self.witness_cols))
+            );
+        }
+        message += &format!("   => Error: {error}");
+        message.into()
+    }
+
     /// Checks whether a given identity is satisfied on a proposed row.
     pub fn check_row_pair(
         &mut self,
@@ -439,3 +665,62 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         true
     }
 }
+
+/// Returns the strongly connected components of the graph with the given adjacency lists,
+/// each one sorted, using Kosaraju's algorithm.
+fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    // The nodes in the order in which a depth-first search finishes them.
+    let mut finished = Vec::with_capacity(edges.len());
+    let mut visited = vec![false; edges.len()];
+    for start in 0..edges.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some((node, next_edge)) = stack.last_mut() {
+            match edges[*node].get(*next_edge) {
+                Some(&successor) => {
+                    *next_edge += 1;
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    finished.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut reversed = vec![vec![]; edges.len()];
+    for (node, successors) in edges.iter().enumerate() {
+        for successor in successors {
+            reversed[*successor].push(node);
+        }
+    }
+    let mut assigned = vec![false; edges.len()];
+    let mut components = vec![];
+    for start in finished.into_iter().rev() {
+        if assigned[start] {
+            continue;
+        }
+        assigned[start] = true;
+        let mut component = vec![start];
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for predecessor in &reversed[node] {
+                if !assigned[*predecessor] {
+                    assigned[*predecessor] = true;
+                    component.push(*predecessor);
+                    stack.push(*predecessor);
+                }
+            }
+        }
+        component.sort();
+        components.push(component);
+    }
+    components
+}
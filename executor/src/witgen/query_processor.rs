@@ -4,7 +4,7 @@ use powdr_ast::analyzed::{
     AlgebraicExpression, AlgebraicReference, Expression, PolyID, PolynomialType,
 };
 use powdr_ast::parsed::types::Type;
-use powdr_number::{BigInt, FieldElement};
+use powdr_number::{BigInt, DegreeType, FieldElement};
 use powdr_pil_analyzer::evaluator::{self, Definitions, EvalError, SymbolLookup, Value};
 
 use super::{rows::RowPair, Constraint, EvalResult, EvalValue, FixedData, IncompleteCause};
@@ -25,15 +25,29 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
         }
     }
 
-    pub fn process_query(&mut self, rows: &RowPair<T>, poly_id: &PolyID) -> EvalResult<'a, T> {
+    pub fn process_query(
+        &mut self,
+        rows: &RowPair<'_, 'a, T>,
+        poly_id: &PolyID,
+    ) -> EvalResult<'a, T> {
         let column = &self.fixed_data.witness_cols[poly_id];
 
+        if rows.get_value(&column.poly).is_some() {
+            return Ok(EvalValue::complete(vec![]));
+        }
         if let Some(query) = column.query.as_ref() {
-            if rows.get_value(&column.poly).is_none() {
-                return self.process_witness_query(query, &column.poly, rows);
+            return self.process_witness_query(query, &column.poly, rows);
+        }
+        if let Some(hint_provider) = self.fixed_data.hint_provider {
+            let row = DegreeType::from(rows.current_row_index);
+            if let Some(value) = hint_provider.hint(poly_id, row, rows) {
+                return Ok(EvalValue::complete(vec![(
+                    &column.poly,
+                    Constraint::Assignment(value),
+                )]));
             }
         }
-        // Either no query or the value is already known.
+        // Neither a query nor a hint.
         Ok(EvalValue::complete(vec![]))
     }
 
@@ -41,7 +55,7 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
         &mut self,
         query: &'a Expression,
         poly: &'a AlgebraicReference,
-        rows: &RowPair<T>,
+        rows: &RowPair<'_, 'a, T>,
     ) -> EvalResult<'a, T> {
         let query_str = match self.interpolate_query(query, rows) {
             Ok(query) => query,
@@ -77,7 +91,7 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
     fn interpolate_query(
         &self,
         query: &'a Expression,
-        rows: &RowPair<T>,
+        rows: &RowPair<'_, 'a, T>,
     ) -> Result<String, EvalError> {
         let arguments = vec![Arc::new(Value::Integer(BigInt::from(u64::from(
             rows.current_row_index,
@@ -92,12 +106,12 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
 }
 
 #[derive(Clone)]
-struct Symbols<'a, T: FieldElement> {
+struct Symbols<'a, 'b, T: FieldElement> {
     fixed_data: &'a FixedData<'a, T>,
-    rows: &'a RowPair<'a, 'a, T>,
+    rows: &'b RowPair<'b, 'a, T>,
 }
 
-impl<'a, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, T> {
+impl<'a, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, '_, T> {
     fn lookup<'b>(
         &mut self,
         name: &'a str,
@@ -28,6 +28,11 @@ impl<T: FieldElement> RangeConstraint<T> {
         Self::from_mask(mask_from_bits::<T>(max_bit + 1))
     }
 
+    /// Constraint that only allows the values zero and one.
+    pub fn boolean() -> Self {
+        Self::from_max_bit(0)
+    }
+
     /// Constraint that forces several bits to be set to zero.
     /// Semantics: x & mask == x
     pub fn from_mask<M: Into<T::Integer>>(mask: M) -> Self {
@@ -69,6 +74,11 @@ impl<T: FieldElement> RangeConstraint<T> {
         &self.mask
     }
 
+    /// Returns true if the constraint only allows the values zero and one.
+    pub fn is_boolean(&self) -> bool {
+        self.mask <= T::Integer::from(1u64)
+    }
+
     /// Returns a min-max inclusive range. Note that `max` can be smaller than `min`. In this case the range wraps.
     /// Semantics, with (min, max) = range():
     /// If min <= max, this means min <= x && x <= max.
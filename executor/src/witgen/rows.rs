@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     collections::HashSet,
     fmt::Debug,
     ops::{Add, Sub},
@@ -13,7 +14,7 @@ use crate::witgen::Constraint;
 use super::{
     affine_expression::{AffineExpression, AffineResult},
     data_structures::column_map::WitnessColumnMap,
-    expression_evaluator::ExpressionEvaluator,
+    expression_evaluator::{EvaluationCache, EvaluationResult, ExpressionEvaluator},
     global_constraints::{GlobalConstraints, RangeConstraintSet},
     range_constraints::RangeConstraint,
     symbolic_witness_evaluator::{SymbolicWitnessEvaluator, WitnessColumnEvaluator},
@@ -355,6 +356,9 @@ pub struct RowPair<'row, 'a, T: FieldElement> {
     pub current_row_index: RowIndex,
     fixed_data: &'a FixedData<'a, T>,
     unknown_strategy: UnknownStrategy,
+    /// If set, results of [RowPair::evaluate_cached] are cached here.
+    /// The cache must be cleared whenever the rows change.
+    evaluation_cache: Option<&'row RefCell<EvaluationCache<'a, T>>>,
 }
 impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
     /// Creates a new row pair.
@@ -371,6 +375,7 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
             current_row_index,
             fixed_data,
             unknown_strategy,
+            evaluation_cache: None,
         }
     }
 
@@ -387,6 +392,16 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
             current_row_index,
             fixed_data,
             unknown_strategy,
+            evaluation_cache: None,
+        }
+    }
+
+    /// Uses the given cache to share the evaluation of structurally equal
+    /// sub-expressions in [RowPair::evaluate_cached].
+    pub fn with_evaluation_cache(self, cache: &'row RefCell<EvaluationCache<'a, T>>) -> Self {
+        Self {
+            evaluation_cache: Some(cache),
+            ..self
         }
     }
 
@@ -403,6 +418,15 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
         }
     }
 
+    /// Returns the range constraint currently stored for the cell of the given reference,
+    /// or `None` if the cell is unconstrained or its value is already known.
+    pub fn range_constraint(&self, poly: &AlgebraicReference) -> Option<RangeConstraint<T>> {
+        match self.get_cell(poly).value {
+            CellValue::RangeConstraint(ref c) => Some(c.clone()),
+            _ => None,
+        }
+    }
+
     pub fn get_value(&self, poly: &AlgebraicReference) -> Option<T> {
         match self.get_cell(poly).value {
             CellValue::Known(value) => Some(value),
@@ -416,7 +440,7 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
     /// Tries to evaluate the expression to an expression affine in the witness polynomials,
     /// taking current values of polynomials into account.
     /// @returns an expression affine in the witness polynomials
-    pub fn evaluate<'b>(&self, expr: &'b Expression<T>) -> AffineResult<&'b AlgebraicReference, T> {
+    pub fn evaluate<'b>(&self, expr: &'b Expression<T>) -> EvaluationResult<'b, T> {
         ExpressionEvaluator::new(SymbolicWitnessEvaluator::new(
             self.fixed_data,
             self.current_row_index.into(),
@@ -424,6 +448,20 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
         ))
         .evaluate(expr)
     }
+
+    /// Like [RowPair::evaluate], but uses the evaluation cache if one was provided
+    /// through [RowPair::with_evaluation_cache].
+    pub fn evaluate_cached(&self, expr: &'a Expression<T>) -> EvaluationResult<'a, T> {
+        let Some(cache) = self.evaluation_cache else {
+            return self.evaluate(expr);
+        };
+        ExpressionEvaluator::new(SymbolicWitnessEvaluator::new(
+            self.fixed_data,
+            self.current_row_index.into(),
+            self,
+        ))
+        .evaluate_with_cache(expr, cache)
+    }
 }
 
 impl<T: FieldElement> WitnessColumnEvaluator<T> for RowPair<'_, '_, T> {
@@ -437,9 +475,91 @@ impl<T: FieldElement> WitnessColumnEvaluator<T> for RowPair<'_, '_, T> {
 
 impl<T: FieldElement> RangeConstraintSet<&AlgebraicReference, T> for RowPair<'_, '_, T> {
     fn range_constraint(&self, poly: &AlgebraicReference) -> Option<RangeConstraint<T>> {
-        match self.get_cell(poly).value {
-            CellValue::RangeConstraint(ref c) => Some(c.clone()),
-            _ => None,
-        }
+        RowPair::range_constraint(self, poly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use powdr_ast::parsed::visitor::AllChildren;
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::{
+        constant_evaluator::generate, witgen::global_constraints::determine_global_constraints,
+    };
+
+    use super::*;
+
+    #[test]
+    fn range_constraint_of_bit_column() {
+        let analyzed = analyze_string::<GoldilocksField>(
+            r"
+namespace N(4);
+    col witness b, c;
+    b * (1 - b) = 0;
+    c * (1 - c) = 0;
+    b + 2 * c = 3;
+",
+        );
+        let constants = generate(&analyzed)
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect::<Vec<_>>();
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default());
+        let (global_constraints, identities) =
+            determine_global_constraints(&fixed_data, analyzed.identities.iter());
+        // The bit constraints are turned into global range constraints.
+        assert_eq!(identities.len(), 1);
+        let identity = identities[0];
+
+        let row_factory = RowFactory::new(&fixed_data, global_constraints);
+        let row_index = RowIndex::from_degree(0, 4);
+        let current = row_factory.fresh_row(row_index);
+        let next = row_factory.fresh_row(row_index + 1);
+        let row_pair = RowPair::new(
+            &current,
+            &next,
+            row_index,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+
+        let reference = |name: &str| {
+            identity
+                .all_children()
+                .find_map(|e| match e {
+                    Expression::Reference(r) if r.name == name => Some(r),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let (b, c) = (reference("N.b"), reference("N.c"));
+        assert_eq!(
+            row_pair.range_constraint(b),
+            Some(RangeConstraint::from_mask(1u32))
+        );
+
+        // With both columns known to be bits, `b + 2 * c = 3` determines both values.
+        let result = row_pair
+            .evaluate(identity.expression_for_poly_id())
+            .unwrap()
+            .unwrap()
+            .solve_with_range_constraints(&row_pair)
+            .unwrap();
+        assert!(result.is_complete());
+        let mut assignments = result
+            .constraints
+            .into_iter()
+            .map(|(poly, constraint)| (poly.name.clone(), constraint))
+            .collect::<Vec<_>>();
+        assignments.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            assignments,
+            vec![
+                (b.name.clone(), Constraint::Assignment(1.into())),
+                (c.name.clone(), Constraint::Assignment(1.into())),
+            ]
+        );
     }
 }
@@ -1,5 +1,12 @@
-use std::collections::BTreeMap;
-
+use std::collections::{BTreeMap, BTreeSet};
+
+use powdr_ast::{
+    analyzed::{
+        AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference, Identity,
+        IdentityKind, PolyID,
+    },
+    parsed::visitor::AllChildren,
+};
 use powdr_number::FieldElement;
 
 use super::affine_expression::AffineExpression;
@@ -15,6 +22,8 @@ pub struct SequenceStep {
 /// In each row, iterates over all identities until no further progress is made.
 pub struct DefaultSequenceIterator {
     identities_count: usize,
+    /// The order in which the identities are processed in each round.
+    identity_order: Vec<usize>,
     row_deltas: Vec<i64>,
     outer_query_row: Option<i64>,
 
@@ -30,8 +39,14 @@ pub struct DefaultSequenceIterator {
     /// If this number gets too large, we will assume that we're in an infinite loop and exit.
     current_round_count: usize,
 
+    /// The total number of rounds over all row deltas.
+    total_round_count: usize,
+
     /// The steps on which we made progress.
     progress_steps: Vec<SequenceStep>,
+    /// The index into `progress_steps` at which the current attempt started,
+    /// see [Self::restart_with_blocked_identities_last].
+    attempt_start: usize,
 }
 
 const MAX_ROUNDS_PER_ROW_DELTA: usize = 100;
@@ -41,6 +56,7 @@ impl DefaultSequenceIterator {
         let max_row = block_size as i64 - 1;
         DefaultSequenceIterator {
             identities_count,
+            identity_order: (0..identities_count).collect(),
             row_deltas: (-1..=max_row)
                 .chain((-1..max_row).rev())
                 .chain(0..=max_row)
@@ -51,10 +67,37 @@ impl DefaultSequenceIterator {
             cur_row_delta_index: 0,
             cur_action_index: -1,
             current_round_count: 0,
+            total_round_count: 0,
             progress_steps: vec![],
+            attempt_start: 0,
         }
     }
 
+    /// Starts another attempt from the first row, processing the identities that
+    /// did not make progress in the previous attempt after the ones that did.
+    /// The steps that made progress so far are kept.
+    pub fn restart_with_blocked_identities_last(&mut self) {
+        let progressed = self.progress_steps[self.attempt_start..]
+            .iter()
+            .filter_map(|step| match step.action {
+                Action::InternalIdentity(identity_index) => Some(identity_index),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        let (unblocked, blocked): (Vec<_>, Vec<_>) = self
+            .identity_order
+            .iter()
+            .copied()
+            .partition(|identity_index| progressed.contains(identity_index));
+        self.identity_order = unblocked.into_iter().chain(blocked).collect();
+
+        self.attempt_start = self.progress_steps.len();
+        self.progress_in_current_round = false;
+        self.cur_row_delta_index = 0;
+        self.cur_action_index = -1;
+        self.current_round_count = 0;
+    }
+
     /// Update the state of the iterator.
     /// If we're not at the last identity in the current row, just moves to the next.
     /// Otherwise, starts with identity 0 and moves to the next row if no progress was made.
@@ -99,6 +142,7 @@ impl DefaultSequenceIterator {
             // Stay and current row delta
             self.current_round_count += 1;
         }
+        self.total_round_count += 1;
         // Reset action index and progress flag
         self.cur_action_index = -1;
         self.progress_in_current_round = false;
@@ -132,7 +176,7 @@ impl DefaultSequenceIterator {
             row_delta: self.row_deltas[self.cur_row_delta_index],
             action: match self.cur_action_index.cmp(&(self.identities_count as i32)) {
                 std::cmp::Ordering::Less => {
-                    Action::InternalIdentity(self.cur_action_index as usize)
+                    Action::InternalIdentity(self.identity_order[self.cur_action_index as usize])
                 }
                 std::cmp::Ordering::Equal => Action::ProverQueries,
                 std::cmp::Ordering::Greater => Action::OuterQuery,
@@ -141,6 +185,218 @@ impl DefaultSequenceIterator {
     }
 }
 
+/// Goes through all rows of the block machine (plus the ones before and after)
+/// alternately forward and backward, processing each identity once per row, in an
+/// order in which an identity that determines a column comes before the identities
+/// reading it. The passes are repeated until one does not make any progress, which
+/// reaches the same fixed point as [DefaultSequenceIterator], but for straight-line
+/// machines needs far fewer rounds.
+pub struct DependencyOrderedIterator {
+    /// The indices of the identities, in dependency order.
+    identity_order: Vec<usize>,
+    /// The row deltas of the forward pass followed by the ones of the backward pass.
+    row_deltas: Vec<i64>,
+    /// The index into `row_deltas` at which the backward pass starts.
+    backward_start: usize,
+    outer_query_row: Option<i64>,
+
+    /// Whether any progress was made in the current pass.
+    progress_in_current_pass: bool,
+    /// The current row delta index.
+    cur_row_delta_index: usize,
+    /// Index of the next action in the current row, see [Self::action].
+    next_action_index: usize,
+    /// The total number of rounds, i.e. rows for which all actions were processed.
+    total_round_count: usize,
+}
+
+impl DependencyOrderedIterator {
+    /// Creates the iterator, or returns `None` if the dependencies between the
+    /// identities contain a cycle.
+    #[cfg(test)]
+    pub fn new<T: FieldElement>(
+        block_size: usize,
+        identities: &[&Identity<Expression<T>>],
+        outer_query_row: Option<i64>,
+    ) -> Option<Self> {
+        Some(Self::with_identity_order(
+            block_size,
+            dependency_order(identities)?,
+            outer_query_row,
+        ))
+    }
+
+    fn with_identity_order(
+        block_size: usize,
+        identity_order: Vec<usize>,
+        outer_query_row: Option<i64>,
+    ) -> Self {
+        let max_row = block_size as i64 - 1;
+        DependencyOrderedIterator {
+            identity_order,
+            row_deltas: (-1..=max_row).chain((-1..max_row).rev()).collect(),
+            backward_start: block_size + 1,
+            outer_query_row,
+            progress_in_current_pass: false,
+            cur_row_delta_index: 0,
+            next_action_index: 0,
+            total_round_count: 0,
+        }
+    }
+
+    /// The number of actions in the row with the given row delta: All identities
+    /// and the prover queries, plus the outer query before and after them if on the
+    /// outer query row, so that the inputs are known before processing the identities.
+    fn action_count(&self, row_delta: i64) -> usize {
+        self.identity_order.len() + 1 + 2 * usize::from(self.outer_query_row == Some(row_delta))
+    }
+
+    fn action(&self, row_delta: i64, action_index: usize) -> Action {
+        let action_index = if self.outer_query_row == Some(row_delta) {
+            if action_index == 0 {
+                return Action::OuterQuery;
+            }
+            action_index - 1
+        } else {
+            action_index
+        };
+        match action_index.cmp(&self.identity_order.len()) {
+            std::cmp::Ordering::Less => Action::InternalIdentity(self.identity_order[action_index]),
+            std::cmp::Ordering::Equal => Action::ProverQueries,
+            std::cmp::Ordering::Greater => Action::OuterQuery,
+        }
+    }
+
+    pub fn report_progress(&mut self, progress_in_last_step: bool) {
+        assert!(
+            self.next_action_index != 0,
+            "Called report_progress() before next()"
+        );
+
+        self.progress_in_current_pass |= progress_in_last_step;
+    }
+
+    pub fn next(&mut self) -> Option<SequenceStep> {
+        if self.cur_row_delta_index == self.row_deltas.len() {
+            return None;
+        }
+        if self.next_action_index == self.action_count(self.row_deltas[self.cur_row_delta_index]) {
+            self.total_round_count += 1;
+            self.next_action_index = 0;
+            self.cur_row_delta_index += 1;
+            if self.cur_row_delta_index == self.backward_start
+                || self.cur_row_delta_index == self.row_deltas.len()
+            {
+                if !self.progress_in_current_pass {
+                    // The last pass did not change anything, so no further pass will.
+                    self.cur_row_delta_index = self.row_deltas.len();
+                    return None;
+                }
+                self.cur_row_delta_index %= self.row_deltas.len();
+                self.progress_in_current_pass = false;
+            }
+        }
+
+        self.next_action_index += 1;
+        Some(self.current_step())
+    }
+
+    fn current_step(&self) -> SequenceStep {
+        let row_delta = self.row_deltas[self.cur_row_delta_index];
+        SequenceStep {
+            row_delta,
+            action: self.action(row_delta, self.next_action_index - 1),
+        }
+    }
+}
+
+/// Sorts the identities topologically, such that an identity defining a
+/// column in the current row comes before all identities using it.
+/// A polynomial identity of the form `c = e`, possibly multiplied by factors
+/// without witness columns (like `ISFIRST * (c - e) = 0`), defines `c` and uses
+/// the witness columns of `e`. All other identities only use their columns.
+/// Returns `None` if the dependencies contain a cycle.
+fn dependency_order<T: FieldElement>(
+    identities: &[&Identity<Expression<T>>],
+) -> Option<Vec<usize>> {
+    let (defined, used): (Vec<_>, Vec<_>) = identities
+        .iter()
+        .map(|identity| {
+            let defined = (identity.kind == IdentityKind::Polynomial)
+                .then(|| defined_column(identity.expression_for_poly_id()))
+                .flatten();
+            let used = identity
+                .all_children()
+                .filter_map(|e| match e {
+                    Expression::Reference(r) if r.is_witness() && !r.next => Some(r.poly_id),
+                    _ => None,
+                })
+                .filter(|poly_id| defined.map(|d| (d.poly_id, d.next)) != Some((*poly_id, false)))
+                .collect::<BTreeSet<PolyID>>();
+            let defined = defined.filter(|d| !d.next).map(|d| d.poly_id);
+            (defined, used)
+        })
+        .unzip();
+
+    // For each identity, the identities that have to be processed before it.
+    let mut dependencies = used
+        .iter()
+        .enumerate()
+        .map(|(i, used)| {
+            (0..identities.len())
+                .filter(|j| *j != i && defined[*j].map_or(false, |d| used.contains(&d)))
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut order = Vec::with_capacity(identities.len());
+    while order.len() < identities.len() {
+        // Always pick the first ready identity to stay close to the source order.
+        let next =
+            (0..identities.len()).find(|i| !order.contains(i) && dependencies[*i].is_empty())?;
+        order.push(next);
+        for deps in &mut dependencies {
+            deps.remove(&next);
+        }
+    }
+    Some(order)
+}
+
+/// Returns the witness column `c` if the expression is of the form `c - e`, where `e`
+/// does not reference `c`, possibly multiplied by factors without witness columns.
+pub(super) fn defined_column<T: FieldElement>(expr: &Expression<T>) -> Option<&AlgebraicReference> {
+    let has_witness_columns = |e: &Expression<T>| {
+        e.all_children()
+            .any(|e| matches!(e, Expression::Reference(r) if r.is_witness()))
+    };
+    match expr {
+        Expression::BinaryOperation(left, AlgebraicBinaryOperator::Mul, right) => {
+            match (has_witness_columns(left), has_witness_columns(right)) {
+                (true, false) => defined_column(left),
+                (false, true) => defined_column(right),
+                _ => None,
+            }
+        }
+        Expression::BinaryOperation(left, AlgebraicBinaryOperator::Sub, right) if matches!(right.as_ref(), Expression::Number(n) if n.is_zero()) => {
+            defined_column(left)
+        }
+        Expression::BinaryOperation(left, AlgebraicBinaryOperator::Sub, right) => {
+            match left.as_ref() {
+                Expression::Reference(c)
+                    if c.is_witness()
+                        && !right.all_children().any(
+                            |e| matches!(e, Expression::Reference(r) if r.poly_id == c.poly_id),
+                        ) =>
+                {
+                    Some(c)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Action {
     InternalIdentity(usize),
@@ -169,6 +425,8 @@ where
 pub enum ProcessingSequenceIterator {
     /// The default strategy
     Default(DefaultSequenceIterator),
+    /// Processes the identities in dependency order, see [DependencyOrderedIterator].
+    DependencyOrdered(DependencyOrderedIterator),
     /// The machine has been run successfully before and the sequence is cached.
     Cached(<Vec<SequenceStep> as IntoIterator>::IntoIter),
     /// The machine has been run before, but did not succeed. There is no point in trying again.
@@ -176,27 +434,92 @@ pub enum ProcessingSequenceIterator {
 }
 
 impl ProcessingSequenceIterator {
+    /// Uses [DependencyOrderedIterator] if the identities can be ordered by their
+    /// dependencies and falls back to [DefaultSequenceIterator] otherwise.
+    #[cfg(test)]
+    pub fn dependency_ordered<T: FieldElement>(
+        block_size: usize,
+        identities: &[&Identity<Expression<T>>],
+        outer_query_row: Option<i64>,
+    ) -> Self {
+        Self::with_identity_order(
+            block_size,
+            identities.len(),
+            dependency_order(identities),
+            outer_query_row,
+        )
+    }
+
+    fn with_identity_order(
+        block_size: usize,
+        identities_count: usize,
+        identity_order: Option<Vec<usize>>,
+        outer_query_row: Option<i64>,
+    ) -> Self {
+        match identity_order {
+            Some(identity_order) => {
+                Self::DependencyOrdered(DependencyOrderedIterator::with_identity_order(
+                    block_size,
+                    identity_order,
+                    outer_query_row,
+                ))
+            }
+            None => {
+                log::trace!("Identities have cyclic dependencies, using default sequence");
+                Self::Default(DefaultSequenceIterator::new(
+                    block_size,
+                    identities_count,
+                    outer_query_row,
+                ))
+            }
+        }
+    }
+
     pub fn report_progress(&mut self, progress_in_last_step: bool) {
         match self {
             Self::Default(it) => it.report_progress(progress_in_last_step),
+            Self::DependencyOrdered(it) => it.report_progress(progress_in_last_step),
             Self::Cached(_) => {} // Progress is ignored
             Self::Incomplete => unreachable!(),
         }
     }
 
+    /// Restarts the iterator for another attempt after the current one did not
+    /// determine all values, see [DefaultSequenceIterator::restart_with_blocked_identities_last].
+    /// Returns `false` if this kind of iterator cannot be restarted.
+    pub fn restart(&mut self) -> bool {
+        match self {
+            Self::Default(it) => {
+                it.restart_with_blocked_identities_last();
+                true
+            }
+            Self::DependencyOrdered(_) | Self::Cached(_) | Self::Incomplete => false,
+        }
+    }
+
     pub fn has_steps(&self) -> bool {
         match self {
-            Self::Default(_) | Self::Cached(_) => true,
+            Self::Default(_) | Self::DependencyOrdered(_) | Self::Cached(_) => true,
             Self::Incomplete => false,
         }
     }
 
     pub fn is_cached(&self) -> bool {
         match self {
-            Self::Default(_) => false,
+            Self::Default(_) | Self::DependencyOrdered(_) => false,
             Self::Cached(_) | Self::Incomplete => true,
         }
     }
+
+    /// The number of rounds so far, i.e. the number of times all actions of a row
+    /// were processed. Returns `None` for cached sequences.
+    pub fn round_count(&self) -> Option<usize> {
+        match self {
+            Self::Default(it) => Some(it.total_round_count),
+            Self::DependencyOrdered(it) => Some(it.total_round_count),
+            Self::Cached(_) | Self::Incomplete => None,
+        }
+    }
 }
 
 impl Iterator for ProcessingSequenceIterator {
@@ -205,6 +528,7 @@ impl Iterator for ProcessingSequenceIterator {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             Self::Default(it) => it.next(),
+            Self::DependencyOrdered(it) => it.next(),
             Self::Cached(it) => it.next(),
             Self::Incomplete => unreachable!(),
         }
@@ -222,15 +546,22 @@ pub struct ProcessingSequenceCache {
     block_size: usize,
     outer_query_row: usize,
     identities_count: usize,
+    /// The dependency order of the identities, or `None` if they have cyclic dependencies.
+    identity_order: Option<Vec<usize>>,
     cache: BTreeMap<SequenceCacheKey, CacheEntry>,
 }
 
 impl ProcessingSequenceCache {
-    pub fn new(block_size: usize, outer_query_row: usize, identities_count: usize) -> Self {
+    pub fn new<T: FieldElement>(
+        block_size: usize,
+        outer_query_row: usize,
+        identities: &[&Identity<Expression<T>>],
+    ) -> Self {
         ProcessingSequenceCache {
             block_size,
             outer_query_row,
-            identities_count,
+            identities_count: identities.len(),
+            identity_order: dependency_order(identities),
             cache: Default::default(),
         }
     }
@@ -257,11 +588,12 @@ impl ProcessingSequenceCache {
     }
 
     pub fn get_default_sequence_iterator(&self) -> ProcessingSequenceIterator {
-        ProcessingSequenceIterator::Default(DefaultSequenceIterator::new(
+        ProcessingSequenceIterator::with_identity_order(
             self.block_size,
             self.identities_count,
+            self.identity_order.clone(),
             Some(self.outer_query_row as i64),
-        ))
+        )
     }
 
     pub fn report_incomplete<K, T>(&mut self, left: &[AffineExpression<K, T>])
@@ -275,23 +607,19 @@ impl ProcessingSequenceCache {
             .is_none());
     }
 
+    /// Caches the steps that solved the machine for the inputs `left`,
+    /// see [super::block_processor::BlockProcessor::recorded_sequence].
     pub fn report_processing_sequence<K, T>(
         &mut self,
         left: &[AffineExpression<K, T>],
-        sequence_iterator: ProcessingSequenceIterator,
+        sequence: Vec<SequenceStep>,
     ) where
         K: Copy + Ord,
         T: FieldElement,
     {
-        match sequence_iterator {
-            ProcessingSequenceIterator::Default(it) => {
-                assert!(self
-                    .cache
-                    .insert(left.into(), CacheEntry::Complete(it.progress_steps))
-                    .is_none());
-            }
-            ProcessingSequenceIterator::Incomplete => unreachable!(),
-            ProcessingSequenceIterator::Cached(_) => {} // Already cached, do nothing
-        }
+        assert!(self
+            .cache
+            .insert(left.into(), CacheEntry::Complete(sequence))
+            .is_none());
     }
 }
@@ -172,6 +172,7 @@ fn free_inputs_in_expression<'a>(
         Expression::LambdaExpression(_) => todo!(),
         Expression::ArrayLiteral(_) => todo!(),
         Expression::IndexAccess(_) => todo!(),
+        Expression::ArraySlice(_) => todo!(),
         Expression::MatchExpression(_, _) => todo!(),
         Expression::IfExpression(_) => todo!(),
         Expression::BlockExpression(_, _) => todo!(),
@@ -207,6 +208,7 @@ fn free_inputs_in_expression_mut<'a>(
         Expression::LambdaExpression(_) => todo!(),
         Expression::ArrayLiteral(_) => todo!(),
         Expression::IndexAccess(_) => todo!(),
+        Expression::ArraySlice(_) => todo!(),
         Expression::MatchExpression(_, _) => todo!(),
         Expression::IfExpression(_) => todo!(),
         Expression::BlockExpression(_, _) => todo!(),
@@ -595,6 +597,9 @@ fn check_expression(
             check_expression(location, function, state, local_variables)?;
             check_expressions(location, arguments, state, local_variables)
         }
+        Expression::ArraySlice(slice) => slice
+            .children()
+            .try_for_each(|e| check_expression(location, e, state, local_variables)),
         Expression::MatchExpression(scrutinee, arms) => {
             check_expression(location, scrutinee, state, local_variables)?;
             arms.iter().try_for_each(|MatchArm { pattern, value }| {
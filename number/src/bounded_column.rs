@@ -0,0 +1,139 @@
+use crate::{FieldElement, LargeInt};
+
+/// The values of a column that are all known to fit into `BITS` bits,
+/// like RISC-V bytes, registers or memory words.
+/// The values are stored in the smallest unsigned integer type that can hold
+/// `BITS` bits and are only converted to field elements when they are read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundedColumn<const BITS: u32> {
+    values: BoundedValues,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum BoundedValues {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+}
+
+impl<const BITS: u32> BoundedColumn<BITS> {
+    /// Creates the column from integers, or returns the first value
+    /// that does not fit into `BITS` bits.
+    pub fn from_u64s(values: impl IntoIterator<Item = u64>) -> Result<Self, u64> {
+        assert!(BITS <= 64, "Bounded columns can have at most 64 bits.");
+        let values = values.into_iter().map(|v| {
+            if v.checked_shr(BITS).unwrap_or(0) == 0 {
+                Ok(v)
+            } else {
+                Err(v)
+            }
+        });
+        Ok(Self {
+            values: match BITS {
+                0..=8 => BoundedValues::U8(
+                    values
+                        .map(|v| v.map(|v| v as u8))
+                        .collect::<Result<_, _>>()?,
+                ),
+                9..=16 => BoundedValues::U16(
+                    values
+                        .map(|v| v.map(|v| v as u16))
+                        .collect::<Result<_, _>>()?,
+                ),
+                17..=32 => BoundedValues::U32(
+                    values
+                        .map(|v| v.map(|v| v as u32))
+                        .collect::<Result<_, _>>()?,
+                ),
+                _ => BoundedValues::U64(values.collect::<Result<_, _>>()?),
+            },
+        })
+    }
+
+    /// Creates the column from field elements, or returns an error naming the first
+    /// value that does not fit into `BITS` bits.
+    pub fn from_field_elements<T: FieldElement>(values: &[T]) -> Result<Self, String> {
+        let too_large = |v: &T| format!("Value {v} does not fit into {BITS} bits.");
+        let values = values
+            .iter()
+            .map(|v| v.to_integer().try_into_u64().ok_or_else(|| too_large(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_u64s(values).map_err(|v| too_large(&T::from(v)))
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.values {
+            BoundedValues::U8(v) => v.len(),
+            BoundedValues::U16(v) => v.len(),
+            BoundedValues::U32(v) => v.len(),
+            BoundedValues::U64(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value in the given row as an integer.
+    pub fn get_u64(&self, row: usize) -> u64 {
+        match &self.values {
+            BoundedValues::U8(v) => v[row] as u64,
+            BoundedValues::U16(v) => v[row] as u64,
+            BoundedValues::U32(v) => v[row] as u64,
+            BoundedValues::U64(v) => v[row],
+        }
+    }
+
+    /// Returns the value in the given row as a field element.
+    pub fn get<T: FieldElement>(&self, row: usize) -> T {
+        T::from(self.get_u64(row))
+    }
+
+    /// Iterates over the values as field elements.
+    pub fn iter<T: FieldElement>(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map(|row| self.get(row))
+    }
+
+    /// Converts all values to field elements.
+    pub fn to_field_elements<T: FieldElement>(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::GoldilocksField;
+    use test_log::test;
+
+    #[test]
+    fn round_trip() {
+        let values = [0u64, 1, 17, 255].map(GoldilocksField::from);
+        let column = BoundedColumn::<8>::from_field_elements(&values).unwrap();
+        assert_eq!(column.len(), 4);
+        assert_eq!(
+            column.get::<GoldilocksField>(2),
+            GoldilocksField::from(17u64)
+        );
+        assert_eq!(column.to_field_elements::<GoldilocksField>(), values);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let values = [0u64, 256].map(GoldilocksField::from);
+        assert_eq!(
+            BoundedColumn::<8>::from_field_elements(&values),
+            Err("Value 256 does not fit into 8 bits.".to_string())
+        );
+        assert_eq!(BoundedColumn::<16>::from_u64s([1, 1 << 16]), Err(1 << 16));
+        assert!(BoundedColumn::<64>::from_u64s([u64::MAX]).is_ok());
+        assert_eq!(
+            BoundedColumn::<8>::from_field_elements(&[-GoldilocksField::from(1u64)]),
+            Err(format!(
+                "Value {} does not fit into 8 bits.",
+                -GoldilocksField::from(1u64)
+            ))
+        );
+    }
+}
@@ -37,6 +37,39 @@ mod test {
         assert_eq!(n ^ p, n_xor_p);
     }
 
+    #[test]
+    fn to_bits_le() {
+        let x = GoldilocksField::from(0b1011);
+        assert_eq!(x.to_bits_le(4), Ok(vec![true, true, false, true]));
+        assert_eq!(
+            x.to_bits_le(6),
+            Ok(vec![true, true, false, true, false, false])
+        );
+        assert!(x.to_bits_le(3).is_err());
+        assert_eq!(GoldilocksField::from(0).to_bits_le(0), Ok(vec![]));
+        assert_eq!(
+            (-GoldilocksField::from(1)).to_bits_le(64).unwrap().len(),
+            64
+        );
+    }
+
+    #[test]
+    fn to_bytes_le_checked() {
+        let x = GoldilocksField::from(0x12345678);
+        assert_eq!(x.to_bytes_le_checked(4), Ok(vec![0x78, 0x56, 0x34, 0x12]));
+        assert_eq!(
+            x.to_bytes_le_checked(10),
+            Ok(vec![0x78, 0x56, 0x34, 0x12, 0, 0, 0, 0, 0, 0])
+        );
+        assert!(x.to_bytes_le_checked(3).is_err());
+        // A single byte, as needed for byte access.
+        assert_eq!(
+            GoldilocksField::from(0xff).to_bytes_le_checked(1),
+            Ok(vec![0xff])
+        );
+        assert!(GoldilocksField::from(0x100).to_bytes_le_checked(1).is_err());
+    }
+
     #[test]
     fn lower_half() {
         let x = GoldilocksField::from(0);
@@ -60,4 +93,51 @@ mod test {
     fn div_by_zero() {
         let _ = GoldilocksField::from(1) / GoldilocksField::from(0);
     }
+
+    #[test]
+    fn from_signed() {
+        assert_eq!(GoldilocksField::from_signed(0), 0.into());
+        assert_eq!(GoldilocksField::from_signed(7), 7.into());
+        assert_eq!(
+            GoldilocksField::from_signed(-7),
+            GoldilocksField::from_str_radix("fffffffefffffffa", 16).unwrap()
+        );
+        assert_eq!(
+            GoldilocksField::from_signed(i64::MAX),
+            GoldilocksField::from(i64::MAX as u64)
+        );
+        // modulus - 2^63
+        assert_eq!(
+            GoldilocksField::from_signed(i64::MIN),
+            GoldilocksField::from_str_radix("7fffffff00000001", 16).unwrap()
+        );
+    }
+
+    #[test]
+    fn powers() {
+        assert_eq!(GoldilocksField::powers(3.into(), 0), vec![]);
+        assert_eq!(
+            GoldilocksField::powers(3.into(), 4),
+            [1, 3, 9, 27].map(GoldilocksField::from).to_vec()
+        );
+        assert_eq!(
+            GoldilocksField::powers_of_two(4),
+            [1, 2, 4, 8].map(GoldilocksField::from).to_vec()
+        );
+    }
+
+    #[test]
+    fn powers_of_two_matches_doubling() {
+        let powers = GoldilocksField::powers_of_two(100);
+        let mut expected = GoldilocksField::from(1);
+        for p in powers {
+            assert_eq!(p, expected);
+            expected = expected + expected;
+        }
+        // 2^64 wraps around to 2^32 - 1.
+        assert_eq!(
+            GoldilocksField::powers_of_two(65)[64],
+            GoldilocksField::from(u32::MAX)
+        );
+    }
 }
@@ -5,16 +5,23 @@
 #[macro_use]
 mod macros;
 mod bn254;
+mod bounded_column;
 mod goldilocks;
+#[cfg(any(test, feature = "testing"))]
+mod mock_field;
 mod serialize;
 mod traits;
 
 pub use serialize::{
-    read_polys_csv_file, read_polys_file, write_polys_csv_file, write_polys_file, CsvRenderMode,
+    read_named_polys_file, read_polys_csv_file, read_polys_file, write_named_polys_file,
+    write_polys_csv_file, write_polys_file, CsvRenderMode,
 };
 
 pub use bn254::Bn254Field;
+pub use bounded_column::BoundedColumn;
 pub use goldilocks::GoldilocksField;
+#[cfg(any(test, feature = "testing"))]
+pub use mock_field::MockField;
 pub use traits::KnownField;
 
 pub use ibig::{IBig as BigInt, UBig as BigUint};
@@ -0,0 +1,34 @@
+use ark_ff::{Fp64, MontBackend, MontConfig};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(MontConfig)]
+#[modulus = "97"]
+#[generator = "5"]
+pub struct MockBaseFieldConfig;
+pub type MockBaseField = Fp64<MontBackend<MockBaseFieldConfig, 1>>;
+
+// A tiny prime field, only meant for tests: Overflows and inverses are
+// easy to compute by hand. It is not a known field for any backend.
+powdr_field!(MockField, MockBaseField, None);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn wrap_around() {
+        assert_eq!(MockField::from(96) + MockField::from(2), MockField::from(1));
+        assert_eq!(MockField::from(100), MockField::from(3));
+        assert_eq!(-MockField::from(1), MockField::from(96));
+        assert_eq!(MockField::modulus().to_arbitrary_integer(), 97u32.into());
+        assert_eq!(MockField::known_field(), None);
+    }
+
+    #[test]
+    fn inverse() {
+        assert_eq!(MockField::from(1) / MockField::from(3), MockField::from(65));
+        assert_eq!(MockField::from(65) * MockField::from(3), MockField::from(1));
+    }
+}
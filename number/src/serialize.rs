@@ -1,4 +1,4 @@
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
 use csv::{Reader, Writer};
@@ -143,6 +143,78 @@ pub fn read_polys_file<T: FieldElement>(
     }
 }
 
+/// Writes the polynomials together with their names, so that they can be read back
+/// with [read_named_polys_file] without knowing the columns in advance.
+/// The format is the number of columns, followed by the length and bytes of each name,
+/// the degree and finally the values in the format of [write_polys_file].
+/// All integers are encoded as little-endian u64.
+pub fn write_named_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String, Vec<T>)]) {
+    file.write_all(&(polys.len() as u64).to_le_bytes()).unwrap();
+    for (name, _) in polys {
+        file.write_all(&(name.len() as u64).to_le_bytes()).unwrap();
+        file.write_all(name.as_bytes()).unwrap();
+    }
+    let degree = polys
+        .first()
+        .map(|(_, values)| values.len())
+        .unwrap_or_default();
+    file.write_all(&(degree as u64).to_le_bytes()).unwrap();
+    write_polys_file(file, polys);
+}
+
+/// The maximum number of columns [read_named_polys_file] accepts, so that a corrupted
+/// file cannot make it allocate arbitrary amounts of memory.
+const MAX_NAMED_POLYS_COLUMNS: u64 = 1 << 20;
+
+fn read_u64(file: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reads polynomials written by [write_named_polys_file].
+pub fn read_named_polys_file<T: FieldElement>(
+    file: &mut impl Read,
+) -> io::Result<Vec<(String, Vec<T>)>> {
+    let column_count = read_u64(file)?;
+    if column_count > MAX_NAMED_POLYS_COLUMNS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected at most {MAX_NAMED_POLYS_COLUMNS} columns but found {column_count}."),
+        ));
+    }
+    let names = (0..column_count)
+        .map(|_| {
+            let length = read_u64(file)?;
+            // Only allocates as much as the file actually contains.
+            let mut name = vec![];
+            file.by_ref().take(length).read_to_end(&mut name)?;
+            if name.len() as u64 != length {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Expected a name of {length} bytes but found {}.",
+                        name.len()
+                    ),
+                ));
+            }
+            String::from_utf8(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let degree = read_u64(file)?;
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+    let (polys, actual_degree) = read_polys_file(file, &names);
+    if actual_degree != degree {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Expected {degree} rows but found {actual_degree}."),
+        ));
+    }
+    Ok(polys)
+}
+
 // Serde wrappers for serialize/deserialize
 
 pub fn ark_se<S, A: CanonicalSerialize>(a: &A, s: S) -> Result<S::Ok, S::Error>
@@ -198,6 +270,44 @@ mod tests {
         assert_eq!(read_degree, degree);
     }
 
+    #[test]
+    fn write_read_named() {
+        let mut buf: Vec<u8> = vec![];
+
+        let (polys, _) = test_polys();
+
+        write_named_polys_file(&mut buf, &polys);
+        let read_polys = read_named_polys_file::<Bn254Field>(&mut Cursor::new(buf)).unwrap();
+
+        assert_eq!(read_polys, polys);
+    }
+
+    #[test]
+    fn read_named_corrupted() {
+        let read = |buf: Vec<u8>| {
+            read_named_polys_file::<Bn254Field>(&mut Cursor::new(buf))
+                .unwrap_err()
+                .to_string()
+        };
+
+        let buf = (MAX_NAMED_POLYS_COLUMNS + 1).to_le_bytes().to_vec();
+        assert_eq!(
+            read(buf),
+            format!(
+                "Expected at most {MAX_NAMED_POLYS_COLUMNS} columns but found {}.",
+                MAX_NAMED_POLYS_COLUMNS + 1
+            )
+        );
+
+        // A name length far beyond the size of the file.
+        let buf = [1u64.to_le_bytes(), u64::MAX.to_le_bytes()].concat();
+        let buf = [buf, b"a".to_vec()].concat();
+        assert_eq!(
+            read(buf),
+            "Expected a name of 18446744073709551615 bytes but found 1."
+        );
+    }
+
     #[test]
     fn write_read_csv() {
         let polys = test_polys()
@@ -59,7 +59,7 @@ pub trait LargeInt:
     fn try_into_u32(&self) -> Option<u32>;
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KnownField {
     GoldilocksField,
     Bn254Field,
@@ -132,6 +132,40 @@ pub trait FieldElement:
 
     fn from_str_radix(s: &str, radix: u32) -> Result<Self, String>;
 
+    /// Compares two elements without branching on their values, so that the
+    /// time taken does not depend on where they differ.
+    fn ct_eq(&self, other: &Self) -> bool {
+        self.to_bytes_le()
+            .iter()
+            .zip(other.to_bytes_le())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+
+    /// Decomposes the value into its `n` least significant bits, least significant first.
+    /// Returns an error if the value does not fit into `n` bits.
+    fn to_bits_le(&self, n: usize) -> Result<Vec<bool>, String> {
+        let value = self.to_integer();
+        let num_bits = value.num_bits();
+        if num_bits > n {
+            return Err(format!("Value {self} does not fit into {n} bits."));
+        }
+        Ok((0..n)
+            .map(|i| i < num_bits && ((value >> i) & Self::Integer::one()).is_one())
+            .collect())
+    }
+
+    /// Decomposes the value into its `n` least significant bytes, least significant first.
+    /// Returns an error if the value does not fit into `n` bytes.
+    fn to_bytes_le_checked(&self, n: usize) -> Result<Vec<u8>, String> {
+        if self.to_integer().num_bits() > 8 * n {
+            return Err(format!("Value {self} does not fit into {n} bytes."));
+        }
+        let mut bytes = self.to_bytes_le();
+        bytes.resize(n, 0);
+        Ok(bytes)
+    }
+
     /// Only converts the value to a field element if it is less than the modulus.
     fn checked_from(value: BigUint) -> Option<Self>;
 
@@ -147,6 +181,27 @@ pub trait FieldElement:
     /// As conventional, negative values are in relation to 0 in the field.
     /// Returns None if out of the range [0 - 2^31, 2^31).
     fn try_into_i32(&self) -> Option<i32>;
+
+    /// Converts a signed integer, mapping negative values `-x` to `modulus - x`.
+    fn from_signed(i: i64) -> Self {
+        if i < 0 {
+            -Self::from(i.unsigned_abs())
+        } else {
+            Self::from(i as u64)
+        }
+    }
+
+    /// Returns the first `n` powers of `base`, i.e. `[1, base, base^2, ..., base^(n-1)]`.
+    fn powers(base: Self, n: usize) -> Vec<Self> {
+        std::iter::successors(Some(Self::one()), |p| Some(*p * base))
+            .take(n)
+            .collect()
+    }
+
+    /// Returns `[1, 2, 4, ..., 2^(n-1)]`, the limb weights of a bit decomposition.
+    fn powers_of_two(n: usize) -> Vec<Self> {
+        Self::powers(2u64.into(), n)
+    }
 }
 
 #[cfg(test)]
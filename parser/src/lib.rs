@@ -221,6 +221,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn serialize_round_trip() {
+        let input = r#"
+namespace Main(8);
+    let N: int = 8;
+    pol commit a, b[2];
+    col fixed P = [1, 2]* + [3];
+    col fixed Q(i) { i + 1 };
+    public out = a(N - 1);
+    #[step] a' = b[0] * P;
+    { a } in { Q };
+"#;
+        let parsed = parse(Some("test.pil"), input).unwrap_err_to_stderr();
+        let json = serde_json::to_string(&parsed).unwrap();
+        let deserialized: PILFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, deserialized);
+    }
+
     fn find_files_with_ext(
         dir: std::path::PathBuf,
         ext: String,
@@ -263,6 +281,10 @@ mod test {
             | PilStatement::ConstantDefinition(s, _, _)
             | PilStatement::Expression(s, _)
             | PilStatement::EnumDeclaration(s, _) => *s = SourceRef::unknown(),
+            PilStatement::LabeledIdentity(s, _, statement) => {
+                *s = SourceRef::unknown();
+                pil_statement_clear_source_ref(statement);
+            }
         }
     }
 
@@ -509,6 +531,32 @@ namespace N(2);
         assert_eq!(input.trim(), printed.trim());
     }
 
+    #[test]
+    fn enum_patterns() {
+        let input = r#"
+namespace N(2);
+    let f = (|o| match o {
+        Opt::Some(Pair::P(a, _)) => a,
+        Opt::Some(x) => x,
+        ::Opt::None => 0,
+    });
+"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
+    #[test]
+    fn array_slices() {
+        let input = r#"
+namespace N(2);
+    let x = arr[2..5];
+    let y = arr[..3];
+    let z = arr[(i + 1)..];
+    let w = arr[..][0];"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
     #[test]
     fn type_args() {
         let input = r#"
@@ -167,12 +167,15 @@ pub struct Condenser<'a, T> {
     /// The names of all new witness columns ever generated, to avoid duplicates.
     all_new_witness_names: HashSet<String>,
     new_constraints: Vec<IdentityWithoutID<AlgebraicExpression<T>>>,
+    /// The names of all columns already constrained to be boolean.
+    boolean_columns: HashSet<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IdentityWithoutID<Expr> {
     pub kind: IdentityKind,
     pub source: SourceRef,
+    pub label: Option<String>,
     /// For a simple polynomial identity, the selector contains
     /// the actual expression (see expression_for_poly_id).
     pub left: SelectedExpressions<Expr>,
@@ -185,6 +188,7 @@ impl<Expr> IdentityWithoutID<Expr> {
         Self {
             kind: IdentityKind::Polynomial,
             source,
+            label: None,
             left: SelectedExpressions {
                 selector: Some(identity),
                 expressions: vec![],
@@ -198,6 +202,7 @@ impl<Expr> IdentityWithoutID<Expr> {
             id,
             kind: self.kind,
             source: self.source,
+            label: self.label,
             left: self.left,
             right: self.right,
         }
@@ -224,12 +229,14 @@ impl<'a, T: FieldElement> Condenser<'a, T> {
             new_witnesses: vec![],
             all_new_witness_names: HashSet::new(),
             new_constraints: vec![],
+            boolean_columns: HashSet::new(),
         }
     }
 
     pub fn condense_identity(&mut self, identity: &'a Identity<Expression>) {
         if identity.kind == IdentityKind::Polynomial {
             let expr = identity.expression_for_poly_id();
+            let first_new_constraint = self.new_constraints.len();
             evaluator::evaluate(expr, self)
                 .and_then(|expr| self.add_constraints(expr, identity.source.clone()))
                 .unwrap_or_else(|err| {
@@ -237,12 +244,17 @@ impl<'a, T: FieldElement> Condenser<'a, T> {
                         "Error reducing expression to constraint:\nExpression: {expr}\nError: {err:?}"
                     )
                 });
+            // All constraints generated from a labeled statement carry its label.
+            for constraint in &mut self.new_constraints[first_new_constraint..] {
+                constraint.label = identity.label.clone();
+            }
         } else {
             let left = self.condense_selected_expressions(&identity.left);
             let right = self.condense_selected_expressions(&identity.right);
             self.new_constraints.push(IdentityWithoutID {
                 kind: identity.kind,
                 source: identity.source.clone(),
+                label: identity.label.clone(),
                 left,
                 right,
             })
@@ -389,6 +401,24 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for Condenser<'a, T> {
         }
         Ok(())
     }
+
+    fn add_booleanity_constraint(
+        &mut self,
+        column: &AlgebraicReference,
+    ) -> Result<(), evaluator::EvalError> {
+        if self.boolean_columns.insert(column.name.clone()) {
+            let column = AlgebraicExpression::Reference(AlgebraicReference {
+                next: false,
+                ..column.clone()
+            });
+            self.new_constraints
+                .push(IdentityWithoutID::from_polynomial_identity(
+                    SourceRef::unknown(),
+                    column.clone() * (AlgebraicExpression::from(T::one()) - column),
+                ));
+        }
+        Ok(())
+    }
 }
 
 impl<'a, T> Condenser<'a, T> {
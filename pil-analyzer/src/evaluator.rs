@@ -7,13 +7,14 @@ use std::{
 use itertools::Itertools;
 use powdr_ast::{
     analyzed::{
-        AlgebraicExpression, AlgebraicReference, Expression, FunctionValueDefinition, Reference,
-        Symbol, SymbolKind, TypedExpression,
+        AlgebraicExpression, AlgebraicReference, Expression, FunctionValueDefinition,
+        PolynomialType, Reference, Symbol, SymbolKind, TypedExpression,
     },
     parsed::{
         display::quote,
         types::{Type, TypeScheme},
-        BinaryOperator, FunctionCall, LambdaExpression, MatchArm, Pattern, UnaryOperator,
+        ArraySlice, BinaryOperator, FunctionCall, LambdaExpression, MatchArm, Pattern,
+        UnaryOperator,
     },
     SourceRef,
 };
@@ -68,15 +69,17 @@ pub fn evaluate_function_call<'a, T: FieldElement>(
 
                 )))?
             }
-            let matched_arguments =
-                arguments
-                    .iter()
-                    .zip(&lambda.params)
-                    .flat_map(|(arg, pattern)| {
-                        Value::try_match_pattern(arg, pattern).unwrap_or_else(|| {
-                            panic!("Irrefutable pattern did not match: {pattern} = {arg}")
-                        })
-                    });
+            let matched_arguments = arguments
+                .iter()
+                .zip(&lambda.params)
+                .map(|(arg, pattern)| {
+                    Ok(Value::try_match_pattern(arg, pattern)?.unwrap_or_else(|| {
+                        panic!("Irrefutable pattern did not match: {pattern} = {arg}")
+                    }))
+                })
+                .collect::<Result<Vec<_>, EvalError>>()?
+                .into_iter()
+                .flatten();
 
             let local_vars = environment
                 .iter()
@@ -258,8 +261,8 @@ impl<'a, T: FieldElement> Value<'a, T> {
     pub fn try_match_pattern<'b>(
         v: &Arc<Value<'b, T>>,
         pattern: &Pattern,
-    ) -> Option<Vec<Arc<Value<'b, T>>>> {
-        match pattern {
+    ) -> Result<Option<Vec<Arc<Value<'b, T>>>>, EvalError> {
+        Ok(match pattern {
             Pattern::Ellipsis => unreachable!("Should be handled higher up"),
             Pattern::CatchAll => Some(vec![]),
             Pattern::Number(n) => match v.as_ref() {
@@ -276,15 +279,7 @@ impl<'a, T: FieldElement> Value<'a, T> {
             Pattern::Tuple(items) => match v.as_ref() {
                 Value::Tuple(values) => {
                     assert_eq!(values.len(), items.len());
-                    values
-                        .iter()
-                        .zip(items)
-                        .try_fold(vec![], |mut vars, (e, p)| {
-                            Value::try_match_pattern(e, p).map(|v| {
-                                vars.extend(v);
-                                vars
-                            })
-                        })
+                    Value::try_match_patterns(values.iter().zip(items))?
                 }
                 _ => unreachable!(),
             },
@@ -300,7 +295,7 @@ impl<'a, T: FieldElement> Value<'a, T> {
                     None => values.len() == items.len(),
                 };
                 if !length_matches {
-                    return None;
+                    return Ok(None);
                 }
                 // Split value into "left" and "right" part.
                 let left_len = ellipsis_pos.unwrap_or(values.len());
@@ -311,17 +306,34 @@ impl<'a, T: FieldElement> Value<'a, T> {
                     left.len() + right.len(),
                     items.len() - ellipsis_pos.map(|_| 1).unwrap_or_default()
                 );
-                left.chain(right)
-                    .zip(items.iter().filter(|&i| *i != Pattern::Ellipsis))
-                    .try_fold(vec![], |mut vars, (e, p)| {
-                        Value::try_match_pattern(e, p).map(|v| {
-                            vars.extend(v);
-                            vars
-                        })
-                    })
+                Value::try_match_patterns(
+                    left.chain(right)
+                        .zip(items.iter().filter(|&i| *i != Pattern::Ellipsis)),
+                )?
             }
             Pattern::Variable(_) => Some(vec![v.clone()]),
+            Pattern::Enum(_, _) => Err(EvalError::Unsupported(format!(
+                "Enum patterns are not yet supported: {pattern}"
+            )))?,
+        })
+    }
+
+    /// Matches each value against its pattern and returns the combined bindings
+    /// if all of them match.
+    fn try_match_patterns<'b, 'c>(
+        values_and_patterns: impl Iterator<Item = (&'c Arc<Value<'b, T>>, &'c Pattern)>,
+    ) -> Result<Option<Vec<Arc<Value<'b, T>>>>, EvalError>
+    where
+        'b: 'c,
+    {
+        let mut vars = vec![];
+        for (v, p) in values_and_patterns {
+            match Value::try_match_pattern(v, p)? {
+                Some(v) => vars.extend(v),
+                None => return Ok(None),
+            }
         }
+        Ok(Some(vars))
     }
 }
 
@@ -531,6 +543,14 @@ pub trait SymbolLookup<'a, T> {
             "Tried to add constraints outside of statement context.".to_string(),
         ))
     }
+
+    /// Constrains the column to be boolean. Used when lowering logical operators
+    /// on algebraic expressions.
+    fn add_booleanity_constraint(&mut self, column: &AlgebraicReference) -> Result<(), EvalError> {
+        Err(EvalError::Unsupported(format!(
+            "Tried to constrain {column} to be boolean outside of statement context."
+        )))
+    }
 }
 
 mod internal {
@@ -574,7 +594,24 @@ mod internal {
             Expression::BinaryOperation(left, op, right) => {
                 let left = evaluate(left, locals, type_args, symbols)?;
                 let right = evaluate(right, locals, type_args, symbols)?;
-                evaluate_binary_operation(&left, *op, &right)?
+                match (left.as_ref(), op, right.as_ref()) {
+                    (
+                        Value::Expression(l),
+                        BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr,
+                        Value::Expression(r),
+                    ) => {
+                        ensure_boolean(l, *op, symbols)?;
+                        ensure_boolean(r, *op, symbols)?;
+                        let product = l.clone() * r.clone();
+                        Value::from(if *op == BinaryOperator::LogicalAnd {
+                            product
+                        } else {
+                            l.clone() + r.clone() - product
+                        })
+                        .into()
+                    }
+                    _ => evaluate_binary_operation(&left, *op, &right)?,
+                }
             }
             Expression::UnaryOperation(op, expr) => {
                 match (op, evaluate(expr, locals, type_args, symbols)?.as_ref()) {
@@ -582,6 +619,10 @@ mod internal {
                         Value::FieldElement(-*e).into()
                     }
                     (UnaryOperator::LogicalNot, Value::Bool(b)) => Value::Bool(!b).into(),
+                    (UnaryOperator::LogicalNot, Value::Expression(e)) => {
+                        ensure_boolean(e, *op, symbols)?;
+                        Value::from(AlgebraicExpression::from(T::one()) - e.clone()).into()
+                    }
                     (UnaryOperator::Minus, Value::Integer(n)) => Value::Integer(-n).into(),
                     (UnaryOperator::Next, Value::Expression(e)) => {
                         let AlgebraicExpression::Reference(reference) = e else {
@@ -646,6 +687,23 @@ mod internal {
                     e => Err(EvalError::TypeError(format!("Expected array, but got {e}")))?,
                 }
             }
+            Expression::ArraySlice(ArraySlice { array, from, to }) => {
+                let array = evaluate(array, locals, type_args, symbols)?;
+                let Value::Array(elements) = array.as_ref() else {
+                    return Err(EvalError::TypeError(format!(
+                        "Expected array, but got {array}"
+                    )));
+                };
+                let from = evaluate_slice_bound(from, 0, locals, type_args, symbols)?;
+                let to = evaluate_slice_bound(to, elements.len(), locals, type_args, symbols)?;
+                if from > to || to > elements.len() {
+                    Err(EvalError::OutOfBounds(format!(
+                        "Array slice out of bounds: Tried to access elements {from}..{to} of array of size {} in: {expr}.",
+                        elements.len()
+                    )))?
+                }
+                Value::Array(elements[from..to].to_vec()).into()
+            }
             Expression::FunctionCall(FunctionCall {
                 function,
                 arguments,
@@ -662,8 +720,11 @@ mod internal {
                 let (vars, body) = arms
                     .iter()
                     .find_map(|MatchArm { pattern, value }| {
-                        Value::try_match_pattern(&v, pattern).map(|vars| (vars, value))
+                        Value::try_match_pattern(&v, pattern)
+                            .map(|vars| vars.map(|vars| (vars, value)))
+                            .transpose()
                     })
+                    .transpose()?
                     .ok_or_else(EvalError::NoMatch)?;
                 let mut locals = locals.to_vec();
                 locals.extend(vars);
@@ -701,7 +762,7 @@ mod internal {
                                 symbols.new_witness_column(name, SourceRef::unknown())?
                             };
                             locals.extend(
-                                Value::try_match_pattern(&value, pattern).unwrap_or_else(|| {
+                                Value::try_match_pattern(&value, pattern)?.unwrap_or_else(|| {
                                     panic!("Irrefutable pattern did not match: {pattern} = {value}")
                                 }),
                             );
@@ -720,6 +781,26 @@ mod internal {
         })
     }
 
+    /// Evaluates an optional bound of an array slice, returning `default` if it is missing.
+    fn evaluate_slice_bound<'a, 'b, T: FieldElement>(
+        bound: &'a Option<Box<Expression>>,
+        default: usize,
+        locals: &[Arc<Value<'a, T>>],
+        type_args: &'b HashMap<String, Type>,
+        symbols: &mut impl SymbolLookup<'a, T>,
+    ) -> Result<usize, EvalError> {
+        let Some(bound) = bound else {
+            return Ok(default);
+        };
+        match evaluate(bound, locals, type_args, symbols)?.as_ref() {
+            Value::Integer(b) if !b.is_negative() => Ok(usize::try_from(b).unwrap_or(usize::MAX)),
+            b => Err(EvalError::TypeError(format!(
+                "Expected non-negative integer for array slice bound but got {b}: {}",
+                b.type_formatted()
+            ))),
+        }
+    }
+
     fn evaluate_literal<'a, T: FieldElement>(
         n: BigUint,
         ty: &Option<Type<u64>>,
@@ -858,6 +939,67 @@ mod internal {
         })
     }
 
+    /// Checks that an operand of a logical operator in a constraint context is
+    /// provably boolean, i.e. a constant zero or one, a witness or fixed column,
+    /// or an expression resulting from lowering another logical operator.
+    /// All witness columns involved are constrained to be boolean, fixed columns
+    /// are taken as they are.
+    fn ensure_boolean<'a, T: FieldElement>(
+        e: &AlgebraicExpression<T>,
+        op: impl Display,
+        symbols: &mut impl SymbolLookup<'a, T>,
+    ) -> Result<(), EvalError> {
+        let mut columns = vec![];
+        if !collect_boolean_columns(e, &mut columns) {
+            return Err(EvalError::TypeError(format!(
+                "Operand of {op} is not provably boolean: {e}"
+            )));
+        }
+        columns
+            .into_iter()
+            .try_for_each(|column| symbols.add_booleanity_constraint(column))
+    }
+
+    /// Returns true if the expression is boolean provided all witness columns
+    /// collected in `columns` are boolean.
+    fn collect_boolean_columns<'b, T: FieldElement>(
+        e: &'b AlgebraicExpression<T>,
+        columns: &mut Vec<&'b AlgebraicReference>,
+    ) -> bool {
+        match e {
+            AlgebraicExpression::Number(n) => n.is_zero() || n.is_one(),
+            AlgebraicExpression::Reference(reference) => match reference.poly_id.ptype {
+                PolynomialType::Committed => {
+                    columns.push(reference);
+                    true
+                }
+                PolynomialType::Constant => true,
+                PolynomialType::Intermediate => false,
+            },
+            // a && b
+            AlgebraicExpression::BinaryOperation(l, AlgebraicBinaryOperator::Mul, r) => {
+                collect_boolean_columns(l, columns) && collect_boolean_columns(r, columns)
+            }
+            // !a
+            AlgebraicExpression::BinaryOperation(one, AlgebraicBinaryOperator::Sub, a) if matches!(one.as_ref(), AlgebraicExpression::Number(n) if n.is_one()) => {
+                collect_boolean_columns(a, columns)
+            }
+            // a || b
+            AlgebraicExpression::BinaryOperation(sum, AlgebraicBinaryOperator::Sub, product) => {
+                match (sum.as_ref(), product.as_ref()) {
+                    (
+                        AlgebraicExpression::BinaryOperation(a, AlgebraicBinaryOperator::Add, b),
+                        AlgebraicExpression::BinaryOperation(a2, AlgebraicBinaryOperator::Mul, b2),
+                    ) if a == a2 && b == b2 => {
+                        collect_boolean_columns(a, columns) && collect_boolean_columns(b, columns)
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
     #[allow(clippy::print_stdout)]
     pub fn evaluate_builtin_function<'a, T: FieldElement>(
         b: BuiltinFunction,
@@ -1088,6 +1230,31 @@ mod test {
         assert_eq!(parse_and_evaluate_symbol(src, "F.y"), "0".to_string());
     }
 
+    #[test]
+    pub fn array_slices() {
+        let src = r#"namespace Main(16);
+            let arr: int[] = [1, 2, 3, 4, 5];
+            let middle = arr[1..4];
+            let head = arr[..2];
+            let tail = arr[3..];
+            let empty = arr[2..2];
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.middle"), "[2, 3, 4]");
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.head"), "[1, 2]");
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.tail"), "[4, 5]");
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.empty"), "[]");
+    }
+
+    #[test]
+    #[should_panic = "Array slice out of bounds"]
+    pub fn array_slice_out_of_bounds() {
+        let src = r#"namespace Main(16);
+            let arr: int[] = [1, 2, 3];
+            let x = arr[2..4];
+        "#;
+        parse_and_evaluate_symbol(src, "Main.x");
+    }
+
     #[test]
     #[should_panic = r#"FailedAssertion("this text")"#]
     pub fn panic_complex() {
@@ -1212,6 +1379,19 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn match_enum_pattern() {
+        let value = Arc::new(Value::<GoldilocksField>::Enum(
+            "Some",
+            Some(vec![Arc::new(Value::Integer(1.into()))]),
+        ));
+        let pattern = Pattern::Enum("Option::Some".parse().unwrap(), vec![Pattern::CatchAll]);
+        assert!(matches!(
+            Value::try_match_pattern(&value, &pattern),
+            Err(EvalError::Unsupported(_))
+        ));
+    }
+
     #[test]
     pub fn match_pattern_complex() {
         let src = r#"
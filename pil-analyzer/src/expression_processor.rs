@@ -93,8 +93,12 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                 })
             }
             PExpression::LambdaExpression(LambdaExpression { kind, params, body }) => {
-                let body = Box::new(self.process_function(&params, *body));
-                Expression::LambdaExpression(LambdaExpression { kind, params, body })
+                let (params, body) = self.process_function(params, *body);
+                Expression::LambdaExpression(LambdaExpression {
+                    kind,
+                    params,
+                    body: Box::new(body),
+                })
             }
             PExpression::BinaryOperation(left, op, right) => Expression::BinaryOperation(
                 Box::new(self.process_expression(*left)),
@@ -110,6 +114,13 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                     index: Box::new(self.process_expression(*index_access.index)),
                 })
             }
+            PExpression::ArraySlice(slice) => Expression::ArraySlice(parsed::ArraySlice {
+                array: Box::new(self.process_expression(*slice.array)),
+                from: slice
+                    .from
+                    .map(|from| Box::new(self.process_expression(*from))),
+                to: slice.to.map(|to| Box::new(self.process_expression(*to))),
+            }),
             PExpression::FunctionCall(c) => Expression::FunctionCall(parsed::FunctionCall {
                 function: Box::new(self.process_expression(*c.function)),
                 arguments: self.process_expressions(c.arguments),
@@ -119,7 +130,7 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                 arms.into_iter()
                     .map(|MatchArm { pattern, value }| {
                         let vars = self.save_local_variables();
-                        self.process_pattern(&pattern);
+                        let pattern = self.process_pattern(pattern);
                         let value = self.process_expression(value);
                         self.reset_local_variables(vars);
                         MatchArm { pattern, value }
@@ -142,29 +153,43 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
         }
     }
 
-    /// Processes a pattern, registering all variables bound in there.
-    fn process_pattern(&mut self, pattern: &Pattern) {
+    /// Processes a pattern, registering all variables bound in there
+    /// and resolving the paths of enum variants to absolute names.
+    fn process_pattern(&mut self, pattern: Pattern) -> Pattern {
         match pattern {
-            Pattern::CatchAll | Pattern::Ellipsis | Pattern::Number(_) | Pattern::String(_) => {}
-            Pattern::Tuple(items) | Pattern::Array(items) => {
-                if matches!(pattern, Pattern::Array(_)) {
-                    // If there is more than one Pattern::Ellipsis in items, it is an error
-                    if items.iter().filter(|p| *p == &Pattern::Ellipsis).count() > 1 {
-                        panic!("Only one \"..\"-item allowed in array pattern");
-                    }
+            Pattern::CatchAll | Pattern::Ellipsis | Pattern::Number(_) | Pattern::String(_) => {
+                pattern
+            }
+            Pattern::Array(items) => {
+                // If there is more than one Pattern::Ellipsis in items, it is an error
+                if items.iter().filter(|p| *p == &Pattern::Ellipsis).count() > 1 {
+                    panic!("Only one \"..\"-item allowed in array pattern");
                 }
-                items.iter().for_each(|p| self.process_pattern(p));
+                Pattern::Array(self.process_patterns(items))
+            }
+            Pattern::Tuple(items) => Pattern::Tuple(self.process_patterns(items)),
+            Pattern::Enum(name, items) => {
+                let name = self.driver.resolve_value_ref(&name).parse().unwrap();
+                Pattern::Enum(name, self.process_patterns(items))
             }
-            Pattern::Variable(name) => {
+            Pattern::Variable(ref name) => {
                 let id = self.local_variable_counter;
                 if self.local_variables.insert(name.clone(), id).is_some() {
                     panic!("Variable already defined: {name}");
                 }
                 self.local_variable_counter += 1;
+                pattern
             }
         }
     }
 
+    fn process_patterns(&mut self, patterns: Vec<Pattern>) -> Vec<Pattern> {
+        patterns
+            .into_iter()
+            .map(|p| self.process_pattern(p))
+            .collect()
+    }
+
     fn process_reference(&mut self, reference: NamespacedPolynomialReference) -> Reference {
         match reference.try_to_identifier() {
             Some(name) if self.local_variables.contains_key(name) => {
@@ -177,21 +202,21 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
 
     pub fn process_function(
         &mut self,
-        params: &[Pattern],
+        params: Vec<Pattern>,
         expression: ::powdr_ast::parsed::Expression,
-    ) -> Expression {
+    ) -> (Vec<Pattern>, Expression) {
         let previous_local_vars = self.save_local_variables();
 
-        for param in params {
+        for param in &params {
             if !param.is_irrefutable() {
                 panic!("Function parameters must be irrefutable, but {param} is refutable.");
             }
-            self.process_pattern(param);
         }
+        let params = self.process_patterns(params);
         let processed_value = self.process_expression(expression);
 
         self.reset_local_variables(previous_local_vars);
-        processed_value
+        (params, processed_value)
     }
 
     fn process_block_expression(
@@ -212,7 +237,7 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                         panic!("Let statement requires an irrefutable pattern, but {pattern} is refutable.");
                     }
                     let value = value.map(|v| self.process_expression(v));
-                    self.process_pattern(&pattern);
+                    let pattern = self.process_pattern(pattern);
                     StatementInsideBlock::LetStatement(LetStatementInsideBlock { pattern, value })
                 }
                 StatementInsideBlock::Expression(expr) => {
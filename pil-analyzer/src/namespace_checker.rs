@@ -0,0 +1,66 @@
+use std::fmt::{self, Display};
+
+use powdr_ast::{
+    analyzed::{AlgebraicExpression, Analyzed, IdentityKind, PolynomialType},
+    parsed::visitor::AllChildren,
+};
+
+/// A polynomial identity that directly references witness columns from different namespaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossNamespaceReference {
+    /// The identity, formatted.
+    pub identity: String,
+    /// The first column of the identity.
+    pub column: String,
+    /// A column of the identity in a different namespace than `column`.
+    pub other_column: String,
+}
+
+impl Display for CrossNamespaceReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Polynomial identity references columns {} and {} from different namespaces. \
+            Use a lookup or permutation to connect machines instead: {}",
+            self.column, self.other_column, self.identity
+        )
+    }
+}
+
+/// Checks that polynomial identities only reference witness columns of a single namespace.
+/// Machines can only be connected through lookups and permutations, since
+/// witness generation processes each machine on its own.
+/// Fixed columns do not need to be solved for, so they can be shared across namespaces.
+pub fn check<T: Display>(analyzed: &Analyzed<T>) -> Result<(), CrossNamespaceReference> {
+    for identity in analyzed
+        .identities
+        .iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+    {
+        let mut columns = identity.all_children().filter_map(|e| match e {
+            AlgebraicExpression::Reference(reference)
+                if reference.poly_id.ptype != PolynomialType::Constant =>
+            {
+                Some(&reference.name)
+            }
+            _ => None,
+        });
+        let Some(first) = columns.next() else {
+            continue;
+        };
+        if let Some(other) = columns.find(|name| namespace_of(name) != namespace_of(first)) {
+            return Err(CrossNamespaceReference {
+                identity: identity.to_string(),
+                column: first.clone(),
+                other_column: other.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn namespace_of(name: &str) -> &str {
+    name.rsplit_once('.')
+        .map(|(namespace, _)| namespace)
+        .unwrap_or_default()
+}
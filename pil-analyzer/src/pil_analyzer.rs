@@ -6,8 +6,10 @@ use std::path::{Path, PathBuf};
 
 use powdr_ast::parsed::asm::{AbsoluteSymbolPath, SymbolPath};
 use powdr_ast::parsed::types::Type;
-use powdr_ast::parsed::visitor::Children;
-use powdr_ast::parsed::{self, FunctionKind, LambdaExpression, PILFile, PilStatement};
+use powdr_ast::parsed::visitor::{AllChildren, Children};
+use powdr_ast::parsed::{
+    self, BinaryOperator, FunctionKind, LambdaExpression, PILFile, PilStatement, UnaryOperator,
+};
 use powdr_number::{DegreeType, FieldElement, GoldilocksField};
 
 use powdr_ast::analyzed::{
@@ -44,6 +46,7 @@ fn analyze<T: FieldElement>(files: Vec<PILFile>) -> Analyzed<T> {
     let mut analyzer = PILAnalyzer::new();
     analyzer.process(files);
     analyzer.side_effect_check();
+    analyzer.string_check();
     analyzer.type_check();
     analyzer.condense::<T>()
 }
@@ -171,6 +174,23 @@ impl PILAnalyzer {
         }
     }
 
+    /// Check that string literals are not used as operands of arithmetic operations
+    /// or as constraints in identities. This would also be a type error, but the
+    /// dedicated message points at the identity and the string.
+    pub fn string_check(&self) {
+        for id in &self.identities {
+            if let Some(s) = id.children().find_map(string_in_arithmetic_context) {
+                let source = &id.source;
+                panic!(
+                    "StringInArithmeticContext: The string {s} is used where a field element is expected, in identity {id} (at {}:{}:{}).",
+                    source.file.as_deref().unwrap_or("<input>"),
+                    source.line,
+                    source.col
+                );
+            }
+        }
+    }
+
     pub fn type_check(&mut self) {
         let query_type: Type = parse_type("int -> std::prover::Query").unwrap().into();
         let mut expressions = vec![];
@@ -419,3 +439,39 @@ impl<'a> AnalysisDriver for Driver<'a> {
         &self.0.definitions
     }
 }
+
+/// Returns a string literal in `e` that is the expression itself or an operand of
+/// an arithmetic operation, if any. Concatenating two strings is not reported.
+fn string_in_arithmetic_context(e: &Expression) -> Option<&Expression> {
+    fn is_string(e: &Expression) -> bool {
+        matches!(e, Expression::String(_))
+    }
+    if is_string(e) {
+        return Some(e);
+    }
+    e.all_children().find_map(|e| match e {
+        Expression::BinaryOperation(left, BinaryOperator::Add, right)
+            if is_string(left) && is_string(right) =>
+        {
+            None
+        }
+        Expression::BinaryOperation(
+            left,
+            BinaryOperator::Add
+            | BinaryOperator::Sub
+            | BinaryOperator::Mul
+            | BinaryOperator::Div
+            | BinaryOperator::Pow
+            | BinaryOperator::Identity,
+            right,
+        ) => [left.as_ref(), right.as_ref()]
+            .into_iter()
+            .find(|e| is_string(e)),
+        Expression::UnaryOperation(UnaryOperator::Minus | UnaryOperator::Next, inner)
+            if is_string(inner) =>
+        {
+            Some(inner.as_ref())
+        }
+        _ => None,
+    })
+}
@@ -307,6 +307,10 @@ where
     }
 
     fn handle_identity_statement(&mut self, statement: PilStatement) -> Vec<PILItem> {
+        let (label, statement) = match statement {
+            PilStatement::LabeledIdentity(_, label, statement) => (Some(label), *statement),
+            statement => (None, statement),
+        };
         let (source, kind, left, right) = match statement {
             PilStatement::Expression(source, expression) => (
                 source,
@@ -362,6 +366,7 @@ where
             id: self.counters.dispense_identity_id(),
             kind,
             source,
+            label,
             left,
             right,
         })]
@@ -67,15 +67,15 @@ lazy_static! {
         (BinaryOperator::NotEqual, ("T: Eq", "T, T -> bool")),
         (BinaryOperator::GreaterEqual, ("T: Ord", "T, T -> bool")),
         (BinaryOperator::Greater, ("T: Ord", "T, T -> bool")),
-        (BinaryOperator::LogicalOr, ("", "bool, bool -> bool")),
-        (BinaryOperator::LogicalAnd, ("", "bool, bool -> bool")),
+        (BinaryOperator::LogicalOr, ("T: Logical", "T, T -> T")),
+        (BinaryOperator::LogicalAnd, ("T: Logical", "T, T -> T")),
     ]
     .into_iter()
     .map(|(op, (vars, ty))| { (op, parse_type_scheme(vars, ty)) })
     .collect();
     static ref UNARY_OPERATOR_SCHEMES: HashMap<UnaryOperator, TypeScheme> = [
         (UnaryOperator::Minus, ("T: Neg", "T -> T")),
-        (UnaryOperator::LogicalNot, ("", "bool -> bool")),
+        (UnaryOperator::LogicalNot, ("T: Logical", "T -> T")),
         (UnaryOperator::Next, ("", "expr -> expr")),
     ]
     .into_iter()
@@ -95,10 +95,24 @@ pub fn unary_operator_scheme(op: UnaryOperator) -> TypeScheme {
     UNARY_OPERATOR_SCHEMES[&op].clone()
 }
 
+/// Returns true if the operator is only meaningful on integers and not on field elements,
+/// i.e. if it is a bitwise, shift or modulo operator.
+pub fn is_integer_operator(op: BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Mod
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight
+            | BinaryOperator::BinaryAnd
+            | BinaryOperator::BinaryOr
+            | BinaryOperator::BinaryXor
+    )
+}
+
 pub fn elementary_type_bounds(ty: &Type) -> &'static [&'static str] {
     match ty {
         Type::Bottom => &[],
-        Type::Bool => &["ToString"],
+        Type::Bool => &["ToString", "Logical"],
         Type::Int => &[
             "ToString",
             "FromLiteral",
@@ -133,6 +147,7 @@ pub fn elementary_type_bounds(ty: &Type) -> &'static [&'static str] {
             "Pow",
             "Neg",
             "Eq",
+            "Logical",
         ],
         Type::Constr => &[],
         Type::Col => &[],
@@ -7,15 +7,16 @@ use powdr_ast::{
         display::format_type_scheme_around_name,
         types::{ArrayType, FunctionType, TupleType, Type, TypeBounds, TypeScheme},
         visitor::ExpressionVisitable,
-        ArrayLiteral, FunctionCall, IndexAccess, LambdaExpression, LetStatementInsideBlock,
-        MatchArm, Pattern, StatementInsideBlock,
+        ArrayLiteral, ArraySlice, BinaryOperator, FunctionCall, IndexAccess, LambdaExpression,
+        LetStatementInsideBlock, MatchArm, Pattern, StatementInsideBlock,
     },
 };
 
 use crate::{
     call_graph::sort_called_first,
     type_builtins::{
-        binary_operator_scheme, builtin_schemes, type_for_reference, unary_operator_scheme,
+        binary_operator_scheme, builtin_schemes, is_integer_operator, type_for_reference,
+        unary_operator_scheme,
     },
     type_unifier::Unifier,
 };
@@ -541,11 +542,16 @@ impl<'a> TypeChecker<'a> {
             Expression::BinaryOperation(left, op, right) => {
                 // TODO at some point, also store the generic args for operators
                 let fun_type = self.instantiate_scheme(binary_operator_scheme(*op)).0;
-                self.infer_type_of_function_call(
+                match self.infer_type_of_function_call(
                     fun_type,
-                    [left, right].into_iter().map(AsMut::as_mut),
+                    [left.as_mut(), right.as_mut()].into_iter(),
                     || format!("applying operator {op}"),
-                )?
+                ) {
+                    Ok(ty) => ty,
+                    Err(err) => {
+                        return Err(self.check_integer_operands(left, *op, right).unwrap_or(err))
+                    }
+                }
             }
             Expression::UnaryOperation(op, inner) => {
                 // TODO at some point, also store the generic args for operators
@@ -569,6 +575,17 @@ impl<'a> TypeChecker<'a> {
                 self.expect_type(&Type::Int, index)?;
                 result
             }
+            Expression::ArraySlice(ArraySlice { array, from, to }) => {
+                let result = Type::Array(ArrayType {
+                    base: Box::new(self.new_type_var()),
+                    length: None,
+                });
+                self.expect_type(&result, array)?;
+                for bound in from.iter_mut().chain(to) {
+                    self.expect_type(&Type::Int, bound)?;
+                }
+                result
+            }
             Expression::FunctionCall(FunctionCall {
                 function,
                 arguments,
@@ -624,6 +641,30 @@ impl<'a> TypeChecker<'a> {
         })
     }
 
+    /// Returns a dedicated error message if `op` is an operator that is only defined
+    /// on integers, but one of its operands is a field element or an algebraic expression.
+    /// Only called after type checking the operation failed, to improve the error message.
+    fn check_integer_operands(
+        &mut self,
+        left: &mut Expression,
+        op: BinaryOperator,
+        right: &mut Expression,
+    ) -> Option<String> {
+        if !is_integer_operator(op) {
+            return None;
+        }
+        [left, right].into_iter().find_map(|operand| {
+            let ty = self.infer_type_of_expression(operand).ok()?;
+            match self.type_into_substituted(ty) {
+                ty @ (Type::Fe | Type::Expr) => Some(format!(
+                    "Operator {op} is only defined on int, but its operand {operand} has type {ty}.\n\
+                    Bitwise, shift and modulo operations are not well-defined on field elements."
+                )),
+                _ => None,
+            }
+        })
+    }
+
     /// Process a function call and return the type of the expression.
     /// The error message is used to clarify which kind of function call it is
     /// (it might be an operator).
@@ -741,6 +782,9 @@ impl<'a> TypeChecker<'a> {
                 self.local_var_types.push(ty.clone());
                 ty
             }
+            Pattern::Enum(_, _) => {
+                return Err(format!("Enum patterns are not yet supported: {pattern}"))
+            }
         })
     }
 
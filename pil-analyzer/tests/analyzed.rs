@@ -0,0 +1,211 @@
+use powdr_ast::analyzed::{
+    AlgebraicExpression, ColumnLayout, CostEstimate, PolyID, PolynomialType,
+};
+use powdr_ast::parsed::visitor::ExpressionVisitable;
+use powdr_number::GoldilocksField;
+use powdr_pil_analyzer::{analyze_file, analyze_string, namespace_checker};
+use std::path::Path;
+use test_log::test;
+
+use pretty_assertions::assert_eq;
+
+#[test]
+fn remove_namespace() {
+    let input = r#"
+namespace N(8);
+    col fixed ONE = [1]*;
+    col witness x;
+    x = ONE;
+namespace M(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness y, z;
+    FIRST * (y - 1) = 0;
+    z = y + 1;
+    { y } in { N.x };
+"#;
+    let mut remaining = analyze_string::<GoldilocksField>(input);
+    let extracted = remaining.remove_namespace("N");
+
+    assert_eq!(extracted.commitment_count(), 1);
+    assert_eq!(extracted.constant_count(), 1);
+    assert_eq!(extracted.identities.len(), 1);
+    assert_eq!(extracted.definitions["N.x"].0.id, 0);
+
+    // The lookup crosses the namespace boundary and is removed from both halves.
+    assert_eq!(remaining.commitment_count(), 2);
+    assert_eq!(remaining.constant_count(), 1);
+    assert_eq!(remaining.identities.len(), 2);
+    assert_eq!(remaining.definitions["M.y"].0.id, 0);
+    assert_eq!(remaining.definitions["M.z"].0.id, 1);
+
+    // Both halves are valid PIL on their own.
+    for pil in [extracted, remaining] {
+        let printed = pil.to_string();
+        assert_eq!(
+            analyze_string::<GoldilocksField>(&printed).to_string(),
+            printed
+        );
+    }
+}
+
+#[test]
+fn cost_estimate() {
+    let input = r#"
+namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y;
+    col sq = x * x;
+    FIRST * (sq - y) = 0;
+    { y } in { FIRST };
+"#;
+    let estimate = analyze_string::<GoldilocksField>(input)
+        .cost_estimate()
+        .unwrap();
+    assert_eq!(
+        estimate,
+        CostEstimate {
+            degree: Some(16),
+            committed_columns: 2,
+            constant_columns: 1,
+            intermediate_columns: 1,
+            polynomial_identities: 1,
+            plookup_identities: 1,
+            permutation_identities: 0,
+            connect_identities: 0,
+            total_constraint_degree: 4,
+            max_constraint_degree: 3,
+        }
+    );
+}
+
+#[test]
+fn max_reference_offset() {
+    let current_row_only = r#"
+namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y;
+    FIRST * (x - y) = 0;
+    { y } in { FIRST };
+"#;
+    assert_eq!(
+        analyze_string::<GoldilocksField>(current_row_only).max_reference_offset(),
+        0
+    );
+
+    // The next reference is only visible after inlining the intermediate polynomial.
+    let next_row = r#"
+namespace N(16);
+    col witness x, y;
+    col step = x' - x;
+    y = step;
+"#;
+    assert_eq!(
+        analyze_string::<GoldilocksField>(next_row).max_reference_offset(),
+        1
+    );
+}
+
+#[test]
+fn cross_namespace_reference() {
+    let input = r#"
+namespace N(8);
+    col witness x;
+namespace M(8);
+    col witness y;
+    N.x = y;
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    let err = namespace_checker::check(&analyzed).unwrap_err();
+    assert_eq!(err.column, "N.x");
+    assert_eq!(err.other_column, "M.y");
+    assert_eq!(
+        err.to_string(),
+        "Polynomial identity references columns N.x and M.y from different namespaces. \
+        Use a lookup or permutation to connect machines instead: N.x = M.y;"
+    );
+}
+
+#[test]
+fn cross_namespace_fixed_column() {
+    // Fixed columns can be shared, e.g. `Global.CLK32` in the polygon-hermez machines.
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_data/polygon-hermez/arith.pil");
+    namespace_checker::check(&analyze_file::<GoldilocksField>(&path)).unwrap();
+}
+
+#[test]
+fn cross_namespace_link() {
+    let input = r#"
+namespace N(8);
+    col witness x;
+namespace M(8);
+    col witness y;
+    y = y';
+    { y } in { N.x };
+"#;
+    namespace_checker::check(&analyze_string::<GoldilocksField>(input)).unwrap();
+}
+
+#[test]
+fn localize() {
+    let input = r#"
+namespace N(8);
+    col fixed ONE = [1]*;
+    col witness x;
+    x = ONE;
+namespace M(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness y, z;
+    FIRST * (y - 1) = 0;
+    z = y + 1;
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    let layout = ColumnLayout::new(&analyzed);
+    // FIRST * (y - 1)
+    let constraint = analyzed.identities[1].expression_for_poly_id().clone();
+    let localized = constraint.localize("M", &layout);
+
+    let mut poly_ids = vec![];
+    localized.pre_visit_expressions(&mut |e| {
+        if let AlgebraicExpression::Reference(reference) = e {
+            poly_ids.push((reference.name.clone(), reference.poly_id));
+        }
+    });
+    assert_eq!(
+        poly_ids,
+        [
+            (
+                "M.FIRST".to_string(),
+                PolyID {
+                    id: 0,
+                    ptype: PolynomialType::Constant
+                }
+            ),
+            (
+                "M.y".to_string(),
+                PolyID {
+                    id: 0,
+                    ptype: PolynomialType::Committed
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+#[should_panic = "Column N.ONE is not part of namespace M."]
+fn localize_foreign_column() {
+    // Fixed columns can be shared across namespaces, but they have no local ID
+    // in the namespaces that do not declare them.
+    let input = r#"
+namespace N(8);
+    col fixed ONE = [1]*;
+namespace M(8);
+    col witness y;
+    y = N.ONE;
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    let layout = ColumnLayout::new(&analyzed);
+    analyzed.identities[0]
+        .expression_for_poly_id()
+        .localize("M", &layout);
+}
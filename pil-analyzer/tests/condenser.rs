@@ -108,3 +108,87 @@ fn create_constrainst() {
     let formatted = analyze_string::<GoldilocksField>(input).to_string();
     assert_eq!(formatted, expected);
 }
+
+#[test]
+fn logical_and() {
+    let input = r#"namespace N(16);
+    col witness x, y, z;
+    (x && y) = z;
+    "#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col witness z;
+    (N.x * (1 - N.x)) = 0;
+    (N.y * (1 - N.y)) = 0;
+    (N.x * N.y) = N.z;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn logical_or() {
+    let input = r#"namespace N(16);
+    col witness x, y, z;
+    (x || y') = z;
+    "#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col witness z;
+    (N.x * (1 - N.x)) = 0;
+    (N.y * (1 - N.y)) = 0;
+    ((N.x + N.y') - (N.x * N.y')) = N.z;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn logical_not() {
+    let input = r#"namespace N(16);
+    col witness x, y, z;
+    !x = y;
+    !(x && y) = z;
+    "#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col witness z;
+    (N.x * (1 - N.x)) = 0;
+    (1 - N.x) = N.y;
+    (N.y * (1 - N.y)) = 0;
+    (1 - (N.x * N.y)) = N.z;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn logical_and_fixed_column() {
+    let input = r#"namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y;
+    (FIRST && x) = y;
+    "#;
+    let expected = r#"namespace N(16);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y;
+    (N.x * (1 - N.x)) = 0;
+    (N.FIRST * N.x) = N.y;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+#[should_panic = "Operand of && is not provably boolean: (N.x + N.y)"]
+fn logical_and_non_boolean() {
+    let input = r#"namespace N(16);
+    col witness x, y, z;
+    ((x + y) && z) = 0;
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
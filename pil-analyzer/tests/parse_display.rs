@@ -713,3 +713,21 @@ namespace T(8);
     assert_eq!(analyzed.degree, Some(8));
     assert_eq!(expected, analyzed.to_string());
 }
+
+#[test]
+fn labeled_identities() {
+    let input = "namespace Fibonacci(8);
+    col witness x;
+    col witness y;
+    #[fibonacci_step] Fibonacci.x' = Fibonacci.y;
+    #[in_range] { Fibonacci.x } in { Fibonacci.y };
+    Fibonacci.y' = (Fibonacci.x + Fibonacci.y);
+";
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(
+        analyzed.identities[0].label.as_deref(),
+        Some("fibonacci_step")
+    );
+    assert_eq!(analyzed.identities[2].label, None);
+    assert_eq!(input, analyzed.to_string());
+}
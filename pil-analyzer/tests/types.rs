@@ -50,6 +50,16 @@ fn use_fun_in_expr_context() {
     analyze_string::<GoldilocksField>(input);
 }
 
+#[test]
+#[should_panic = "StringInArithmeticContext: The string \"abc\" is used where a field element is expected"]
+fn string_in_identity() {
+    let input = r#"namespace N(16);
+    let w;
+    w = "abc" * 2;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
 #[test]
 fn single_literal() {
     let input = "let<T: FromLiteral> x: T[] = [1, 2];";
@@ -417,3 +427,30 @@ fn type_from_pattern() {
     ";
     type_check(input, &[("f", "", "(int, int[]) -> int")]);
 }
+
+#[test]
+#[should_panic = "Operator & is only defined on int, but its operand N.x has type expr."]
+fn bitwise_and_on_column() {
+    let input = "namespace N(16);
+    col witness x, y;
+    y = x & 0xff;
+";
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "Operator >> is only defined on int, but its operand v has type fe."]
+fn shift_on_field_element() {
+    let input = "let v: fe = 5;
+    let w = v >> 2;
+";
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+fn bitwise_and_on_int() {
+    let input = "let int_val: int = 7;
+    let masked = int_val & 0xff;
+";
+    type_check(input, &[("masked", "", "int")]);
+}
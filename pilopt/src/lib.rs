@@ -2,20 +2,37 @@
 #![deny(clippy::print_stdout)]
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::iter::once;
 
 use powdr_ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
     Analyzed, Expression, FunctionValueDefinition, IdentityKind, PolyID, PolynomialReference,
-    Reference, SymbolKind, TypedExpression,
+    PolynomialType, Reference, StatementIdentifier, Symbol, SymbolKind, TypedExpression,
 };
 use powdr_ast::parsed::types::Type;
 use powdr_ast::parsed::visitor::{AllChildren, Children, ExpressionVisitable};
-use powdr_ast::parsed::EnumDeclaration;
+use powdr_ast::parsed::{ArrayLiteral, EnumDeclaration, FunctionCall};
+use powdr_ast::SourceRef;
 use powdr_number::{BigUint, FieldElement};
 
-pub fn optimize<T: FieldElement>(mut pil_file: Analyzed<T>) -> Analyzed<T> {
+pub fn optimize<T: FieldElement>(pil_file: Analyzed<T>) -> Analyzed<T> {
+    optimize_with_cse(pil_file, None)
+}
+
+/// Like [optimize], but if `cse_min_size` is set, it also hoists repeated sub-expressions
+/// with at least that many nodes into intermediate polynomials, see [extract_common_subexpressions].
+pub fn optimize_with_cse<T: FieldElement>(
+    mut pil_file: Analyzed<T>,
+    cse_min_size: Option<usize>,
+) -> Analyzed<T> {
+    if pil_file.identities.is_empty() {
+        // Without identities, no column would be considered referenced and
+        // the whole PIL would be removed. Keep it as it is instead, so that
+        // a proof can still attest to the column values.
+        log::info!("PIL has no identities, skipping optimization.");
+        return pil_file;
+    }
     let col_count_pre = (pil_file.commitment_count(), pil_file.constant_count());
     remove_unreferenced_definitions(&mut pil_file);
     remove_constant_fixed_columns(&mut pil_file);
@@ -26,6 +43,9 @@ pub fn optimize<T: FieldElement>(mut pil_file: Analyzed<T>) -> Analyzed<T> {
     remove_trivial_identities(&mut pil_file);
     remove_duplicate_identities(&mut pil_file);
     remove_unreferenced_definitions(&mut pil_file);
+    if let Some(min_size) = cse_min_size {
+        extract_common_subexpressions(&mut pil_file, min_size);
+    }
     let col_count_post = (pil_file.commitment_count(), pil_file.constant_count());
     log::info!(
         "Removed {} witness and {} fixed columns. Total count now: {} witness and {} fixed columns.",
@@ -215,13 +235,10 @@ fn constant_value(function: &FunctionValueDefinition) -> Option<BigUint> {
                 .iter()
                 .filter(|e| !e.is_empty())
                 .flat_map(|e| e.pattern().iter())
-                .map(|e| match e {
-                    Expression::Number(n, _) => Some(n),
-                    _ => None,
-                });
+                .map(constant_number);
             let first = values.next()??;
-            if values.all(|x| x == Some(first)) {
-                Some(first.clone())
+            if values.all(|x| x.as_ref() == Some(&first)) {
+                Some(first)
             } else {
                 None
             }
@@ -232,6 +249,44 @@ fn constant_value(function: &FunctionValueDefinition) -> Option<BigUint> {
     }
 }
 
+/// Evaluates an expression that consists of a number literal or of a call to
+/// a pure builtin function with constant arguments.
+fn constant_number(e: &Expression) -> Option<BigUint> {
+    match e {
+        Expression::Number(n, _) => Some(n.clone()),
+        Expression::FunctionCall(FunctionCall {
+            function,
+            arguments,
+        }) => match function.as_ref() {
+            Expression::Reference(Reference::Poly(function)) => {
+                fold_builtin_call(function, arguments)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The pure builtin functions that can be folded when their arguments are constant.
+/// `min` and `max` are only folded for integers, since the order of other types
+/// might differ from the order of their literals.
+fn fold_builtin_call(function: &PolynomialReference, arguments: &[Expression]) -> Option<BigUint> {
+    let integer_args = function.type_args.as_deref() == Some(&[Type::Int]);
+    match (function.name.as_str(), arguments) {
+        ("std::array::len", [Expression::ArrayLiteral(ArrayLiteral { items })]) => {
+            Some(items.len().into())
+        }
+        ("std::convert::fe" | "std::convert::int", [value]) => constant_number(value),
+        ("std::utils::min", [a, b]) if integer_args => {
+            Some(constant_number(a)?.min(constant_number(b)?))
+        }
+        ("std::utils::max", [a, b]) if integer_args => {
+            Some(constant_number(a)?.max(constant_number(b)?))
+        }
+        _ => None,
+    }
+}
+
 /// Simplifies multiplications by zero and one.
 fn simplify_identities<T: FieldElement>(pil_file: &mut Analyzed<T>) {
     pil_file.post_visit_expressions_in_identities_mut(&mut simplify_expression_single);
@@ -507,12 +562,230 @@ fn remove_duplicate_identities<T: FieldElement>(pil_file: &mut Analyzed<T>) {
     pil_file.remove_identities(&to_remove);
 }
 
+/// Hoists sub-expressions that occur at least twice in the identities and consist of
+/// at least `min_size` nodes into new intermediate polynomials, so that backends only
+/// process them once. Larger sub-expressions are hoisted first.
+/// Sub-expressions without column references or with references to the next row are kept.
+pub fn extract_common_subexpressions<T: FieldElement>(pil_file: &mut Analyzed<T>, min_size: usize) {
+    // Number all distinct sub-expressions and count their occurrences.
+    let mut keys = HashMap::new();
+    let mut subexpressions = vec![];
+    for identity in &pil_file.identities {
+        for e in identity.children() {
+            number_subexpressions(e, &mut keys, &mut subexpressions);
+        }
+    }
+
+    // Decide which sub-expressions to hoist, larger ones first. Ties are broken by
+    // the ID, i.e. by the order of first occurrence.
+    let mut candidates = (0..subexpressions.len())
+        .filter(|id| {
+            let s = &subexpressions[*id];
+            s.has_reference && !s.has_next_ref && s.size >= min_size
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|id| (std::cmp::Reverse(subexpressions[*id].size), *id));
+    let mut hoisted = HashMap::new();
+    for id in candidates {
+        let count = subexpressions[id].count;
+        if count < 2 {
+            continue;
+        }
+        // All but one occurrence of the operands vanish in the identities.
+        discount_operands(&mut subexpressions, id, count - 1);
+        let reference = new_intermediate_reference(pil_file, subexpressions[id].namespace);
+        let symbol = Symbol {
+            id: reference.poly_id.id,
+            source: SourceRef::unknown(),
+            absolute_name: reference.name.clone(),
+            stage: None,
+            kind: SymbolKind::Poly(PolynomialType::Intermediate),
+            length: None,
+        };
+        pil_file
+            .intermediate_columns
+            .insert(reference.name.clone(), (symbol, vec![]));
+        hoisted.insert(id, reference);
+    }
+    if hoisted.is_empty() {
+        return;
+    }
+
+    // Replace the hoisted sub-expressions and define the intermediate polynomials
+    // right before their first use, operands before the expressions using them.
+    let mut bodies = BTreeMap::new();
+    let mut source_order = vec![];
+    for statement in std::mem::take(&mut pil_file.source_order) {
+        if let StatementIdentifier::Identity(index) = statement {
+            let mut new_definitions = vec![];
+            for e in pil_file.identities[index].children_mut() {
+                replace_hoisted_subexpressions(
+                    e,
+                    &keys,
+                    &hoisted,
+                    &mut bodies,
+                    &mut new_definitions,
+                );
+            }
+            source_order.extend(
+                new_definitions
+                    .into_iter()
+                    .map(StatementIdentifier::Definition),
+            );
+        }
+        source_order.push(statement);
+    }
+    pil_file.source_order = source_order;
+    for (name, body) in bodies {
+        pil_file.intermediate_columns.get_mut(&name).unwrap().1 = vec![body];
+    }
+}
+
+/// Identifies a sub-expression through its operator and the IDs of its operands,
+/// so that the cost of hashing it does not depend on its size.
+#[derive(PartialEq, Eq, Hash)]
+enum SubexpressionKey<T> {
+    Leaf(AlgebraicExpression<T>),
+    BinaryOperation(usize, AlgebraicBinaryOperator, usize),
+    UnaryOperation(AlgebraicUnaryOperator, usize),
+}
+
+struct Subexpression<'a> {
+    operands: Vec<usize>,
+    /// The number of nodes.
+    size: usize,
+    /// The number of occurrences in the identities.
+    count: usize,
+    has_reference: bool,
+    has_next_ref: bool,
+    /// The namespace of the first referenced column.
+    namespace: Option<&'a str>,
+}
+
+/// Assigns IDs to `e` and all its sub-expressions, equal sub-expressions receive
+/// the same ID. Returns the ID of `e`.
+fn number_subexpressions<'a, T: FieldElement>(
+    e: &'a AlgebraicExpression<T>,
+    keys: &mut HashMap<SubexpressionKey<T>, usize>,
+    subexpressions: &mut Vec<Subexpression<'a>>,
+) -> usize {
+    let (key, operands) = match e {
+        AlgebraicExpression::BinaryOperation(left, op, right) => {
+            let left = number_subexpressions(left, keys, subexpressions);
+            let right = number_subexpressions(right, keys, subexpressions);
+            (
+                SubexpressionKey::BinaryOperation(left, *op, right),
+                vec![left, right],
+            )
+        }
+        AlgebraicExpression::UnaryOperation(op, inner) => {
+            let inner = number_subexpressions(inner, keys, subexpressions);
+            (SubexpressionKey::UnaryOperation(*op, inner), vec![inner])
+        }
+        _ => (SubexpressionKey::Leaf(e.clone()), vec![]),
+    };
+    let id = *keys.entry(key).or_insert_with(|| {
+        let operand_subexpressions = operands.iter().map(|o| &subexpressions[*o]);
+        let reference = match e {
+            AlgebraicExpression::Reference(r) => Some(r),
+            _ => None,
+        };
+        let subexpression = Subexpression {
+            size: 1 + operand_subexpressions
+                .clone()
+                .map(|s| s.size)
+                .sum::<usize>(),
+            count: 0,
+            has_reference: reference.is_some()
+                || operand_subexpressions.clone().any(|s| s.has_reference),
+            has_next_ref: reference.map_or(false, |r| r.next)
+                || operand_subexpressions.clone().any(|s| s.has_next_ref),
+            namespace: match reference {
+                Some(r) => r.name.rsplit_once('.').map(|(namespace, _)| namespace),
+                None => operand_subexpressions.clone().find_map(|s| s.namespace),
+            },
+            operands,
+        };
+        subexpressions.push(subexpression);
+        subexpressions.len() - 1
+    });
+    subexpressions[id].count += 1;
+    id
+}
+
+/// Removes `by` occurrences of all (direct and indirect) operands of sub-expression `id`.
+fn discount_operands(subexpressions: &mut [Subexpression], id: usize, by: usize) {
+    for operand in subexpressions[id].operands.clone() {
+        subexpressions[operand].count -= by;
+        discount_operands(subexpressions, operand, by);
+    }
+}
+
+/// Replaces all hoisted sub-expressions in `e` by references to their intermediate polynomials.
+/// The first time a hoisted sub-expression is encountered, it is stored in `bodies` and the name of
+/// its intermediate polynomial is appended to `new_definitions`. Returns the ID of `e`.
+fn replace_hoisted_subexpressions<T: FieldElement>(
+    e: &mut AlgebraicExpression<T>,
+    keys: &HashMap<SubexpressionKey<T>, usize>,
+    hoisted: &HashMap<usize, AlgebraicReference>,
+    bodies: &mut BTreeMap<String, AlgebraicExpression<T>>,
+    new_definitions: &mut Vec<String>,
+) -> usize {
+    let mut replace = |e: &mut AlgebraicExpression<T>| {
+        replace_hoisted_subexpressions(e, keys, hoisted, bodies, new_definitions)
+    };
+    let key = match e {
+        AlgebraicExpression::BinaryOperation(left, op, right) => {
+            SubexpressionKey::BinaryOperation(replace(left), *op, replace(right))
+        }
+        AlgebraicExpression::UnaryOperation(op, inner) => {
+            SubexpressionKey::UnaryOperation(*op, replace(inner))
+        }
+        _ => SubexpressionKey::Leaf(e.clone()),
+    };
+    let id = keys[&key];
+    if let Some(reference) = hoisted.get(&id) {
+        let body = std::mem::replace(e, AlgebraicExpression::Reference(reference.clone()));
+        if !bodies.contains_key(&reference.name) {
+            bodies.insert(reference.name.clone(), body);
+            new_definitions.push(reference.name.clone());
+        }
+    }
+    id
+}
+
+/// Returns a reference to a new intermediate polynomial in `namespace`,
+/// with a name that is not used yet.
+fn new_intermediate_reference<T: FieldElement>(
+    pil_file: &Analyzed<T>,
+    namespace: Option<&str>,
+) -> AlgebraicReference {
+    let namespace = namespace
+        .map(|namespace| format!("{namespace}."))
+        .unwrap_or_default();
+    let name = (0..)
+        .map(|i| format!("{namespace}cse_{i}"))
+        .find(|name| {
+            !pil_file.definitions.contains_key(name)
+                && !pil_file.intermediate_columns.contains_key(name)
+        })
+        .unwrap();
+    AlgebraicReference {
+        name,
+        poly_id: PolyID {
+            id: pil_file.intermediate_count() as u64,
+            ptype: PolynomialType::Intermediate,
+        },
+        next: false,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use powdr_number::GoldilocksField;
     use powdr_pil_analyzer::analyze_string;
 
-    use crate::optimize;
+    use crate::{extract_common_subexpressions, optimize, optimize_with_cse};
 
     use pretty_assertions::assert_eq;
 
@@ -536,6 +809,30 @@ mod test {
         assert_eq!(optimized, expectation);
     }
 
+    #[test]
+    fn replace_fixed_builtin_calls() {
+        let input = r#"
+        namespace std::array(65536);
+            let<T> len: T[] -> int = [];
+        namespace std::convert(65536);
+            let fe = [];
+        namespace std::utils(65536);
+            let<T: Ord> min: T, T -> T = |a, b| if a < b { a } else { b };
+            let<T: Ord> max: T, T -> T = |a, b| if a < b { b } else { a };
+        namespace N(65536);
+            col fixed LEN = [std::convert::fe(std::array::len::<int>([1, 2, 3]))]*;
+            col fixed MIN_MAX = [std::convert::fe(std::utils::min::<int>(5, 2)), std::convert::fe(std::utils::max::<int>(1, 2))]*;
+            col witness X;
+            X' = X * LEN + MIN_MAX;
+    "#;
+        let expectation = r#"namespace N(65536);
+    col witness X;
+    N.X' = ((N.X * 3) + 2);
+"#;
+        let optimized = optimize(analyze_string::<GoldilocksField>(input)).to_string();
+        assert_eq!(optimized, expectation);
+    }
+
     #[test]
     fn replace_lookup() {
         let input = r#"namespace N(65536);
@@ -717,4 +1014,58 @@ namespace N(65536);
         let optimized = optimize(analyze_string::<GoldilocksField>(input)).to_string();
         assert_eq!(optimized, expectation);
     }
+
+    #[test]
+    fn extract_repeated_subexpression() {
+        let input = r#"namespace N(65536);
+    col witness X;
+    col witness Y;
+    col witness A;
+    col witness B;
+    A = X * Y + 1;
+    B = X * Y + 2;
+"#;
+        let expectation = r#"namespace N(65536);
+    col witness X;
+    col witness Y;
+    col witness A;
+    col witness B;
+    col cse_0 = (N.X * N.Y);
+    N.A = (N.cse_0 + 1);
+    N.B = (N.cse_0 + 2);
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        extract_common_subexpressions(&mut pil, 3);
+        assert_eq!(pil.intermediate_count(), 1);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn extract_nested_subexpressions() {
+        let input = r#"namespace N(65536);
+    col witness X;
+    col witness Y;
+    col witness A;
+    col witness B;
+    A = (X * Y + 1) * (X * Y + 1);
+    B = X * Y + 3;
+    A * B = X * Y;
+"#;
+        let expectation = r#"namespace N(65536);
+    col witness X;
+    col witness Y;
+    col witness A;
+    col witness B;
+    col cse_1 = (N.X * N.Y);
+    col cse_0 = (N.cse_1 + 1);
+    N.A = (N.cse_0 * N.cse_0);
+    N.B = (N.cse_1 + 3);
+    (N.A * N.B) = N.cse_1;
+"#;
+        let input = analyze_string::<GoldilocksField>(input);
+        assert_eq!(optimize(input.clone()).intermediate_count(), 0);
+        let optimized = optimize_with_cse(input, Some(3));
+        assert_eq!(optimized.intermediate_count(), 2);
+        assert_eq!(optimized.to_string(), expectation);
+    }
 }
@@ -31,7 +31,7 @@ fn executor_benchmark(c: &mut Criterion) {
     let tmp_dir = Temp::new_dir().unwrap();
     let riscv_asm_files =
         compile_rust_crate_to_riscv_asm("../riscv/tests/riscv_data/keccak/Cargo.toml", &tmp_dir);
-    let contents = compiler::compile::<T>(riscv_asm_files, &Runtime::base(), false);
+    let contents = compiler::compile::<T>(riscv_asm_files, &Runtime::base(), false).unwrap();
     let mut pipeline = Pipeline::<T>::default().from_asm_string(contents, None);
     let pil = pipeline.compute_optimized_pil().unwrap();
     let fixed_cols = pipeline.compute_fixed_cols().unwrap();
@@ -43,7 +43,8 @@ fn executor_benchmark(c: &mut Criterion) {
         "../riscv/tests/riscv_data/many_chunks/Cargo.toml",
         &tmp_dir,
     );
-    let contents = compiler::compile::<T>(riscv_asm_files, &Runtime::base().with_poseidon(), true);
+    let contents =
+        compiler::compile::<T>(riscv_asm_files, &Runtime::base().with_poseidon(), true).unwrap();
     let mut pipeline = Pipeline::<T>::default().from_asm_string(contents, None);
     let pil = pipeline.compute_optimized_pil().unwrap();
     let fixed_cols = pipeline.compute_fixed_cols().unwrap();
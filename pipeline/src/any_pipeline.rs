@@ -0,0 +1,101 @@
+//! A [Pipeline] whose field is chosen at runtime.
+
+use std::path::PathBuf;
+
+use powdr_backend::{BackendType, Proof};
+use powdr_number::{Bn254Field, GoldilocksField, KnownField};
+
+use crate::{pipeline::Stage, Pipeline};
+
+/// A [Pipeline] over one of the [KnownField]s, selected at runtime.
+///
+/// This is useful for front-ends like the CLI that get the field from a flag:
+/// the builder and stage methods are dispatched to the monomorphized pipeline
+/// of the selected field. Artifacts that depend on the field are only
+/// available through the inner pipelines.
+pub enum AnyPipeline {
+    Goldilocks(Pipeline<GoldilocksField>),
+    Bn254(Pipeline<Bn254Field>),
+}
+
+/// Calls `$body` with `$pipeline` bound to the inner pipeline of `$self`.
+macro_rules! dispatch {
+    ($self:expr, $pipeline:ident => $body:expr) => {
+        match $self {
+            AnyPipeline::Goldilocks($pipeline) => $body,
+            AnyPipeline::Bn254($pipeline) => $body,
+        }
+    };
+}
+
+/// Like [dispatch], but wraps the resulting pipeline again.
+macro_rules! map {
+    ($self:expr, $pipeline:ident => $body:expr) => {
+        match $self {
+            AnyPipeline::Goldilocks($pipeline) => AnyPipeline::Goldilocks($body),
+            AnyPipeline::Bn254($pipeline) => AnyPipeline::Bn254($body),
+        }
+    };
+}
+
+impl AnyPipeline {
+    /// Creates a default pipeline over the given field.
+    pub fn new(field: KnownField) -> Self {
+        match field {
+            KnownField::GoldilocksField => AnyPipeline::Goldilocks(Pipeline::default()),
+            KnownField::Bn254Field => AnyPipeline::Bn254(Pipeline::default()),
+        }
+    }
+
+    pub fn field(&self) -> KnownField {
+        match self {
+            AnyPipeline::Goldilocks(_) => KnownField::GoldilocksField,
+            AnyPipeline::Bn254(_) => KnownField::Bn254Field,
+        }
+    }
+
+    pub fn with_output(self, output_dir: PathBuf, force_overwrite: bool) -> Self {
+        map!(self, p => p.with_output(output_dir, force_overwrite))
+    }
+
+    pub fn with_tmp_output(self, tmp_dir: &mktemp::Temp) -> Self {
+        map!(self, p => p.with_tmp_output(tmp_dir))
+    }
+
+    pub fn with_backend(self, backend: BackendType) -> Self {
+        map!(self, p => p.with_backend(backend))
+    }
+
+    pub fn with_name(self, name: String) -> Self {
+        map!(self, p => p.with_name(name))
+    }
+
+    pub fn from_file(self, file: PathBuf) -> Self {
+        map!(self, p => p.from_file(file))
+    }
+
+    pub fn from_asm_string(self, asm_string: String, path: Option<PathBuf>) -> Self {
+        map!(self, p => p.from_asm_string(asm_string, path))
+    }
+
+    pub fn from_pil_string(self, pil_string: String) -> Self {
+        map!(self, p => p.from_pil_string(pil_string))
+    }
+
+    /// Runs the pipeline up to and including the given stage, see `Pipeline::run_until`.
+    pub fn run_until(&mut self, stage: Stage) -> Result<(), Vec<String>> {
+        dispatch!(self, p => p.run_until(stage).map(|_| ()))
+    }
+
+    pub fn is_computed(&self, stage: Stage) -> bool {
+        dispatch!(self, p => p.is_computed(stage))
+    }
+
+    pub fn compute_proof(&mut self) -> Result<&Proof, Vec<String>> {
+        dispatch!(self, p => p.compute_proof())
+    }
+
+    pub fn name(&self) -> &str {
+        dispatch!(self, p => p.name())
+    }
+}
@@ -4,17 +4,21 @@
 
 use std::marker::{Send, Sync};
 
+pub mod any_pipeline;
+mod normal_form;
 pub mod pipeline;
 pub mod test_util;
 pub mod util;
 pub mod verify;
 
+pub use any_pipeline::AnyPipeline;
 pub use pipeline::Pipeline;
 
-pub use powdr_backend::{BackendType, Proof};
+pub use powdr_backend::{BackendType, Proof, ProofMetadata};
 use powdr_executor::witgen::QueryCallback;
 
 use powdr_number::FieldElement;
+use powdr_riscv_syscalls::NAMED_INPUTS_CHANNEL;
 
 // TODO at some point, we could also just pass evaluator::Values around - would be much faster.
 pub fn parse_query(query: &str) -> Result<(&str, Vec<&str>), String> {
@@ -69,7 +73,7 @@ pub fn serde_data_to_query_callback<T: FieldElement, S: serde::Serialize + Send
                     .map_err(|e| format!("Error parsing callback data channel: {e})"))?;
 
                 if channel != cb_channel {
-                    return Ok(None);
+                    return handle_simple_queries(id, &data, query);
                 }
 
                 let index = index
@@ -120,10 +124,27 @@ fn handle_simple_queries<T: FieldElement>(
             );
             Ok(Some(0.into()))
         }
+        "Output" => {
+            assert_eq!(data.len(), 1);
+            Ok(Some(0.into()))
+        }
+        "HeapUsage" => {
+            assert_eq!(data.len(), 1);
+            log::info!("Peak heap usage reported by the guest: {} bytes", data[0]);
+            Ok(Some(0.into()))
+        }
         "Hint" => {
             assert_eq!(data.len(), 1);
             Ok(Some(T::from_str(data[0]).unwrap()))
         }
+        // Without named inputs, the guest reads an empty named inputs channel,
+        // see `Pipeline::with_named_inputs`.
+        "DataIdentifier"
+            if data.get(1).and_then(|channel| channel.parse().ok())
+                == Some(NAMED_INPUTS_CHANNEL) =>
+        {
+            Ok(Some(0.into()))
+        }
         _ => Err(format!("Unsupported query: {query}")),
     }
 }
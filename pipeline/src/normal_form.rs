@@ -0,0 +1,284 @@
+//! A representation of PIL that does not depend on the order of declarations and
+//! identities or on the names of columns, see [normal_form].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+use itertools::Itertools;
+use powdr_ast::{
+    analyzed::{AlgebraicExpression, Analyzed, PolyID, Symbol, SymbolKind},
+    parsed::visitor::ExpressionVisitable,
+};
+use powdr_number::FieldElement;
+
+/// The name of a column (array) and the array index.
+type Column = (String, Option<usize>);
+
+/// Returns a representation of the PIL that does not depend on the order of declarations
+/// and identities, their ids, the labels of identities or the names of columns,
+/// see [crate::Pipeline::pils_equivalent].
+///
+/// The columns are renamed by their position in an order computed by colour refinement
+/// (the Weisfeiler-Lehman algorithm): Each column starts with a colour given by its
+/// declaration and is then repeatedly recoloured by the identities, intermediate column
+/// definitions and public declarations it appears in, written with the colours of the
+/// other columns. Columns that still share a colour are told apart by giving one of them
+/// a new colour and refining again. This choice uses the names of the columns, but it
+/// does not matter as long as the columns are symmetric, which is the case unless the
+/// PIL is built to defeat colour refinement.
+pub(crate) fn normal_form<T: FieldElement>(pil: &Analyzed<T>) -> Vec<String> {
+    let column_symbols = pil
+        .definitions
+        .values()
+        .map(|(symbol, definition)| (symbol, definition.as_ref().map(|d| d.to_string())))
+        .chain(
+            pil.intermediate_columns
+                .values()
+                .map(|(symbol, _)| (symbol, None)),
+        )
+        .filter(|(symbol, _)| matches!(symbol.kind, SymbolKind::Poly(_)))
+        .collect::<Vec<_>>();
+    let columns = column_symbols
+        .iter()
+        .flat_map(|(symbol, _)| {
+            symbol
+                .array_elements()
+                .enumerate()
+                .map(move |(index, (_, poly_id))| {
+                    let index = symbol.is_array().then_some(index);
+                    (poly_id, (symbol.absolute_name.clone(), index))
+                })
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let identities = pil.identities.iter().map(|identity| {
+        let mut identity = identity.clone();
+        identity.label = None;
+        let mut slots = vec![];
+        identity.post_visit_expressions_mut(&mut |e| mark_reference(e, &columns, &mut slots));
+        Template::parse(&identity.to_string(), slots)
+    });
+    let intermediates = pil
+        .intermediate_columns
+        .iter()
+        .map(|(name, (symbol, definition))| {
+            let mut slots = vec![];
+            let definition = definition
+                .iter()
+                .enumerate()
+                .map(|(index, e)| {
+                    let column = Template::marker(slots.len());
+                    slots.push((name.clone(), symbol.is_array().then_some(index)));
+                    let mut e = e.clone();
+                    e.post_visit_expressions_mut(&mut |e| mark_reference(e, &columns, &mut slots));
+                    format!("{column} = {e}")
+                })
+                .join(", ");
+            Template::parse(&format!("[{definition}]"), slots)
+        });
+    let publics = pil.public_declarations.iter().map(|(name, declaration)| {
+        Template::parse(
+            &format!(
+                "public {name} = {}({})",
+                Template::marker(0),
+                declaration.index
+            ),
+            vec![(declaration.polynomial.name.clone(), declaration.array_index)],
+        )
+    });
+    let templates = identities
+        .chain(intermediates)
+        .chain(publics)
+        .collect::<Vec<_>>();
+
+    let declarations = column_symbols
+        .iter()
+        .map(|(symbol, definition)| {
+            (
+                symbol.absolute_name.as_str(),
+                declaration(symbol, definition.as_deref()),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    let colours = canonical_colours(&declarations, &templates);
+
+    let canonical_name = |name: &str| {
+        colours
+            .get(name)
+            .map_or_else(|| name.to_string(), |colour| format!("col{colour}"))
+    };
+    let definitions = pil.definitions.iter().map(|(name, (symbol, definition))| {
+        format!(
+            "{}: {}",
+            canonical_name(name),
+            declaration(
+                symbol,
+                definition.as_ref().map(|d| d.to_string()).as_deref()
+            )
+        )
+    });
+    std::iter::once(format!("degree {:?}", pil.degree))
+        .chain(definitions)
+        .chain(templates.iter().map(|t| t.render(canonical_name)))
+        .sorted()
+        .collect()
+}
+
+/// Returns the declaration of a symbol without its name.
+fn declaration(symbol: &Symbol, definition: Option<&str>) -> String {
+    format!(
+        "{:?}, stage {:?}, length {:?} = {}",
+        symbol.kind,
+        symbol.stage,
+        symbol.length,
+        definition.unwrap_or_default()
+    )
+}
+
+/// Replaces the name of a column reference by the marker of a new slot, see [Template].
+fn mark_reference<T>(
+    e: &mut AlgebraicExpression<T>,
+    columns: &BTreeMap<PolyID, Column>,
+    slots: &mut Vec<Column>,
+) {
+    if let AlgebraicExpression::Reference(r) = e {
+        r.name = Template::marker(slots.len());
+        slots.push(columns[&r.poly_id].clone());
+    }
+}
+
+/// Returns a distinct number for each column in `declarations`, see [normal_form].
+fn canonical_colours<'a>(
+    declarations: &BTreeMap<&'a str, String>,
+    templates: &[Template],
+) -> HashMap<&'a str, usize> {
+    let mut uses: HashMap<&str, Vec<&Template>> = HashMap::new();
+    for template in templates {
+        for (name, _) in template.slots.iter().unique_by(|(name, _)| name) {
+            uses.entry(name.as_str()).or_default().push(template);
+        }
+    }
+
+    let mut colours = ranks(
+        declarations
+            .iter()
+            .map(|(name, declaration)| (*name, declaration.clone())),
+    );
+    loop {
+        colours = refine(colours, &uses);
+        let class_sizes = colours.values().counts();
+        let Some(individual) = declarations
+            .keys()
+            .filter(|name| class_sizes[&colours[*name]] > 1)
+            .min_by_key(|name| colours[*name])
+        else {
+            return colours;
+        };
+        colours = colours
+            .iter()
+            .map(|(name, colour)| (*name, 2 * colour + usize::from(name != individual)))
+            .collect();
+    }
+}
+
+/// Recolours each column by its colour and the templates it appears in, until the
+/// number of colours does not increase any more.
+fn refine<'a>(
+    mut colours: HashMap<&'a str, usize>,
+    uses: &HashMap<&str, Vec<&Template>>,
+) -> HashMap<&'a str, usize> {
+    loop {
+        let signatures = colours.iter().map(|(column, colour)| {
+            let contexts = uses
+                .get(column)
+                .into_iter()
+                .flatten()
+                .map(|template| {
+                    template.render(|name| match colours.get(name) {
+                        _ if name == *column => "self".to_string(),
+                        Some(colour) => format!("col{colour}"),
+                        None => name.to_string(),
+                    })
+                })
+                .sorted()
+                .join("; ");
+            (*column, format!("{colour}: {contexts}"))
+        });
+        let refined = ranks(signatures);
+        if refined.values().unique().count() == colours.values().unique().count() {
+            return refined;
+        }
+        colours = refined;
+    }
+}
+
+/// Replaces each value by its position in the sorted list of distinct values.
+fn ranks<K: Hash + Eq>(values: impl Iterator<Item = (K, String)>) -> HashMap<K, usize> {
+    let values = values.collect::<Vec<_>>();
+    let positions = values
+        .iter()
+        .map(|(_, value)| value)
+        .sorted()
+        .dedup()
+        .enumerate()
+        .map(|(position, value)| (value.clone(), position))
+        .collect::<HashMap<_, _>>();
+    values
+        .into_iter()
+        .map(|(key, value)| {
+            let position = positions[&value];
+            (key, position)
+        })
+        .collect()
+}
+
+/// A text with the references to columns replaced by slots, so that it can be rendered
+/// with different names for the columns.
+struct Template {
+    /// The text between the slots, one more than there are slots.
+    parts: Vec<String>,
+    /// The column of each slot.
+    slots: Vec<Column>,
+}
+
+impl Template {
+    const MARKER: char = '\u{1}';
+
+    /// Returns how the slot with index `slot` is written in the text passed to [Template::parse].
+    fn marker(slot: usize) -> String {
+        format!("{}{slot}{}", Self::MARKER, Self::MARKER)
+    }
+
+    /// Creates the template from a text in which the columns are replaced by the markers
+    /// of their index in `slots`.
+    fn parse(text: &str, slots: Vec<Column>) -> Self {
+        let mut parts = vec![];
+        let mut slots_in_order = vec![];
+        for (i, part) in text.split(Self::MARKER).enumerate() {
+            if i % 2 == 0 {
+                parts.push(part.to_string());
+            } else {
+                slots_in_order.push(slots[part.parse::<usize>().unwrap()].clone());
+            }
+        }
+        Self {
+            parts,
+            slots: slots_in_order,
+        }
+    }
+
+    /// Returns the text with the columns named by `name`.
+    fn render(&self, name: impl Fn(&str) -> String) -> String {
+        let mut text = self.parts[0].clone();
+        for ((column, index), part) in self.slots.iter().zip(&self.parts[1..]) {
+            text += &name(column);
+            if let Some(index) = index {
+                text += &format!("[{index}]");
+            }
+            text += part;
+        }
+        text
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     borrow::Borrow,
+    collections::{BTreeMap, HashMap},
     fmt::Display,
     fs,
     io::{self, BufReader, BufWriter},
@@ -12,28 +13,211 @@ use std::{
 
 use log::Level;
 use powdr_ast::{
-    analyzed::Analyzed,
+    analyzed::{
+        AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicUnaryOperator, Analyzed,
+        FunctionValueDefinition, IdentityKind, PolyID, PolynomialType, Reference, RepeatedArray,
+        SymbolKind,
+    },
     asm_analysis::AnalysisASMFile,
     object::PILGraph,
-    parsed::{asm::ASMProgram, PILFile},
+    parsed::{
+        asm::ASMProgram,
+        visitor::{AllChildren, ExpressionVisitable},
+        Expression, FunctionCall, PILFile,
+    },
+    SourceRef,
 };
-use powdr_backend::{BackendType, Proof};
+use powdr_backend::{BackendType, Proof, ProofMetadata};
 use powdr_executor::{
     constant_evaluator,
     witgen::{
-        chain_callbacks, unused_query_callback, QueryCallback, WitgenCallback, WitnessGenerator,
+        chain_callbacks, extract_publics, unused_query_callback, QueryCallback, WitgenCallback,
+        WitnessGenerator, WrappingPolicy,
     },
 };
-use powdr_number::{write_polys_csv_file, write_polys_file, CsvRenderMode, FieldElement};
+use powdr_number::{
+    read_named_polys_file, read_polys_csv_file, write_named_polys_file, write_polys_csv_file,
+    write_polys_file, CsvRenderMode, DegreeType, FieldElement, KnownField,
+};
+use powdr_riscv_syscalls::NAMED_INPUTS_CHANNEL;
 use powdr_schemas::SerializedAnalyzed;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
 
 use crate::{
-    inputs_to_query_callback, serde_data_to_query_callback,
+    inputs_to_query_callback,
+    normal_form::normal_form,
+    serde_data_to_query_callback,
     util::{try_read_poly_set, write_or_panic, FixedPolySet, WitnessPolySet},
 };
 
 type Columns<T> = Vec<(String, Vec<T>)>;
 
+/// The result of checking the prover inputs against the program, see `Pipeline::validate_inputs`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InputError {
+    /// The program reads prover inputs, but none were provided.
+    MissingInputs,
+    /// The program reads the prover input at a fixed index beyond the provided ones.
+    NotEnoughInputs { required: usize, provided: usize },
+    /// The program could not be analyzed.
+    Pipeline(Vec<String>),
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::MissingInputs => {
+                write!(f, "The program reads prover inputs, but none were provided.")
+            }
+            InputError::NotEnoughInputs { required, provided } => write!(
+                f,
+                "The program reads at least {required} prover inputs, but only {provided} were provided."
+            ),
+            InputError::Pipeline(errors) => write!(f, "{}", errors.join("\n")),
+        }
+    }
+}
+
+/// A problem found by `Pipeline::verify_fixed_cols`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FixedColError<T> {
+    /// The column is defined, but its values are missing.
+    Missing(String),
+    /// The column does not have one value per row.
+    WrongLength {
+        column: String,
+        expected: DegreeType,
+        actual: usize,
+    },
+    /// The value of the column in the given row does not match its definition.
+    Mismatch {
+        column: String,
+        row: DegreeType,
+        expected: T,
+        actual: T,
+    },
+    /// The fixed columns could not be computed.
+    Pipeline(Vec<String>),
+    /// The definition of a column could not be evaluated at a sampled row.
+    Evaluation(String),
+}
+
+impl<T: Display> Display for FixedColError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixedColError::Missing(column) => write!(f, "Fixed column {column} is missing."),
+            FixedColError::WrongLength {
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Fixed column {column} has {actual} rows, but the degree is {expected}."
+            ),
+            FixedColError::Mismatch {
+                column,
+                row,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Fixed column {column} has value {actual} in row {row}, but its definition evaluates to {expected}."
+            ),
+            FixedColError::Pipeline(errors) => write!(f, "{}", errors.join("\n")),
+            FixedColError::Evaluation(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// The file format used by `Pipeline::export_fixed_columns` and `Pipeline::import_fixed_columns`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixedColumnsFormat {
+    /// A compact binary format that stores the column names along with the values.
+    Binary,
+    /// A CSV file with one column per fixed column, rendered according to the CSV settings.
+    Csv,
+}
+
+/// The identities referencing a column, see `Pipeline::column_usage`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnUsage {
+    pub identities: Vec<IdentityUsage>,
+}
+
+/// An identity that references a column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityUsage {
+    pub id: u64,
+    pub kind: IdentityKind,
+    pub source: SourceRef,
+    /// Whether the column is referenced in the current row.
+    pub current: bool,
+    /// Whether the column is referenced in the next row.
+    pub next: bool,
+}
+
+/// Describes the proof in a [ProofBundle].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// The name of the backend, as accepted by [BackendType]'s `FromStr`.
+    pub backend: String,
+    /// The field the proof is over, if it is a known field.
+    pub field: Option<KnownField>,
+    pub degree: DegreeType,
+    /// See `Pipeline::program_hash`.
+    pub program_hash: [u8; 32],
+}
+
+/// A proof together with everything needed to verify it, see `Pipeline::export_proof_bundle`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "T: FieldElement")]
+pub struct ProofBundle<T> {
+    pub manifest: BundleManifest,
+    /// The optimized PIL the proof is for, in its display format.
+    pub pil: String,
+    /// The setup the proof was generated with, for backends that have one (e.g. halo2).
+    pub setup: Option<Vec<u8>>,
+    pub verification_key: Vec<u8>,
+    pub proof: Proof,
+    /// The public values, by name, in the order expected by the verifier.
+    pub publics: Vec<(String, T)>,
+}
+
+/// The stages of the pipeline, in the order in which they are computed,
+/// see `Pipeline::run_until`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    AsmString,
+    ParsedAsmFile,
+    ResolvedModuleTree,
+    AnalyzedAsm,
+    ConstrainedMachineCollection,
+    LinkedMachineGraph,
+    ParsedPilFile,
+    AnalyzedPil,
+    OptimizedPil,
+    FixedCols,
+    Witness,
+    Proof,
+}
+
+/// The artifact computed by a stage, see `Pipeline::run_until`.
+pub enum StageArtifact<'a, T: FieldElement> {
+    AsmString(&'a (Option<PathBuf>, String)),
+    ParsedAsmFile(&'a (Option<PathBuf>, ASMProgram)),
+    ResolvedModuleTree(&'a ASMProgram),
+    AnalyzedAsm(&'a AnalysisASMFile),
+    ConstrainedMachineCollection(&'a AnalysisASMFile),
+    LinkedMachineGraph(&'a PILGraph),
+    ParsedPilFile(&'a PILFile),
+    AnalyzedPil(&'a Analyzed<T>),
+    OptimizedPil(Rc<Analyzed<T>>),
+    FixedCols(Rc<Columns<T>>),
+    Witness(Rc<Columns<T>>),
+    Proof(&'a Proof),
+}
+
 #[derive(Default, Clone)]
 pub struct Artifacts<T: FieldElement> {
     /// The path to a single .asm file.
@@ -69,6 +253,8 @@ pub struct Artifacts<T: FieldElement> {
     witness: Option<Rc<Columns<T>>>,
     /// The proof (if successful).
     proof: Option<Proof>,
+    /// Information about how the proof was generated.
+    proof_metadata: Option<ProofMetadata>,
 }
 
 /// Helper trait to make it prettier to get an `Option<&mut dyn io::Read>`` from
@@ -90,6 +276,18 @@ struct Arguments<T: FieldElement> {
     external_witness_values: Vec<(String, Vec<T>)>,
     /// Callback for queries for witness generation.
     query_callback: Option<Arc<dyn QueryCallback<T>>>,
+    /// The value witness generation fills undetermined cells with, see
+    /// [Pipeline::with_unknown_cell_sentinel].
+    unknown_cell_sentinel: Option<T>,
+    /// Whether and how witness generation validates the blocks of block machines, see
+    /// [Pipeline::with_block_validation].
+    block_validation: Option<WrappingPolicy>,
+    /// Whether witness generation caches the evaluation of shared sub-expressions, see
+    /// [Pipeline::with_evaluation_cache].
+    evaluation_cache: bool,
+    /// The file RISC-V execution writes its instruction-level trace to, see
+    /// [Pipeline::with_execution_trace_log].
+    execution_trace_log: Option<PathBuf>,
     /// Backend to use for proving. If None, proving will fail.
     backend: Option<BackendType>,
     /// CSV render mode for witness generation.
@@ -102,6 +300,11 @@ struct Arguments<T: FieldElement> {
     vkey_file: Option<PathBuf>,
     /// The optional existing proof file to use for aggregation.
     existing_proof_file: Option<PathBuf>,
+    /// The number of prover inputs passed via `with_prover_inputs`.
+    prover_input_count: usize,
+    /// The minimum size of the sub-expressions the optimizer hoists into intermediate
+    /// polynomials, see [Pipeline::with_common_subexpression_extraction].
+    cse_min_size: Option<usize>,
 }
 
 #[derive(Clone)]
@@ -253,10 +456,73 @@ impl<T: FieldElement> Pipeline<T> {
             .fold(self, |pipeline, data| pipeline.add_data(data.0, &data.1))
     }
 
-    pub fn with_prover_inputs(self, inputs: Vec<T>) -> Self {
+    pub fn with_prover_inputs(mut self, inputs: Vec<T>) -> Self {
+        self.arguments.prover_input_count = inputs.len();
         self.add_query_callback(Arc::new(inputs_to_query_callback(inputs)))
     }
 
+    /// Provides named inputs to a RISC-V guest, which reads them with
+    /// `powdr_riscv_runtime::input::get_named`.
+    /// Without named inputs, the guest reads an empty map.
+    pub fn with_named_inputs(mut self, inputs: BTreeMap<String, u32>) -> Self {
+        let query_callback: Arc<dyn QueryCallback<T>> =
+            Arc::new(serde_data_to_query_callback(NAMED_INPUTS_CHANNEL, &inputs));
+        // The other callbacks answer the named inputs channel as empty,
+        // so this one has to come first.
+        self.arguments.query_callback = Some(match self.arguments.query_callback.take() {
+            Some(old_callback) => Arc::new(chain_callbacks(query_callback, old_callback)),
+            None => query_callback,
+        });
+        self
+    }
+
+    /// Debugging aid: Fills the cells of the main machine that witness generation
+    /// could not determine with `sentinel` instead of zero, so that the shape of the
+    /// undetermined region is visible in the exported witness.
+    pub fn with_unknown_cell_sentinel(mut self, sentinel: T) -> Self {
+        self.arguments.unknown_cell_sentinel = Some(sentinel);
+        self
+    }
+
+    /// Debugging aid: Makes witness generation check every block a block machine solves
+    /// against the polynomial identities of the machine, see
+    /// [WitnessGenerator::with_block_validation].
+    pub fn with_block_validation(mut self, wrapping_policy: WrappingPolicy) -> Self {
+        self.arguments.block_validation = Some(wrapping_policy);
+        self
+    }
+
+    /// Makes witness generation evaluate structurally equal sub-expressions of the
+    /// identities of a row only once, see [WitnessGenerator::with_evaluation_cache].
+    pub fn with_evaluation_cache(mut self) -> Self {
+        self.arguments.evaluation_cache = true;
+        self
+    }
+
+    /// Debugging aid: Makes the RISC-V execution driven by this pipeline write
+    /// each executed statement, the PC and the registers it changed to `path`.
+    /// This log is independent of the witness.
+    /// Only the RISC-V executor writes the log, i.e. the full execution in
+    /// `rust_continuations_dry_run`. Witness generation does not run the executor,
+    /// so [Pipeline::compute_witness] fails if the log is set.
+    pub fn with_execution_trace_log(mut self, path: PathBuf) -> Self {
+        self.arguments.execution_trace_log = Some(path);
+        self
+    }
+
+    /// Unsets the path set by [Pipeline::with_execution_trace_log].
+    pub fn without_execution_trace_log(mut self) -> Self {
+        self.arguments.execution_trace_log = None;
+        self
+    }
+
+    /// Makes the optimizer hoist sub-expressions that occur repeatedly in the identities
+    /// and consist of at least `min_size` nodes into intermediate polynomials.
+    pub fn with_common_subexpression_extraction(mut self, min_size: usize) -> Self {
+        self.arguments.cse_min_size = Some(min_size);
+        self
+    }
+
     pub fn with_backend(mut self, backend: BackendType) -> Self {
         self.arguments.backend = Some(backend);
         self
@@ -389,6 +655,74 @@ impl<T: FieldElement> Pipeline<T> {
         }
     }
 
+    /// Imports fixed columns previously written by `Pipeline::export_fixed_columns`,
+    /// so that they do not need to be re-generated.
+    /// The columns in the file have to match the fixed columns of the optimized PIL
+    /// in name and degree.
+    pub fn import_fixed_columns(
+        mut self,
+        path: &Path,
+        format: FixedColumnsFormat,
+    ) -> Result<Self, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+
+        let mut file = BufReader::new(fs::File::open(path).map_err(|e| {
+            vec![format!(
+                "Could not open fixed columns file {}: {e}",
+                path.display()
+            )]
+        })?);
+        let mut imported = match format {
+            FixedColumnsFormat::Binary => read_named_polys_file(&mut file).map_err(|e| {
+                vec![format!(
+                    "Could not read fixed columns file {}: {e}",
+                    path.display()
+                )]
+            })?,
+            FixedColumnsFormat::Csv => read_polys_csv_file(file),
+        }
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+        let degree = pil.degree.unwrap_or_default() as usize;
+        let mut errors = vec![];
+        let fixed = pil
+            .constant_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(poly, _)| poly.array_elements())
+            .filter_map(|(name, _)| match imported.remove(&name) {
+                Some(values) if values.len() == degree => Some((name, values)),
+                Some(values) => {
+                    errors.push(format!(
+                        "Fixed column {name} has {} rows, but the degree is {degree}.",
+                        values.len()
+                    ));
+                    None
+                }
+                None => {
+                    errors.push(format!("Fixed column {name} is missing."));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        errors.extend(
+            imported
+                .into_keys()
+                .map(|name| format!("Column {name} is not a fixed column of the program.")),
+        );
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Pipeline {
+            artifact: Artifacts {
+                fixed_cols: Some(Rc::new(fixed)),
+                ..self.artifact
+            },
+            ..self
+        })
+    }
+
     /// Reads a previously generated witness from the provided directory.
     pub fn read_witness(mut self, directory: &Path) -> Self {
         let pil = self.compute_optimized_pil().unwrap();
@@ -749,7 +1083,10 @@ impl<T: FieldElement> Pipeline<T> {
                 } else {
                     panic!()
                 };
-            self.artifact.analyzed_pil = Some(analyzed_pil?)
+            let analyzed_pil = analyzed_pil?;
+            powdr_pil_analyzer::namespace_checker::check(&analyzed_pil)
+                .map_err(|e| vec![e.to_string()])?;
+            self.artifact.analyzed_pil = Some(analyzed_pil)
         }
 
         Ok(self.artifact.analyzed_pil.as_ref().unwrap())
@@ -768,7 +1105,7 @@ impl<T: FieldElement> Pipeline<T> {
         let analyzed_pil = self.artifact.analyzed_pil.take().unwrap();
 
         self.log("Optimizing pil...");
-        let optimized = powdr_pilopt::optimize(analyzed_pil);
+        let optimized = powdr_pilopt::optimize_with_cse(analyzed_pil, self.arguments.cse_min_size);
         self.maybe_write_pil(&optimized, "_opt")?;
         self.maybe_write_pil_object(&optimized, "_opt")?;
 
@@ -777,10 +1114,62 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(self.artifact.optimized_pil.as_ref().unwrap().clone())
     }
 
+    /// Computes an identifier of the program that is stable across runs:
+    /// the Keccak-256 hash of the optimized PIL (without source references),
+    /// its degree and the modulus of the field.
+    /// Pipelines for the same program have the same hash, so it can be used as a
+    /// cache key for fixed columns or verification keys.
+    pub fn program_hash(&mut self) -> Result<[u8; 32], Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+
+        let mut hasher = Keccak::v256();
+        // The display format of the PIL is canonical and does not include source references.
+        hasher.update(pil.to_string().as_bytes());
+        hasher.update(&pil.degree.unwrap_or_default().to_le_bytes());
+        hasher.update(T::modulus().to_string().as_bytes());
+
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash);
+        Ok(hash)
+    }
+
     pub fn optimized_pil(&self) -> Result<Rc<Analyzed<T>>, Vec<String>> {
         Ok(self.artifact.optimized_pil.as_ref().unwrap().clone())
     }
 
+    /// Changes the degree of the optimized PIL (which is computed if necessary), e.g. to
+    /// prove a part of an execution with fewer rows. The artifacts derived from the optimized
+    /// PIL, like the fixed columns, are computed again for the new degree.
+    /// The repeated part of fixed columns defined by arrays is resized to the new degree.
+    /// Fails if such a column does not fit into the new degree.
+    pub fn with_degree(mut self, degree: DegreeType) -> Result<Self, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+        if pil.degree == Some(degree) {
+            return Ok(self);
+        }
+        let mut pil = (*pil).clone();
+        pil.degree = Some(degree);
+        for (symbol, value) in pil.definitions.values_mut() {
+            if let Some(FunctionValueDefinition::Array(array)) = value {
+                resize_array(array, degree).map_err(|size| {
+                    vec![format!(
+                        "The fixed column {} has {size} values, which do not fit the degree {degree}.",
+                        symbol.absolute_name
+                    )]
+                })?;
+            }
+        }
+        self.artifact = Artifacts {
+            optimized_pil: Some(Rc::new(pil)),
+            fixed_cols: None,
+            witness: None,
+            proof: None,
+            proof_metadata: None,
+            ..self.artifact
+        };
+        Ok(self)
+    }
+
     pub fn compute_fixed_cols(&mut self) -> Result<Rc<Columns<T>>, Vec<String>> {
         if let Some(ref fixed_cols) = self.artifact.fixed_cols {
             return Ok(fixed_cols.clone());
@@ -800,6 +1189,53 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(self.artifact.fixed_cols.as_ref().unwrap().clone())
     }
 
+    /// Checks the fixed columns against their definitions by re-evaluating each definition
+    /// at a few sampled rows, see [constant_evaluator::sample]. This is a cheap sanity check
+    /// for the constant evaluator and for columns loaded with `Pipeline::import_fixed_columns`.
+    pub fn verify_fixed_cols(&mut self) -> Result<(), Vec<FixedColError<T>>> {
+        let pil = self
+            .compute_optimized_pil()
+            .map_err(|e| vec![FixedColError::Pipeline(e)])?;
+        let fixed_cols = self
+            .compute_fixed_cols()
+            .map_err(|e| vec![FixedColError::Pipeline(e)])?;
+        let fixed_cols = fixed_cols
+            .iter()
+            .map(|(name, values)| (name.as_str(), values))
+            .collect::<HashMap<_, _>>();
+
+        let degree = pil.degree();
+        let errors = constant_evaluator::sample(&pil)
+            .map_err(|e| vec![FixedColError::Evaluation(e)])?
+            .into_iter()
+            .flat_map(|(column, samples)| match fixed_cols.get(column.as_str()) {
+                None => vec![FixedColError::Missing(column)],
+                Some(values) if values.len() as DegreeType != degree => {
+                    vec![FixedColError::WrongLength {
+                        column,
+                        expected: degree,
+                        actual: values.len(),
+                    }]
+                }
+                Some(values) => samples
+                    .into_iter()
+                    .filter(|(row, expected)| values[*row as usize] != *expected)
+                    .map(|(row, expected)| FixedColError::Mismatch {
+                        column: column.clone(),
+                        row,
+                        expected,
+                        actual: values[row as usize],
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn fixed_cols(&self) -> Result<Rc<Columns<T>>, Vec<String>> {
         Ok(self.artifact.fixed_cols.as_ref().unwrap().clone())
     }
@@ -809,6 +1245,13 @@ impl<T: FieldElement> Pipeline<T> {
             return Ok(witness.clone());
         }
 
+        if let Some(path) = &self.arguments.execution_trace_log {
+            return Err(vec![format!(
+                "Cannot write the execution trace log to {}: witness generation does not run the RISC-V executor.",
+                path.display()
+            )]);
+        }
+
         self.log("Deducing witness columns...");
 
         let pil = self.compute_optimized_pil()?;
@@ -823,9 +1266,18 @@ impl<T: FieldElement> Pipeline<T> {
             .query_callback
             .take()
             .unwrap_or_else(|| Arc::new(unused_query_callback()));
-        let witness = WitnessGenerator::new(&pil, &fixed_cols, query_callback.borrow())
-            .with_external_witness_values(&external_witness_values)
-            .generate();
+        let mut generator = WitnessGenerator::new(&pil, &fixed_cols, query_callback.borrow())
+            .with_external_witness_values(&external_witness_values);
+        if let Some(sentinel) = self.arguments.unknown_cell_sentinel {
+            generator = generator.with_unknown_cell_sentinel(sentinel);
+        }
+        if let Some(wrapping_policy) = self.arguments.block_validation {
+            generator = generator.with_block_validation(wrapping_policy);
+        }
+        if self.arguments.evaluation_cache {
+            generator = generator.with_evaluation_cache();
+        }
+        let witness = generator.generate();
 
         self.log(&format!("Took {}", start.elapsed().as_secs_f32()));
 
@@ -836,10 +1288,214 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(self.artifact.witness.as_ref().unwrap().clone())
     }
 
+    /// Computes the witness and groups its columns by machine, i.e. by the namespace
+    /// they are declared in. Within each machine, the columns are in declaration order.
+    pub fn compute_witness_by_machine(
+        &mut self,
+    ) -> Result<BTreeMap<String, Columns<T>>, Vec<String>> {
+        let witness = self.compute_witness()?;
+        let mut by_machine: BTreeMap<String, Columns<T>> = BTreeMap::new();
+        for (name, values) in witness.iter() {
+            let machine = name
+                .rsplit_once('.')
+                .map(|(namespace, _)| namespace)
+                .unwrap_or_default();
+            by_machine
+                .entry(machine.to_string())
+                .or_default()
+                .push((name.clone(), values.clone()));
+        }
+        Ok(by_machine)
+    }
+
+    /// Evaluates the intermediate columns on all rows, using the witness and fixed columns.
+    /// Elements of intermediate column arrays are returned as separate columns.
+    /// This is mostly useful for debugging.
+    pub fn compute_intermediates(&mut self) -> Result<Columns<T>, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+        let fixed_cols = self.compute_fixed_cols()?;
+        let witness = self.compute_witness()?;
+
+        let columns = fixed_cols
+            .iter()
+            .chain(witness.iter())
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+            .collect::<HashMap<_, _>>();
+        let intermediates = pil
+            .intermediate_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(symbol, definitions)| symbol.array_elements().zip(definitions))
+            .collect::<Vec<_>>();
+        let definitions = intermediates
+            .iter()
+            .map(|((_, poly_id), definition)| (*poly_id, *definition))
+            .collect::<BTreeMap<_, _>>();
+
+        let degree = pil.degree() as usize;
+        intermediates
+            .iter()
+            .map(|((name, _), definition)| {
+                let values = (0..degree)
+                    .map(|row| evaluate_on_row(definition, row, degree, &columns, &definitions))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| vec![format!("Failed to evaluate {name}: {e}")])?;
+                Ok((name.clone(), values))
+            })
+            .collect()
+    }
+
     pub fn witness(&self) -> Result<Rc<Columns<T>>, Vec<String>> {
         Ok(self.artifact.witness.as_ref().unwrap().clone())
     }
 
+    /// Checks the prover inputs passed via `with_prover_inputs` against the
+    /// `std::prover::Query::Input` queries of the program, so that missing inputs
+    /// are reported before running witness generation.
+    /// Inputs read at a constant index must all be provided. If the index is only
+    /// known at runtime (as in the RISC-V machine), at least one input is required.
+    /// Note that this cannot know about inputs answered by custom query callbacks.
+    pub fn validate_inputs(&mut self) -> Result<(), InputError> {
+        let provided = self.arguments.prover_input_count;
+        let pil = self.compute_optimized_pil().map_err(InputError::Pipeline)?;
+
+        let mut reads_inputs = false;
+        let mut required = 0;
+        pil.definitions
+            .values()
+            .filter_map(|(_, value)| value.as_ref())
+            .for_each(|value| {
+                value.pre_visit_expressions(&mut |e| {
+                    let Expression::FunctionCall(FunctionCall {
+                        function,
+                        arguments,
+                    }) = e
+                    else {
+                        return;
+                    };
+                    match function.as_ref() {
+                        Expression::Reference(Reference::Poly(r))
+                            if r.name == "std::prover::Query::Input" => {}
+                        _ => return,
+                    }
+                    reads_inputs = true;
+                    if let [Expression::Number(index, _)] = &arguments[..] {
+                        if let Ok(index) = usize::try_from(index) {
+                            required = required.max(index + 1);
+                        }
+                    }
+                })
+            });
+
+        if provided < required {
+            Err(InputError::NotEnoughInputs { required, provided })
+        } else if reads_inputs && provided == 0 {
+            Err(InputError::MissingInputs)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Lists the identities of the optimized PIL that reference the given column,
+    /// either directly or through intermediate columns.
+    /// Array elements can be queried by their name, e.g. `main.x[1]`.
+    pub fn column_usage(&mut self, name: &str) -> Result<ColumnUsage, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+
+        let symbol_name = name.split('[').next().unwrap();
+        if !matches!(
+            pil.definitions.get(symbol_name),
+            Some((symbol, _)) if matches!(symbol.kind, SymbolKind::Poly(_))
+        ) {
+            return Err(vec![format!("Column {name} not found.")]);
+        }
+
+        let identities = pil
+            .identities_with_inlined_intermediate_polynomials()
+            .into_iter()
+            .filter_map(|identity| {
+                let (current, next) = identity
+                    .all_children()
+                    .filter_map(|e| match e {
+                        AlgebraicExpression::Reference(r) if r.name == name => Some(r.next),
+                        _ => None,
+                    })
+                    .fold((false, false), |(current, next), is_next| {
+                        (current || !is_next, next || is_next)
+                    });
+                (current || next).then(|| IdentityUsage {
+                    id: identity.id,
+                    kind: identity.kind,
+                    source: identity.source.clone(),
+                    current,
+                    next,
+                })
+            })
+            .collect();
+        Ok(ColumnUsage { identities })
+    }
+
+    /// Computes the witness and returns the witness columns that have the same value in
+    /// every row, together with that value. Such columns are often a modeling mistake or
+    /// could be replaced by a constant.
+    pub fn constant_witness_columns(&mut self) -> Result<Vec<(String, T)>, Vec<String>> {
+        let witness = self.compute_witness()?;
+        Ok(witness
+            .iter()
+            .filter_map(|(name, values)| {
+                let (first, rest) = values.split_first()?;
+                rest.iter()
+                    .all(|v| v == first)
+                    .then(|| (name.clone(), *first))
+            })
+            .collect())
+    }
+
+    /// Runs the pipeline up to and including the given stage and returns its artifact.
+    /// Later stages are not computed.
+    pub fn run_until(&mut self, stage: Stage) -> Result<StageArtifact<T>, Vec<String>> {
+        Ok(match stage {
+            Stage::AsmString => StageArtifact::AsmString(self.compute_asm_string()?),
+            Stage::ParsedAsmFile => StageArtifact::ParsedAsmFile(self.compute_parsed_asm_file()?),
+            Stage::ResolvedModuleTree => {
+                StageArtifact::ResolvedModuleTree(self.compute_resolved_module_tree()?)
+            }
+            Stage::AnalyzedAsm => StageArtifact::AnalyzedAsm(self.compute_analyzed_asm()?),
+            Stage::ConstrainedMachineCollection => StageArtifact::ConstrainedMachineCollection(
+                self.compute_constrained_machine_collection()?,
+            ),
+            Stage::LinkedMachineGraph => {
+                StageArtifact::LinkedMachineGraph(self.compute_linked_machine_graph()?)
+            }
+            Stage::ParsedPilFile => StageArtifact::ParsedPilFile(self.compute_parsed_pil_file()?),
+            Stage::AnalyzedPil => StageArtifact::AnalyzedPil(self.compute_analyzed_pil()?),
+            Stage::OptimizedPil => StageArtifact::OptimizedPil(self.compute_optimized_pil()?),
+            Stage::FixedCols => StageArtifact::FixedCols(self.compute_fixed_cols()?),
+            Stage::Witness => StageArtifact::Witness(self.compute_witness()?),
+            Stage::Proof => StageArtifact::Proof(self.compute_proof()?),
+        })
+    }
+
+    /// Returns true if the artifact of the given stage is currently available.
+    /// Note that computing a stage consumes some of the artifacts of earlier stages.
+    pub fn is_computed(&self, stage: Stage) -> bool {
+        match stage {
+            Stage::AsmString => self.artifact.asm_string.is_some(),
+            Stage::ParsedAsmFile => self.artifact.parsed_asm_file.is_some(),
+            Stage::ResolvedModuleTree => self.artifact.resolved_module_tree.is_some(),
+            Stage::AnalyzedAsm => self.artifact.analyzed_asm.is_some(),
+            Stage::ConstrainedMachineCollection => {
+                self.artifact.constrained_machine_collection.is_some()
+            }
+            Stage::LinkedMachineGraph => self.artifact.linked_machine_graph.is_some(),
+            Stage::ParsedPilFile => self.artifact.parsed_pil_file.is_some(),
+            Stage::AnalyzedPil => self.artifact.analyzed_pil.is_some(),
+            Stage::OptimizedPil => self.artifact.optimized_pil.is_some(),
+            Stage::FixedCols => self.artifact.fixed_cols.is_some(),
+            Stage::Witness => self.artifact.witness.is_some(),
+            Stage::Proof => self.artifact.proof.is_some(),
+        }
+    }
+
     pub fn witgen_callback(&mut self) -> Result<WitgenCallback<T>, Vec<String>> {
         Ok(WitgenCallback::new(
             self.compute_optimized_pil()?,
@@ -848,21 +1504,46 @@ impl<T: FieldElement> Pipeline<T> {
         ))
     }
 
+    /// Estimates the size in bytes of the proof the selected backend would
+    /// produce for the optimized PIL, without computing the witness or proving.
+    pub fn estimated_proof_size(&mut self) -> Result<usize, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+        let backend = self
+            .arguments
+            .backend
+            .ok_or_else(|| vec!["No backend selected.".to_string()])?;
+        backend
+            .factory::<T>()
+            .estimated_proof_size(pil.borrow())
+            .map_err(|e| vec![e.to_string()])
+    }
+
     pub fn compute_proof(&mut self) -> Result<&Proof, Vec<String>> {
         if self.artifact.proof.is_some() {
             return Ok(self.artifact.proof.as_ref().unwrap());
         }
 
         let pil = self.compute_optimized_pil()?;
-        let fixed_cols = self.compute_fixed_cols()?;
-        let witness = self.compute_witness()?;
-        let witgen_callback = self.witgen_callback()?;
 
-        let backend = self
+        let backend_type = self
             .arguments
             .backend
             .expect("backend must be set before calling proving!");
-        let factory = backend.factory::<T>();
+        let factory = backend_type.factory::<T>();
+        factory
+            .capabilities()
+            .check(pil.borrow())
+            .map_err(|e| vec![e.to_string()])?;
+        if self.arguments.existing_proof_file.is_some() && !factory.capabilities().aggregation {
+            return Err(vec![
+                powdr_backend::Error::NoAggregationAvailable.to_string()
+            ]);
+        }
+
+        let fixed_cols = self.compute_fixed_cols()?;
+        let witness = self.compute_witness()?;
+        let witgen_callback = self.witgen_callback()?;
+        let program_hash = self.program_hash()?;
 
         // Opens the setup file, if set.
         let mut setup = self
@@ -896,12 +1577,22 @@ impl<T: FieldElement> Pipeline<T> {
             .as_ref()
             .map(|path| fs::read(path).unwrap());
 
+        let start = Instant::now();
         let proof = match backend.prove(&witness, existing_proof, witgen_callback) {
             Ok(proof) => proof,
             Err(powdr_backend::Error::BackendError(e)) => {
                 return Err(vec![e.to_string()]);
             }
-            _ => panic!(),
+            Err(e) => return Err(vec![e.to_string()]),
+        };
+        let metadata = ProofMetadata {
+            backend: backend_type.to_string(),
+            field: T::known_field(),
+            degree: pil.degree(),
+            prover_time: start.elapsed(),
+            proof_size: proof.len(),
+            parameters: backend.parameters(),
+            program_hash,
         };
 
         drop(backend);
@@ -909,14 +1600,80 @@ impl<T: FieldElement> Pipeline<T> {
         self.maybe_write_proof(&proof)?;
 
         self.artifact.proof = Some(proof);
+        self.artifact.proof_metadata = Some(metadata);
 
         Ok(self.artifact.proof.as_ref().unwrap())
     }
 
+    /// Computes the proof (if not done yet) and verifies it in-process, using the native
+    /// verifier of the backend and the public values of the witness.
+    /// In contrast to [crate::verify::verify], this does not need any external tools.
+    pub fn verify_proof(&mut self) -> Result<(), Vec<String>> {
+        let proof = self.compute_proof()?.clone();
+        let pil = self.compute_optimized_pil()?;
+        let fixed_cols = self.compute_fixed_cols()?;
+        let witness = self.compute_witness()?;
+        let publics = extract_publics(&witness, &pil)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+
+        let backend = self
+            .arguments
+            .backend
+            .expect("backend must be set before verifying a proof!");
+        let factory = backend.factory::<T>();
+
+        let mut setup = self
+            .arguments
+            .setup_file
+            .as_ref()
+            .map(|path| BufReader::new(fs::File::open(path).unwrap()));
+        let mut vkey = self
+            .arguments
+            .vkey_file
+            .as_ref()
+            .map(|path| BufReader::new(fs::File::open(path).unwrap()));
+
+        let backend = factory
+            .create(
+                pil.borrow(),
+                &fixed_cols[..],
+                self.output_dir(),
+                setup.as_io_read(),
+                vkey.as_io_read(),
+            )
+            .map_err(|e| vec![e.to_string()])?;
+
+        match backend.verify(&proof, &[publics]) {
+            Ok(()) => Ok(()),
+            Err(powdr_backend::Error::BackendError(e)) => Err(vec![e]),
+            Err(e) => Err(vec![e.to_string()]),
+        }
+    }
+
+    /// Computes the proof together with the public values and their names, in the order
+    /// of their declaration, which is the order the verifier expects them in. Both are
+    /// derived from the same witness, so the proof is guaranteed to be over exactly these publics.
+    pub fn prove_with_publics(&mut self) -> Result<(Proof, Vec<(String, T)>), Vec<String>> {
+        let proof = self.compute_proof()?.clone();
+        let pil = self.compute_optimized_pil()?;
+        let witness = self.compute_witness()?;
+        let publics = extract_publics(&witness, &pil);
+        Ok((proof, publics))
+    }
+
     pub fn proof(&self) -> Result<&Proof, Vec<String>> {
         Ok(self.artifact.proof.as_ref().unwrap())
     }
 
+    /// Computes the proof (if not done yet) and returns information about how it was
+    /// generated: prover time, proof size, backend parameters and the program hash.
+    pub fn compute_proof_metadata(&mut self) -> Result<&ProofMetadata, Vec<String>> {
+        self.compute_proof()?;
+        Ok(self.artifact.proof_metadata.as_ref().unwrap())
+    }
+
     pub fn output_dir(&self) -> Option<&Path> {
         self.output_dir.as_ref().map(|p| p.as_ref())
     }
@@ -929,6 +1686,10 @@ impl<T: FieldElement> Pipeline<T> {
         self.arguments.query_callback.as_deref()
     }
 
+    pub fn execution_trace_log(&self) -> Option<&Path> {
+        self.arguments.execution_trace_log.as_deref()
+    }
+
     pub fn export_verification_key<W: io::Write>(
         &mut self,
         mut writer: W,
@@ -967,7 +1728,11 @@ impl<T: FieldElement> Pipeline<T> {
         }
     }
 
-    pub fn verify(&mut self, proof: &[u8], instances: &[Vec<T>]) -> Result<(), Vec<String>> {
+    /// Returns the setup (`None` if the backend has none) and the verification key, see
+    /// [Pipeline::export_proof_bundle].
+    fn export_setup_and_verification_key(
+        &mut self,
+    ) -> Result<(Option<Vec<u8>>, Vec<u8>), Vec<String>> {
         let backend = self
             .arguments
             .backend
@@ -980,12 +1745,88 @@ impl<T: FieldElement> Pipeline<T> {
             .as_ref()
             .map(|path| BufReader::new(fs::File::open(path).unwrap()));
 
+        let pil = self.compute_optimized_pil()?;
+        let fixed_cols = self.compute_fixed_cols()?;
+
+        let backend = factory
+            .create(
+                pil.borrow(),
+                &fixed_cols[..],
+                self.output_dir(),
+                setup_file.as_io_read(),
+                None,
+            )
+            .map_err(|e| vec![e.to_string()])?;
+
+        let mut setup = vec![];
+        let setup = match backend.export_setup(&mut setup) {
+            Ok(()) => Some(setup),
+            Err(powdr_backend::Error::NoSetupAvailable) => None,
+            Err(e) => return Err(vec![e.to_string()]),
+        };
+        let mut verification_key = vec![];
+        backend
+            .export_verification_key(&mut verification_key)
+            .map_err(|e| vec![e.to_string()])?;
+        Ok((setup, verification_key))
+    }
+
+    /// Exports the fixed columns of the optimized PIL to the given file, so that they
+    /// can be loaded again with `Pipeline::import_fixed_columns` instead of being
+    /// re-generated.
+    pub fn export_fixed_columns(
+        &mut self,
+        path: &Path,
+        format: FixedColumnsFormat,
+    ) -> Result<(), Vec<String>> {
+        let fixed_cols = self.compute_fixed_cols()?;
+        let file = BufWriter::new(fs::File::create(path).map_err(|e| {
+            vec![format!(
+                "Could not create fixed columns file {}: {e}",
+                path.display()
+            )]
+        })?);
+        match format {
+            FixedColumnsFormat::Binary => {
+                write_or_panic(file, |file| write_named_polys_file(file, &fixed_cols))
+            }
+            FixedColumnsFormat::Csv => {
+                let columns = fixed_cols.iter().collect::<Vec<_>>();
+                write_polys_csv_file(file, self.arguments.csv_render_mode, &columns)
+            }
+        }
+        Ok(())
+    }
+
+    pub fn verify(&mut self, proof: &[u8], instances: &[Vec<T>]) -> Result<(), Vec<String>> {
         let mut vkey_file = if let Some(ref path) = self.arguments.vkey_file {
             BufReader::new(fs::File::open(path).unwrap())
         } else {
             panic!("Verification key should have been provided for verification")
         };
 
+        self.verify_with_verification_key(proof, instances, &mut vkey_file)
+    }
+
+    /// Verifies the proof with the given verification key, see [Pipeline::verify].
+    fn verify_with_verification_key(
+        &mut self,
+        proof: &[u8],
+        instances: &[Vec<T>],
+        verification_key: &mut dyn io::Read,
+    ) -> Result<(), Vec<String>> {
+        let backend = self
+            .arguments
+            .backend
+            .expect("backend must be set before verifying a proof!");
+        let factory = backend.factory::<T>();
+
+        let mut setup_file = self
+            .arguments
+            .setup_file
+            .as_ref()
+            .map(|path| BufReader::new(fs::File::open(path).unwrap()));
+
         let pil = self.compute_optimized_pil()?;
         let fixed_cols = self.compute_fixed_cols()?;
 
@@ -997,14 +1838,210 @@ impl<T: FieldElement> Pipeline<T> {
                 setup_file
                     .as_mut()
                     .map(|file| file as &mut dyn std::io::Read),
-                Some(&mut vkey_file),
+                Some(verification_key),
             )
             .unwrap();
 
         match backend.verify(proof, instances) {
             Ok(_) => Ok(()),
             Err(powdr_backend::Error::BackendError(e)) => Err(vec![e]),
-            _ => panic!(),
+            Err(e) => Err(vec![e.to_string()]),
+        }
+    }
+
+    /// Like [Pipeline::verify], but first checks that the PIL the proof is verified
+    /// against has the expected [Pipeline::program_hash]. This makes sure that the
+    /// verifier checks the proof against the intended program and not, for example,
+    /// a weaker one that was provided together with the proof.
+    pub fn verify_with_program_hash(
+        &mut self,
+        proof: &[u8],
+        instances: &[Vec<T>],
+        expected_program_hash: [u8; 32],
+    ) -> Result<(), Vec<String>> {
+        self.check_program_hash(expected_program_hash)?;
+        self.verify(proof, instances)
+    }
+
+    fn check_program_hash(&mut self, expected_program_hash: [u8; 32]) -> Result<(), Vec<String>> {
+        let program_hash = self.program_hash()?;
+        if program_hash != expected_program_hash {
+            let to_hex =
+                |hash: [u8; 32]| hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+            return Err(vec![format!(
+                "PIL mismatch: expected program hash {}, but the PIL has hash {}",
+                to_hex(expected_program_hash),
+                to_hex(program_hash)
+            )]);
+        }
+        Ok(())
+    }
+
+    /// Writes the proof, its publics, the optimized PIL, the setup (if the backend has one),
+    /// the verification key and a manifest describing the proof to a single file, which can
+    /// be checked with [Pipeline::verify_bundle].
+    ///
+    /// For backends with a setup, the setup file has to be set, so that the proof is generated
+    /// with the same setup as the one in the bundle.
+    pub fn export_proof_bundle(&mut self, path: &Path) -> Result<(), Vec<String>> {
+        let backend = self
+            .arguments
+            .backend
+            .expect("backend must be set before exporting a proof bundle!");
+        let (setup, verification_key) = self.export_setup_and_verification_key()?;
+        if setup.is_some() && self.arguments.setup_file.is_none() {
+            return Err(vec![format!(
+                "A setup file is needed to export a proof bundle with the backend {backend}"
+            )]);
+        }
+        let proof = self.compute_proof()?.clone();
+        let pil = self.compute_optimized_pil()?;
+        let publics = extract_publics(&self.compute_witness()?, &pil);
+
+        let bundle = ProofBundle {
+            manifest: BundleManifest {
+                backend: backend.to_string(),
+                field: T::known_field(),
+                degree: pil.degree(),
+                program_hash: self.program_hash()?,
+            },
+            pil: pil.to_string(),
+            setup,
+            verification_key,
+            proof,
+            publics,
+        };
+        let file = BufWriter::new(fs::File::create(path).map_err(|e| vec![e.to_string()])?);
+        serde_cbor::to_writer(file, &bundle).map_err(|e| vec![e.to_string()])
+    }
+
+    /// Verifies a bundle written by [Pipeline::export_proof_bundle]: its manifest has to match
+    /// this pipeline's backend, field and program hash, and the proof has to verify with the
+    /// bundled setup, verification key and publics.
+    pub fn verify_bundle(&mut self, path: &Path) -> Result<(), Vec<String>> {
+        let file = BufReader::new(fs::File::open(path).map_err(|e| vec![e.to_string()])?);
+        let bundle: ProofBundle<T> =
+            serde_cbor::from_reader(file).map_err(|e| vec![format!("Invalid bundle: {e}")])?;
+
+        let backend = self
+            .arguments
+            .backend
+            .expect("backend must be set before verifying a proof bundle!");
+        if bundle.manifest.backend != backend.to_string() {
+            return Err(vec![format!(
+                "The bundle was created with the backend {}, but the pipeline uses {backend}",
+                bundle.manifest.backend
+            )]);
+        }
+        if bundle.manifest.field != T::known_field() {
+            return Err(vec![format!(
+                "The bundle is over the field {:?}, but the pipeline uses {:?}",
+                bundle.manifest.field,
+                T::known_field()
+            )]);
+        }
+        self.check_program_hash(bundle.manifest.program_hash)?;
+        if bundle.pil != self.compute_optimized_pil()?.to_string() {
+            return Err(vec![
+                "The PIL in the bundle does not match the program".to_string()
+            ]);
+        }
+
+        let pil = self.compute_optimized_pil()?;
+        let fixed_cols = self.compute_fixed_cols()?;
+        let mut setup = bundle.setup.as_deref();
+        let verifier = backend
+            .factory::<T>()
+            .create(
+                pil.borrow(),
+                &fixed_cols[..],
+                self.output_dir(),
+                setup.as_io_read(),
+                Some(&mut bundle.verification_key.as_slice()),
+            )
+            .map_err(|e| vec![e.to_string()])?;
+
+        let publics = bundle.publics.into_iter().map(|(_, v)| v).collect();
+        match verifier.verify(&bundle.proof, &[publics]) {
+            Ok(()) => Ok(()),
+            Err(powdr_backend::Error::BackendError(e)) => Err(vec![e]),
+            Err(e) => Err(vec![e.to_string()]),
+        }
+    }
+
+    /// Returns whether the optimized PIL and `other` are equivalent, i.e. only differ in the
+    /// order of declarations and identities (and therefore in the ids of columns and identities),
+    /// in the labels of identities or in the names of columns.
+    pub fn pils_equivalent(&mut self, other: &Analyzed<T>) -> Result<bool, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+        Ok(normal_form(&pil) == normal_form(other))
+    }
+}
+
+/// Resizes the repeated part of the array (if any) such that it has `degree` values in total.
+/// Returns the number of values that are not repeated if they do not fit.
+fn resize_array(array: &mut [RepeatedArray], degree: DegreeType) -> Result<(), DegreeType> {
+    let repeated = array.iter().position(|part| part.is_repeated());
+    let fixed_size = array
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != repeated)
+        .map(|(_, part)| part.size())
+        .sum::<DegreeType>();
+    match repeated {
+        Some(i) if fixed_size <= degree => {
+            array[i] = RepeatedArray::new(array[i].pattern().to_vec(), degree - fixed_size);
+            Ok(())
         }
+        None if fixed_size == degree => Ok(()),
+        _ => Err(fixed_size),
     }
 }
+
+/// Evaluates an expression on the given row, with all columns fully known.
+fn evaluate_on_row<T: FieldElement>(
+    expr: &AlgebraicExpression<T>,
+    row: usize,
+    degree: usize,
+    columns: &HashMap<&str, &[T]>,
+    intermediates: &BTreeMap<PolyID, &AlgebraicExpression<T>>,
+) -> Result<T, String> {
+    Ok(match expr {
+        AlgebraicExpression::Reference(poly) => {
+            let row = if poly.next { (row + 1) % degree } else { row };
+            if poly.poly_id.ptype == PolynomialType::Intermediate {
+                evaluate_on_row(
+                    intermediates[&poly.poly_id],
+                    row,
+                    degree,
+                    columns,
+                    intermediates,
+                )?
+            } else {
+                columns
+                    .get(poly.name.as_str())
+                    .ok_or_else(|| format!("Column {} not found.", poly.name))?[row]
+            }
+        }
+        AlgebraicExpression::Number(n) => *n,
+        AlgebraicExpression::BinaryOperation(left, op, right) => {
+            let left = evaluate_on_row(left, row, degree, columns, intermediates)?;
+            let right = evaluate_on_row(right, row, degree, columns, intermediates)?;
+            match op {
+                AlgebraicBinaryOperator::Add => left + right,
+                AlgebraicBinaryOperator::Sub => left - right,
+                AlgebraicBinaryOperator::Mul => left * right,
+                AlgebraicBinaryOperator::Pow => left.pow(right.to_integer()),
+            }
+        }
+        AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, e) => {
+            -evaluate_on_row(e, row, degree, columns, intermediates)?
+        }
+        AlgebraicExpression::PublicReference(name) => {
+            return Err(format!("Cannot evaluate public reference {name}."))
+        }
+        AlgebraicExpression::Challenge(challenge) => {
+            return Err(format!("Cannot evaluate challenge {}.", challenge.id))
+        }
+    })
+}
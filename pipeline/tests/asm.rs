@@ -1,5 +1,6 @@
 use powdr_number::{Bn254Field, FieldElement, GoldilocksField};
 use powdr_pipeline::{
+    pipeline::InputError,
     test_util::{gen_estark_proof, resolve_test_file, test_halo2, verify_test_file},
     util::{try_read_poly_set, FixedPolySet, WitnessPolySet},
     Pipeline,
@@ -23,6 +24,25 @@ fn simple_sum_asm() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn simple_sum_asm_validate_inputs() {
+    let f = "asm/simple_sum.asm";
+    let pipeline = Pipeline::<GoldilocksField>::default().from_file(resolve_test_file(f));
+
+    // The program reads the inputs at indices 0 and 1 directly.
+    let mut too_few = pipeline.clone().with_prover_inputs(slice_to_vec(&[16]));
+    assert_eq!(
+        too_few.validate_inputs(),
+        Err(InputError::NotEnoughInputs {
+            required: 2,
+            provided: 1
+        })
+    );
+
+    let mut enough = pipeline.with_prover_inputs(slice_to_vec(&[16, 4, 1, 2, 8, 5]));
+    assert_eq!(enough.validate_inputs(), Ok(()));
+}
+
 #[test]
 fn secondary_block_machine_add2() {
     let f = "asm/secondary_block_machine_add2.asm";
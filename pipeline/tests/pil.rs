@@ -1,14 +1,21 @@
+use std::fs::File;
+
+use powdr_ast::analyzed::IdentityKind;
+use powdr_backend::{BackendFactory, EStarkFactory, Error, StarkStructConfig};
+use powdr_executor::witgen::{extract_publics, WrappingPolicy};
 #[cfg(feature = "halo2")]
 use powdr_number::Bn254Field;
 use powdr_number::GoldilocksField;
+use powdr_number::KnownField;
 use powdr_pipeline::{
+    pipeline::{ColumnUsage, FixedColError, FixedColumnsFormat, ProofBundle, Stage, StageArtifact},
     test_util::{
         assert_proofs_fail_for_invalid_witnesses, assert_proofs_fail_for_invalid_witnesses_estark,
         assert_proofs_fail_for_invalid_witnesses_halo2,
         assert_proofs_fail_for_invalid_witnesses_pilcom, gen_estark_proof, resolve_test_file,
         test_halo2, verify_test_file,
     },
-    Pipeline,
+    AnyPipeline, Pipeline,
 };
 use test_log::test;
 
@@ -265,6 +272,92 @@ fn test_fixed_columns() {
     // Starky requires at least one witness column, this test has none.
 }
 
+#[test]
+fn export_import_fixed_columns() {
+    let f = resolve_test_file("pil/fixed_columns.pil");
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let path = tmp_dir.as_path().join("fixed_columns.bin");
+
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_file(f.clone());
+    pipeline
+        .export_fixed_columns(&path, FixedColumnsFormat::Binary)
+        .unwrap();
+    let expected = pipeline.compute_fixed_cols().unwrap();
+
+    let mut imported = Pipeline::<GoldilocksField>::default()
+        .from_file(f.clone())
+        .import_fixed_columns(&path, FixedColumnsFormat::Binary)
+        .unwrap();
+    assert_eq!(imported.compute_fixed_cols().unwrap(), expected);
+
+    // A file exported from a different program does not match the PIL.
+    let other = tmp_dir.as_path().join("other.bin");
+    Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/constant_in_identity.pil"))
+        .export_fixed_columns(&other, FixedColumnsFormat::Binary)
+        .unwrap();
+    assert!(Pipeline::<GoldilocksField>::default()
+        .from_file(f)
+        .import_fixed_columns(&other, FixedColumnsFormat::Binary)
+        .is_err());
+}
+
+#[test]
+fn any_pipeline() {
+    let pil = "namespace Main(8); col witness x; x = 1;";
+    for field in [KnownField::GoldilocksField, KnownField::Bn254Field] {
+        let mut pipeline = AnyPipeline::new(field).from_pil_string(pil.to_string());
+        assert_eq!(pipeline.field(), field);
+        pipeline.run_until(Stage::AnalyzedPil).unwrap();
+        assert!(pipeline.is_computed(Stage::AnalyzedPil));
+    }
+}
+
+#[test]
+fn verify_fixed_cols() {
+    let pil = r#"
+namespace Main(8);
+    col fixed A = [1, 2] + [3, 4]* + [5];
+    col fixed B(i) { i + 1 };
+    col witness w;
+    w = A + B;
+"#;
+    let pipeline = || Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+    let mut correct = pipeline();
+    correct.verify_fixed_cols().unwrap();
+
+    // Corrupt the first repetition of the pattern of A.
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let path = tmp_dir.as_path().join("fixed_columns.csv");
+    correct
+        .export_fixed_columns(&path, FixedColumnsFormat::Csv)
+        .unwrap();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(csv.matches("\n4,0x3,0x5\n").count(), 1);
+    std::fs::write(&path, csv.replace("\n4,0x3,0x5\n", "\n4,0x9,0x5\n")).unwrap();
+
+    let mut corrupted = pipeline()
+        .import_fixed_columns(&path, FixedColumnsFormat::Csv)
+        .unwrap();
+    assert_eq!(
+        corrupted.verify_fixed_cols(),
+        Err(vec![FixedColError::Mismatch {
+            column: "Main.A".to_string(),
+            row: 4,
+            expected: 3.into(),
+            actual: 9.into(),
+        }])
+    );
+}
+
+#[test]
+fn no_identities() {
+    let f = "pil/no_identities.pil";
+    verify_pil(f, Default::default());
+    test_halo2(f, Default::default());
+    gen_estark_proof(f, Default::default());
+}
+
 #[test]
 fn test_witness_via_let() {
     verify_pil("pil/witness_via_let.pil", Default::default());
@@ -330,3 +423,715 @@ mod book {
 
     include!(concat!(env!("OUT_DIR"), "/pil_book_tests.rs"));
 }
+
+#[test]
+fn constant_witness_columns() {
+    // x is forced to 7 in every row, but not directly enough for the optimizer to remove it.
+    let pil = r#"
+namespace N(4);
+    col fixed FIRST = [1] + [0]*;
+    col fixed STEP(i) { i };
+    col witness x, y;
+    FIRST * (x - 7) = 0;
+    x' = x;
+    y = x + STEP;
+"#;
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+    assert_eq!(
+        pipeline.constant_witness_columns().unwrap(),
+        vec![("N.x".to_string(), 7.into())]
+    );
+}
+
+#[test]
+fn column_usage() {
+    let pil = r#"
+namespace N(4);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y;
+    FIRST * (x - 1) = 0;
+    x' = x + y;
+"#;
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+
+    let usage = |pipeline: &mut Pipeline<GoldilocksField>, name| {
+        let ColumnUsage { identities } = pipeline.column_usage(name).unwrap();
+        identities
+            .into_iter()
+            .map(|i| (i.kind, i.current, i.next))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        usage(&mut pipeline, "N.x"),
+        vec![
+            (IdentityKind::Polynomial, true, false),
+            (IdentityKind::Polynomial, true, true)
+        ]
+    );
+    assert_eq!(
+        usage(&mut pipeline, "N.y"),
+        vec![(IdentityKind::Polynomial, true, false)]
+    );
+    assert!(pipeline.column_usage("N.z").is_err());
+}
+
+#[test]
+fn estimated_proof_size() {
+    let f = resolve_test_file("pil/fibonacci.pil");
+    let size = Pipeline::<GoldilocksField>::default()
+        .from_file(f.clone())
+        .with_backend(powdr_backend::BackendType::EStark)
+        .estimated_proof_size()
+        .unwrap();
+    assert!(size > 0);
+
+    // No backend selected.
+    assert!(Pipeline::<GoldilocksField>::default()
+        .from_file(f)
+        .estimated_proof_size()
+        .is_err());
+}
+
+#[test]
+fn proof_metadata() {
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+    let proof_size = pipeline.compute_proof().unwrap().len();
+    let program_hash = pipeline.program_hash().unwrap();
+    let degree = pipeline.optimized_pil().unwrap().degree();
+
+    let metadata = pipeline.compute_proof_metadata().unwrap();
+    assert_eq!(metadata.backend, "estark");
+    assert_eq!(metadata.field, Some(KnownField::GoldilocksField));
+    assert_eq!(metadata.degree, degree);
+    assert_eq!(metadata.proof_size, proof_size);
+    assert_eq!(metadata.program_hash, program_hash);
+    assert_eq!(metadata.parameters["hash"], "GL");
+    assert!(metadata.parameters.contains_key("n_queries"));
+}
+
+#[test]
+fn estark_custom_stark_struct() {
+    let mut pipeline =
+        Pipeline::<GoldilocksField>::default().from_file(resolve_test_file("pil/fibonacci.pil"));
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let fixed = pipeline.compute_fixed_cols().unwrap();
+    let verification_key = |factory: &EStarkFactory| {
+        let backend =
+            BackendFactory::<GoldilocksField>::create(factory, &pil, &fixed, None, None, None)
+                .unwrap();
+        let mut key = vec![];
+        backend.export_verification_key(&mut key).unwrap();
+        key
+    };
+
+    let params = StarkStructConfig::DEFAULT
+        .stark_struct::<GoldilocksField>(pil.degree())
+        .unwrap();
+    let default_key = verification_key(&EStarkFactory::new(StarkStructConfig::DEFAULT));
+    let factory = EStarkFactory::with_params(pil.degree(), params.clone()).unwrap();
+    assert_eq!(verification_key(&factory), default_key);
+
+    // The setup commits to the fixed columns on the extended domain,
+    // so it changes with the blowup factor.
+    let mut params = params;
+    params.nBitsExt += 1;
+    params.steps[0].nBits += 1;
+    let factory = EStarkFactory::with_params(pil.degree(), params).unwrap();
+    assert_ne!(verification_key(&factory), default_key);
+}
+
+#[test]
+fn estark_witness_provider() {
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+    let proof = pipeline.compute_proof().unwrap().clone();
+    let witgen_callback = pipeline.witgen_callback().unwrap();
+    let pil = pipeline.optimized_pil().unwrap();
+    let fixed = pipeline.fixed_cols().unwrap();
+    let witness = pipeline.witness().unwrap();
+
+    let factory = EStarkFactory::new(StarkStructConfig::DEFAULT);
+    let backend =
+        BackendFactory::<GoldilocksField>::create(&factory, &pil, &fixed, None, None, None)
+            .unwrap();
+    let column_names = witness
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let mut requested = vec![];
+    let mut provider = |name: &str| {
+        requested.push(name.to_string());
+        witness.iter().find(|(n, _)| n == name).unwrap().1.clone()
+    };
+    let streamed_proof = backend
+        .prove_with_witness_provider(&column_names, &mut provider, None, witgen_callback)
+        .unwrap();
+
+    assert_eq!(requested, column_names);
+    assert_eq!(streamed_proof, proof);
+}
+
+#[test]
+fn estark_rejects_previous_proof() {
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+    let proof = pipeline.compute_proof().unwrap().clone();
+    let witgen_callback = pipeline.witgen_callback().unwrap();
+    let pil = pipeline.optimized_pil().unwrap();
+    let fixed = pipeline.fixed_cols().unwrap();
+    let witness = pipeline.witness().unwrap();
+
+    let factory = EStarkFactory::new(StarkStructConfig::DEFAULT);
+    let backend =
+        BackendFactory::<GoldilocksField>::create(&factory, &pil, &fixed, None, None, None)
+            .unwrap();
+    let column_names = witness
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let mut provider = |name: &str| witness.iter().find(|(n, _)| n == name).unwrap().1.clone();
+
+    assert!(matches!(
+        backend.prove(&witness, Some(proof.clone()), witgen_callback.clone()),
+        Err(Error::NoAggregationAvailable)
+    ));
+    assert!(matches!(
+        backend.prove_with_witness_provider(
+            &column_names,
+            &mut provider,
+            Some(proof),
+            witgen_callback
+        ),
+        Err(Error::NoAggregationAvailable)
+    ));
+
+    // The pipeline rejects an existing proof before reading it.
+    let errors = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark)
+        .with_existing_proof_file(Some("fibonacci_proof.bin".into()))
+        .compute_proof()
+        .unwrap_err();
+    assert_eq!(errors, vec![Error::NoAggregationAvailable.to_string()]);
+}
+
+#[test]
+fn estark_rejects_mismatching_witness() {
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+    let witness = pipeline.compute_witness().unwrap();
+    let witgen_callback = pipeline.witgen_callback().unwrap();
+    let pil = pipeline.optimized_pil().unwrap();
+    let fixed = pipeline.fixed_cols().unwrap();
+
+    let factory = EStarkFactory::new(StarkStructConfig::DEFAULT);
+    let backend =
+        BackendFactory::<GoldilocksField>::create(&factory, &pil, &fixed, None, None, None)
+            .unwrap();
+    let column_names = witness
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    let mut provider = |name: &str| witness.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    assert!(matches!(
+        backend.prove_with_witness_provider(
+            &column_names[1..],
+            &mut provider,
+            None,
+            witgen_callback.clone()
+        ),
+        Err(Error::ColumnCountMismatch { expected, actual })
+            if expected == column_names.len() && actual == column_names.len() - 1
+    ));
+
+    let mut short_provider = |name: &str| {
+        let mut values = witness.iter().find(|(n, _)| n == name).unwrap().1.clone();
+        values.pop();
+        values
+    };
+    assert!(matches!(
+        backend.prove_with_witness_provider(
+            &column_names,
+            &mut short_provider,
+            None,
+            witgen_callback
+        ),
+        Err(Error::ColumnLengthMismatch { column, .. }) if column == column_names[0]
+    ));
+}
+
+#[test]
+fn cross_namespace_reference() {
+    let pil = r#"
+namespace N(8);
+    col witness x;
+namespace M(8);
+    col witness y;
+    N.x = y;
+"#;
+    let err = Pipeline::<GoldilocksField>::default()
+        .from_pil_string(pil.to_string())
+        .compute_analyzed_pil()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        vec![
+            "Polynomial identity references columns N.x and M.y from different namespaces. \
+            Use a lookup or permutation to connect machines instead: N.x = M.y;"
+                .to_string()
+        ]
+    );
+}
+
+#[test]
+fn non_power_of_two_degree() {
+    let pil = r#"
+namespace Odd(6);
+    col fixed F = [1, 2, 3, 4, 5, 6];
+    col witness a;
+    a = F * 2;
+"#;
+    let err = Pipeline::<GoldilocksField>::default()
+        .from_pil_string(pil.to_string())
+        .with_backend(powdr_backend::BackendType::EStark)
+        .compute_proof()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        vec!["the degree 6 of namespace Odd is not a power of two".to_string()]
+    );
+}
+
+#[test]
+fn unknown_cell_sentinel() {
+    // `y` is only determined in the rows where `F` is 1.
+    let pil = r#"
+namespace Main(4);
+    col fixed F = [1, 0, 1, 0];
+    col witness y;
+    F * (y - 7) = 0;
+"#;
+    let witness = |sentinel: Option<u64>| {
+        let mut pipeline = Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+        if let Some(sentinel) = sentinel {
+            pipeline = pipeline.with_unknown_cell_sentinel(sentinel.into());
+        }
+        let witness = pipeline.compute_witness().unwrap();
+        assert_eq!(witness[0].0, "Main.y");
+        witness[0].1.clone()
+    };
+
+    assert_eq!(witness(None), [7, 0, 7, 0].map(GoldilocksField::from));
+    let sentinel = 0xdead_beef;
+    let unknown_cells = witness(Some(sentinel))
+        .iter()
+        .map(|v| *v == sentinel.into())
+        .collect::<Vec<_>>();
+    assert_eq!(unknown_cells, [false, true, false, true]);
+}
+
+#[test]
+fn block_validation() {
+    let f = resolve_test_file("pil/block_lookup_or.pil");
+    Pipeline::<GoldilocksField>::default()
+        .from_file(f)
+        .with_block_validation(WrappingPolicy::Acyclic)
+        .compute_witness()
+        .unwrap();
+}
+
+#[test]
+fn evaluation_cache() {
+    let f = resolve_test_file("pil/block_lookup_or.pil");
+    let witness = |pipeline: Pipeline<GoldilocksField>| {
+        (*pipeline.from_file(f.clone()).compute_witness().unwrap()).clone()
+    };
+    assert_eq!(
+        witness(Pipeline::default().with_evaluation_cache()),
+        witness(Pipeline::default())
+    );
+}
+
+#[test]
+fn with_degree_resizes_arrays() {
+    let pil = r#"
+namespace Main(8);
+    col fixed F = [1, 2, 3] + [0]*;
+    col fixed G = [4, 5]*;
+    col witness w;
+    w = F + G;
+"#;
+    let fixed_cols = |degree: u64| {
+        Pipeline::<GoldilocksField>::default()
+            .from_pil_string(pil.to_string())
+            .with_degree(degree)
+            .and_then(|mut pipeline| pipeline.compute_fixed_cols())
+            .map(|fixed_cols| (*fixed_cols).clone())
+    };
+
+    assert_eq!(
+        fixed_cols(4).unwrap(),
+        vec![
+            (
+                "Main.F".to_string(),
+                [1, 2, 3, 0].map(GoldilocksField::from).to_vec()
+            ),
+            (
+                "Main.G".to_string(),
+                [4, 5, 4, 5].map(GoldilocksField::from).to_vec()
+            )
+        ]
+    );
+    assert_eq!(
+        fixed_cols(2).unwrap_err(),
+        vec!["The fixed column Main.F has 3 values, which do not fit the degree 2.".to_string()]
+    );
+}
+
+#[test]
+fn run_until_fixed_cols() {
+    let f = resolve_test_file("pil/fibonacci.pil");
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_file(f);
+    let StageArtifact::FixedCols(fixed_cols) = pipeline.run_until(Stage::FixedCols).unwrap() else {
+        panic!("Expected fixed columns");
+    };
+    assert!(!fixed_cols.is_empty());
+    assert!(pipeline.is_computed(Stage::OptimizedPil));
+    assert!(pipeline.is_computed(Stage::FixedCols));
+    assert!(!pipeline.is_computed(Stage::Witness));
+    assert!(!pipeline.is_computed(Stage::Proof));
+}
+
+#[test]
+fn program_hash() {
+    let hash = |pil: &str| {
+        Pipeline::<GoldilocksField>::default()
+            .from_pil_string(pil.to_string())
+            .program_hash()
+            .unwrap()
+    };
+    let pil = r#"
+namespace main(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    FIRST * (x - 1) = 0;
+    x' = x * 2;
+"#;
+    assert_eq!(hash(pil), hash(pil));
+    assert_ne!(hash(pil), hash(&pil.replace("x * 2", "x * 3")));
+}
+
+#[test]
+fn prove_with_publics() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file("pil/fibonacci_two_publics.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+
+    let vkey_file_path = tmp_dir.as_path().join("verification_key.bin");
+    pipeline
+        .export_verification_key(File::create(&vkey_file_path).unwrap())
+        .unwrap();
+    let (proof, publics) = pipeline.prove_with_publics().unwrap();
+
+    let witness = pipeline.witness().unwrap();
+    let column = |name: &str| &witness.iter().find(|(n, _)| n == name).unwrap().1;
+    // The publics are in declaration order, not sorted by name.
+    assert_eq!(
+        publics,
+        vec![
+            ("Fibonacci.out".to_string(), column("Fibonacci.y")[3]),
+            ("Fibonacci.first".to_string(), column("Fibonacci.x")[2]),
+        ]
+    );
+    assert_eq!(publics[0].1, GoldilocksField::from(5));
+    assert_eq!(publics[1].1, GoldilocksField::from(2));
+
+    let mut pipeline = pipeline.with_vkey_file(Some(vkey_file_path));
+    let values = publics.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    pipeline.verify(&proof, &[values.clone()]).unwrap();
+    // The verifier expects the publics in declaration order.
+    let swapped = values.into_iter().rev().collect();
+    assert!(pipeline.verify(&proof, &[swapped]).is_err());
+}
+
+#[test]
+fn estark_rejects_tampered_proof() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+
+    let vkey_file_path = tmp_dir.as_path().join("verification_key.bin");
+    pipeline
+        .export_verification_key(File::create(&vkey_file_path).unwrap())
+        .unwrap();
+    let (proof, publics) = pipeline.prove_with_publics().unwrap();
+    let publics = vec![publics.into_iter().map(|(_, v)| v).collect::<Vec<_>>()];
+
+    let mut pipeline = pipeline.with_vkey_file(Some(vkey_file_path));
+    pipeline.verify(&proof, &publics).unwrap();
+
+    // Flipping the lowest bit of a digit keeps the proof well-formed, but changes a value.
+    let mut tampered = proof.clone();
+    let digit = tampered.len() / 2
+        + tampered[tampered.len() / 2..]
+            .iter()
+            .position(u8::is_ascii_digit)
+            .unwrap();
+    tampered[digit] ^= 1;
+    assert!(pipeline.verify(&tampered, &publics).is_err());
+
+    // A proof that cannot be deserialized is rejected as well.
+    let mut tampered = proof;
+    tampered[0] ^= 1;
+    let errors = pipeline.verify(&tampered, &publics).unwrap_err();
+    assert!(errors[0].starts_with("Could not deserialize proof"));
+}
+
+#[test]
+fn proof_bundle() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let bundle_path = tmp_dir.as_path().join("fibonacci.bundle");
+    let pipeline = |file: &str| {
+        Pipeline::<GoldilocksField>::default()
+            .from_file(resolve_test_file(file))
+            .with_backend(powdr_backend::BackendType::EStark)
+    };
+
+    pipeline("pil/fibonacci.pil")
+        .export_proof_bundle(&bundle_path)
+        .unwrap();
+    pipeline("pil/fibonacci.pil")
+        .verify_bundle(&bundle_path)
+        .unwrap();
+
+    let errors = pipeline("pil/trivial.pil")
+        .verify_bundle(&bundle_path)
+        .unwrap_err();
+    assert!(errors[0].starts_with("PIL mismatch"));
+
+    let bundle: ProofBundle<GoldilocksField> =
+        serde_cbor::from_reader(File::open(&bundle_path).unwrap()).unwrap();
+    assert!(bundle.setup.is_none());
+    let verify_modified = |modify: &dyn Fn(&mut ProofBundle<GoldilocksField>)| {
+        let mut bundle = bundle.clone();
+        modify(&mut bundle);
+        serde_cbor::to_writer(File::create(&bundle_path).unwrap(), &bundle).unwrap();
+        pipeline("pil/fibonacci.pil").verify_bundle(&bundle_path)
+    };
+
+    // The proof is verified with the bundled verification key and publics.
+    assert!(
+        verify_modified(&|bundle| bundle.publics[0].1 = bundle.publics[0].1 + 1.into()).is_err()
+    );
+    assert!(verify_modified(&|bundle| {
+        let last = bundle.verification_key.len() - 1;
+        bundle.verification_key[last] ^= 1;
+    })
+    .is_err());
+    verify_modified(&|_| {}).unwrap();
+}
+
+#[cfg(feature = "halo2")]
+#[test]
+fn proof_bundle_halo2() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let bundle_path = tmp_dir.as_path().join("fibonacci.bundle");
+    let pipeline = || {
+        Pipeline::<Bn254Field>::default()
+            .from_file(resolve_test_file("pil/fibonacci.pil"))
+            .with_backend(powdr_backend::BackendType::Halo2)
+    };
+
+    // The proof would be generated with a different, random setup than the bundled one.
+    let errors = pipeline().export_proof_bundle(&bundle_path).unwrap_err();
+    assert!(errors[0].starts_with("A setup file is needed"));
+
+    let setup_path = tmp_dir.as_path().join("params.bin");
+    let mut pipeline_with_setup = pipeline();
+    let degree = pipeline_with_setup
+        .compute_optimized_pil()
+        .unwrap()
+        .degree();
+    powdr_backend::BackendType::Halo2
+        .factory::<Bn254Field>()
+        .generate_setup(degree, &mut File::create(&setup_path).unwrap())
+        .unwrap();
+    pipeline_with_setup
+        .with_setup_file(Some(setup_path))
+        .export_proof_bundle(&bundle_path)
+        .unwrap();
+
+    // The verifier only needs the bundle, not the setup file.
+    pipeline().verify_bundle(&bundle_path).unwrap();
+}
+
+#[test]
+fn pils_equivalent() {
+    let optimized_pil = |pil: &str| {
+        Pipeline::<GoldilocksField>::default()
+            .from_pil_string(pil.to_string())
+            .compute_optimized_pil()
+            .unwrap()
+    };
+    let pil = r#"
+namespace main(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    col witness y;
+    FIRST * (x - 1) = 0;
+    x' = x + y;
+    y' = x;
+"#;
+    let reordered = r#"
+namespace main(8);
+    col witness y;
+    col witness x;
+    col fixed FIRST = [1] + [0]*;
+    y' = x;
+    FIRST * (x - 1) = 0;
+    x' = x + y;
+"#;
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+    assert!(pipeline.pils_equivalent(&optimized_pil(pil)).unwrap());
+    assert!(pipeline.pils_equivalent(&optimized_pil(reordered)).unwrap());
+    assert!(!pipeline
+        .pils_equivalent(&optimized_pil(&reordered.replace("    y' = x;\n", "")))
+        .unwrap());
+
+    let renamed = r#"
+namespace main(8);
+    col witness b;
+    col witness a;
+    col fixed START = [1] + [0]*;
+    b' = a;
+    START * (a - 1) = 0;
+    #[step]
+    a' = a + b;
+"#;
+    assert!(pipeline.pils_equivalent(&optimized_pil(renamed)).unwrap());
+    assert!(!pipeline
+        .pils_equivalent(&optimized_pil(&renamed.replace("a' = a + b", "a' = a + a")))
+        .unwrap());
+
+    // The order of the columns does not depend on their names.
+    let pil = r#"
+namespace main(8);
+    col witness a, b, c;
+    a' = b;
+    b' = c;
+    a' = c';
+"#;
+    let renamed = r#"
+namespace main(8);
+    col witness z, y, x;
+    z' = y;
+    y' = x;
+    z' = x';
+"#;
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_pil_string(pil.to_string());
+    assert!(pipeline.pils_equivalent(&optimized_pil(renamed)).unwrap());
+}
+
+#[test]
+fn verify_with_program_hash() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+
+    let vkey_file_path = tmp_dir.as_path().join("verification_key.bin");
+    pipeline
+        .export_verification_key(File::create(&vkey_file_path).unwrap())
+        .unwrap();
+    let proof = pipeline.compute_proof().unwrap().clone();
+    let publics = extract_publics(
+        &pipeline.witness().unwrap(),
+        &pipeline.optimized_pil().unwrap(),
+    )
+    .into_iter()
+    .map(|(_name, v)| v)
+    .collect::<Vec<_>>();
+    let program_hash = pipeline.program_hash().unwrap();
+
+    let mut pipeline = pipeline.with_vkey_file(Some(vkey_file_path));
+    pipeline
+        .verify_with_program_hash(&proof, &[publics.clone()], program_hash)
+        .unwrap();
+    let err = pipeline
+        .verify_with_program_hash(&proof, &[publics], [0; 32])
+        .unwrap_err();
+    assert!(err[0].starts_with("PIL mismatch"));
+}
+
+#[test]
+fn witness_by_machine() {
+    let pil = r#"
+namespace A(4);
+    col fixed F = [1, 2, 3, 4];
+    col witness a;
+    a = F * 2;
+namespace B(4);
+    col fixed G = [5, 6, 7, 8];
+    col witness b, c;
+    b = G + 1;
+    c = b * b;
+"#;
+    let by_machine = Pipeline::<GoldilocksField>::default()
+        .from_pil_string(pil.to_string())
+        .compute_witness_by_machine()
+        .unwrap();
+    let column_names = |machine: &str| {
+        by_machine[machine]
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(by_machine.len(), 2);
+    assert_eq!(column_names("A"), ["A.a"]);
+    assert_eq!(column_names("B"), ["B.b", "B.c"]);
+    assert_eq!(by_machine["B"][1].1[0], GoldilocksField::from(36));
+}
+
+#[test]
+fn intermediates() {
+    let pil = r#"
+namespace Inter(4);
+    col fixed F = [1, 2, 3, 4];
+    col witness a, b, c;
+    a = F;
+    b = F + 1;
+    col inter = a * b;
+    c = inter;
+"#;
+    let intermediates = Pipeline::<GoldilocksField>::default()
+        .from_pil_string(pil.to_string())
+        .compute_intermediates()
+        .unwrap();
+    assert_eq!(
+        intermediates,
+        vec![(
+            "Inter.inter".to_string(),
+            [2, 6, 12, 20].map(GoldilocksField::from).to_vec()
+        )]
+    );
+}
+
+#[test]
+fn verify_proof_in_process() {
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file("pil/fibonacci.pil"))
+        .with_backend(powdr_backend::BackendType::EStark);
+    pipeline.verify_proof().unwrap();
+}
@@ -24,6 +24,12 @@ fn poseidon_gl_test() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn sha256_test() {
+    let f = "std/sha256_test.asm";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
+}
+
 #[test]
 fn split_bn254_test() {
     let f = "std/split_bn254_test.asm";
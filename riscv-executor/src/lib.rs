@@ -10,7 +10,7 @@
 //! from execution.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display, Formatter},
     io,
 };
@@ -28,6 +28,15 @@ use powdr_number::{FieldElement, LargeInt};
 use powdr_riscv_syscalls::SYSCALL_REGISTERS;
 
 pub mod poseidon_gl;
+pub mod sha256;
+
+/// Native implementation of an instruction the executor does not know about,
+/// e.g. one of a user-defined co-processor: Maps the values of the
+/// instruction's inputs to the values of its outputs.
+pub type InstructionHandler = fn(&[u32]) -> Vec<u32>;
+
+/// The native implementations of instructions the executor does not know about, by name.
+pub type NativeInstructions = BTreeMap<String, InstructionHandler>;
 
 /// Initial value of the PC.
 ///
@@ -134,6 +143,18 @@ impl<F: FieldElement> Display for Elem<F> {
 
 pub type MemoryState = HashMap<u32, u32>;
 
+/// Changes the value of a bootloader input from a given row of the execution on,
+/// e.g. to provide the index of the current chunk when simulating all chunks of
+/// an execution at once.
+#[derive(Clone, Copy, Debug)]
+pub struct BootloaderInputUpdate<F: FieldElement> {
+    /// The first row at which the input has the new value.
+    pub row: usize,
+    /// The index of the input.
+    pub index: usize,
+    pub value: Elem<F>,
+}
+
 #[derive(Debug)]
 pub enum MemOperationKind {
     Read,
@@ -172,6 +193,12 @@ pub struct ExecutionTrace<F: FieldElement> {
 
     /// The length of the trace, after applying the reg_writes.
     pub len: usize,
+
+    /// The values the guest committed to its output, in the order they were committed.
+    pub output: Vec<u32>,
+
+    /// The peak heap usage in bytes, as last reported by the guest.
+    pub heap_usage: Option<u32>,
 }
 
 impl<F: FieldElement> ExecutionTrace<F> {
@@ -225,6 +252,7 @@ impl<'a, F: FieldElement> TraceReplay<'a, F> {
 mod builder {
     use std::{cmp, collections::HashMap};
 
+    use itertools::Itertools;
     use powdr_ast::asm_analysis::{Machine, RegisterTy};
     use powdr_number::FieldElement;
 
@@ -325,6 +353,8 @@ mod builder {
                     reg_writes,
                     mem_ops: Vec::new(),
                     len: PC_INITIAL_VAL + 1,
+                    output: Vec::new(),
+                    heap_usage: None,
                 },
                 next_statement_line: 1,
                 batch_to_line_map,
@@ -351,6 +381,26 @@ mod builder {
             self.get_reg_idx(self.trace.reg_map[idx])
         }
 
+        /// Returns the row of the execution trace that is currently being executed.
+        pub(crate) fn row(&self) -> usize {
+            self.trace.len
+        }
+
+        /// Returns the current register bank, indexed like [ExecutionTrace::reg_map].
+        pub(crate) fn regs(&self) -> &[Elem<F>] {
+            &self.regs
+        }
+
+        /// Returns the register names, ordered by their index.
+        pub(crate) fn reg_names(&self) -> Vec<&str> {
+            self.trace
+                .reg_map
+                .iter()
+                .sorted_by_key(|(_, idx)| **idx)
+                .map(|(name, _)| name.as_str())
+                .collect()
+        }
+
         /// get current value of register by register index instead of name
         fn get_reg_idx(&self, idx: u16) -> Elem<F> {
             self.regs[idx as usize]
@@ -439,6 +489,18 @@ mod builder {
             *self.mem.get(&addr).unwrap_or(&0)
         }
 
+        pub fn push_output(&mut self, value: u32) {
+            self.trace.output.push(value);
+        }
+
+        pub fn set_heap_usage(&mut self, value: u32) {
+            self.trace.heap_usage = Some(value);
+        }
+
+        pub fn heap_usage(&self) -> Option<u32> {
+            self.trace.heap_usage
+        }
+
         pub fn finish(self) -> (ExecutionTrace<F>, MemoryState) {
             (self.trace, self.mem)
         }
@@ -555,6 +617,10 @@ fn preprocess_main_function<T: FieldElement>(machine: &Machine) -> PreprocessedM
     }
 }
 
+/// The maximum number of bytes of the guest output the executor keeps
+/// to report it when the guest fails.
+const MAX_GUEST_OUTPUT_LEN: usize = 4096;
+
 type Callback<'a, F> = dyn powdr_executor::witgen::QueryCallback<F> + 'a;
 
 struct Executor<'a, 'b, F: FieldElement> {
@@ -562,10 +628,29 @@ struct Executor<'a, 'b, F: FieldElement> {
     label_map: HashMap<&'a str, Elem<F>>,
     inputs: &'b Callback<'b, F>,
     bootloader_inputs: &'b [Elem<F>],
+    /// Updates of the bootloader inputs, sorted by row.
+    bootloader_input_updates: &'b [BootloaderInputUpdate<F>],
+    native_instructions: &'b NativeInstructions,
     _stdout: io::Stdout,
+    /// The end of what the guest printed so far, reported if it reaches a fail instruction.
+    /// At most [MAX_GUEST_OUTPUT_LEN] bytes are kept. The bytes are only decoded when
+    /// reporting them, because the guest prints multi-byte characters byte by byte.
+    guest_output: Vec<u8>,
 }
 
 impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
+    /// Returns the value of the bootloader input `index` in the current row.
+    fn bootloader_input(&self, index: usize) -> Elem<F> {
+        let row = self.proc.row();
+        self.bootloader_input_updates
+            .iter()
+            .take_while(|update| update.row <= row)
+            .filter(|update| update.index == index)
+            .last()
+            .map(|update| update.value)
+            .unwrap_or(self.bootloader_inputs[index])
+    }
+
     fn exec_instruction(&mut self, name: &str, args: &[Expression]) -> Vec<Elem<F>> {
         let args = args
             .iter()
@@ -589,13 +674,13 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             }
             "load_bootloader_input" => {
                 let addr = args[0].bin() as usize;
-                let val = self.bootloader_inputs[addr];
+                let val = self.bootloader_input(addr);
 
                 vec![val]
             }
             "assert_bootloader_input" => {
                 let addr = args[0].bin() as usize;
-                let actual_val = self.bootloader_inputs[addr];
+                let actual_val = self.bootloader_input(addr);
 
                 assert_eq!(args[1], actual_val);
 
@@ -610,7 +695,7 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             }
             "jump_to_bootloader_input" => {
                 let bootloader_input_idx = args[0].bin() as usize;
-                let addr = self.bootloader_inputs[bootloader_input_idx];
+                let addr = self.bootloader_input(bootloader_input_idx);
                 self.proc.set_pc(addr);
 
                 Vec::new()
@@ -688,7 +773,14 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             }
             "fail" => {
                 // TODO: handle it better
-                panic!("reached a fail instruction")
+                if self.guest_output.is_empty() {
+                    panic!("reached a fail instruction")
+                } else {
+                    panic!(
+                        "reached a fail instruction, guest output:\n{}",
+                        String::from_utf8_lossy(&self.guest_output)
+                    )
+                }
             }
             "divremu" => {
                 let y = args[0].u();
@@ -739,8 +831,31 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                 });
                 vec![]
             }
+            "sha256_round" => {
+                assert!(args.is_empty());
+                let reg = |i| self.proc.get_reg(SYSCALL_REGISTERS[i]).u();
+                let state = std::array::from_fn(reg);
+                let result = sha256::round(state, reg(8), reg(9));
+                (0..8).for_each(|i| {
+                    self.proc
+                        .set_reg(SYSCALL_REGISTERS[i], Elem::Binary(result[i] as i64))
+                });
+                vec![]
+            }
+            "sha256_schedule" => {
+                assert!(args.is_empty());
+                let reg = |i| self.proc.get_reg(SYSCALL_REGISTERS[i]).u();
+                let result = sha256::schedule(reg(0), reg(1), reg(2), reg(3));
+                self.proc
+                    .set_reg(SYSCALL_REGISTERS[0], Elem::Binary(result as i64));
+                vec![]
+            }
             instr => {
-                panic!("unknown instruction: {instr}");
+                let Some(handler) = self.native_instructions.get(instr) else {
+                    panic!("unknown instruction: {instr}");
+                };
+                let args = args.iter().map(|a| a.u()).collect::<Vec<_>>();
+                handler(&args).into_iter().map(Elem::from).collect()
             }
         }
     }
@@ -875,6 +990,27 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                     .iter()
                     .map(|arg| self.eval_expression(arg)[0].to_string())
                     .collect::<Vec<_>>();
+                if variant == "PrintChar" {
+                    if let Ok(c) = values[0].parse::<u8>() {
+                        self.guest_output.push(c);
+                        if self.guest_output.len() > MAX_GUEST_OUTPUT_LEN {
+                            let excess = self.guest_output.len() - MAX_GUEST_OUTPUT_LEN;
+                            self.guest_output.drain(..excess);
+                        }
+                    }
+                }
+                if variant == "Output" {
+                    let value = values[0]
+                        .parse::<u32>()
+                        .unwrap_or_else(|e| panic!("Invalid output value: {e}"));
+                    self.proc.push_output(value);
+                }
+                if variant == "HeapUsage" {
+                    let value = values[0]
+                        .parse::<u32>()
+                        .unwrap_or_else(|e| panic!("Invalid heap usage: {e}"));
+                    self.proc.set_heap_usage(value);
+                }
                 let query = format!("{variant}({})", values.join(","));
                 match (self.inputs)(&query).unwrap() {
                     Some(val) => vec![Elem::new_from_fe_as_bin(&val)],
@@ -887,10 +1023,35 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             Expression::IfExpression(_) => panic!(),
             Expression::BlockExpression(_, _) => panic!(),
             Expression::IndexAccess(_) => todo!(),
+            Expression::ArraySlice(_) => todo!(),
         }
     }
 }
 
+/// Optional inputs of [execute_ast] and [execute].
+pub struct ExecutionOptions<'a, F: FieldElement> {
+    /// Updates of the bootloader inputs, sorted by row.
+    pub bootloader_input_updates: &'a [BootloaderInputUpdate<F>],
+    /// The handlers of the instructions the executor does not know about.
+    pub native_instructions: &'a NativeInstructions,
+    /// If set, a human-readable log of the execution is written to it:
+    /// each executed statement together with the PC and the registers it changed.
+    pub trace_log: Option<&'a mut dyn io::Write>,
+}
+
+static NO_NATIVE_INSTRUCTIONS: NativeInstructions = BTreeMap::new();
+
+impl<F: FieldElement> Default for ExecutionOptions<'_, F> {
+    fn default() -> Self {
+        Self {
+            bootloader_input_updates: &[],
+            native_instructions: &NO_NATIVE_INSTRUCTIONS,
+            trace_log: None,
+        }
+    }
+}
+
+/// Executes the main machine of `program`, see [ExecutionOptions] for the optional inputs.
 pub fn execute_ast<T: FieldElement>(
     program: &AnalysisASMFile,
     initial_memory: MemoryState,
@@ -898,7 +1059,13 @@ pub fn execute_ast<T: FieldElement>(
     bootloader_inputs: &[Elem<T>],
     max_steps_to_execute: usize,
     mode: ExecMode,
+    options: ExecutionOptions<T>,
 ) -> (ExecutionTrace<T>, MemoryState) {
+    let ExecutionOptions {
+        bootloader_input_updates,
+        native_instructions,
+        mut trace_log,
+    } = options;
     let main_machine = get_main_machine(program);
     let PreprocessedMain {
         statements,
@@ -923,15 +1090,30 @@ pub fn execute_ast<T: FieldElement>(
         label_map,
         inputs,
         bootloader_inputs,
+        bootloader_input_updates,
+        native_instructions,
         _stdout: io::stdout(),
+        guest_output: Vec::new(),
     };
 
+    // The register names are only needed for the trace log, in the order of `regs()`.
+    let reg_names = trace_log.as_ref().map(|_| {
+        e.proc
+            .reg_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    });
+
     let mut curr_pc = 0u32;
     loop {
         let stm = statements[curr_pc as usize];
 
         log::trace!("l {curr_pc}: {stm}",);
 
+        let regs_before = trace_log.as_ref().map(|_| e.proc.regs().to_vec());
+        let pc = e.proc.get_pc();
+
         match stm {
             FunctionStatement::Assignment(a) => {
                 let results = e.eval_expression(a.rhs.as_ref());
@@ -961,15 +1143,46 @@ pub fn execute_ast<T: FieldElement>(
             }
         };
 
+        if let (Some(trace_log), Some(reg_names), Some(regs_before)) =
+            (trace_log.as_mut(), &reg_names, regs_before)
+        {
+            if !matches!(stm, FunctionStatement::DebugDirective(_)) {
+                write_trace_log_entry(trace_log, pc, stm, reg_names, &regs_before, e.proc.regs())
+                    .expect("Failed to write the execution trace log");
+            }
+        }
+
         curr_pc = match e.proc.advance() {
             Some(pc) => pc,
             None => break,
         };
     }
 
+    if let Some(usage) = e.proc.heap_usage() {
+        log::info!("Peak heap usage reported by the guest: {usage} bytes");
+    }
+
     e.proc.finish()
 }
 
+/// Writes an executed statement and the registers it changed to the execution trace log.
+fn write_trace_log_entry<F: FieldElement>(
+    trace_log: &mut dyn io::Write,
+    pc: Elem<F>,
+    stm: &FunctionStatement,
+    reg_names: &[String],
+    regs_before: &[Elem<F>],
+    regs_after: &[Elem<F>],
+) -> io::Result<()> {
+    writeln!(trace_log, "{pc}: {stm}")?;
+    for ((name, before), after) in reg_names.iter().zip(regs_before).zip(regs_after) {
+        if before != after {
+            writeln!(trace_log, "    {name}: {before} -> {after}")?;
+        }
+    }
+    Ok(())
+}
+
 pub enum ExecMode {
     Fast,
     Trace,
@@ -985,6 +1198,7 @@ pub fn execute<F: FieldElement>(
     inputs: &Callback<F>,
     bootloader_inputs: &[Elem<F>],
     mode: ExecMode,
+    options: ExecutionOptions<F>,
 ) -> (ExecutionTrace<F>, MemoryState) {
     log::info!("Parsing...");
     let parsed = powdr_parser::parse_asm(None, asm_source).unwrap();
@@ -1001,5 +1215,6 @@ pub fn execute<F: FieldElement>(
         bootloader_inputs,
         usize::MAX,
         mode,
+        options,
     )
 }
@@ -0,0 +1,81 @@
+/// One round of the SHA-256 compression function: Takes the working variables
+/// `a, ..., h`, the word `w` of the message schedule and the round constant `k`.
+/// It's equivalent to std::hash::sha256::Sha256Round from the Powdr standard library.
+pub fn round(state: [u32; 8], w: u32, k: u32) -> [u32; 8] {
+    let [a, b, c, d, e, f, g, h] = state;
+    let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+    let ch = (e & f) ^ (!e & g);
+    let temp1 = h
+        .wrapping_add(s1)
+        .wrapping_add(ch)
+        .wrapping_add(k)
+        .wrapping_add(w);
+    let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+    let maj = (a & b) ^ (a & c) ^ (b & c);
+    let temp2 = s0.wrapping_add(maj);
+    [
+        temp1.wrapping_add(temp2),
+        a,
+        b,
+        c,
+        d.wrapping_add(temp1),
+        e,
+        f,
+        g,
+    ]
+}
+
+/// Computes the word `w[i]` of the SHA-256 message schedule from
+/// `w[i - 2]`, `w[i - 7]`, `w[i - 15]` and `w[i - 16]`.
+/// It's equivalent to std::hash::sha256::Sha256Schedule from the Powdr standard library.
+pub fn schedule(w2: u32, w7: u32, w15: u32, w16: u32) -> u32 {
+    let s0 = w15.rotate_right(7) ^ w15.rotate_right(18) ^ (w15 >> 3);
+    let s1 = w2.rotate_right(17) ^ w2.rotate_right(19) ^ (w2 >> 10);
+    s1.wrapping_add(w7).wrapping_add(s0).wrapping_add(w16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+
+    #[test]
+    fn test_sha256_abc() {
+        // The single padded block of "abc".
+        let mut w = [0u32; 64];
+        w[0] = 0x61626380;
+        w[15] = 0x18;
+        for i in 16..64 {
+            w[i] = schedule(w[i - 2], w[i - 7], w[i - 15], w[i - 16]);
+        }
+
+        let initial = [
+            0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+            0x5be0cd19,
+        ];
+        let state = (0..64).fold(initial, |state, i| round(state, w[i], K[i]));
+        let digest: Vec<u32> = state
+            .iter()
+            .zip(initial)
+            .map(|(s, i)| s.wrapping_add(i))
+            .collect();
+        assert_eq!(
+            digest,
+            [
+                0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+                0xf20015ad
+            ]
+        );
+    }
+}
@@ -1,13 +1,17 @@
 //! A very simple global allocator.
 //!
-//! Allocates on a global array and never deallocates.
+//! Allocates on a global array and never deallocates, so the peak heap usage
+//! is the number of bytes allocated so far.
 
 use core::{
     alloc::{GlobalAlloc, Layout},
+    arch::asm,
     cell::Cell,
     ptr::{self, addr_of},
 };
 
+use powdr_riscv_syscalls::Syscall;
+
 // Force C representation so that the large buffer is at the end.
 // This might avoid access to memory with large gaps.
 #[repr(C)]
@@ -23,6 +27,11 @@ impl<const SIZE: usize> FixedMemoryAllocator<SIZE> {
             next_available: Cell::new(0),
         }
     }
+
+    /// The number of bytes allocated so far, including alignment padding.
+    fn used(&self) -> usize {
+        self.next_available.get()
+    }
 }
 
 unsafe impl<const SIZE: usize> GlobalAlloc for FixedMemoryAllocator<SIZE> {
@@ -62,6 +71,19 @@ unsafe impl<const SIZE: usize> GlobalAlloc for FixedMemoryAllocator<SIZE> {
 #[global_allocator]
 static mut GLOBAL: FixedMemoryAllocator<{ 1024 * 1024 * 1024 }> = FixedMemoryAllocator::new();
 
+/// Returns the peak number of bytes allocated on the heap, including alignment padding.
+pub fn peak_heap_usage() -> usize {
+    unsafe { (*addr_of!(GLOBAL)).used() }
+}
+
+/// Reports the peak heap usage to the host.
+pub fn report_heap_usage() {
+    let usage = peak_heap_usage() as u32;
+    unsafe {
+        asm!("ecall", in("a0") usage, in("t0") u32::from(Syscall::HeapUsage));
+    }
+}
+
 #[alloc_error_handler]
 fn alloc_error(layout: Layout) -> ! {
     panic!(
@@ -0,0 +1,49 @@
+use core::arch::asm;
+
+/// The code the guest aborts with when a `powdr_assert!` or `powdr_assert_eq!` fails.
+pub const ASSERTION_FAILED_CODE: u32 = 101;
+
+/// Prints the code and stops execution. Unlike a panic, this does not
+/// go through the panic handler, so the printed output is the last thing
+/// the guest produced.
+pub fn abort(code: u32) -> ! {
+    crate::print!("Guest aborted with code {code}\n");
+    unsafe {
+        asm!("unimp");
+    }
+    loop {}
+}
+
+/// Like `assert!`, but prints the failed condition (or the given message)
+/// and aborts with [ASSERTION_FAILED_CODE].
+#[macro_export]
+macro_rules! powdr_assert {
+    ($cond:expr $(,)?) => {
+        $crate::powdr_assert!($cond, "{}", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {{
+        if !$cond {
+            $crate::print!("assertion failed: {}\n", format_args!($($arg)+));
+            $crate::assert::abort($crate::assert::ASSERTION_FAILED_CODE);
+        }
+    }};
+}
+
+/// Like `assert_eq!`, but prints both values and aborts with [ASSERTION_FAILED_CODE].
+#[macro_export]
+macro_rules! powdr_assert_eq {
+    ($left:expr, $right:expr $(,)?) => {{
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::print!(
+                        "assertion `left == right` failed\n  left: {:?}\n right: {:?}\n",
+                        left,
+                        right
+                    );
+                    $crate::assert::abort($crate::assert::ASSERTION_FAILED_CODE);
+                }
+            }
+        }
+    }};
+}
@@ -0,0 +1,15 @@
+use core::arch::asm;
+
+use powdr_riscv_syscalls::Syscall;
+
+/// Returns the index of the continuation chunk that is currently being executed.
+///
+/// The value is provided by the bootloader, so it is always 0 when the program is
+/// compiled without it.
+pub fn chunk_index() -> u32 {
+    let mut value: u32;
+    unsafe {
+        asm!("ecall", lateout("a0") value, in("t0") u32::from(Syscall::ChunkIndex));
+    }
+    value
+}
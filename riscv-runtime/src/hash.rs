@@ -27,3 +27,46 @@ pub fn poseidon_gl_unsafe(mut data: [u64; 12]) -> [u64; 4] {
 
     [data[0], data[1], data[2], data[3]]
 }
+
+/// Calls the low level SHA-256 PIL machines to apply the compression function
+/// to `state` with the message block `block`, both given as big-endian words.
+pub fn sha256_compress(state: &mut [u32; 8], block: &[u32; 16]) {
+    unsafe {
+        asm!("ecall", in("a0") state as *mut [u32; 8], in("a1") block as *const [u32; 16], in("t0") u32::from(Syscall::Sha256));
+    }
+}
+
+/// Computes the SHA-256 digest of `data`, using [sha256_compress] for each block.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let to_block = |bytes: &[u8]| -> [u32; 16] {
+        core::array::from_fn(|i| u32::from_be_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()))
+    };
+
+    let mut chunks = data.chunks_exact(64);
+    for chunk in &mut chunks {
+        sha256_compress(&mut state, &to_block(chunk));
+    }
+
+    // The remaining bytes, followed by a one bit, zeros and the length in bits,
+    // fill either one or two more blocks.
+    let rest = chunks.remainder();
+    let mut tail = [0u8; 128];
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let tail_len = if rest.len() < 56 { 64 } else { 128 };
+    tail[tail_len - 8..tail_len].copy_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in tail[..tail_len].chunks_exact(64) {
+        sha256_compress(&mut state, &to_block(chunk));
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
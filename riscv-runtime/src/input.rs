@@ -1,9 +1,12 @@
 use core::arch::asm;
+use core::ptr::addr_of_mut;
 
 extern crate alloc;
 
-use powdr_riscv_syscalls::Syscall;
+use powdr_riscv_syscalls::{Syscall, NAMED_INPUTS_CHANNEL};
 
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -43,3 +46,23 @@ pub fn get_data_serde<T: DeserializeOwned>(channel: u32) -> T {
 
     serde_cbor::from_slice(&data.as_slice()).unwrap()
 }
+
+/// Returns the named input `key` provided by the host (see `Pipeline::with_named_inputs`),
+/// or `None` if the host did not provide a value for it.
+/// The named inputs are read from the host once, on the first call.
+pub fn get_named(key: &str) -> Option<u32> {
+    static mut NAMED_INPUTS: Option<BTreeMap<String, u32>> = None;
+
+    // The guest is single-threaded, so there are no concurrent accesses.
+    let inputs = unsafe { (*addr_of_mut!(NAMED_INPUTS)).get_or_insert_with(read_named_inputs) };
+    inputs.get(key).copied()
+}
+
+/// Reads the named inputs from the host, which provides an empty channel if there are none.
+fn read_named_inputs() -> BTreeMap<String, u32> {
+    if get_data_len(NAMED_INPUTS_CHANNEL) == 0 {
+        BTreeMap::new()
+    } else {
+        get_data_serde(NAMED_INPUTS_CHANNEL)
+    }
+}
@@ -12,9 +12,14 @@ use core::panic::PanicInfo;
 use crate::fmt::print_str;
 
 mod allocator;
+pub mod assert;
+pub mod continuations;
 pub mod fmt;
 pub mod hash;
 pub mod input;
+pub mod output;
+
+pub use allocator::{peak_heap_usage, report_heap_usage};
 
 #[panic_handler]
 unsafe fn panic(panic: &PanicInfo<'_>) -> ! {
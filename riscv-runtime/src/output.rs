@@ -0,0 +1,18 @@
+use core::arch::asm;
+
+use powdr_riscv_syscalls::Syscall;
+
+/// Commits a value to the output of the program.
+/// The host receives all committed values in the order they were committed.
+pub fn commit(value: u32) {
+    unsafe {
+        asm!("ecall", in("a0") value, in("t0") u32::from(Syscall::Output));
+    }
+}
+
+/// Commits several values to the output of the program, in order.
+pub fn commit_slice(values: &[u32]) {
+    for value in values {
+        commit(*value);
+    }
+}
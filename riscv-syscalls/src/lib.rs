@@ -13,6 +13,10 @@ pub static SYSCALL_REGISTERS: [&str; 14] = [
     "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x6", "x7", "x28", "x29", "x30", "x31",
 ];
 
+/// The data channel the host uses to provide named inputs to the guest,
+/// as a serialized `BTreeMap<String, u32>`.
+pub const NAMED_INPUTS_CHANNEL: u32 = u32::MAX;
+
 // NB. Must be kept in sync with conversion trait implementations
 /// Powdr RISCV syscalls
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -22,6 +26,10 @@ pub enum Syscall {
     DataIdentifier = 1,
     PrintChar = 2,
     PoseidonGL = 3,
+    ChunkIndex = 4,
+    Output = 5,
+    Sha256 = 6,
+    HeapUsage = 7,
 }
 
 impl core::fmt::Display for Syscall {
@@ -31,6 +39,10 @@ impl core::fmt::Display for Syscall {
             Syscall::DataIdentifier => write!(f, "data_identifier"),
             Syscall::PrintChar => write!(f, "print_char"),
             Syscall::PoseidonGL => write!(f, "poseidon_gl"),
+            Syscall::ChunkIndex => write!(f, "chunk_index"),
+            Syscall::Output => write!(f, "output"),
+            Syscall::Sha256 => write!(f, "sha256"),
+            Syscall::HeapUsage => write!(f, "heap_usage"),
         }
     }
 }
@@ -44,6 +56,10 @@ impl core::str::FromStr for Syscall {
             "data_identifier" => Ok(Syscall::DataIdentifier),
             "print_char" => Ok(Syscall::PrintChar),
             "poseidon_gl" => Ok(Syscall::PoseidonGL),
+            "chunk_index" => Ok(Syscall::ChunkIndex),
+            "output" => Ok(Syscall::Output),
+            "sha256" => Ok(Syscall::Sha256),
+            "heap_usage" => Ok(Syscall::HeapUsage),
             _ => Err(()),
         }
     }
@@ -64,6 +80,10 @@ impl TryFrom<u32> for Syscall {
             1 => Ok(Syscall::DataIdentifier),
             2 => Ok(Syscall::PrintChar),
             3 => Ok(Syscall::PoseidonGL),
+            4 => Ok(Syscall::ChunkIndex),
+            5 => Ok(Syscall::Output),
+            6 => Ok(Syscall::Sha256),
+            7 => Ok(Syscall::HeapUsage),
             _ => Err(()),
         }
     }
@@ -23,6 +23,10 @@ use crate::parser::RiscParser;
 use crate::runtime::Runtime;
 use crate::{Argument, Expression, Statement};
 
+/// The base 2 logarithm of the minimum degree of the main machine, which is
+/// the lower bound for the Binary and Shift machines.
+pub const MIN_DEGREE_LOG: u32 = 18;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Register {
     value: u8,
@@ -103,11 +107,14 @@ impl Architecture for RiscvArchitecture {
 }
 
 /// Compiles riscv assembly to a powdr assembly file. Adds required library routines.
+/// Fails if the co-processors of the runtime conflict, see [Runtime::build].
 pub fn compile<T: FieldElement>(
     mut assemblies: BTreeMap<String, String>,
     runtime: &Runtime,
     with_bootloader: bool,
-) -> String {
+) -> Result<String, String> {
+    runtime.check_conflicts()?;
+
     // stack grows towards zero
     let stack_start = 0x10000;
     // data grows away from zero
@@ -232,30 +239,30 @@ pub fn compile<T: FieldElement>(
                 .to_string(),
         ]));
     }
-    program.extend(runtime.ecall_handler());
+    program.extend(runtime.ecall_handler(with_bootloader));
 
     // The program ROM needs to fit the degree, so we use the next power of 2.
     let degree = program.len().ilog2() + 1;
-    let degree = std::cmp::max(degree, 18);
+    let degree = std::cmp::max(degree, MIN_DEGREE_LOG);
     log::info!("Inferred degree 2^{degree}");
 
     // In practice, these are the lengths of single proofs that we want to support.
     // Reasoning:
-    // - 18: is the lower bound for the Binary and Shift machines.
+    // - 18: is the lower bound for the Binary and Shift machines, see MIN_DEGREE_LOG.
     // - 20: revm's ROM does not fit in 2^19.
     // - >20: may be needed in the future.
     // This is an assert for now, but could be a compiler warning or error.
     // TODO note that if the degree is higher than 18 we might need mux machines for Binary and
     // Shift.
-    assert!((18..=20).contains(&degree));
+    assert!((MIN_DEGREE_LOG..=20).contains(&degree));
     let degree = 1 << degree;
 
-    riscv_machine(
+    Ok(riscv_machine(
         runtime,
         &preamble::<T>(degree, runtime, with_bootloader),
         initial_mem,
         program,
-    )
+    ))
 }
 
 /// Replace certain patterns of references to code labels by
@@ -411,6 +418,7 @@ fn riscv_machine(
     format!(
         r#"
 {}
+{}
 machine Main {{
 {}
 
@@ -425,6 +433,7 @@ let initial_memory: (fe, fe)[] = [
     }}
 }}    
 "#,
+        runtime.submachines_definitions(),
         runtime.submachines_import(),
         runtime.submachines_declare(),
         preamble,
@@ -1,12 +1,19 @@
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{BufWriter, Write},
+};
 
 use powdr_ast::{
     asm_analysis::{AnalysisASMFile, RegisterTy},
     parsed::{asm::parse_absolute_path, Expression, PilStatement},
 };
 use powdr_number::FieldElement;
-use powdr_pipeline::Pipeline;
-use powdr_riscv_executor::{get_main_machine, Elem, ExecutionTrace, MemoryState};
+use powdr_pipeline::{Pipeline, Proof};
+use powdr_riscv_executor::{
+    get_main_machine, BootloaderInputUpdate, Elem, ExecutionOptions, ExecutionTrace, MemoryState,
+    NativeInstructions,
+};
 
 pub mod bootloader;
 mod memory_merkle_tree;
@@ -14,12 +21,90 @@ mod memory_merkle_tree;
 use bootloader::{default_input, PAGE_SIZE_BYTES_LOG, PC_INDEX, REGISTER_NAMES};
 use memory_merkle_tree::MerkleTree;
 
+use crate::compiler::MIN_DEGREE_LOG;
 use crate::continuations::bootloader::{
     default_register_values, shutdown_routine_upper_bound, BOOTLOADER_INPUTS_PER_PAGE,
-    BOOTLOADER_SPECIFIC_INSTRUCTION_NAMES, DEFAULT_PC, MEMORY_HASH_START_INDEX, PAGE_INPUTS_OFFSET,
-    WORDS_PER_PAGE,
+    BOOTLOADER_SPECIFIC_INSTRUCTION_NAMES, CHUNK_INDEX_INDEX, DEFAULT_PC, MEMORY_HASH_START_INDEX,
+    NUM_PAGES_INDEX, PAGE_INPUTS_OFFSET, WORDS_PER_PAGE,
 };
 
+/// Options controlling how an execution is split into chunks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContinuationsOptions {
+    /// The number of rows of each chunk, including the bootloader and the shutdown routine.
+    /// The chunks are proven with this degree instead of the degree of the main machine,
+    /// so it has to be supported by the backend.
+    /// Defaults to the degree of the main machine, which is also its upper bound.
+    /// The lower bound is `1 << MIN_DEGREE_LOG`, and the ROM has to fit into a chunk.
+    pub chunk_size: Option<usize>,
+    /// The maximum number of chunks the execution may be split into.
+    pub max_chunks: Option<usize>,
+}
+
+/// The bootloader inputs of all chunks of an execution, as computed by
+/// [rust_continuations_dry_run].
+#[derive(Clone, Debug, Default)]
+pub struct BootloaderInputs<F> {
+    pub chunks: Vec<ChunkBootloaderInputs<F>>,
+}
+
+impl<F: FieldElement> BootloaderInputs<F> {
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The memory root hash at the beginning of the execution.
+    pub fn initial_memory_root(&self) -> Option<&[F; 4]> {
+        self.chunks.first().map(|chunk| &chunk.initial_memory_root)
+    }
+}
+
+/// The bootloader inputs of a single chunk.
+#[derive(Clone, Debug)]
+pub struct ChunkBootloaderInputs<F> {
+    /// The index of the chunk.
+    pub chunk_index: u64,
+    /// The register values at the beginning of the chunk, in the order of [REGISTER_NAMES].
+    pub initial_registers: Vec<F>,
+    /// The register values at the end of the chunk, in the order of [REGISTER_NAMES].
+    pub final_registers: Vec<F>,
+    /// The memory root hash at the beginning of the chunk.
+    pub initial_memory_root: [F; 4],
+    /// The memory root hash at the end of the chunk.
+    pub final_memory_root: [F; 4],
+    /// The indices of the memory pages accessed in the chunk.
+    pub accessed_pages: Vec<u64>,
+    /// The number of rows after which the prover should jump to the shutdown routine.
+    pub start_of_shutdown_routine: u64,
+    /// The inputs in the layout expected by the bootloader.
+    raw: Vec<F>,
+}
+
+impl<F: FieldElement> ChunkBootloaderInputs<F> {
+    fn new(raw: Vec<F>, start_of_shutdown_routine: u64) -> Self {
+        let registers = REGISTER_NAMES.len();
+        let hash = |start: usize| -> [F; 4] { raw[start..start + 4].try_into().unwrap() };
+        let num_pages = raw[NUM_PAGES_INDEX].to_degree() as usize;
+        Self {
+            chunk_index: raw[CHUNK_INDEX_INDEX].to_degree(),
+            initial_registers: raw[..registers].to_vec(),
+            final_registers: raw[registers..2 * registers].to_vec(),
+            initial_memory_root: hash(MEMORY_HASH_START_INDEX),
+            final_memory_root: hash(MEMORY_HASH_START_INDEX + 4),
+            accessed_pages: (0..num_pages)
+                .map(|i| raw[PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * i].to_degree())
+                .collect(),
+            start_of_shutdown_routine,
+            raw,
+        }
+    }
+
+    /// The inputs in the layout expected by the bootloader.
+    pub fn raw(&self) -> &[F] {
+        &self.raw
+    }
+}
+
 fn transposed_trace<F: FieldElement>(trace: &ExecutionTrace<F>) -> HashMap<String, Vec<Elem<F>>> {
     let mut reg_values: HashMap<&str, Vec<Elem<F>>> = HashMap::with_capacity(trace.reg_map.len());
 
@@ -39,6 +124,22 @@ fn transposed_trace<F: FieldElement>(trace: &ExecutionTrace<F>) -> HashMap<Strin
         .collect()
 }
 
+/// Returns the offset and the name of the first register in which the chunk trace,
+/// starting at row `start`, differs from the full trace, starting at row `full_start`.
+fn first_difference<F: FieldElement>(
+    chunk_trace: &HashMap<String, Vec<Elem<F>>>,
+    start: usize,
+    full_trace: &HashMap<String, Vec<Elem<F>>>,
+    full_start: usize,
+) -> Option<(usize, &'static str)> {
+    (0..(chunk_trace["main.pc"].len() - start)).find_map(|i| {
+        REGISTER_NAMES
+            .iter()
+            .find(|&&reg| chunk_trace[reg][i + start] != full_trace[reg][i + full_start])
+            .map(|&reg| (i, reg))
+    })
+}
+
 fn render_hash<F: FieldElement>(hash: &[Elem<F>]) -> String {
     hash.iter()
         .map(|&f| format!("{:016x}", f.fe().to_arbitrary_integer()))
@@ -52,54 +153,141 @@ fn render_hash<F: FieldElement>(hash: &[Elem<F>]) -> String {
 /// - `pipeline`: The pipeline that should be the starting point for all the chunks.
 /// - `pipeline_callback`: A function that will be called for each chunk. It will be passed the `pipeline`,
 ///   but with the `PilWithEvaluatedFixedCols` stage already advanced to and all chunk-specific parameters set.
-/// - `bootloader_inputs`: The inputs to the bootloader and the index of the row at which the shutdown routine
-///   is supposed to execute, for each chunk, as returned by `rust_continuations_dry_run`.
+///   All chunks have the degree `options.chunk_size` (or the degree of `pipeline` if it is not set),
+///   so the fixed columns are computed only once and shared by the pipelines of all chunks.
+/// - `bootloader_inputs`: The inputs to the bootloader for each chunk, as returned by `rust_continuations_dry_run`.
+/// - `options`: The options `bootloader_inputs` were computed with.
+///
+/// Fails if `bootloader_inputs` do not match `options`.
 pub fn rust_continuations<F: FieldElement, PipelineCallback, E>(
-    mut pipeline: Pipeline<F>,
+    pipeline: Pipeline<F>,
     pipeline_callback: PipelineCallback,
-    bootloader_inputs: Vec<(Vec<F>, u64)>,
+    bootloader_inputs: BootloaderInputs<F>,
+    options: ContinuationsOptions,
 ) -> Result<(), E>
 where
     PipelineCallback: Fn(Pipeline<F>) -> Result<(), E>,
+    E: From<Vec<String>>,
 {
-    let num_chunks = bootloader_inputs.len();
-
+    let num_chunks = bootloader_inputs.chunk_count();
     log::info!("Computing fixed columns...");
-    pipeline.compute_fixed_cols().unwrap();
-
-    // we can assume optimized_pil has been computed
-    let length = pipeline.compute_optimized_pil().unwrap().degree();
+    let (pipeline, length) = chunks_pipeline(pipeline, &bootloader_inputs, options)?;
 
     bootloader_inputs
-        .into_iter()
+        .chunks
+        .iter()
         .enumerate()
-        .map(
-            |(i, (bootloader_inputs, start_of_shutdown_routine))| -> Result<(), E> {
-                log::info!("\nRunning chunk {} / {}...", i + 1, num_chunks);
-                let pipeline = pipeline.clone();
-                let name = format!("{}_chunk_{}", pipeline.name(), i);
-                let pipeline = pipeline.with_name(name);
-                // The `jump_to_shutdown_routine` column indicates when the execution should jump to the shutdown routine.
-                // In that row, the normal PC update is ignored and the PC is set to the address of the shutdown routine.
-                // In other words, it should be a one-hot encoding of `start_of_shutdown_routine`.
-                let jump_to_shutdown_routine = (0..length)
-                    .map(|i| (i == start_of_shutdown_routine - 1).into())
-                    .collect();
-                let pipeline = pipeline.add_external_witness_values(vec![
-                    ("main.bootloader_input_value".to_string(), bootloader_inputs),
-                    (
-                        "main.jump_to_shutdown_routine".to_string(),
-                        jump_to_shutdown_routine,
-                    ),
-                ]);
-                pipeline_callback(pipeline)?;
-                Ok(())
-            },
-        )
+        .map(|(i, chunk)| -> Result<(), E> {
+            log::info!("\nRunning chunk {} / {}...", i + 1, num_chunks);
+            pipeline_callback(chunk_pipeline(&pipeline, length, i, chunk))?;
+            Ok(())
+        })
         .collect::<Result<Vec<_>, E>>()?;
     Ok(())
 }
 
+/// Proves a single chunk of the execution in isolation, e.g. to reproduce the failure
+/// of one chunk without proving the chunks before it.
+///
+/// # Arguments
+/// - `pipeline`: The pipeline that should be the starting point for the chunk, with a backend set.
+/// - `chunk_index`: The index of the chunk to prove.
+/// - `bootloader_inputs`: The inputs to the bootloader for each chunk, as returned by `rust_continuations_dry_run`.
+/// - `options`: The options `bootloader_inputs` were computed with.
+pub fn prove_chunk<F: FieldElement>(
+    pipeline: Pipeline<F>,
+    chunk_index: usize,
+    bootloader_inputs: &BootloaderInputs<F>,
+    options: ContinuationsOptions,
+) -> Result<Proof, Vec<String>> {
+    let chunk = bootloader_inputs.chunks.get(chunk_index).ok_or_else(|| {
+        vec![format!(
+            "Chunk {chunk_index} does not exist, the execution has {} chunks.",
+            bootloader_inputs.chunk_count()
+        )]
+    })?;
+
+    let (pipeline, length) = chunks_pipeline(pipeline, bootloader_inputs, options)?;
+    let mut pipeline = chunk_pipeline(&pipeline, length, chunk_index, chunk);
+    pipeline.compute_proof().cloned()
+}
+
+/// Returns the pipeline all chunk pipelines are derived from, with the degree of the chunks
+/// and the fixed columns computed, together with that degree.
+/// Fails if `bootloader_inputs` do not match `options`.
+fn chunks_pipeline<F: FieldElement>(
+    mut pipeline: Pipeline<F>,
+    bootloader_inputs: &BootloaderInputs<F>,
+    options: ContinuationsOptions,
+) -> Result<(Pipeline<F>, u64), Vec<String>> {
+    let num_chunks = bootloader_inputs.chunk_count();
+    if let Some(max_chunks) = options.max_chunks {
+        if num_chunks > max_chunks {
+            return Err(vec![format!(
+                "The bootloader inputs have {num_chunks} chunks, but at most {max_chunks} are allowed."
+            )]);
+        }
+    }
+
+    if let Some(chunk_size) = options.chunk_size {
+        check_chunk_size(chunk_size).map_err(|e| vec![e])?;
+        pipeline = pipeline.with_degree(chunk_size as u64)?;
+    }
+    pipeline.compute_fixed_cols()?;
+    let length = pipeline.compute_optimized_pil()?.degree();
+
+    if bootloader_inputs
+        .chunks
+        .iter()
+        .any(|chunk| chunk.start_of_shutdown_routine > length)
+    {
+        return Err(vec![format!(
+            "The bootloader inputs were not computed with a chunk size of {length}."
+        )]);
+    }
+    Ok((pipeline, length))
+}
+
+/// Checks that the chunk size is not below the lower bound for the degree of the
+/// machines, see [MIN_DEGREE_LOG]. Whether the ROM fits is checked when the
+/// degree of the pipeline is changed, see [Pipeline::with_degree].
+fn check_chunk_size(chunk_size: usize) -> Result<(), String> {
+    let min_degree = 1 << MIN_DEGREE_LOG;
+    if chunk_size < min_degree {
+        return Err(format!(
+            "The chunk size {chunk_size} is below the minimum degree of the machines ({min_degree})."
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the pipeline for the given chunk: `pipeline`, renamed and with the
+/// chunk-specific external witness values set.
+fn chunk_pipeline<F: FieldElement>(
+    pipeline: &Pipeline<F>,
+    length: u64,
+    chunk_index: usize,
+    chunk: &ChunkBootloaderInputs<F>,
+) -> Pipeline<F> {
+    // The execution trace log is written by the dry run, the chunks only run witgen.
+    let pipeline = pipeline.clone().without_execution_trace_log();
+    let name = format!("{}_chunk_{}", pipeline.name(), chunk_index);
+    let pipeline = pipeline.with_name(name);
+    // The `jump_to_shutdown_routine` column indicates when the execution should jump to the shutdown routine.
+    // In that row, the normal PC update is ignored and the PC is set to the address of the shutdown routine.
+    // In other words, it should be a one-hot encoding of `start_of_shutdown_routine`.
+    let jump_to_shutdown_routine = (0..length)
+        .map(|i| (i == chunk.start_of_shutdown_routine - 1).into())
+        .collect();
+    pipeline.add_external_witness_values(vec![
+        ("main.bootloader_input_value".to_string(), chunk.raw.clone()),
+        (
+            "main.jump_to_shutdown_routine".to_string(),
+            jump_to_shutdown_routine,
+        ),
+    ])
+}
+
 fn sanity_check(program: &AnalysisASMFile) {
     let main_machine = program.items[&parse_absolute_path("::Main")]
         .try_to_machine()
@@ -171,11 +359,19 @@ pub fn load_initial_memory(program: &AnalysisASMFile) -> MemoryState {
 /// Runs the entire execution using the RISC-V executor. For each chunk, it collects:
 /// - The inputs to the bootloader, needed to restore the correct state.
 /// - The number of rows after which the prover should jump to the shutdown routine.
+///
+/// The executor runs the instructions of user-defined co-processors with the handlers in
+/// `native_instructions`, see [crate::Runtime::native_instructions].
+///
+/// Fails if the chunks of `options.chunk_size` rows cannot fit the bootloader and the
+/// shutdown routine, or if the execution needs more than `options.max_chunks` chunks.
 pub fn rust_continuations_dry_run<F: FieldElement>(
     pipeline: &mut Pipeline<F>,
-) -> Vec<(Vec<F>, u64)> {
+    native_instructions: &NativeInstructions,
+    options: ContinuationsOptions,
+) -> Result<BootloaderInputs<F>, String> {
     // All inputs for all chunks.
-    let mut bootloader_inputs_and_num_rows = vec![];
+    let mut chunks = vec![];
 
     // Initial register values for the current chunk.
     let mut register_values = default_register_values();
@@ -196,23 +392,42 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
 
     // TODO: commit to the merkle_tree root in the verifier.
 
-    log::info!("Executing powdr-asm...");
-    let (full_trace, memory_accesses) = {
-        let trace = powdr_riscv_executor::execute_ast::<F>(
+    // The chunk index is a bootloader input, so in the full execution, it is updated
+    // at the rows at which the chunks after the first one start.
+    let mut chunk_index_updates: Vec<BootloaderInputUpdate<F>> = vec![];
+    let execute_full = |chunk_index_updates: &[BootloaderInputUpdate<F>]| {
+        log::info!("Executing powdr-asm...");
+        // Run full trace without any accessed pages. This would actually violate the
+        // constraints, but the executor does the right thing (read zero if the memory
+        // cell has never been accessed). We can't pass the accessed pages here, because
+        // we only know them after the full trace has been generated.
+        let bootloader_inputs = default_input(&[]);
+        let mut trace_log = pipeline
+            .execution_trace_log()
+            .map(|path| BufWriter::new(fs::File::create(path).unwrap()));
+        let (trace, _) = powdr_riscv_executor::execute_ast::<F>(
             &program,
-            initial_memory,
+            initial_memory.clone(),
             pipeline.data_callback().unwrap(),
-            // Run full trace without any accessed pages. This would actually violate the
-            // constraints, but the executor does the right thing (read zero if the memory
-            // cell has never been accessed). We can't pass the accessed pages here, because
-            // we only know them after the full trace has been generated.
-            &default_input(&[]),
+            &bootloader_inputs,
             usize::MAX,
             powdr_riscv_executor::ExecMode::Trace,
-        )
-        .0;
+            ExecutionOptions {
+                bootloader_input_updates: chunk_index_updates,
+                native_instructions,
+                trace_log: trace_log
+                    .as_mut()
+                    .map(|trace_log| trace_log as &mut dyn Write),
+            },
+        );
+        if let Some(mut trace_log) = trace_log {
+            trace_log.flush().unwrap();
+        }
         (transposed_trace::<F>(&trace), trace.mem_ops)
     };
+    let (mut full_trace, mut memory_accesses) = execute_full(&chunk_index_updates);
+    // Whether the chunk index has been updated since the full trace was computed.
+    let mut full_trace_is_outdated = false;
 
     let full_trace_length = full_trace["main.pc"].len();
     log::info!("Total trace length: {}", full_trace_length);
@@ -243,6 +458,19 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
         ),
     };
 
+    let chunk_size = match options.chunk_size {
+        Some(chunk_size) if chunk_size > length => {
+            return Err(format!(
+                "The chunk size {chunk_size} exceeds the degree of the main machine ({length})."
+            ))
+        }
+        Some(chunk_size) => {
+            check_chunk_size(chunk_size)?;
+            chunk_size
+        }
+        None => length,
+    };
+
     loop {
         log::info!("\nRunning chunk {}...", chunk_index);
 
@@ -254,10 +482,10 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
             .unwrap_or_else(|v| v);
 
         for access in &memory_accesses[start_idx..] {
-            // proven_trace + length is an upper bound for the last row index we'll reach in the next chunk.
+            // proven_trace + chunk_size is an upper bound for the last row index we'll reach in the next chunk.
             // In practice, we'll stop earlier, because the bootloader & shutdown routine need to run as well,
             // but we don't know for how long as that depends on the number of pages.
-            if access.row >= proven_trace + length {
+            if access.row >= proven_trace + chunk_size {
                 break;
             }
             accessed_addresses.insert(access.address);
@@ -275,7 +503,13 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
             "Estimating the shutdown routine to use {} rows.",
             shutdown_routine_rows
         );
-        let num_rows = length - shutdown_routine_rows;
+        let num_rows = chunk_size
+            .checked_sub(shutdown_routine_rows)
+            .ok_or_else(|| {
+                format!(
+                    "The chunk size {chunk_size} is too small to fit the shutdown routine ({shutdown_routine_rows} rows)."
+                )
+            })?;
 
         // Build the bootloader inputs for the current chunk.
         // Note that while we do know the accessed pages, we don't yet know the hashes
@@ -288,7 +522,8 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
         // - The updated root hash is equal to the current root hash.
         // After simulating the chunk execution, we'll replace those values with the actual values.
         let mut bootloader_inputs = bootloader::create_input(
-            register_values,
+            register_values.clone(),
+            chunk_index as u32,
             &merkle_tree,
             accessed_pages.iter().cloned(),
         );
@@ -304,9 +539,60 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
                 &bootloader_inputs,
                 num_rows,
                 powdr_riscv_executor::ExecMode::Trace,
+                ExecutionOptions {
+                    native_instructions,
+                    ..Default::default()
+                },
             );
             (transposed_trace(&trace), memory_snapshot_update)
         };
+        log::info!("Chunk trace length: {}", chunk_trace["main.pc"].len());
+        log::info!("Validating chunk...");
+        let (start, _) = chunk_trace["main.pc"]
+            .iter()
+            .enumerate()
+            .find(|(_, &pc)| pc == bootloader_inputs[PC_INDEX])
+            .ok_or_else(|| {
+                format!("The chunk size {chunk_size} is too small to fit the bootloader.")
+            })?;
+        log::info!("Bootloader used {} rows.", start);
+        log::info!(
+            "  => {} / {} ({}%) of rows are used for the actual computation!",
+            chunk_size - start - shutdown_routine_rows,
+            chunk_size,
+            (chunk_size - start - shutdown_routine_rows) * 100 / chunk_size
+        );
+        if let Some((i, reg)) = first_difference(&chunk_trace, start, &full_trace, proven_trace) {
+            if full_trace_is_outdated {
+                // The guest might have read the chunk index, which was not known yet
+                // when the full trace was computed. So we re-compute it and repeat the chunk.
+                log::info!(
+                    "Chunk trace differs from the full trace, re-computing the full trace..."
+                );
+                (full_trace, memory_accesses) = execute_full(&chunk_index_updates);
+                full_trace_is_outdated = false;
+                continue;
+            }
+            let chunk_i = i + start;
+            let full_i = i + proven_trace;
+            log::error!("The Chunk trace differs from the full trace!");
+            log::error!(
+                "Started comparing from row {start} in the chunk to row {proven_trace} in the full trace; the difference is at offset {i}."
+            );
+            log::error!(
+                "The PCs are {} and {}.",
+                chunk_trace["main.pc"][chunk_i],
+                full_trace["main.pc"][full_i]
+            );
+            log::error!(
+                "The first difference is in register {}: {} != {} ",
+                reg,
+                chunk_trace[reg][chunk_i],
+                full_trace[reg][full_i],
+            );
+            panic!();
+        }
+
         let mut memory_updates_by_page =
             merkle_tree.organize_updates_by_page(memory_snapshot_update.into_iter());
         for (i, &page_index) in accessed_pages.iter().enumerate() {
@@ -373,61 +659,87 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
         );
 
         let actual_num_rows = chunk_trace["main.pc"].len();
-        bootloader_inputs_and_num_rows.push((
+        chunks.push(ChunkBootloaderInputs::new(
             bootloader_inputs.iter().map(|e| e.into_fe()).collect(),
             actual_num_rows as u64,
         ));
 
-        log::info!("Chunk trace length: {}", chunk_trace["main.pc"].len());
-        log::info!("Validating chunk...");
-        let (start, _) = chunk_trace["main.pc"]
-            .iter()
-            .enumerate()
-            .find(|(_, &pc)| pc == bootloader_inputs[PC_INDEX])
-            .unwrap();
-        log::info!("Bootloader used {} rows.", start);
-        log::info!(
-            "  => {} / {} ({}%) of rows are used for the actual computation!",
-            length - start - shutdown_routine_rows,
-            length,
-            (length - start - shutdown_routine_rows) * 100 / length
-        );
-        for i in 0..(chunk_trace["main.pc"].len() - start) {
-            for &reg in REGISTER_NAMES.iter() {
-                let chunk_i = i + start;
-                let full_i = i + proven_trace;
-                if chunk_trace[reg][chunk_i] != full_trace[reg][full_i] {
-                    log::error!("The Chunk trace differs from the full trace!");
-                    log::error!(
-                        "Started comparing from row {start} in the chunk to row {proven_trace} in the full trace; the difference is at offset {i}."
-                    );
-                    log::error!(
-                        "The PCs are {} and {}.",
-                        chunk_trace["main.pc"][chunk_i],
-                        full_trace["main.pc"][full_i]
-                    );
-                    log::error!(
-                        "The first difference is in register {}: {} != {} ",
-                        reg,
-                        chunk_trace[reg][chunk_i],
-                        full_trace[reg][full_i],
-                    );
-                    panic!();
-                }
-            }
-        }
-
         if chunk_trace["main.pc"].len() < num_rows {
             log::info!("Done!");
             break;
         }
 
+        if options.max_chunks == Some(chunks.len()) {
+            return Err(format!(
+                "The execution does not fit into {} chunks of {chunk_size} rows.",
+                chunks.len()
+            ));
+        }
+
         // Minus one, because the last row will have to be repeated in the next chunk.
         let new_rows = num_rows - start - 1;
+        if new_rows == 0 {
+            return Err(format!(
+                "The chunk size {chunk_size} leaves no rows for the execution after the bootloader."
+            ));
+        }
         proven_trace += new_rows;
         log::info!("Proved {} rows.", new_rows);
 
         chunk_index += 1;
+        chunk_index_updates.push(BootloaderInputUpdate {
+            row: proven_trace,
+            index: CHUNK_INDEX_INDEX,
+            value: (chunk_index as u32).into(),
+        });
+        full_trace_is_outdated = true;
+    }
+    Ok(BootloaderInputs { chunks })
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+
+    #[test]
+    fn chunks_share_fixed_cols() {
+        let pil = r#"
+namespace main(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    FIRST * x = 0;
+"#;
+        let pipeline = Pipeline::<GoldilocksField>::default()
+            .from_pil_string(pil.to_string())
+            .with_name("chunks".to_string());
+        let raw = default_input::<GoldilocksField>(&[])
+            .iter()
+            .map(|e| e.into_fe())
+            .collect::<Vec<_>>();
+        let bootloader_inputs = BootloaderInputs {
+            chunks: vec![ChunkBootloaderInputs::new(raw, 4); 3],
+        };
+
+        // Every chunk is handed the fixed columns computed before splitting into chunks,
+        // i.e. they are computed once and not once per chunk.
+        let fixed_cols = RefCell::new(vec![]);
+        rust_continuations(
+            pipeline,
+            |pipeline: Pipeline<GoldilocksField>| -> Result<(), Vec<String>> {
+                fixed_cols.borrow_mut().push(pipeline.fixed_cols()?);
+                Ok(())
+            },
+            bootloader_inputs,
+            Default::default(),
+        )
+        .unwrap();
+        let fixed_cols = fixed_cols.into_inner();
+        assert_eq!(fixed_cols.len(), 3);
+        assert!(fixed_cols.iter().all(|f| Rc::ptr_eq(f, &fixed_cols[0])));
     }
-    bootloader_inputs_and_num_rows
 }
@@ -22,7 +22,8 @@ pub const PAGE_SIZE_BYTES: usize = 1 << PAGE_SIZE_BYTES_LOG;
 pub const PAGE_NUMBER_MASK: usize = (1 << N_LEAVES_LOG) - 1;
 pub const BOOTLOADER_INPUTS_PER_PAGE: usize = WORDS_PER_PAGE + 1 + 4 + (MERKLE_TREE_DEPTH - 1) * 4;
 pub const MEMORY_HASH_START_INDEX: usize = 2 * REGISTER_NAMES.len();
-pub const NUM_PAGES_INDEX: usize = MEMORY_HASH_START_INDEX + 8;
+pub const CHUNK_INDEX_INDEX: usize = MEMORY_HASH_START_INDEX + 8;
+pub const NUM_PAGES_INDEX: usize = CHUNK_INDEX_INDEX + 1;
 pub const PAGE_INPUTS_OFFSET: usize = NUM_PAGES_INDEX + 1;
 
 /// Computes an upper bound of how long the shutdown routine will run, for a given number of pages.
@@ -142,6 +143,7 @@ static P11: &str = SYSCALL_REGISTERS[11];
 /// - Second 49 values: The same values, but after this chunk's execution
 /// - The root hash of the memory Merkle tree (4 elements)
 /// - The root hash of the memory Merkle tree *after this chunk's execution* (4 elements)
+/// - The index of the chunk, as returned by the `chunk_index` syscall
 /// - Number of pages
 /// - For each page:
 ///   - The page number
@@ -566,6 +568,7 @@ where
 {
     register_values: Vec<Elem<F>>,
     merkle_tree_root_hash: &'a [F; 4],
+    chunk_index: u32,
     pages: Pages,
 }
 
@@ -587,6 +590,7 @@ where
         inputs.extend_from_within(..);
         inputs.extend(self.merkle_tree_root_hash.map(Elem::Field));
         inputs.extend(self.merkle_tree_root_hash.map(Elem::Field));
+        inputs.push(self.chunk_index.into());
         inputs.push(Elem::Binary(self.pages.len() as i64));
         for page in self.pages {
             inputs.push(page.page_idx.into());
@@ -602,12 +606,14 @@ where
 
 pub fn create_input<F: FieldElement, Pages: ExactSizeIterator<Item = u32>>(
     register_values: Vec<Elem<F>>,
+    chunk_index: u32,
     merkle_tree: &MerkleTree<F>,
     accessed_pages: Pages,
 ) -> Vec<Elem<F>> {
     InputCreator {
         register_values,
         merkle_tree_root_hash: merkle_tree.root_hash(),
+        chunk_index,
         pages: accessed_pages.map(|page_index| {
             let (page, page_hash, proof) = merkle_tree.get(page_index as usize);
             InputPage {
@@ -630,6 +636,7 @@ pub fn default_register_values<T: FieldElement>() -> Vec<Elem<T>> {
 /// The bootloader input that is equivalent to not using a bootloader, i.e.:
 /// - No pages are initialized
 /// - All registers are set to 0 (including the PC, which causes the bootloader to do nothing)
+/// - The chunk index is 0
 /// - The state at the end of the execution is the same as the beginning
 pub fn default_input<T: FieldElement>(accessed_pages: &[u64]) -> Vec<Elem<T>> {
     // Set all registers and the number of pages to zero
@@ -643,6 +650,7 @@ pub fn default_input<T: FieldElement>(accessed_pages: &[u64]) -> Vec<Elem<T>> {
     // supported anymore (it's anyway only used by the benchmark).
     create_input(
         register_values,
+        0,
         &merkle_tree,
         accessed_pages.iter().map(|&x| x as u32),
     )
@@ -105,7 +105,13 @@ pub fn compile_riscv_asm_bundle<T: FieldElement>(
         return None;
     }
 
-    let powdr_asm = compiler::compile::<T>(riscv_asm_files, runtime, with_bootloader);
+    let powdr_asm = match compiler::compile::<T>(riscv_asm_files, runtime, with_bootloader) {
+        Ok(powdr_asm) => powdr_asm,
+        Err(e) => {
+            eprintln!("{e}");
+            return None;
+        }
+    };
 
     fs::write(powdr_asm_file_name.clone(), &powdr_asm).unwrap();
     log::info!("Wrote {}", powdr_asm_file_name.to_str().unwrap());
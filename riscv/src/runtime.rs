@@ -2,12 +2,14 @@ use std::{collections::BTreeMap, convert::TryFrom};
 
 use powdr_riscv_syscalls::{Syscall, SYSCALL_REGISTERS};
 
-use powdr_ast::parsed::asm::{FunctionStatement, MachineStatement, SymbolPath};
+use powdr_ast::parsed::asm::{FunctionStatement, MachineStatement, SymbolPath, SymbolValue};
 
 use itertools::Itertools;
 use powdr_parser::ParserContext;
+pub use powdr_riscv_executor::{InstructionHandler, NativeInstructions};
 
 use crate::compiler::{pop_register, push_register};
+use crate::continuations::bootloader::CHUNK_INDEX_INDEX;
 
 lazy_static::lazy_static! {
     static ref INSTRUCTION_DECLARATION_PARSER: powdr_parser::powdr::InstructionDeclarationParser
@@ -17,19 +19,40 @@ lazy_static::lazy_static! {
 }
 
 pub fn parse_instruction_declaration(input: &str) -> MachineStatement {
+    try_parse_instruction_declaration(input).expect("invalid instruction declaration")
+}
+
+pub fn parse_function_statement(input: &str) -> FunctionStatement {
+    try_parse_function_statement(input).expect("invalid function statement")
+}
+
+fn try_parse_instruction_declaration(input: &str) -> Result<MachineStatement, String> {
     let ctx = ParserContext::new(None, input);
     INSTRUCTION_DECLARATION_PARSER
         .parse(&ctx, input)
-        .expect("invalid instruction declaration")
+        .map_err(|e| format!("invalid instruction declaration `{input}`: {e}"))
 }
 
-pub fn parse_function_statement(input: &str) -> FunctionStatement {
+fn try_parse_function_statement(input: &str) -> Result<FunctionStatement, String> {
     let ctx = ParserContext::new(None, input);
     FUNCTION_STATEMENT_PARSER
         .parse(&ctx, input)
-        .expect("invalid function statement")
+        .map_err(|e| format!("invalid function statement `{input}`: {e}"))
 }
 
+/// The round constants of SHA-256.
+#[rustfmt::skip]
+const SHA256_ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
 struct SubMachine {
     /// Full path to machine (e.g, `path::to::Machine`)
     path: SymbolPath,
@@ -41,6 +64,8 @@ struct SubMachine {
     instructions: Vec<MachineStatement>,
     /// TODO: only needed because of witgen requiring that each machine be called at least once
     init_call: Vec<FunctionStatement>,
+    /// Source of the module defining the machine, if it is not part of the standard library
+    definition: Option<String>,
 }
 
 impl SubMachine {
@@ -59,6 +84,25 @@ impl SubMachine {
     }
 }
 
+/// How the main machine dispatches a user-defined syscall to its co-processor,
+/// see [Runtime::with_syscall].
+pub struct SyscallHandler {
+    /// The name of the syscall. It is also the instance name of the co-processor
+    /// and the name of the module its machine is defined in.
+    pub name: String,
+    /// Declarations of the instructions calling the co-processor, e.g.
+    /// `instr double Y -> X ~ double.double;`.
+    pub instructions: Vec<String>,
+    /// Native implementations of these instructions, run by the RISC-V executor.
+    pub native_instructions: Vec<(String, InstructionHandler)>,
+    /// Statements calling the co-processor once, because witgen requires each
+    /// machine to be called at least once.
+    pub init_call: Vec<String>,
+    /// The statements of the ecall handler, with the same requirements as the
+    /// ones of the built-in syscalls.
+    pub implementation: Vec<String>,
+}
+
 /// Sequence of asm function statements.
 /// Any of the registers used as input/output to the syscall should be usable without issue.
 /// Other registers should be saved/restored from memory, as LLVM doesn't know about their usage here.
@@ -66,9 +110,17 @@ struct SyscallImpl(Vec<FunctionStatement>);
 
 /// RISCV powdr assembly runtime.
 /// Determines submachines, instructions and syscalls avaiable to the main machine.
+///
+/// Co-processors are added fluently via the `with_*` methods. Conflicts between
+/// them (two submachines with the same instance name or two implementations of
+/// the same syscall) are collected and reported by [Runtime::build].
 pub struct Runtime {
     submachines: BTreeMap<String, SubMachine>,
-    syscalls: BTreeMap<Syscall, SyscallImpl>,
+    /// The syscalls by ecall number, with their names.
+    syscalls: BTreeMap<u32, (String, SyscallImpl)>,
+    /// The native implementations of the instructions of user-defined co-processors.
+    native_instructions: NativeInstructions,
+    conflicts: Vec<String>,
 }
 
 impl Runtime {
@@ -76,6 +128,8 @@ impl Runtime {
         let mut r = Runtime {
             submachines: Default::default(),
             syscalls: Default::default(),
+            native_instructions: Default::default(),
+            conflicts: Default::default(),
         };
 
         // Base submachines
@@ -129,6 +183,22 @@ impl Runtime {
             ["x0 <=X= ${ std::prover::Query::PrintChar(std::convert::int(std::prover::eval(x10))) };"]
         );
 
+        r.add_syscall(
+            Syscall::Output,
+            // Like PrintChar, this does not introduce nondeterminism.
+            ["x0 <=X= ${ std::prover::Query::Output(std::convert::int(std::prover::eval(x10))) };"]
+        );
+
+        r.add_syscall(
+            Syscall::HeapUsage,
+            // Like PrintChar, this does not introduce nondeterminism.
+            ["x0 <=X= ${ std::prover::Query::HeapUsage(std::convert::int(std::prover::eval(x10))) };"]
+        );
+
+        // Without the bootloader, the whole execution is a single chunk.
+        // With the bootloader, this implementation is replaced in `ecall_handler`.
+        r.add_syscall(Syscall::ChunkIndex, ["x10 <=X= 0;"]);
+
         r
     }
 
@@ -137,7 +207,25 @@ impl Runtime {
     }
 
     pub fn has_syscall(&self, s: Syscall) -> bool {
-        self.syscalls.contains_key(&s)
+        self.syscalls.contains_key(&(s as u32))
+    }
+
+    /// Checks that no two co-processors added to the runtime conflict with each other.
+    pub fn build(self) -> Result<Self, String> {
+        self.check_conflicts()?;
+        Ok(self)
+    }
+
+    /// Returns an error listing the conflicts between the co-processors, if any.
+    pub fn check_conflicts(&self) -> Result<(), String> {
+        if self.conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Conflicting co-processors in runtime:\n{}",
+                self.conflicts.join("\n")
+            ))
+        }
     }
 
     pub fn with_poseidon(mut self) -> Self {
@@ -206,6 +294,116 @@ impl Runtime {
         self
     }
 
+    pub fn with_sha256(mut self) -> Self {
+        self.add_submachine(
+            "std::hash::sha256::Sha256Round",
+            None,
+            "sha256_round",
+            ["instr sha256_round ~ sha256_round.round x10, x11, x12, x13, x14, x15, x16, x17, x6, x7 -> x10', x11', x12', x13', x14', x15', x16', x17';"],
+            // init call
+            [
+                "sha256_round;",
+                "x10 <=X= 0;",
+                "x11 <=X= 0;",
+                "x12 <=X= 0;",
+                "x13 <=X= 0;",
+                "x14 <=X= 0;",
+                "x15 <=X= 0;",
+                "x16 <=X= 0;",
+                "x17 <=X= 0;",
+            ]);
+
+        self.add_submachine(
+            "std::hash::sha256::Sha256Schedule",
+            None,
+            "sha256_schedule",
+            ["instr sha256_schedule ~ sha256_schedule.schedule x10, x11, x12, x13 -> x10';"],
+            // init call
+            ["sha256_schedule;", "x10 <=X= 0;"],
+        );
+
+        // The sha256 syscall has two arguments: x10 is the memory address of the
+        // state (8 words), which is updated in place, and x11 is the memory address
+        // of the message block (16 words), both already in big-endian word order.
+
+        // The instructions use the first 10 SYSCALL_REGISTERS, so we keep the
+        // addresses in tmp3 and tmp4.
+        let setup = ["tmp3 <=X= x10;".to_string(), "tmp4 <=X= x11;".to_string()].into_iter();
+
+        // The 64 words of the message schedule live on the stack.
+        let allocate_schedule = std::iter::once("x2 <=X= wrap(x2 - 256);".to_string());
+        let free_schedule = std::iter::once("x2 <=X= wrap(x2 + 256);".to_string());
+
+        // The first 16 words are the message block.
+        let copy_block = (0..16).flat_map(|i| {
+            [
+                format!("x10, tmp1 <== mload({} + tmp4);", 4 * i),
+                format!("mstore {} + x2, x10;", 4 * i),
+            ]
+        });
+
+        // The other words are computed from the previous ones.
+        let extend_schedule = (16..64).flat_map(|i| {
+            [
+                format!("x10, tmp1 <== mload({} + x2);", 4 * (i - 2)),
+                format!("x11, tmp1 <== mload({} + x2);", 4 * (i - 7)),
+                format!("x12, tmp1 <== mload({} + x2);", 4 * (i - 15)),
+                format!("x13, tmp1 <== mload({} + x2);", 4 * (i - 16)),
+                "sha256_schedule;".to_string(),
+                format!("mstore {} + x2, x10;", 4 * i),
+            ]
+        });
+
+        // The working variables a, ..., h are kept in the first 8 SYSCALL_REGISTERS.
+        let load_state = (0..8).map(|i| {
+            let reg = SYSCALL_REGISTERS[i];
+            format!("{reg}, tmp1 <== mload({} + tmp3);", 4 * i)
+        });
+
+        let rounds = SHA256_ROUND_CONSTANTS
+            .iter()
+            .enumerate()
+            .flat_map(|(i, k)| {
+                [
+                    format!("x6, tmp1 <== mload({} + x2);", 4 * i),
+                    format!("x7 <=X= {k};"),
+                    "sha256_round;".to_string(),
+                ]
+            });
+
+        // The new state is the sum of the old state and the working variables.
+        let store_state = (0..8).flat_map(|i| {
+            let reg = SYSCALL_REGISTERS[i];
+            [
+                format!("tmp1, tmp2 <== mload({} + tmp3);", 4 * i),
+                format!("tmp1 <== wrap(tmp1 + {reg});"),
+                format!("mstore {} + tmp3, tmp1;", 4 * i),
+            ]
+        });
+
+        let implementation = setup
+            // The instructions might overwrite registers in use by the riscv machine,
+            // so we save them on the stack.
+            .chain((0..10).flat_map(|i| push_register(SYSCALL_REGISTERS[i])))
+            .chain(allocate_schedule)
+            .chain(copy_block)
+            .chain(extend_schedule)
+            .chain(load_state)
+            .chain(rounds)
+            .chain(store_state)
+            .chain(free_schedule)
+            // Restore the original register values.
+            .chain(
+                (0..10)
+                    .rev()
+                    .flat_map(|i| pop_register(SYSCALL_REGISTERS[i])),
+            );
+
+        self.add_syscall(Syscall::Sha256, implementation);
+        self
+    }
+
+    /// Adds a submachine, recording a conflict if its instance name is already taken.
     pub fn add_submachine<S: AsRef<str>, I1: IntoIterator<Item = S>, I2: IntoIterator<Item = S>>(
         &mut self,
         path: &str,
@@ -226,19 +424,37 @@ impl Runtime {
                 .into_iter()
                 .map(|s| parse_function_statement(s.as_ref()))
                 .collect(),
+            definition: None,
         };
-        assert!(
-            self.submachines
-                .insert(instance_name.to_string(), subm)
-                .is_none(),
-            "submachine {instance_name} already present"
-        );
+        self.insert_submachine(subm);
     }
 
+    fn insert_submachine(&mut self, subm: SubMachine) {
+        if let Some(existing) = self.submachines.get(&subm.instance_name) {
+            self.conflicts.push(format!(
+                "submachine {} already present (declared as {}, now also as {})",
+                subm.instance_name, existing.path, subm.path
+            ));
+            return;
+        }
+        self.submachines.insert(subm.instance_name.clone(), subm);
+    }
+
+    /// Adds a syscall implementation, recording a conflict if the syscall
+    /// (i.e. the ecall number) is already implemented.
     pub fn add_syscall<S: AsRef<str>, I: IntoIterator<Item = S>>(
         &mut self,
         syscall: Syscall,
         implementation: I,
+    ) {
+        self.insert_syscall(syscall as u32, syscall.to_string(), implementation);
+    }
+
+    fn insert_syscall<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        &mut self,
+        id: u32,
+        name: String,
+        implementation: I,
     ) {
         let implementation = SyscallImpl(
             implementation
@@ -247,9 +463,108 @@ impl Runtime {
                 .collect(),
         );
 
-        if self.syscalls.insert(syscall, implementation).is_some() {
-            panic!("duplicate syscall {}", syscall);
+        if self.syscalls.contains_key(&id) {
+            self.conflicts
+                .push(format!("duplicate syscall {name} (ecall number {id})"));
+            return;
+        }
+        if self.syscalls.values().any(|(n, _)| *n == name) {
+            self.conflicts
+                .push(format!("duplicate syscall name {name}"));
+            return;
+        }
+        self.syscalls.insert(id, (name, implementation));
+    }
+
+    /// Adds a user-defined co-processor, called via the ecall number `id`.
+    ///
+    /// `machine_pil` is the source of a module defining exactly one machine, which
+    /// becomes available to the main machine as a submachine called `dispatch.name`.
+    /// Returns an error if the co-processor is invalid or clashes with an existing one,
+    /// including when `id` is the ecall number of a built-in syscall.
+    pub fn with_syscall(
+        mut self,
+        id: u32,
+        machine_pil: &str,
+        dispatch: SyscallHandler,
+    ) -> Result<Self, String> {
+        let name = &dispatch.name;
+        if let Ok(syscall) = Syscall::try_from(id) {
+            return Err(format!(
+                "ecall number {id} of syscall {name} is reserved for {syscall}"
+            ));
+        }
+        if let Some((existing, _)) = self.syscalls.get(&id) {
+            return Err(format!(
+                "ecall number {id} of syscall {name} is already used by {existing}"
+            ));
+        }
+        if self.syscalls.values().any(|(n, _)| n == name) {
+            return Err(format!("duplicate syscall name {name}"));
+        }
+        if self.submachines.contains_key(name) {
+            return Err(format!("submachine {name} already present"));
         }
+        if let Some((instr, _)) = dispatch
+            .native_instructions
+            .iter()
+            .find(|(instr, _)| self.native_instructions.contains_key(instr))
+        {
+            return Err(format!(
+                "native instruction {instr} of syscall {name} is already defined"
+            ));
+        }
+
+        let module = powdr_parser::parse_asm(None, machine_pil)
+            .map_err(|e| format!("invalid co-processor module of syscall {name}: {e:?}"))?
+            .main;
+        let machine_names = module
+            .symbol_definitions()
+            .filter(|d| matches!(d.value, SymbolValue::Machine(_)))
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        let [machine_name] = machine_names[..] else {
+            return Err(format!(
+                "co-processor module of syscall {name} defines {} machines, expected one",
+                machine_names.len()
+            ));
+        };
+        let path = str::parse(&format!("{name}::{machine_name}"))
+            .map_err(|_| format!("invalid syscall name {name}"))?;
+        let instructions = dispatch
+            .instructions
+            .iter()
+            .map(|s| try_parse_instruction_declaration(s))
+            .collect::<Result<_, _>>()?;
+        let init_call = dispatch
+            .init_call
+            .iter()
+            .map(|s| try_parse_function_statement(s))
+            .collect::<Result<_, _>>()?;
+        let implementation = dispatch
+            .implementation
+            .iter()
+            .map(|s| try_parse_function_statement(s))
+            .collect::<Result<_, _>>()?;
+
+        self.insert_submachine(SubMachine {
+            path,
+            alias: None,
+            instance_name: name.clone(),
+            instructions,
+            init_call,
+            definition: Some(machine_pil.to_string()),
+        });
+        self.native_instructions.extend(dispatch.native_instructions);
+        self.syscalls
+            .insert(id, (dispatch.name, SyscallImpl(implementation)));
+        Ok(self)
+    }
+
+    /// The native implementations of the instructions of user-defined co-processors,
+    /// to be passed to the RISC-V executor.
+    pub fn native_instructions(&self) -> &NativeInstructions {
+        &self.native_instructions
     }
 
     pub fn submachines_init(&self) -> Vec<String> {
@@ -260,6 +575,17 @@ impl Runtime {
             .collect()
     }
 
+    /// The modules defining the machines of user-defined co-processors.
+    pub fn submachines_definitions(&self) -> String {
+        self.submachines
+            .values()
+            .filter_map(|m| {
+                let definition = m.definition.as_ref()?;
+                Some(format!("mod {} {{\n{definition}\n}}", m.instance_name))
+            })
+            .join("\n")
+    }
+
     pub fn submachines_import(&self) -> String {
         self.submachines.values().map(|m| m.import()).join("\n")
     }
@@ -322,22 +648,37 @@ __rust_no_alloc_shim_is_unstable: .byte 0
 "
     }
 
-    pub fn ecall_handler(&self) -> Vec<String> {
+    pub fn ecall_handler(&self, with_bootloader: bool) -> Vec<String> {
         let ecall = [
             "// ecall handler".to_string(),
             "__ecall_handler:".to_string(),
         ]
         .into_iter();
 
-        let jump_table = self
+        // The chunk index is provided as a bootloader input.
+        let bootloader_chunk_index = SyscallImpl(vec![parse_function_statement(&format!(
+            "x10 <== load_bootloader_input({CHUNK_INDEX_INDEX});"
+        ))]);
+        let syscalls = self
             .syscalls
-            .keys()
-            .map(|s| format!("branch_if_zero x5 - {}, __ecall_handler_{};", *s as u32, s));
+            .iter()
+            .map(|(&id, (name, implementation))| {
+                if with_bootloader && id == Syscall::ChunkIndex as u32 {
+                    (id, name, &bootloader_chunk_index)
+                } else {
+                    (id, name, implementation)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let jump_table = syscalls
+            .iter()
+            .map(|(id, name, _)| format!("branch_if_zero x5 - {id}, __ecall_handler_{name};"));
 
         let invalid_handler = ["__invalid_syscall:".to_string(), "fail;".to_string()].into_iter();
 
-        let handlers = self.syscalls.iter().flat_map(|(syscall, implementation)| {
-            std::iter::once(format!("__ecall_handler_{}:", syscall))
+        let handlers = syscalls.iter().flat_map(|(_, name, implementation)| {
+            std::iter::once(format!("__ecall_handler_{name}:"))
                 .chain(implementation.0.iter().map(|i| i.to_string()))
                 .chain(std::iter::once("tmp1 <== jump_dyn(x1);".to_string()))
         });
@@ -366,9 +707,15 @@ impl TryFrom<&[&str]> for Runtime {
             }
             match *name {
                 "poseidon_gl" => runtime = runtime.with_poseidon(),
+                // The co-processor consists of two submachines, none of them named "sha256".
+                "sha256" => {
+                    if !runtime.has_syscall(Syscall::Sha256) {
+                        runtime = runtime.with_sha256()
+                    }
+                }
                 _ => return Err(format!("Invalid co-processor specified: {name}")),
             }
         }
-        Ok(runtime)
+        runtime.build()
     }
 }
@@ -1,5 +1,6 @@
 use powdr_number::GoldilocksField;
 use powdr_pipeline::{test_util::verify_pipeline, Pipeline};
+use powdr_riscv::runtime::NativeInstructions;
 use std::path::PathBuf;
 
 /// Like compiler::test_util::verify_asm_string, but also runs RISCV executor.
@@ -8,6 +9,7 @@ pub fn verify_riscv_asm_string<S: serde::Serialize + Send + Sync + 'static>(
     contents: &str,
     inputs: Vec<GoldilocksField>,
     data: Option<Vec<(u32, S)>>,
+    native_instructions: &NativeInstructions,
 ) {
     let temp_dir = mktemp::Temp::new_dir().unwrap().release();
 
@@ -29,6 +31,10 @@ pub fn verify_riscv_asm_string<S: serde::Serialize + Send + Sync + 'static>(
         &[],
         usize::MAX,
         powdr_riscv_executor::ExecMode::Fast,
+        powdr_riscv_executor::ExecutionOptions {
+            native_instructions,
+            ..Default::default()
+        },
     );
     verify_pipeline(pipeline).unwrap();
 }
@@ -13,9 +13,16 @@ mod instruction_tests {
             [(name.to_string(), assembly.to_string())].into(),
             &Runtime::base(),
             false,
-        );
+        )
+        .unwrap();
 
-        verify_riscv_asm_string::<()>(&format!("{name}.asm"), &powdr_asm, Default::default(), None);
+        verify_riscv_asm_string::<()>(
+            &format!("{name}.asm"),
+            &powdr_asm,
+            Default::default(),
+            None,
+            &Default::default(),
+        );
     }
 
     include!(concat!(env!("OUT_DIR"), "/instruction_tests.rs"));
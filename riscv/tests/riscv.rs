@@ -4,14 +4,23 @@ use common::verify_riscv_asm_string;
 use mktemp::Temp;
 use powdr_backend::BackendType;
 use powdr_number::{FieldElement, GoldilocksField};
-use powdr_pipeline::{verify::verify, Pipeline};
+use powdr_pipeline::{inputs_to_query_callback, verify::verify, Pipeline};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::rc::Rc;
 use test_log::test;
 
 use powdr_riscv::{
-    continuations::{rust_continuations, rust_continuations_dry_run},
+    compiler::MIN_DEGREE_LOG,
+    continuations::{
+        bootloader::REGISTER_NAMES, prove_chunk, rust_continuations, rust_continuations_dry_run,
+        ContinuationsOptions,
+    },
+    runtime::{InstructionHandler, SyscallHandler},
     Runtime,
 };
+use powdr_riscv_syscalls::Syscall;
 
 /// Compiles and runs a rust program with continuations, runs the full
 /// witness generation & verifies it using Pilcom.
@@ -22,7 +31,8 @@ pub fn test_continuations(case: &str) {
         &format!("tests/riscv_data/{case}/Cargo.toml"),
         &temp_dir,
     );
-    let powdr_asm = powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true);
+    let powdr_asm =
+        powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true).unwrap();
 
     // Manually create tmp dir, so that it is the same in all chunks.
     let tmp_dir = mktemp::Temp::new_dir().unwrap();
@@ -31,7 +41,7 @@ pub fn test_continuations(case: &str) {
         .from_asm_string(powdr_asm.clone(), Some(PathBuf::from(&case)))
         .with_prover_inputs(Default::default())
         .with_output(tmp_dir.to_path_buf(), false);
-    let pipeline_callback = |pipeline: Pipeline<GoldilocksField>| -> Result<(), ()> {
+    let pipeline_callback = |pipeline: Pipeline<GoldilocksField>| -> Result<(), Vec<String>> {
         // Can't use `verify_pipeline`, because the pipeline was renamed in the middle of after
         // computing the constants file.
         let mut pipeline = pipeline.with_backend(BackendType::PilStarkCli);
@@ -39,8 +49,135 @@ pub fn test_continuations(case: &str) {
         verify(pipeline.output_dir().unwrap(), pipeline.name(), Some(case)).unwrap();
         Ok(())
     };
-    let bootloader_inputs = rust_continuations_dry_run(&mut pipeline);
-    rust_continuations(pipeline, pipeline_callback, bootloader_inputs).unwrap();
+    let bootloader_inputs = rust_continuations_dry_run(
+        &mut pipeline,
+        runtime.native_instructions(),
+        ContinuationsOptions::default(),
+    )
+    .unwrap();
+    rust_continuations(
+        pipeline,
+        pipeline_callback,
+        bootloader_inputs,
+        ContinuationsOptions::default(),
+    )
+    .unwrap();
+}
+
+/// Compiles the `many_chunks` example with a main machine of `degree` rows instead of the
+/// inferred minimum, so that it can be split into chunks of fewer rows than the main machine.
+fn compile_many_chunks(degree: u64) -> String {
+    let runtime = Runtime::base().with_poseidon();
+    let temp_dir = Temp::new_dir().unwrap();
+    let riscv_asm = powdr_riscv::compile_rust_crate_to_riscv_asm(
+        "tests/riscv_data/many_chunks/Cargo.toml",
+        &temp_dir,
+    );
+    let powdr_asm =
+        powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true).unwrap();
+    let inferred_degree = format!("degree {};", 1u64 << MIN_DEGREE_LOG);
+    assert!(powdr_asm.contains(&inferred_degree));
+    powdr_asm.replacen(&inferred_degree, &format!("degree {degree};"), 1)
+}
+
+#[test]
+fn runtime_conflicting_coprocessors() {
+    assert!(Runtime::base().with_poseidon().build().is_ok());
+
+    // Another co-processor claiming the ecall of poseidon.
+    let mut runtime = Runtime::base();
+    runtime.add_syscall(Syscall::PoseidonGL, ["x10 <=X= 0;"]);
+    let runtime = runtime.with_poseidon();
+
+    // Compiling with the runtime reports the conflict as well.
+    let err = powdr_riscv::compiler::compile::<GoldilocksField>(
+        [("guest".to_string(), "main:\n    ret\n".to_string())].into(),
+        &runtime,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(err.contains("duplicate syscall poseidon_gl (ecall number 3)"));
+
+    let err = runtime.build().err().unwrap();
+    assert!(err.contains("duplicate syscall poseidon_gl (ecall number 3)"));
+}
+
+/// A co-processor doubling its input.
+const DOUBLE_MACHINE: &str = "
+machine Double(latch, operation_id) {
+    operation double<0> x -> y;
+
+    call_selectors sel;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+
+    col witness x, y;
+    y = 2 * x;
+}
+";
+
+fn double_native(args: &[u32]) -> Vec<u32> {
+    vec![2 * args[0]]
+}
+
+fn double_syscall() -> SyscallHandler {
+    SyscallHandler {
+        name: "double".to_string(),
+        instructions: vec!["instr double Y -> X ~ double.double;".to_string()],
+        native_instructions: vec![("double".to_string(), double_native as InstructionHandler)],
+        init_call: vec!["x10 <== double(x10);".to_string()],
+        implementation: vec!["x10 <== double(x10);".to_string()],
+    }
+}
+
+#[test]
+fn runtime_custom_syscall_reserved_ecall_number() {
+    let err = Runtime::base()
+        .with_syscall(Syscall::PoseidonGL as u32, DOUBLE_MACHINE, double_syscall())
+        .err()
+        .unwrap();
+    assert!(err.contains("ecall number 3 of syscall double is reserved for poseidon_gl"));
+}
+
+#[test]
+fn runtime_custom_syscall_errors() {
+    let runtime = Runtime::base()
+        .with_syscall(100, DOUBLE_MACHINE, double_syscall())
+        .unwrap();
+    assert_eq!(
+        runtime.native_instructions().keys().collect::<Vec<_>>(),
+        vec!["double"]
+    );
+
+    let err = runtime
+        .with_syscall(100, DOUBLE_MACHINE, double_syscall())
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        "ecall number 100 of syscall double is already used by double"
+    );
+
+    let err = Runtime::base()
+        .with_syscall(100, "", double_syscall())
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        "co-processor module of syscall double defines 0 machines, expected one"
+    );
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_custom_syscall() {
+    let case = "double_syscall";
+    let runtime = Runtime::base()
+        .with_syscall(100, DOUBLE_MACHINE, double_syscall())
+        .unwrap();
+    verify_riscv_crate(case, Default::default(), &runtime);
 }
 
 #[test]
@@ -64,6 +201,13 @@ fn test_poseidon_gl() {
     verify_riscv_crate(case, Default::default(), &Runtime::base().with_poseidon());
 }
 
+#[test]
+#[ignore = "Too slow"]
+fn test_sha256() {
+    let case = "sha256_via_coprocessor";
+    verify_riscv_crate(case, Default::default(), &Runtime::base().with_sha256());
+}
+
 #[test]
 #[ignore = "Too slow"]
 fn test_sum() {
@@ -201,6 +345,84 @@ fn test_print() {
     verify_riscv_crate(case, Default::default(), &Runtime::base());
 }
 
+#[test]
+#[ignore = "Too slow"]
+#[should_panic(
+    expected = "assertion `left == right` failed\n  left: [\"größer\", \"1\"]\n right: [\"kleiner\", \"2\"]\nGuest aborted with code 101"
+)]
+fn test_assert_eq() {
+    let case = "assert_eq";
+    verify_riscv_crate(case, Default::default(), &Runtime::base());
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_heap_usage() {
+    let case = "heap_usage";
+    let powdr_asm = compile_riscv_crate::<GoldilocksField>(case, &Runtime::base());
+    let (trace, _) = powdr_riscv_executor::execute::<GoldilocksField>(
+        &powdr_asm,
+        Default::default(),
+        &inputs_to_query_callback(vec![]),
+        &[],
+        powdr_riscv_executor::ExecMode::Fast,
+        Default::default(),
+    );
+    assert_eq!(trace.heap_usage, Some(1040));
+
+    verify_riscv_crate(case, Default::default(), &Runtime::base());
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_output() {
+    let case = "output";
+    let powdr_asm = compile_riscv_crate::<GoldilocksField>(case, &Runtime::base());
+    let inputs = [4, 15, 3, 42, 8].map(GoldilocksField::from).to_vec();
+    let (trace, _) = powdr_riscv_executor::execute::<GoldilocksField>(
+        &powdr_asm,
+        Default::default(),
+        &inputs_to_query_callback(inputs.clone()),
+        &[],
+        powdr_riscv_executor::ExecMode::Fast,
+        Default::default(),
+    );
+    assert_eq!(trace.output, vec![3, 42, 68]);
+
+    verify_riscv_crate(case, inputs, &Runtime::base());
+}
+
+#[test]
+fn test_named_inputs() {
+    // Runs the guest without proving, which fails if one of its assertions fails.
+    let case = "named_inputs";
+    let runtime = Runtime::base().with_poseidon();
+    let temp_dir = Temp::new_dir().unwrap();
+    let riscv_asm = powdr_riscv::compile_rust_crate_to_riscv_asm(
+        &format!("tests/riscv_data/{case}/Cargo.toml"),
+        &temp_dir,
+    );
+    let powdr_asm =
+        powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true).unwrap();
+
+    let pipeline = Pipeline::default().from_asm_string(powdr_asm, Some(PathBuf::from(case)));
+    let named_inputs = BTreeMap::from([("threshold".to_string(), 42), ("rounds".to_string(), 3)]);
+    let mut with_named_inputs = pipeline
+        .clone()
+        .with_prover_inputs(vec![45.into()])
+        .with_named_inputs(named_inputs);
+    // The guest falls back to defaults if the host does not provide named inputs.
+    let mut without_named_inputs = pipeline.with_prover_inputs(vec![42.into()]);
+    for pipeline in [&mut with_named_inputs, &mut without_named_inputs] {
+        rust_continuations_dry_run::<GoldilocksField>(
+            pipeline,
+            &Default::default(),
+            ContinuationsOptions::default(),
+        )
+        .unwrap();
+    }
+}
+
 #[test]
 fn test_many_chunks_dry() {
     // Compiles and runs the many_chunks example with continuations, just computing
@@ -213,12 +435,271 @@ fn test_many_chunks_dry() {
         &format!("tests/riscv_data/{case}/Cargo.toml"),
         &temp_dir,
     );
-    let powdr_asm = powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true);
+    let powdr_asm =
+        powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true).unwrap();
+
+    let mut pipeline = Pipeline::default()
+        .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
+        .with_prover_inputs(Default::default());
+    let bootloader_inputs = rust_continuations_dry_run::<GoldilocksField>(
+        &mut pipeline,
+        &Default::default(),
+        ContinuationsOptions::default(),
+    )
+    .unwrap();
+
+    assert!(bootloader_inputs.chunk_count() > 1);
+    assert_eq!(
+        bootloader_inputs.initial_memory_root(),
+        Some(&bootloader_inputs.chunks[0].initial_memory_root)
+    );
+    for (i, chunk) in bootloader_inputs.chunks.iter().enumerate() {
+        assert_eq!(chunk.chunk_index, i as u64);
+        assert_eq!(chunk.initial_registers.len(), REGISTER_NAMES.len());
+        assert_eq!(chunk.final_registers.len(), REGISTER_NAMES.len());
+    }
+    // Each chunk continues where the previous one stopped.
+    for (previous, next) in bootloader_inputs
+        .chunks
+        .iter()
+        .zip(&bootloader_inputs.chunks[1..])
+    {
+        assert_eq!(next.initial_memory_root, previous.final_memory_root);
+        assert_eq!(next.initial_registers, previous.final_registers);
+    }
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_continuations_share_fixed_cols() {
+    let case = "many_chunks";
+    let chunk_size = 1 << MIN_DEGREE_LOG;
+    let powdr_asm = compile_many_chunks(2 * chunk_size);
+
+    let mut pipeline = Pipeline::default()
+        .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
+        .with_prover_inputs(Default::default());
+    let options = ContinuationsOptions {
+        chunk_size: Some(chunk_size as usize),
+        max_chunks: None,
+    };
+    let bootloader_inputs =
+        rust_continuations_dry_run::<GoldilocksField>(&mut pipeline, &Default::default(), options)
+            .unwrap();
+    let chunk_count = bootloader_inputs.chunk_count();
+    assert!(chunk_count > 1);
+
+    // The fixed columns are computed once, not once per chunk, with the degree of the chunks.
+    let fixed_cols = RefCell::new(vec![]);
+    rust_continuations(
+        pipeline,
+        |pipeline: Pipeline<GoldilocksField>| -> Result<(), Vec<String>> {
+            assert_eq!(pipeline.optimized_pil().unwrap().degree(), chunk_size);
+            fixed_cols.borrow_mut().push(pipeline.fixed_cols().unwrap());
+            Ok(())
+        },
+        bootloader_inputs,
+        options,
+    )
+    .unwrap();
+    let fixed_cols = fixed_cols.into_inner();
+    assert_eq!(fixed_cols.len(), chunk_count);
+    assert!(fixed_cols.iter().all(|f| Rc::ptr_eq(f, &fixed_cols[0])));
+    assert!(fixed_cols[0]
+        .iter()
+        .all(|(_, values)| values.len() == chunk_size as usize));
+}
+
+#[test]
+fn test_many_chunks_dry_chunk_size() {
+    // A smaller chunk size splits the same execution into more chunks.
+    let case = "many_chunks";
+    let chunk_size = 1 << MIN_DEGREE_LOG;
+    let powdr_asm = compile_many_chunks(2 * chunk_size as u64);
+
+    let mut pipeline = Pipeline::default()
+        .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
+        .with_prover_inputs(Default::default());
+    let default_inputs = rust_continuations_dry_run::<GoldilocksField>(
+        &mut pipeline,
+        &Default::default(),
+        Default::default(),
+    )
+    .unwrap();
+    let default_chunks = default_inputs.chunk_count();
+
+    let options = ContinuationsOptions {
+        chunk_size: Some(chunk_size),
+        max_chunks: None,
+    };
+    let small_chunks =
+        rust_continuations_dry_run::<GoldilocksField>(&mut pipeline, &Default::default(), options)
+            .unwrap()
+            .chunk_count();
+    assert!(small_chunks > default_chunks);
+
+    // The machines need at least `chunk_size` rows.
+    let options = ContinuationsOptions {
+        chunk_size: Some(chunk_size / 2),
+        max_chunks: None,
+    };
+    assert_eq!(
+        rust_continuations_dry_run::<GoldilocksField>(&mut pipeline, &Default::default(), options)
+            .unwrap_err(),
+        format!(
+            "The chunk size {} is below the minimum degree of the machines ({chunk_size}).",
+            chunk_size / 2
+        )
+    );
+
+    // Too few chunks for the execution.
+    let options = ContinuationsOptions {
+        chunk_size: Some(chunk_size),
+        max_chunks: Some(small_chunks - 1),
+    };
+    assert_eq!(
+        rust_continuations_dry_run::<GoldilocksField>(&mut pipeline, &Default::default(), options)
+            .unwrap_err(),
+        format!(
+            "The execution does not fit into {} chunks of {} rows.",
+            small_chunks - 1,
+            chunk_size
+        )
+    );
+
+    // Proving fails if the bootloader inputs do not match the options.
+    let options = ContinuationsOptions {
+        chunk_size: None,
+        max_chunks: Some(default_chunks - 1),
+    };
+    assert_eq!(
+        rust_continuations(
+            pipeline,
+            |_| -> Result<(), Vec<String>> { unreachable!() },
+            default_inputs,
+            options,
+        ),
+        Err(vec![format!(
+            "The bootloader inputs have {default_chunks} chunks, but at most {} are allowed.",
+            default_chunks - 1
+        )])
+    );
+}
+
+#[test]
+fn execution_trace_log() {
+    let guest = r#"
+.globl __runtime_start
+__runtime_start:
+    li x10, 7
+    addi x11, x10, 5
+    ret
+"#;
+    let powdr_asm = powdr_riscv::compiler::compile::<GoldilocksField>(
+        [("guest".to_string(), guest.to_string())].into(),
+        &Runtime::base().with_poseidon(),
+        true,
+    )
+    .unwrap();
+
+    let temp_dir = Temp::new_dir().unwrap();
+    let log_path = temp_dir.join("execution_trace.log");
+    let mut pipeline = Pipeline::default()
+        .from_asm_string(powdr_asm, Some(PathBuf::from("guest.asm")))
+        .with_prover_inputs(Default::default())
+        .with_execution_trace_log(log_path.clone());
+    rust_continuations_dry_run::<GoldilocksField>(
+        &mut pipeline,
+        &Default::default(),
+        ContinuationsOptions::default(),
+    )
+    .unwrap();
+
+    // The guest's register writes appear in the order they were executed.
+    let log = std::fs::read_to_string(log_path).unwrap();
+    let write_x10 = log
+        .lines()
+        .position(|line| line.starts_with("    x10: ") && line.ends_with(" -> 7"))
+        .unwrap();
+    let write_x11 = log
+        .lines()
+        .position(|line| line.starts_with("    x11: ") && line.ends_with(" -> 12"))
+        .unwrap();
+    assert!(write_x10 < write_x11);
+
+    // Witness generation does not run the executor, so it cannot write the log.
+    assert!(pipeline.compute_witness().is_err());
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_chunk_index() {
+    // The guest reads its chunk index in the first and in a later chunk, and each
+    // chunk receives its own index via the bootloader inputs. The dry run validates
+    // the chunks against a full execution, which has to see the same indices.
+    let case = "chunk_index";
+    let runtime = Runtime::base().with_poseidon();
+    let temp_dir = Temp::new_dir().unwrap();
+    let riscv_asm = powdr_riscv::compile_rust_crate_to_riscv_asm(
+        &format!("tests/riscv_data/{case}/Cargo.toml"),
+        &temp_dir,
+    );
+    let powdr_asm =
+        powdr_riscv::compiler::compile::<GoldilocksField>(riscv_asm, &runtime, true).unwrap();
 
     let mut pipeline = Pipeline::default()
         .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
         .with_prover_inputs(Default::default());
-    rust_continuations_dry_run::<GoldilocksField>(&mut pipeline);
+    let bootloader_inputs = rust_continuations_dry_run::<GoldilocksField>(
+        &mut pipeline,
+        &Default::default(),
+        ContinuationsOptions::default(),
+    )
+    .unwrap();
+    assert!(bootloader_inputs.chunk_count() > 1);
+    for (i, chunk) in bootloader_inputs.chunks.iter().enumerate() {
+        assert_eq!(chunk.chunk_index, i as u64);
+    }
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_prove_single_chunk() {
+    // The chunks have fewer rows than the main machine.
+    let case = "many_chunks";
+    let chunk_size = 1 << MIN_DEGREE_LOG;
+    let powdr_asm = compile_many_chunks(2 * chunk_size as u64);
+    let options = ContinuationsOptions {
+        chunk_size: Some(chunk_size),
+        max_chunks: None,
+    };
+
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
+        .with_prover_inputs(Default::default())
+        .with_output(tmp_dir.to_path_buf(), false)
+        .with_backend(BackendType::PilStarkCli);
+    let bootloader_inputs =
+        rust_continuations_dry_run(&mut pipeline, &Default::default(), options).unwrap();
+    assert!(bootloader_inputs.chunk_count() > 1);
+
+    // Only the second chunk is proven, without running the first one.
+    prove_chunk(pipeline.clone(), 1, &bootloader_inputs, options).unwrap();
+    verify(
+        pipeline.output_dir().unwrap(),
+        &format!("{}_chunk_1", pipeline.name()),
+        Some(case),
+    )
+    .unwrap();
+
+    let chunk_count = bootloader_inputs.chunk_count();
+    assert_eq!(
+        prove_chunk(pipeline, chunk_count, &bootloader_inputs, options),
+        Err(vec![format!(
+            "Chunk {chunk_count} does not exist, the execution has {chunk_count} chunks."
+        )])
+    );
 }
 
 #[test]
@@ -235,7 +716,13 @@ fn test_many_chunks_memory() {
 
 fn verify_riscv_crate(case: &str, inputs: Vec<GoldilocksField>, runtime: &Runtime) {
     let powdr_asm = compile_riscv_crate::<GoldilocksField>(case, runtime);
-    verify_riscv_asm_string::<()>(&format!("{case}.asm"), &powdr_asm, inputs, None);
+    verify_riscv_asm_string::<()>(
+        &format!("{case}.asm"),
+        &powdr_asm,
+        inputs,
+        None,
+        runtime.native_instructions(),
+    );
 }
 
 fn verify_riscv_crate_with_data<S: serde::Serialize + Send + Sync + 'static>(
@@ -246,7 +733,13 @@ fn verify_riscv_crate_with_data<S: serde::Serialize + Send + Sync + 'static>(
 ) {
     let powdr_asm = compile_riscv_crate::<GoldilocksField>(case, runtime);
 
-    verify_riscv_asm_string(&format!("{case}.asm"), &powdr_asm, inputs, Some(data));
+    verify_riscv_asm_string(
+        &format!("{case}.asm"),
+        &powdr_asm,
+        inputs,
+        Some(data),
+        runtime.native_instructions(),
+    );
 }
 
 fn compile_riscv_crate<T: FieldElement>(case: &str, runtime: &Runtime) -> String {
@@ -255,5 +748,5 @@ fn compile_riscv_crate<T: FieldElement>(case: &str, runtime: &Runtime) -> String
         &format!("tests/riscv_data/{case}/Cargo.toml"),
         &temp_dir,
     );
-    powdr_riscv::compiler::compile::<T>(riscv_asm, runtime, false)
+    powdr_riscv::compiler::compile::<T>(riscv_asm, runtime, false).unwrap()
 }
@@ -0,0 +1,10 @@
+#![no_std]
+
+use powdr_riscv_runtime::{powdr_assert, powdr_assert_eq};
+
+#[no_mangle]
+pub fn main() {
+    powdr_assert!(1 + 1 == 2);
+    // The output contains multi-byte characters, which the guest prints byte by byte.
+    powdr_assert_eq!(["größer", "1"], ["kleiner", "2"]);
+}
@@ -0,0 +1,27 @@
+#![no_std]
+
+use powdr_riscv_runtime::continuations::chunk_index;
+use powdr_riscv_runtime::print;
+
+#[no_mangle]
+pub fn main() {
+    // The program starts in the first chunk.
+    let index = chunk_index();
+    print!("Running in chunk {index}\n");
+    assert_eq!(index, 0);
+
+    // Compute some fibonacci numbers, so that the execution spans several chunks.
+    let mut a = 1;
+    let mut b = 1;
+    for _ in 0..150000 {
+        let tmp = a + b;
+        a = b;
+        b = tmp;
+    }
+    assert!(a > 0);
+
+    // By now, we are in a later chunk.
+    let index = chunk_index();
+    print!("Running in chunk {index}\n");
+    assert!(index > 0);
+}
@@ -0,0 +1,21 @@
+#![no_std]
+
+use core::arch::asm;
+
+/// The ecall number the host registers the "double" syscall with.
+const DOUBLE: u32 = 100;
+
+fn double(x: u32) -> u32 {
+    let mut result = x;
+    unsafe {
+        asm!("ecall", inout("a0") result, in("t0") DOUBLE);
+    }
+    result
+}
+
+#[no_mangle]
+fn main() {
+    assert_eq!(double(0), 0);
+    assert_eq!(double(21), 42);
+    assert_eq!(double(0x7fffffff), 0xfffffffe);
+}
@@ -0,0 +1,25 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use powdr_riscv_runtime::{peak_heap_usage, powdr_assert_eq, report_heap_usage};
+
+#[no_mangle]
+pub fn main() {
+    powdr_assert_eq!(peak_heap_usage(), 0);
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(1000);
+    bytes.extend((0..1000).map(|i| i as u8));
+    powdr_assert_eq!(peak_heap_usage(), 1000);
+
+    // Aligned to 4 bytes, so this starts at 1000 and ends at 1040.
+    let mut words: Vec<u32> = Vec::with_capacity(10);
+    words.extend(0..10);
+    powdr_assert_eq!(peak_heap_usage(), 1040);
+
+    let sum = bytes.iter().map(|b| *b as u32).sum::<u32>() + words.iter().sum::<u32>();
+    powdr_assert_eq!(sum, 124761);
+
+    report_heap_usage();
+}
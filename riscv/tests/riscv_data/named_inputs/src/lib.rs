@@ -0,0 +1,12 @@
+#![no_std]
+
+use powdr_riscv_runtime::input::{get_named, get_prover_input};
+
+#[no_mangle]
+pub fn main() {
+    // Without named inputs from the host, the defaults are used.
+    let threshold = get_named("threshold").unwrap_or(40);
+    let rounds = get_named("rounds").unwrap_or(2);
+    assert_eq!(get_named("missing"), None);
+    assert_eq!(threshold + rounds, get_prover_input(0));
+}
@@ -0,0 +1,26 @@
+//! Commits the minimum, the maximum and the sum of the prover inputs
+//! to the output of the program.
+//!
+//! The first input is the number of elements, the others are the elements.
+
+#![no_std]
+
+use powdr_riscv_runtime::input::get_prover_input;
+use powdr_riscv_runtime::output::commit_slice;
+
+#[no_mangle]
+fn main() {
+    let len = get_prover_input(0);
+
+    let mut min = u32::MAX;
+    let mut max = 0;
+    let mut sum = 0;
+    for idx in 1..=len {
+        let value = get_prover_input(idx);
+        min = min.min(value);
+        max = max.max(value);
+        sum += value;
+    }
+
+    commit_slice(&[min, max, sum]);
+}
@@ -0,0 +1,114 @@
+use std::array;
+use std::convert::expr;
+use std::utils::force_bool;
+
+// Building blocks of the SHA-256 compression function, as used by the machines below.
+// A 32-bit word is represented by its little-endian bit decomposition.
+
+/// Puts the word back together from its bits.
+let word: expr[] -> expr = |bits| array::sum(array::map_enumerated(bits, |i, bit| bit * expr(1 << i)));
+
+/// Rotates the word to the right by `n` bits.
+let rotr: expr[], int -> expr[] = |bits, n| array::new(32, |i| bits[(i + n) % 32]);
+
+/// Shifts the word to the right by `n` bits.
+let shr: expr[], int -> expr[] = |bits, n| array::new(32, |i| if i + n < 32 { bits[i + n] } else { 0 });
+
+/// The XOR of two bits.
+let xor: expr, expr -> expr = |x, y| x + y - 2 * x * y;
+
+/// The bitwise XOR of three words.
+let xor3: expr[], expr[], expr[] -> expr[] = |x, y, z| array::new(32, |i| xor(xor(x[i], y[i]), z[i]));
+
+// Computes one round of the SHA-256 compression function.
+machine Sha256Round(latch, operation_id) {
+
+    // Takes the working variables a, ..., h, the word w of the message schedule
+    // and the round constant k and returns the working variables after the round.
+    operation round<0> a, b, c, d, e, f, g, h, w, k -> out[0], out[1], out[2], out[3], out[4], out[5], out[6], out[7];
+
+    // Allow this machine to be connected via a permutation
+    call_selectors sel;
+
+    col witness operation_id;
+
+    col fixed latch = [1]*;
+
+    col witness a, b, c, d, e, f, g, h, w, k;
+    pol commit out[8];
+
+    // Bit decompositions of the inputs that enter the bitwise functions
+    pol commit a_bits[32];
+    pol commit b_bits[32];
+    pol commit c_bits[32];
+    pol commit e_bits[32];
+    pol commit f_bits[32];
+    pol commit g_bits[32];
+    array::map(a_bits + b_bits + c_bits + e_bits + f_bits + g_bits, |bit| force_bool(bit));
+    a = word(a_bits);
+    b = word(b_bits);
+    c = word(c_bits);
+    e = word(e_bits);
+    f = word(f_bits);
+    g = word(g_bits);
+
+    let S0 = word(xor3(rotr(a_bits, 2), rotr(a_bits, 13), rotr(a_bits, 22)));
+    let S1 = word(xor3(rotr(e_bits, 6), rotr(e_bits, 11), rotr(e_bits, 25)));
+    let ch = word(array::new(32, |i| e_bits[i] * f_bits[i] + (1 - e_bits[i]) * g_bits[i]));
+    let maj = word(array::new(32, |i| a_bits[i] * b_bits[i] + a_bits[i] * c_bits[i] + b_bits[i] * c_bits[i] - 2 * a_bits[i] * b_bits[i] * c_bits[i]));
+    let temp1 = h + S1 + ch + k + w;
+    let temp2 = S0 + maj;
+
+    // The new values of a and e are sums of up to seven words, so the carry
+    // fits into three bits. Since all of this is far below the field modulus,
+    // the decomposition into the result and the carry is unique.
+    pol commit new_a_bits[32];
+    pol commit new_e_bits[32];
+    pol commit carry_a[3];
+    pol commit carry_e[3];
+    array::map(new_a_bits + new_e_bits + carry_a + carry_e, |bit| force_bool(bit));
+    word(new_a_bits) + 2**32 * word(carry_a) = temp1 + temp2;
+    word(new_e_bits) + 2**32 * word(carry_e) = d + temp1;
+
+    out[0] = word(new_a_bits);
+    out[1] = a;
+    out[2] = b;
+    out[3] = c;
+    out[4] = word(new_e_bits);
+    out[5] = e;
+    out[6] = f;
+    out[7] = g;
+}
+
+// Computes one word of the SHA-256 message schedule.
+machine Sha256Schedule(latch, operation_id) {
+
+    // Returns w[i] given w[i - 2], w[i - 7], w[i - 15] and w[i - 16].
+    operation schedule<0> w2, w7, w15, w16 -> w;
+
+    // Allow this machine to be connected via a permutation
+    call_selectors sel;
+
+    col witness operation_id;
+
+    col fixed latch = [1]*;
+
+    col witness w2, w7, w15, w16, w;
+
+    pol commit w2_bits[32];
+    pol commit w15_bits[32];
+    array::map(w2_bits + w15_bits, |bit| force_bool(bit));
+    w2 = word(w2_bits);
+    w15 = word(w15_bits);
+
+    let s0 = word(xor3(rotr(w15_bits, 7), rotr(w15_bits, 18), shr(w15_bits, 3)));
+    let s1 = word(xor3(rotr(w2_bits, 17), rotr(w2_bits, 19), shr(w2_bits, 10)));
+
+    // The sum of four words has a carry of at most two bits.
+    pol commit w_bits[32];
+    pol commit carry[2];
+    array::map(w_bits + carry, |bit| force_bool(bit));
+    word(w_bits) + 2**32 * word(carry) = s1 + w7 + s0 + w16;
+
+    w = word(w_bits);
+}
@@ -12,7 +12,11 @@ enum Query {
     /// Generate a hint to fill a witness column with.
     Hint(fe),
     /// Query a prover input element by index and data id.
-    DataIdentifier(int, int)
+    DataIdentifier(int, int),
+    /// Commit a value to the public output of the program.
+    Output(int),
+    /// Report the peak heap usage of the program in bytes.
+    HeapUsage(int)
 }
 
 /// Constructs a challenge object.
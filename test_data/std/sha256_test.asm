@@ -0,0 +1,65 @@
+use std::hash::sha256::Sha256Round;
+use std::hash::sha256::Sha256Schedule;
+
+machine Main {
+    degree 256;
+
+    reg pc[@pc];
+    reg X0[<=];
+    reg X1[<=];
+    reg X2[<=];
+    reg X3[<=];
+    reg X4[<=];
+    reg X5[<=];
+    reg X6[<=];
+    reg X7[<=];
+    reg X8[<=];
+    reg X9[<=];
+    reg A;
+    reg B;
+    reg C;
+    reg D;
+    reg E;
+    reg F;
+    reg G;
+    reg H;
+
+    Sha256Round sha256_round;
+    Sha256Schedule sha256_schedule;
+
+    instr round X0, X1, X2, X3, X4, X5, X6, X7, X8, X9 -> A, B, C, D, E, F, G, H ~ sha256_round.round;
+    instr schedule X0, X1, X2, X3 -> A ~ sha256_schedule.schedule;
+
+    instr assert_eq X0, X1 {
+        X0 = X1
+    }
+
+    function main {
+
+        // The first round of hashing "abc", see the example in
+        // https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/SHA256.pdf
+        A, B, C, D, E, F, G, H <== round(0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19, 0x61626380, 0x428a2f98);
+        assert_eq A, 0x5d6aebcd;
+        assert_eq B, 0x6a09e667;
+        assert_eq C, 0xbb67ae85;
+        assert_eq D, 0x3c6ef372;
+        assert_eq E, 0xfa2a4622;
+        assert_eq F, 0x510e527f;
+        assert_eq G, 0x9b05688c;
+        assert_eq H, 0x1f83d9ab;
+
+        // All carries are set.
+        A, B, C, D, E, F, G, H <== round(0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff);
+        assert_eq A, 0xfffffff9;
+        assert_eq E, 0xfffffffa;
+
+        // w[16] of the single block of "abc".
+        A <== schedule(0, 0, 0, 0x61626380);
+        assert_eq A, 0x61626380;
+
+        A <== schedule(0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff);
+        assert_eq A, 0x203ffffc;
+
+        return;
+    }
+}